@@ -42,6 +42,8 @@ pub fn rust_tmp_main(hart_id: usize, device_tree: usize) {
         });
 
         info!("There has {} CPU(s)", fdt.cpus().count());
+        crate::init_initrd(&fdt);
+        crate::init_cpus(&fdt);
 
         fdt.memory()
             .regions()
@@ -52,9 +54,9 @@ pub fn rust_tmp_main(hart_id: usize, device_tree: usize) {
                     x.starting_address as usize + x.size.unwrap()
                 );
 
-                ArchInterface::add_memory_region(
-                    x.starting_address as usize | VIRT_ADDR_START,
-                    (x.starting_address as usize + x.size.unwrap()) | VIRT_ADDR_START,
+                crate::add_memory_region(
+                    x.starting_address as usize,
+                    x.starting_address as usize + x.size.unwrap(),
                 );
             });
     }
@@ -20,9 +20,13 @@ mod api;
 #[cfg(target_arch = "riscv64")]
 mod riscv64;
 
-use core::mem::size_of;
+use core::{
+    mem::size_of,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use alloc::vec::Vec;
+use alloc::{string::String, vec::Vec};
 #[cfg(target_arch = "riscv64")]
 pub use riscv64::*;
 
@@ -120,6 +124,122 @@ pub fn get_int_records() -> Vec<usize> {
     unsafe { INT_RECORDS.clone() }
 }
 
+/// The time spent by the user tasks in the user mode and the kernel mode.
+static USER_TIME: AtomicUsize = AtomicUsize::new(0);
+static SYSTEM_TIME: AtomicUsize = AtomicUsize::new(0);
+
+/// Account the time got by get_time, the tasks only run on the boot cpu.
+pub fn add_cpu_time(user: usize, system: usize) {
+    USER_TIME.fetch_add(user, Ordering::Relaxed);
+    SYSTEM_TIME.fetch_add(system, Ordering::Relaxed);
+}
+
+/// Return the user time and the system time, the same unit as get_time.
+pub fn get_cpu_time() -> (usize, usize) {
+    (
+        USER_TIME.load(Ordering::Relaxed),
+        SYSTEM_TIME.load(Ordering::Relaxed),
+    )
+}
+
+/// A cpu described by the device tree.
+#[derive(Debug, Clone, Default)]
+pub struct CpuInfo {
+    pub id: usize,
+    /// The compatible string, like "arm,cortex-a72".
+    pub model: String,
+    /// The isa string of riscv, like "rv64imafdc".
+    pub isa: String,
+    /// The mmu type of riscv, like "riscv,sv39".
+    pub mmu: String,
+    /// The clock frequency in Hz, 0 if it is unknown.
+    pub frequency: usize,
+}
+
+static mut CPUS: Vec<CpuInfo> = Vec::new();
+static mut BOOTARGS: String = String::new();
+
+/// Record the cpus and the kernel command line in the device tree.
+#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
+fn init_cpus(fdt: &fdt::Fdt) {
+    let cpus = fdt.cpus().map(|cpu| {
+        let prop = |name: &str| cpu.property(name).and_then(|x| x.as_str());
+        CpuInfo {
+            id: cpu.ids().first(),
+            model: prop("compatible").unwrap_or_default().into(),
+            isa: prop("riscv,isa").unwrap_or_default().into(),
+            mmu: prop("mmu-type").unwrap_or_default().into(),
+            frequency: cpu
+                .property("clock-frequency")
+                .and_then(|x| x.as_usize())
+                .unwrap_or(0),
+        }
+    });
+    let bootargs = fdt
+        .find_node("/chosen")
+        .and_then(|x| x.property("bootargs")?.as_str());
+    unsafe {
+        CPUS = cpus.collect();
+        BOOTARGS = bootargs.unwrap_or_default().into();
+    }
+}
+
+/// The cpus in the device tree, only the boot cpu is known without it.
+pub fn get_cpus() -> Vec<CpuInfo> {
+    match unsafe { CPUS.is_empty() } {
+        true => alloc::vec![CpuInfo::default()],
+        false => unsafe { CPUS.clone() },
+    }
+}
+
+/// The kernel command line passed by the bootloader.
+pub fn get_bootargs() -> String {
+    unsafe { BOOTARGS.clone() }
+}
+
+/// The physical range of the initrd passed by the bootloader, empty if it doesn't exist.
+static INITRD_START: AtomicUsize = AtomicUsize::new(0);
+static INITRD_END: AtomicUsize = AtomicUsize::new(0);
+
+/// Find the initrd in the /chosen node of the device tree.
+#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
+fn init_initrd(fdt: &fdt::Fdt) {
+    let chosen = fdt.find_node("/chosen");
+    let prop = |name: &str| chosen.and_then(|x| x.property(name)?.as_usize());
+    if let (Some(start), Some(end)) = (prop("linux,initrd-start"), prop("linux,initrd-end")) {
+        info!("initrd range {:#X} - {:#X}", start, end);
+        INITRD_START.store(start, Ordering::Relaxed);
+        INITRD_END.store(end, Ordering::Relaxed);
+    }
+}
+
+/// Add the memory region to the frame allocator, the pages of the initrd are kept.
+#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
+fn add_memory_region(start: usize, end: usize) {
+    let initrd = INITRD_START.load(Ordering::Relaxed) / PAGE_SIZE * PAGE_SIZE
+        ..(INITRD_END.load(Ordering::Relaxed) + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    if initrd.is_empty() || initrd.end <= start || initrd.start >= end {
+        ArchInterface::add_memory_region(start | VIRT_ADDR_START, end | VIRT_ADDR_START);
+        return;
+    }
+    if initrd.start > start {
+        ArchInterface::add_memory_region(start | VIRT_ADDR_START, initrd.start | VIRT_ADDR_START);
+    }
+    if initrd.end < end {
+        ArchInterface::add_memory_region(initrd.end | VIRT_ADDR_START, end | VIRT_ADDR_START);
+    }
+}
+
+/// The virtual address range of the initrd passed by the bootloader.
+/// Its pages aren't in the frame allocator, they can be added after it is unpacked.
+pub fn get_initrd() -> Option<Range<usize>> {
+    let initrd = INITRD_START.load(Ordering::Relaxed)..INITRD_END.load(Ordering::Relaxed);
+    match initrd.is_empty() {
+        true => None,
+        false => Some(initrd.start | VIRT_ADDR_START..initrd.end | VIRT_ADDR_START),
+    }
+}
+
 pub fn clear_bss() {
     extern "C" {
         fn _sbss();
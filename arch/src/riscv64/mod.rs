@@ -43,6 +43,8 @@ extern "C" fn rust_main(hartid: usize, device_tree: usize) {
         });
 
         info!("There has {} CPU(s)", fdt.cpus().count());
+        crate::init_initrd(&fdt);
+        crate::init_cpus(&fdt);
 
         fdt.memory().regions().for_each(|x| {
             info!(
@@ -51,9 +53,9 @@ extern "C" fn rust_main(hartid: usize, device_tree: usize) {
                 x.starting_address as usize + x.size.unwrap()
             );
 
-            ArchInterface::add_memory_region(
-                x.starting_address as usize | VIRT_ADDR_START,
-                (x.starting_address as usize + x.size.unwrap()) | VIRT_ADDR_START,
+            crate::add_memory_region(
+                x.starting_address as usize,
+                x.starting_address as usize + x.size.unwrap(),
             );
         });
     }
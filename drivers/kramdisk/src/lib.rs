@@ -62,6 +62,10 @@ impl BlkDriver for RamDiskBlock {
             // dest.as_mut().unwrap().copy_from_slice(buf);
         }
     }
+
+    fn capacity(&self) -> usize {
+        self.size / 0x200
+    }
 }
 
 global_asm!(
@@ -5,10 +5,13 @@ extern crate alloc;
 #[macro_use]
 extern crate log;
 
+pub mod virtio_9p;
 pub mod virtio_blk;
 pub mod virtio_impl;
 pub mod virtio_input;
 pub mod virtio_net;
+pub mod virtio_rng;
+mod virtqueue;
 
 use core::ptr::NonNull;
 
@@ -67,6 +70,8 @@ fn virtio_device(transport: MmioTransport, node: &FdtNode) -> Arc<dyn Driver> {
         DeviceType::Block => virtio_blk::init(transport, irqs),
         DeviceType::Input => virtio_input::init(transport, irqs),
         DeviceType::Network => virtio_net::init(transport, irqs),
+        DeviceType::_9P => virtio_9p::init(transport, irqs),
+        DeviceType::EntropySource => virtio_rng::init(transport, irqs),
         device_type => {
             warn!("Unrecognized virtio device: {:?}", device_type);
             Arc::new(UnsupportedDriver)
@@ -113,6 +118,8 @@ fn virtio_device_probe(transport: impl Transport + 'static) {
         DeviceType::Block => Some(virtio_blk::init(transport, Vec::new())),
         // DeviceType::Input => virtio_input::init(transport, Vec::new()),
         DeviceType::Network => Some(virtio_net::init(transport, Vec::new())),
+        DeviceType::_9P => Some(virtio_9p::init(transport, Vec::new())),
+        DeviceType::EntropySource => Some(virtio_rng::init(transport, Vec::new())),
         t => {
             warn!("Unrecognized virtio device: {:?}", t);
             None
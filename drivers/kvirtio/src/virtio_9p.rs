@@ -0,0 +1,80 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use arch::PAGE_SIZE;
+use devices::device::{DeviceType, Driver, P9Driver};
+use sync::Mutex;
+use virtio_drivers::transport::{DeviceStatus, Transport};
+
+use super::virtqueue::VirtQueue;
+
+/// The device has the mount tag in the config space.
+const VIRTIO_9P_MOUNT_TAG: u64 = 1 << 0;
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+struct VirtIO9pInner<T: Transport> {
+    transport: T,
+    queue: VirtQueue,
+}
+
+pub struct VirtIO9p<T: Transport> {
+    inner: Mutex<VirtIO9pInner<T>>,
+    tag: String,
+}
+
+unsafe impl<T: Transport> Sync for VirtIO9p<T> {}
+unsafe impl<T: Transport> Send for VirtIO9p<T> {}
+
+impl<T: Transport + 'static> Driver for VirtIO9p<T> {
+    fn get_id(&self) -> &str {
+        "virtio-9p"
+    }
+
+    fn get_device_wrapper(self: Arc<Self>) -> DeviceType {
+        DeviceType::P9(self.clone())
+    }
+}
+
+impl<T: Transport + 'static> P9Driver for VirtIO9p<T> {
+    fn mount_tag(&self) -> &str {
+        &self.tag
+    }
+
+    fn request(&self, req: &[u8], resp: &mut [u8]) -> usize {
+        let inner = &mut *self.inner.lock();
+        inner.queue.submit(&mut inner.transport, req, resp)
+    }
+}
+
+/// Read the mount tag, a u16 length and the bytes without the zero.
+fn read_mount_tag<T: Transport>(transport: &T) -> String {
+    let config = transport
+        .config_space::<u8>()
+        .expect("can't read the config of virtio-9p");
+    let read = |offset: usize| unsafe { config.as_ptr().add(offset).read_volatile() };
+    let len = u16::from_le_bytes([read(0), read(1)]) as usize;
+    let tag: Vec<u8> = (0..len).map(|x| read(2 + x)).collect();
+    String::from_utf8_lossy(&tag).into_owned()
+}
+
+pub fn init<T: Transport + 'static>(mut transport: T, _irqs: Vec<u32>) -> Arc<dyn Driver> {
+    transport.set_status(DeviceStatus::empty());
+    transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+    let features = transport.read_device_features();
+    transport.write_driver_features(features & (VIRTIO_9P_MOUNT_TAG | VIRTIO_F_VERSION_1));
+    transport
+        .set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK);
+    transport.set_guest_page_size(PAGE_SIZE as u32);
+    let queue = VirtQueue::new(&mut transport, 0);
+    transport.set_status(
+        DeviceStatus::ACKNOWLEDGE
+            | DeviceStatus::DRIVER
+            | DeviceStatus::FEATURES_OK
+            | DeviceStatus::DRIVER_OK,
+    );
+
+    let tag = read_mount_tag(&transport);
+    info!("Initailize virtio-9p device, mount tag: {}", tag);
+    Arc::new(VirtIO9p {
+        inner: Mutex::new(VirtIO9pInner { transport, queue }),
+        tag,
+    })
+}
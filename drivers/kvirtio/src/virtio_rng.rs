@@ -0,0 +1,64 @@
+use alloc::{sync::Arc, vec::Vec};
+use arch::PAGE_SIZE;
+use devices::device::{DeviceType, Driver, RngDriver};
+use sync::Mutex;
+use virtio_drivers::transport::{DeviceStatus, Transport};
+
+use super::virtqueue::VirtQueue;
+
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+struct VirtIORngInner<T: Transport> {
+    transport: T,
+    queue: VirtQueue,
+}
+
+pub struct VirtIORng<T: Transport> {
+    inner: Mutex<VirtIORngInner<T>>,
+}
+
+unsafe impl<T: Transport> Sync for VirtIORng<T> {}
+unsafe impl<T: Transport> Send for VirtIORng<T> {}
+
+impl<T: Transport + 'static> Driver for VirtIORng<T> {
+    fn get_id(&self) -> &str {
+        "virtio-rng"
+    }
+
+    fn get_device_wrapper(self: Arc<Self>) -> DeviceType {
+        DeviceType::RNG(self.clone())
+    }
+}
+
+impl<T: Transport + 'static> RngDriver for VirtIORng<T> {
+    fn read_random(&self, buf: &mut [u8]) -> usize {
+        let inner = &mut *self.inner.lock();
+        // the device only writes the buffer, there is no request.
+        inner
+            .queue
+            .submit(&mut inner.transport, &[], buf)
+            .min(buf.len())
+    }
+}
+
+pub fn init<T: Transport + 'static>(mut transport: T, _irqs: Vec<u32>) -> Arc<dyn Driver> {
+    transport.set_status(DeviceStatus::empty());
+    transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+    let features = transport.read_device_features();
+    transport.write_driver_features(features & VIRTIO_F_VERSION_1);
+    transport
+        .set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK);
+    transport.set_guest_page_size(PAGE_SIZE as u32);
+    let queue = VirtQueue::new(&mut transport, 0);
+    transport.set_status(
+        DeviceStatus::ACKNOWLEDGE
+            | DeviceStatus::DRIVER
+            | DeviceStatus::FEATURES_OK
+            | DeviceStatus::DRIVER_OK,
+    );
+
+    info!("Initailize virtio-rng device");
+    Arc::new(VirtIORng {
+        inner: Mutex::new(VirtIORngInner { transport, queue }),
+    })
+}
@@ -0,0 +1,161 @@
+//! The split virtqueue polled by the simple drivers, like virtio-9p and virtio-rng.
+
+use core::{
+    mem::size_of,
+    ptr::NonNull,
+    sync::atomic::{fence, Ordering},
+};
+
+use arch::PAGE_SIZE;
+use virtio_drivers::{transport::Transport, BufferDirection, Hal};
+
+use super::virtio_impl::HalImpl;
+
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+/// The requests are sent one by one, the queue doesn't need to be large.
+const QUEUE_SIZE: u16 = 16;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[inline]
+const fn align_up(x: usize) -> usize {
+    (x + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+/// The split virtqueue in the legacy layout, it works for the modern devices too.
+pub(crate) struct VirtQueue {
+    index: u16,
+    paddr: usize,
+    vaddr: NonNull<u8>,
+    pages: usize,
+    size: u16,
+    avail_idx: u16,
+    used_idx: u16,
+}
+
+impl VirtQueue {
+    pub(crate) fn new<T: Transport>(transport: &mut T, index: u16) -> Self {
+        let size = QUEUE_SIZE.min(transport.max_queue_size() as u16);
+        let (desc_size, avail_size, used_size) = Self::layout(size);
+        let pages = (align_up(desc_size + avail_size) + align_up(used_size)) / PAGE_SIZE;
+        let (paddr, vaddr) = HalImpl::dma_alloc(pages, BufferDirection::Both);
+        let queue = Self {
+            index,
+            paddr,
+            vaddr,
+            pages,
+            size,
+            avail_idx: 0,
+            used_idx: 0,
+        };
+        // the response is polled, the interrupt isn't needed.
+        unsafe {
+            queue.avail().write_volatile(VIRTQ_AVAIL_F_NO_INTERRUPT);
+        }
+        transport.queue_set(
+            index,
+            size as u32,
+            paddr,
+            paddr + desc_size,
+            paddr + align_up(desc_size + avail_size),
+        );
+        queue
+    }
+
+    /// The size of the descriptor table, the available ring and the used ring.
+    const fn layout(size: u16) -> (usize, usize, usize) {
+        let size = size as usize;
+        (
+            size_of::<Descriptor>() * size,
+            size_of::<u16>() * (3 + size),
+            size_of::<u16>() * 3 + size_of::<u32>() * 2 * size,
+        )
+    }
+
+    fn desc(&self, index: u16) -> *mut Descriptor {
+        unsafe { (self.vaddr.as_ptr() as *mut Descriptor).add(index as usize) }
+    }
+
+    /// The available ring, flags, idx and the ring.
+    fn avail(&self) -> *mut u16 {
+        let (desc_size, ..) = Self::layout(self.size);
+        unsafe { self.vaddr.as_ptr().add(desc_size) as *mut u16 }
+    }
+
+    /// The used ring, flags, idx and the ring of (id, len).
+    fn used(&self) -> *mut u16 {
+        let (desc_size, avail_size, _) = Self::layout(self.size);
+        unsafe { self.vaddr.as_ptr().add(align_up(desc_size + avail_size)) as *mut u16 }
+    }
+
+    /// Add the request and the response buffer, wait for the device to use them.
+    /// The empty request isn't added, the device only writes the response.
+    pub(crate) fn submit<T: Transport>(
+        &mut self,
+        transport: &mut T,
+        req: &[u8],
+        resp: &mut [u8],
+    ) -> usize {
+        unsafe {
+            let resp_addr = HalImpl::share(NonNull::from(&*resp), BufferDirection::DeviceToDriver);
+            let head = match req.is_empty() {
+                true => 1,
+                false => {
+                    let req_addr =
+                        HalImpl::share(NonNull::from(req), BufferDirection::DriverToDevice);
+                    self.desc(0).write_volatile(Descriptor {
+                        addr: req_addr as _,
+                        len: req.len() as _,
+                        flags: VIRTQ_DESC_F_NEXT,
+                        next: 1,
+                    });
+                    0
+                }
+            };
+            self.desc(1).write_volatile(Descriptor {
+                addr: resp_addr as _,
+                len: resp.len() as _,
+                flags: VIRTQ_DESC_F_WRITE,
+                next: 0,
+            });
+            let avail = self.avail();
+            avail
+                .add(2 + (self.avail_idx % self.size) as usize)
+                .write_volatile(head);
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            avail.add(1).write_volatile(self.avail_idx);
+            fence(Ordering::SeqCst);
+            transport.notify(self.index);
+
+            let used = self.used();
+            while used.add(1).read_volatile() == self.used_idx {
+                core::hint::spin_loop();
+            }
+            fence(Ordering::SeqCst);
+            let elem = used.add(2) as *const u32;
+            let len = elem
+                .add(2 * (self.used_idx % self.size) as usize + 1)
+                .read_volatile();
+            self.used_idx = self.used_idx.wrapping_add(1);
+            len as usize
+        }
+    }
+}
+
+impl Drop for VirtQueue {
+    fn drop(&mut self) {
+        unsafe {
+            HalImpl::dma_dealloc(self.paddr, self.vaddr, self.pages);
+        }
+    }
+}
@@ -112,8 +112,12 @@ impl ArchInterface for ArchInterfaceImpl {
                 // return UserTaskControlFlow::Break;
             }
             TrapType::SupervisorExternal => {
+                devices::random::add_interrupt_randomness(u32::MAX);
                 get_int_device().try_handle_interrupt(u32::MAX);
             }
+            TrapType::Time => {
+                devices::random::add_interrupt_randomness(0);
+            }
             _ => {
                 // warn!("trap_type: {:?}  context: {:#x?}", trap_type, cx);
                 // debug!("kernel_interrupt");
@@ -168,6 +172,12 @@ impl ArchInterface for ArchInterfaceImpl {
         // get devices and init
         devices::regist_devices_irq();
 
+        // expose the partitions as block devices
+        devices::partition::scan_partitions();
+
+        // seed the random pool with the devices probed
+        devices::random::init();
+
         // initialize filesystem
         fs::init();
         {
@@ -205,6 +215,9 @@ impl ArchInterface for ArchInterfaceImpl {
         // init kernel threads and async executor
         tasks::init();
 
+        // write back the cached blocks before the shutdown
+        fs::sync();
+
         println!("Task All Finished!");
     }
 }
@@ -93,6 +93,12 @@ pub enum LinuxError {
     ENOSYS = 38,
     /// Directory not empty
     ENOTEMPTY = 39,
+    /// Too many symbolic links encountered
+    ELOOP = 40,
+    /// No data available
+    ENODATA = 61,
+    /// Operation not supported on transport endpoint
+    EOPNOTSUPP = 95,
     /// Address family not supported
     EAFNOSUPPORT = 97,
     /// Transport endpoint is not connected
@@ -148,6 +154,9 @@ impl LinuxError {
             ENOLCK => "No record locks available",
             ENOSYS => "Invalid system call number",
             ENOTEMPTY => "Directory not empty",
+            ELOOP => "Too many symbolic links encountered",
+            ENODATA => "No data available",
+            EOPNOTSUPP => "Operation not supported on transport endpoint",
             EAFNOSUPPORT => "Address family not supported",
             ENOTCONN => "Transport endpoint is not connected",
             ETIMEDOUT => "Connection time out",
@@ -163,7 +172,8 @@ impl LinuxError {
 
 pub fn from_vfs(vfs_error: VfsError) -> LinuxError {
     match vfs_error {
-        VfsError::NotLinkFile => LinuxError::EBADF,
+        // NotLinkFile is kept for too many symbolic links in the path walking.
+        VfsError::NotLinkFile => LinuxError::ELOOP,
         VfsError::NotDir => LinuxError::ENOTDIR,
         VfsError::NotFile => LinuxError::EBADF,
         VfsError::NotSupported => LinuxError::EPERM,
@@ -186,6 +196,18 @@ pub fn from_vfs(vfs_error: VfsError) -> LinuxError {
 // 中断调用列表
 cfg_if::cfg_if! {
     if #[cfg(any(target_arch = "riscv64", target_arch = "aarch64", target_arch = "loongarch64"))] {
+        pub const SYS_SETXATTR: usize = 5;
+        pub const SYS_LSETXATTR: usize = 6;
+        pub const SYS_FSETXATTR: usize = 7;
+        pub const SYS_GETXATTR: usize = 8;
+        pub const SYS_LGETXATTR: usize = 9;
+        pub const SYS_FGETXATTR: usize = 10;
+        pub const SYS_LISTXATTR: usize = 11;
+        pub const SYS_LLISTXATTR: usize = 12;
+        pub const SYS_FLISTXATTR: usize = 13;
+        pub const SYS_REMOVEXATTR: usize = 14;
+        pub const SYS_LREMOVEXATTR: usize = 15;
+        pub const SYS_FREMOVEXATTR: usize = 16;
         pub const SYS_GETCWD: usize = 17;
         pub const SYS_EPOLL_CREATE: usize = 20;
         pub const SYS_EPOLL_CTL: usize = 21;
@@ -193,15 +215,27 @@ cfg_if::cfg_if! {
         pub const SYS_DUP: usize = 23;
         pub const SYS_DUP3: usize = 24;
         pub const SYS_FCNTL: usize = 25;
+        pub const SYS_INOTIFY_INIT1: usize = 26;
+        pub const SYS_INOTIFY_ADD_WATCH: usize = 27;
+        pub const SYS_INOTIFY_RM_WATCH: usize = 28;
+        pub const SYS_FLOCK: usize = 32;
         pub const SYS_IOCTL: usize = 29;
+        pub const SYS_MKNODAT: usize = 33;
         pub const SYS_MKDIRAT: usize = 34;
         pub const SYS_UNLINKAT: usize = 35;
+        pub const SYS_SYMLINKAT: usize = 36;
+        pub const SYS_LINKAT: usize = 37;
         pub const SYS_UMOUNT2: usize = 39;
         pub const SYS_MOUNT: usize = 40;
         pub const SYS_STATFS: usize = 43;
         pub const SYS_FTRUNCATE: usize = 46;
+        pub const SYS_FALLOCATE: usize = 47;
         pub const SYS_FACCESSAT: usize = 48;
         pub const SYS_CHDIR: usize = 49;
+        pub const SYS_FCHMOD: usize = 52;
+        pub const SYS_FCHMODAT: usize = 53;
+        pub const SYS_FCHOWNAT: usize = 54;
+        pub const SYS_FCHOWN: usize = 55;
         pub const SYS_OPENAT: usize = 56;
         pub const SYS_CLOSE: usize = 57;
         pub const SYS_PIPE2: usize = 59;
@@ -219,7 +253,9 @@ cfg_if::cfg_if! {
         pub const SYS_READLINKAT: usize = 78;
         pub const SYS_FSTATAT: usize = 79;
         pub const SYS_FSTAT: usize = 80;
+        pub const SYS_SYNC: usize = 81;
         pub const SYS_FSYNC: usize = 82;
+        pub const SYS_FDATASYNC: usize = 83;
         pub const SYS_UTIMEAT: usize = 88;
         pub const SYS_EXIT: usize = 93;
         pub const SYS_EXIT_GROUP: usize = 94;
@@ -258,6 +294,9 @@ cfg_if::cfg_if! {
         pub const SYS_GETPID: usize = 172;
         pub const SYS_GETPPID: usize = 173;
         pub const SYS_GETUID: usize = 174;
+        pub const SYS_SETGID: usize = 144;
+        pub const SYS_SETUID: usize = 146;
+        pub const SYS_UMASK: usize = 166;
         pub const SYS_GETEUID: usize = 175;
         pub const SYS_GETGID: usize = 176;
         pub const SYS_GETEGID: usize = 177;
@@ -289,9 +328,11 @@ cfg_if::cfg_if! {
         pub const SYS_ACCEPT4: usize = 242;
         pub const SYS_WAIT4: usize = 260;
         pub const SYS_PRLIMIT64: usize = 261;
+        pub const SYS_SYNCFS: usize = 267;
         pub const SYS_RENAMEAT2: usize = 276;
         pub const SYS_GETRANDOM: usize = 278;
         pub const SYS_COPY_FILE_RANGE: usize = 285;
+        pub const SYS_STATX: usize = 291;
         pub const SYS_FACCESSAT2: usize = 439;
     } else if #[cfg(target_arch = "x86_64")] {
         pub const SYS_OPEN: usize = 2;
@@ -304,20 +345,39 @@ cfg_if::cfg_if! {
         pub const SYS_FORK: usize = 57;
         pub const SYS_DUP3: usize = 292;
         pub const SYS_FCNTL: usize = 72;
+        pub const SYS_FLOCK: usize = 73;
+        pub const SYS_INOTIFY_INIT: usize = 253;
+        pub const SYS_INOTIFY_ADD_WATCH: usize = 254;
+        pub const SYS_INOTIFY_RM_WATCH: usize = 255;
+        pub const SYS_INOTIFY_INIT1: usize = 294;
         pub const SYS_IOCTL: usize = 16;
         pub const SYS_MKDIR: usize = 83;
         pub const SYS_MKDIRAT: usize = 258;
+        pub const SYS_MKNOD: usize = 133;
+        pub const SYS_MKNODAT: usize = 259;
         pub const SYS_UNLINK: usize = 87;
         pub const SYS_STAT: usize = 4;
         pub const SYS_LSTAT: usize = 6;
         pub const SYS_POLL: usize = 7;
         pub const SYS_UNLINKAT: usize = 263;
+        pub const SYS_SYMLINKAT: usize = 266;
+        pub const SYS_LINKAT: usize = 265;
         pub const SYS_UMOUNT2: usize = 166;
         pub const SYS_MOUNT: usize = 165;
         pub const SYS_STATFS: usize = 137;
         pub const SYS_FTRUNCATE: usize = 77;
+        pub const SYS_FALLOCATE: usize = 285;
         pub const SYS_FACCESSAT: usize = 269;
+        pub const SYS_ACCESS: usize = 21;
         pub const SYS_CHDIR: usize = 80;
+        pub const SYS_CHMOD: usize = 90;
+        pub const SYS_FCHMOD: usize = 91;
+        pub const SYS_CHOWN: usize = 92;
+        pub const SYS_FCHOWN: usize = 93;
+        pub const SYS_LCHOWN: usize = 94;
+        pub const SYS_UMASK: usize = 95;
+        pub const SYS_FCHOWNAT: usize = 260;
+        pub const SYS_FCHMODAT: usize = 268;
         pub const SYS_OPENAT: usize = 257;
         pub const SYS_CLOSE: usize = 3;
         pub const SYS_PIPE2: usize = 293;
@@ -335,11 +395,28 @@ cfg_if::cfg_if! {
         pub const SYS_SETGROUPS: usize = 116;
         pub const SYS_PSELECT: usize = 270;
         pub const SYS_PPOLL: usize = 271;
+        pub const SYS_SYMLINK: usize = 88;
+        pub const SYS_LINK: usize = 86;
         pub const SYS_READLINK: usize = 89;
         pub const SYS_READLINKAT: usize = 267;
         pub const SYS_FSTATAT: usize = 262;
         pub const SYS_FSTAT: usize = 5;
+        pub const SYS_SYNC: usize = 162;
         pub const SYS_FSYNC: usize = 74;
+        pub const SYS_FDATASYNC: usize = 75;
+        pub const SYS_SYNCFS: usize = 306;
+        pub const SYS_SETXATTR: usize = 188;
+        pub const SYS_LSETXATTR: usize = 189;
+        pub const SYS_FSETXATTR: usize = 190;
+        pub const SYS_GETXATTR: usize = 191;
+        pub const SYS_LGETXATTR: usize = 192;
+        pub const SYS_FGETXATTR: usize = 193;
+        pub const SYS_LISTXATTR: usize = 194;
+        pub const SYS_LLISTXATTR: usize = 195;
+        pub const SYS_FLISTXATTR: usize = 196;
+        pub const SYS_REMOVEXATTR: usize = 197;
+        pub const SYS_LREMOVEXATTR: usize = 198;
+        pub const SYS_FREMOVEXATTR: usize = 199;
         pub const SYS_UTIMEAT: usize = 280;
         pub const SYS_EXIT: usize = 60;
         pub const SYS_EXIT_GROUP: usize = 231;
@@ -378,6 +455,8 @@ cfg_if::cfg_if! {
         pub const SYS_GETPID: usize = 39;
         pub const SYS_GETPPID: usize = 110;
         pub const SYS_GETUID: usize = 102;
+        pub const SYS_SETUID: usize = 105;
+        pub const SYS_SETGID: usize = 106;
         pub const SYS_GETEUID: usize = 107;
         pub const SYS_GETGID: usize = 104;
         pub const SYS_GETEGID: usize = 108;
@@ -412,12 +491,35 @@ cfg_if::cfg_if! {
         pub const SYS_RENAMEAT2: usize = 316;
         pub const SYS_GETRANDOM: usize = 318;
         pub const SYS_COPY_FILE_RANGE: usize = 326;
+        pub const SYS_STATX: usize = 332;
         pub const SYS_FACCESSAT2: usize = 439;
     }
 
 }
 
 pub const AT_CWD: usize = -100 as isize as usize;
+pub const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+pub const AT_REMOVEDIR: usize = 0x200;
+pub const AT_SYMLINK_FOLLOW: usize = 0x400;
+pub const AT_EMPTY_PATH: usize = 0x1000;
+
+/// The flags of renameat2(2).
+pub const RENAME_NOREPLACE: usize = 1 << 0;
+
+// the file types in the mode of mknod(2).
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+// the mode of access(2).
+pub const F_OK: usize = 0;
+pub const X_OK: usize = 1;
+pub const W_OK: usize = 2;
+pub const R_OK: usize = 4;
 
 pub struct UTSname {
     pub sysname: [u8; 65],
@@ -466,6 +568,14 @@ bitflags! {
         const SYNC = 1 << 2;
     }
 
+    /// The flags of getrandom(2).
+    #[derive(Debug)]
+    pub struct GetRandomFlags: u32 {
+        const GRND_NONBLOCK = 1 << 0;
+        const GRND_RANDOM = 1 << 1;
+        const GRND_INSECURE = 1 << 2;
+    }
+
     #[derive(Debug)]
     pub struct ProtFlags: u32 {
         const PROT_NONE = 0;
@@ -600,6 +710,7 @@ pub mod fcntl_cmd {
 
 #[repr(u32)]
 #[derive(Debug, Clone, PartialEq, FromPrimitive)]
+#[allow(non_camel_case_types)]
 pub enum FcntlCmd {
     /// dup
     DUPFD = 0,
@@ -617,10 +728,103 @@ pub enum FcntlCmd {
     SETLK = 6,
     /// Set record locking info (blocking).
     SETLKW = 7,
+    /// Get the open file description lock.
+    OFD_GETLK = 36,
+    /// Set the open file description lock (non-blocking).
+    OFD_SETLK = 37,
+    /// Set the open file description lock (blocking).
+    OFD_SETLKW = 38,
     /// like F_DUPFD, but additionally set the close-on-exec flag
     DUPFDCLOEXEC = 0x406,
 }
 
+/// The record lock of fcntl(2).
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+// the type of the record lock.
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+// the operation of flock(2).
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+// the mode of fallocate(2).
+pub const FALLOC_FL_KEEP_SIZE: usize = 1;
+pub const FALLOC_FL_PUNCH_HOLE: usize = 2;
+
+// the whence of lseek(2) for the sparse files.
+pub const SEEK_DATA: usize = 3;
+pub const SEEK_HOLE: usize = 4;
+
+/// The timestamp of statx(2).
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct StatxTimestamp {
+    pub sec: i64,
+    pub nsec: u32,
+    pub __reserved: i32,
+}
+
+/// The result of statx(2), the fields not in the mask are zero.
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct Statx {
+    pub mask: u32,
+    pub blksize: u32,
+    pub attributes: u64,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16,
+    pub __spare0: u16,
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub attributes_mask: u64,
+    pub atime: StatxTimestamp,
+    pub btime: StatxTimestamp,
+    pub ctime: StatxTimestamp,
+    pub mtime: StatxTimestamp,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub mnt_id: u64,
+    pub dio_mem_align: u32,
+    pub dio_offset_align: u32,
+    pub __spare3: [u64; 12],
+}
+
+// the mask of statx(2).
+pub const STATX_BASIC_STATS: u32 = 0x7ff;
+pub const STATX_BTIME: u32 = 0x800;
+pub const STATX_MNT_ID: u32 = 0x1000;
+pub const STATX_RESERVED: u32 = 0x8000_0000;
+
+// the attributes of statx(2).
+pub const STATX_ATTR_COMPRESSED: u64 = 0x4;
+pub const STATX_ATTR_IMMUTABLE: u64 = 0x10;
+pub const STATX_ATTR_APPEND: u64 = 0x20;
+pub const STATX_ATTR_NODUMP: u64 = 0x40;
+pub const STATX_ATTR_ENCRYPTED: u64 = 0x800;
+pub const STATX_ATTR_MOUNT_ROOT: u64 = 0x2000;
+
+// the flags of statx(2) besides AT_SYMLINK_NOFOLLOW and AT_EMPTY_PATH.
+pub const AT_NO_AUTOMOUNT: usize = 0x800;
+pub const AT_STATX_SYNC_TYPE: usize = 0x6000;
+
 #[repr(usize)]
 #[derive(Debug, Clone, FromPrimitive)]
 #[allow(non_camel_case_types)]
@@ -1,21 +1,38 @@
-use alloc::string::String;
 use core::cmp;
-use fs::dentry::{dentry_open, DentryNode};
+use fs::dentry::dentry_open;
+use fs::inotify::{self, Inotify, InotifyMask};
+use fs::lock::{
+    test_lock, try_lock, unlock, FileLock, LockClass, LockError, LockKey, LockOwner, LockType,
+};
+use fs::mount::{mount_dev, mount_flags, mount_id, MountFlags};
 use num_traits::FromPrimitive;
-use vfscore::FileType;
+use vfscore::{FileType, VfsError, VfsResult};
 
 use alloc::sync::Arc;
 use arch::VirtAddr;
 use bit_field::BitArray;
-use executor::{yield_now, AsyncTask, FileItem, UserTask};
+use devices::{major, minor};
+use executor::{yield_now, AsyncTask, FileItem, FileOptions, UserTask};
 use fs::pipe::create_pipe;
-use fs::{OpenFlags, PollEvent, PollFd, SeekFrom, Stat, StatFS, StatMode, TimeSpec, UTIME_NOW};
+use fs::{
+    INodeInterface, OpenFlags, PollEvent, PollFd, SeekFrom, Stat, StatFS, StatMode, TimeSpec,
+    UTIME_NOW,
+};
 use log::debug;
 
 use crate::epoll::{EpollEvent, EpollFile};
-use crate::syscall::consts::{from_vfs, FcntlCmd, IoVec, AT_CWD};
+use crate::syscall::consts::{
+    from_vfs, FcntlCmd, Flock, IoVec, Statx, StatxTimestamp, AT_CWD, AT_EMPTY_PATH,
+    AT_NO_AUTOMOUNT, AT_REMOVEDIR, AT_STATX_SYNC_TYPE, AT_SYMLINK_FOLLOW, AT_SYMLINK_NOFOLLOW,
+    FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE, F_OK, F_RDLCK, F_UNLCK, F_WRLCK, LOCK_EX, LOCK_NB,
+    LOCK_SH, LOCK_UN, RENAME_NOREPLACE, R_OK, SEEK_DATA, SEEK_HOLE, STATX_ATTR_APPEND,
+    STATX_ATTR_COMPRESSED, STATX_ATTR_ENCRYPTED, STATX_ATTR_IMMUTABLE, STATX_ATTR_MOUNT_ROOT,
+    STATX_ATTR_NODUMP, STATX_BASIC_STATS, STATX_BTIME, STATX_MNT_ID, STATX_RESERVED, S_IFBLK,
+    S_IFCHR, S_IFDIR, S_IFIFO, S_IFMT, S_IFREG, S_IFSOCK, W_OK, X_OK,
+};
 use crate::syscall::func::timespc_now;
 use crate::syscall::time::current_nsec;
+use crate::tasks::WaitLock;
 use crate::user::UserTaskContainer;
 
 use super::consts::{LinuxError, UserRef};
@@ -32,6 +49,191 @@ pub fn to_node(task: &Arc<UserTask>, fd: usize, path: &str) -> Result<Arc<FileIt
     }
 }
 
+/// Split the path into the parent directory and the name of the new file.
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => (".", path),
+    }
+}
+
+fn statx_time(time: TimeSpec) -> StatxTimestamp {
+    StatxTimestamp {
+        sec: time.sec as _,
+        nsec: time.nsec as _,
+        __reserved: 0,
+    }
+}
+
+fn node_stat(node: &Arc<dyn INodeInterface>) -> Result<Stat, LinuxError> {
+    let mut stat = Stat::default();
+    node.stat(&mut stat).map_err(from_vfs)?;
+    Ok(stat)
+}
+
+/// Check the access of the node with the credential of the task.
+/// mask is the combination of R_OK, W_OK and X_OK.
+pub fn check_access(
+    task: &Arc<UserTask>,
+    node: &Arc<dyn INodeInterface>,
+    mask: usize,
+) -> Result<(), LinuxError> {
+    let stat = node_stat(node)?;
+    let (uid, gid) = task.inner_map(|pcb| (pcb.uid, pcb.gid));
+    let mode = stat.mode.bits() as usize;
+    // root passes the checks, but it can't execute the file without any execute bits.
+    if uid == 0 {
+        let is_dir = mode & 0o170000 == StatMode::DIR.bits() as usize;
+        return match mask & X_OK != 0 && !is_dir && mode & 0o111 == 0 {
+            true => Err(LinuxError::EACCES),
+            false => Ok(()),
+        };
+    }
+    let perm = if stat.uid as u32 == uid {
+        mode >> 6
+    } else if stat.gid as u32 == gid {
+        mode >> 3
+    } else {
+        mode
+    };
+    match perm & mask == mask {
+        true => Ok(()),
+        false => Err(LinuxError::EACCES),
+    }
+}
+
+/// The files on the read-only mount can't be changed.
+pub(crate) fn check_writable(path: &str) -> Result<(), LinuxError> {
+    match mount_flags(path).contains(MountFlags::MS_RDONLY) {
+        true => Err(LinuxError::EROFS),
+        false => Ok(()),
+    }
+}
+
+/// The file in the sticky directory can only be removed by the owner of the file or the directory.
+fn check_sticky(
+    task: &Arc<UserTask>,
+    dir: &Arc<dyn INodeInterface>,
+    node: &Arc<dyn INodeInterface>,
+) -> Result<(), LinuxError> {
+    let uid = task.inner_map(|pcb| pcb.uid);
+    let dir_stat = node_stat(dir)?;
+    if uid == 0 || dir_stat.mode.bits() & 0o1000 == 0 || dir_stat.uid as u32 == uid {
+        return Ok(());
+    }
+    match node_stat(node)?.uid as u32 == uid {
+        true => Ok(()),
+        false => Err(LinuxError::EPERM),
+    }
+}
+
+/// Give the new node to the task, the mode is masked by the umask.
+/// The symbolic link has no mode, so the mode is None.
+fn init_owner(
+    task: &Arc<UserTask>,
+    node: &Arc<dyn INodeInterface>,
+    mode: Option<usize>,
+) -> Result<(), LinuxError> {
+    let (uid, gid, umask) = task.inner_map(|pcb| (pcb.uid, pcb.gid, pcb.umask));
+    let res = fs::chown(node, Some(uid), Some(gid)).and_then(|_| match mode {
+        Some(mode) => fs::chmod(node, mode as u32 & 0o7777 & !umask),
+        None => Ok(()),
+    });
+    // the filesystems without owners, like fat, keep their fixed modes.
+    match res {
+        Ok(()) | Err(VfsError::NotSupported) => Ok(()),
+        Err(err) => Err(from_vfs(err)),
+    }
+}
+
+/// Move the file by the links, the filesystems which can't rename it may link it.
+/// The replaced file is linked aside first, it is only removed after the new link succeeds.
+fn link_rename(
+    old_dir: &Arc<dyn INodeInterface>,
+    old_name: &str,
+    new_dir: &Arc<dyn INodeInterface>,
+    new_name: &str,
+) -> VfsResult<()> {
+    let node = old_dir.open(old_name, OpenFlags::O_NOFOLLOW)?;
+    let replaced = match new_dir.open(new_name, OpenFlags::O_NOFOLLOW) {
+        Ok(replaced) => Some(replaced),
+        Err(VfsError::FileNotFound) => None,
+        Err(err) => return Err(err),
+    };
+    let aside = format!(".rename-{}", new_name);
+    if let Some(replaced) = replaced {
+        new_dir.link(&aside, replaced)?;
+        if let Err(err) = new_dir.remove(new_name) {
+            new_dir.remove(&aside)?;
+            return Err(err);
+        }
+    }
+    if let Err(err) = new_dir.link(new_name, node) {
+        // put the replaced file back.
+        if let Ok(replaced) = new_dir.open(&aside, OpenFlags::O_NOFOLLOW) {
+            new_dir.link(new_name, replaced)?;
+            new_dir.remove(&aside)?;
+        }
+        return Err(err);
+    }
+    match new_dir.remove(&aside) {
+        Ok(()) | Err(VfsError::FileNotFound) => old_dir.remove(old_name),
+        Err(err) => Err(err),
+    }
+}
+
+fn chmod_file(task: &Arc<UserTask>, file: &FileItem, mode: usize) -> SysResult {
+    let uid = task.inner_map(|pcb| pcb.uid);
+    if uid != 0 && node_stat(&file.inner)?.uid as u32 != uid {
+        return Err(LinuxError::EPERM);
+    }
+    check_writable(&file.path().map_err(from_vfs)?)?;
+    fs::chmod(&file.inner, mode as u32).map_err(from_vfs)?;
+    Ok(0)
+}
+
+fn chown_file(task: &Arc<UserTask>, file: &FileItem, uid: usize, gid: usize) -> SysResult {
+    // -1 keeps the current owner or group.
+    let uid = Some(uid as u32).filter(|x| *x != u32::MAX);
+    let gid = Some(gid as u32).filter(|x| *x != u32::MAX);
+    let (task_uid, task_gid) = task.inner_map(|pcb| (pcb.uid, pcb.gid));
+    let stat = node_stat(&file.inner)?;
+    // only root can give the file away, the owner can change the group to its own group.
+    if task_uid != 0
+        && (stat.uid as u32 != task_uid
+            || uid.is_some_and(|x| x != task_uid)
+            || gid.is_some_and(|x| x != task_gid && x != stat.gid as u32))
+    {
+        return Err(LinuxError::EPERM);
+    }
+    check_writable(&file.path().map_err(from_vfs)?)?;
+    fs::chown(&file.inner, uid, gid).map_err(from_vfs)?;
+    Ok(0)
+}
+
+/// Convert the range of the record lock to [start, end).
+fn lock_range(file: &FileItem, flock: &Flock) -> Result<(u64, u64), LinuxError> {
+    let base = match flock.l_whence {
+        // SEEK_SET, SEEK_CUR and SEEK_END.
+        0 => 0,
+        1 => *file.offset.lock() as i64,
+        2 => file.metadata().map_err(from_vfs)?.size as i64,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let start = base.checked_add(flock.l_start).ok_or(LinuxError::EINVAL)?;
+    // len 0 locks to the end of the file, the negative len locks the bytes before start.
+    let (start, end) = match flock.l_len {
+        0 => (start, u64::MAX),
+        len if len > 0 => (start, start.saturating_add(len) as u64),
+        len => (start + len, start as u64),
+    };
+    if start < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok((start as u64, end))
+}
+
 impl UserTaskContainer {
     pub async fn sys_dup(&self, fd: usize) -> SysResult {
         debug!("sys_dup3 @ fd_src: {}", fd);
@@ -124,12 +326,21 @@ impl UserTaskContainer {
             dir_fd as isize, path, mode
         );
         let dir = to_node(&self.task, dir_fd, path)?;
-        if path == "/" {
+        // the dangling link exists too, its target isn't created.
+        if path == "/" || dir.dentry_open(path, OpenFlags::O_NOFOLLOW).is_ok() {
             return Err(LinuxError::EEXIST);
         }
+        let (parent, _) = split_path(path.trim_end_matches('/'));
+        let parent = dir
+            .dentry_open(parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        check_writable(&parent.path().map_err(from_vfs)?)?;
+        check_access(&self.task, &parent.inner, W_OK | X_OK)?;
 
-        dir.dentry_open(path, OpenFlags::O_CREAT | OpenFlags::O_DIRECTORY)
+        let new_dir = dir
+            .dentry_open(path, OpenFlags::O_CREAT | OpenFlags::O_DIRECTORY)
             .map_err(from_vfs)?;
+        init_owner(&self.task, &new_dir.inner, Some(mode))?;
         // let path_str = rebuild_path(path);
         // let paths: Vec<&str> = path_str.split("/").collect();
         // let mut pfile = dir.inner.clone();
@@ -144,6 +355,62 @@ impl UserTaskContainer {
         Ok(0)
     }
 
+    /// Make the special file, the regular file is created like open(2).
+    pub async fn sys_mknodat(
+        &self,
+        dir_fd: usize,
+        path: UserRef<i8>,
+        mode: usize,
+        dev: usize,
+    ) -> SysResult {
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_mknodat @ dir_fd: {}, path: {}, mode: {:#o}, dev: {:#x}",
+            dir_fd as isize, path, mode, dev
+        );
+        let file_type = mode as u32 & S_IFMT;
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Err(LinuxError::EEXIST);
+        }
+        let dir = to_node(&self.task, dir_fd, path)?
+            .dentry_open(parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        if dir.dentry_open(name, OpenFlags::O_NOFOLLOW).is_ok() {
+            return Err(LinuxError::EEXIST);
+        }
+        check_writable(&dir.path().map_err(from_vfs)?)?;
+        check_access(&self.task, &dir.inner, W_OK | X_OK)?;
+        match file_type {
+            0 | S_IFREG => {
+                dir.dentry_open(name, OpenFlags::O_CREAT)
+                    .map_err(from_vfs)?;
+            }
+            // only root can make the device nodes.
+            S_IFCHR | S_IFBLK if self.task.inner_map(|pcb| pcb.uid) != 0 => {
+                return Err(LinuxError::EPERM)
+            }
+            S_IFCHR | S_IFBLK | S_IFIFO | S_IFSOCK => {
+                let file_type = StatMode::from_bits_truncate(file_type);
+                fs::mknod(&dir.inner, name, file_type, dev as _).map_err(from_vfs)?;
+            }
+            S_IFDIR => return Err(LinuxError::EPERM),
+            _ => return Err(LinuxError::EINVAL),
+        }
+        dir.invalidate(name);
+        let node = dir
+            .dentry_open(name, OpenFlags::O_NOFOLLOW)
+            .map_err(from_vfs)?;
+        init_owner(&self.task, &node.inner, Some(mode))?;
+        node.notify(InotifyMask::IN_CREATE);
+        Ok(0)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_mknod(&self, path: UserRef<i8>, mode: usize, dev: usize) -> SysResult {
+        self.sys_mknodat(AT_CWD, path, mode, dev).await
+    }
+
     pub async fn sys_renameat2(
         &self,
         olddir_fd: usize,
@@ -156,32 +423,84 @@ impl UserTaskContainer {
             "sys_renameat2 @ olddir_fd: {}, oldpath: {}, newdir_fd: {}, newpath: {}, flags: {}",
             olddir_fd, oldpath, newdir_fd, newpath, flags
         );
-
+        // RENAME_EXCHANGE and RENAME_WHITEOUT aren't supported.
+        if flags & !RENAME_NOREPLACE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
         let old_path = oldpath.get_cstr().map_err(|_| LinuxError::EINVAL)?;
-        let old_dir = to_node(&self.task, olddir_fd, old_path)?;
+        let new_path = newpath.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        let (old_parent, old_name) = split_path(old_path.trim_end_matches('/'));
+        let (new_parent, new_name) = split_path(new_path.trim_end_matches('/'));
+        if [old_name, new_name]
+            .iter()
+            .any(|x| x.is_empty() || *x == "." || *x == "..")
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let old_dir = to_node(&self.task, olddir_fd, old_path)?
+            .dentry_open(old_parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        let new_dir = to_node(&self.task, newdir_fd, new_path)?
+            .dentry_open(new_parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        // the symbolic link itself is renamed.
         let old_file = old_dir
-            .dentry_open(old_path, OpenFlags::empty())
+            .dentry_open(old_name, OpenFlags::O_NOFOLLOW)
             .map_err(from_vfs)?;
-
-        let old_file_type = old_file.metadata().map_err(from_vfs)?.file_type;
-        let new_path = newpath.get_cstr().map_err(|_| LinuxError::EINVAL)?;
-        let new_dir = to_node(&self.task, newdir_fd, new_path)?;
-        if old_file_type == FileType::File {
-            let new_file = new_dir
-                .dentry_open(new_path, OpenFlags::empty())
-                .expect("can't find new file");
-            // TODO: Check the file exists
-            let file_size = old_file.metadata().map_err(from_vfs)?.size;
-            let mut buffer = vec![0u8; file_size];
-            old_file.read(&mut buffer).map_err(from_vfs)?;
-            new_file.write(&buffer).map_err(from_vfs)?;
-            new_file.truncate(buffer.len()).map_err(from_vfs)?;
-        } else if old_file_type == FileType::Directory {
-            new_dir.mkdir(new_path).map_err(from_vfs)?;
-        } else {
-            panic!("can't handle the file: {:?} now", old_file_type);
+        let old_file_path = old_file.path().map_err(from_vfs)?;
+        let new_dir_path = new_dir.path().map_err(from_vfs)?;
+        check_writable(&old_file_path)?;
+        check_writable(&new_dir_path)?;
+        if mount_dev(&old_file_path) != mount_dev(&new_dir_path) {
+            return Err(LinuxError::EXDEV);
         }
+        check_access(&self.task, &old_dir.inner, W_OK | X_OK)?;
+        check_access(&self.task, &new_dir.inner, W_OK | X_OK)?;
+        check_sticky(&self.task, &old_dir.inner, &old_file.inner)?;
 
+        let is_dir = old_file.metadata().map_err(from_vfs)?.file_type == FileType::Directory;
+        // the directory can't be moved into itself.
+        if is_dir && (new_dir_path + "/").starts_with(&(old_file_path + "/")) {
+            return Err(LinuxError::EINVAL);
+        }
+        // the new name is replaced, the directory can only replace an empty directory.
+        if let Ok(new_file) = new_dir.dentry_open(new_name, OpenFlags::O_NOFOLLOW) {
+            if flags & RENAME_NOREPLACE != 0 {
+                return Err(LinuxError::EEXIST);
+            }
+            // the both names are the links of the same file.
+            let (old_stat, new_stat) = (node_stat(&old_file.inner)?, node_stat(&new_file.inner)?);
+            if (old_stat.dev, old_stat.ino) == (new_stat.dev, new_stat.ino) {
+                return Ok(0);
+            }
+            let new_is_dir =
+                new_file.metadata().map_err(from_vfs)?.file_type == FileType::Directory;
+            match (is_dir, new_is_dir) {
+                (true, false) => return Err(LinuxError::ENOTDIR),
+                (false, true) => return Err(LinuxError::EISDIR),
+                (true, true) if !new_file.inner.read_dir().map_err(from_vfs)?.is_empty() => {
+                    return Err(LinuxError::ENOTEMPTY)
+                }
+                _ => {}
+            }
+            check_sticky(&self.task, &new_dir.inner, &new_file.inner)?;
+        }
+        // the directory can't be linked, it is only moved by the filesystem.
+        let res = match fs::rename(&old_dir.inner, old_name, &new_dir.inner, new_name) {
+            Err(VfsError::NotSupported) if is_dir => Err(LinuxError::EXDEV),
+            Err(VfsError::NotSupported) => {
+                link_rename(&old_dir.inner, old_name, &new_dir.inner, new_name).map_err(from_vfs)
+            }
+            res => res.map_err(from_vfs),
+        };
+        old_dir.invalidate(old_name);
+        new_dir.invalidate(new_name);
+        res?;
+        if let Ok(new_file) = new_dir.dentry_open(new_name, OpenFlags::O_NOFOLLOW)
+            && let (Some(old), Some(new)) = (&old_file.dentry, &new_file.dentry)
+        {
+            inotify::notify_rename(old, new);
+        }
         Ok(0)
     }
 
@@ -201,14 +520,113 @@ impl UserTaskContainer {
             "sys_unlinkat @ dir_fd: {}, path: {}, flags: {}",
             dir_fd as isize, path, flags
         );
-        let flags = OpenFlags::from_bits_truncate(flags);
+        // remove the symbolic link itself.
         let dir = to_node(&self.task, dir_fd, path)?;
-        let file = dir.dentry_open(path, flags).map_err(from_vfs)?;
+        let file = dir
+            .dentry_open(path, OpenFlags::O_NOFOLLOW)
+            .map_err(from_vfs)?;
+        let is_dir = file.metadata().map_err(from_vfs)?.file_type == FileType::Directory;
+        match (is_dir, flags & AT_REMOVEDIR != 0) {
+            (true, false) => return Err(LinuxError::EISDIR),
+            (false, true) => return Err(LinuxError::ENOTDIR),
+            _ => {}
+        }
+        check_writable(&file.path().map_err(from_vfs)?)?;
+        if let Some(parent) = file.dentry.as_ref().and_then(|x| x.parent.upgrade()) {
+            check_access(&self.task, &parent.node, W_OK | X_OK)?;
+            check_sticky(&self.task, &parent.node, &file.inner)?;
+        }
 
         file.remove_self().map_err(from_vfs)?;
         Ok(0)
     }
 
+    pub async fn sys_symlinkat(
+        &self,
+        target: UserRef<i8>,
+        dir_fd: usize,
+        path: UserRef<i8>,
+    ) -> SysResult {
+        let target = target.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_symlinkat @ target: {}, dir_fd: {}, path: {}",
+            target, dir_fd as isize, path
+        );
+        if target.is_empty() {
+            return Err(LinuxError::ENOENT);
+        }
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Err(LinuxError::EEXIST);
+        }
+        let dir = to_node(&self.task, dir_fd, path)?
+            .dentry_open(parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        check_writable(&dir.path().map_err(from_vfs)?)?;
+        check_access(&self.task, &dir.inner, W_OK | X_OK)?;
+        fs::symlink(&dir.inner, name, target).map_err(from_vfs)?;
+        dir.invalidate(name);
+        let symlink = dir
+            .dentry_open(name, OpenFlags::O_NOFOLLOW)
+            .map_err(from_vfs)?;
+        init_owner(&self.task, &symlink.inner, None)?;
+        symlink.notify(InotifyMask::IN_CREATE);
+        Ok(0)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_symlink(&self, target: UserRef<i8>, path: UserRef<i8>) -> SysResult {
+        self.sys_symlinkat(target, AT_CWD, path).await
+    }
+
+    pub async fn sys_linkat(
+        &self,
+        old_dir_fd: usize,
+        old_path: UserRef<i8>,
+        new_dir_fd: usize,
+        new_path: UserRef<i8>,
+        flags: usize,
+    ) -> SysResult {
+        let old_path = old_path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        let new_path = new_path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_linkat @ old_dir_fd: {}, old_path: {}, new_dir_fd: {}, new_path: {}, flags: {:#x}",
+            old_dir_fd as isize, old_path, new_dir_fd as isize, new_path, flags
+        );
+        // the symbolic link itself is linked unless AT_SYMLINK_FOLLOW is given.
+        let open_flags = match flags & AT_SYMLINK_FOLLOW != 0 {
+            true => OpenFlags::NONE,
+            false => OpenFlags::O_NOFOLLOW,
+        };
+        let file = to_node(&self.task, old_dir_fd, old_path)?
+            .dentry_open(old_path, open_flags)
+            .map_err(from_vfs)?;
+        if file.metadata().map_err(from_vfs)?.file_type == FileType::Directory {
+            return Err(LinuxError::EPERM);
+        }
+        let (parent, name) = split_path(new_path);
+        if name.is_empty() {
+            return Err(LinuxError::EEXIST);
+        }
+        let dir = to_node(&self.task, new_dir_fd, new_path)?
+            .dentry_open(parent, OpenFlags::O_DIRECTORY)
+            .map_err(from_vfs)?;
+        let dir_path = dir.path().map_err(from_vfs)?;
+        if mount_dev(&file.path().map_err(from_vfs)?) != mount_dev(&dir_path) {
+            return Err(LinuxError::EXDEV);
+        }
+        check_writable(&dir_path)?;
+        check_access(&self.task, &dir.inner, W_OK | X_OK)?;
+        dir.link(name, file.inner.clone()).map_err(from_vfs)?;
+        Ok(0)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_link(&self, old_path: UserRef<i8>, new_path: UserRef<i8>) -> SysResult {
+        self.sys_linkat(AT_CWD, old_path, AT_CWD, new_path, 0).await
+    }
+
     pub async fn sys_openat(
         &self,
         fd: usize,
@@ -227,7 +645,46 @@ impl UserTaskContainer {
             fd as isize, filename, flags, mode
         );
         let dir = to_node(&self.task, fd, filename)?;
+        // the new file needs the write permission of the directory.
+        let create = flags.contains(OpenFlags::O_CREAT)
+            && dir
+                .dentry_open(filename, flags.difference(OpenFlags::O_CREAT))
+                .is_err();
+        if create {
+            let parent = dir
+                .dentry_open(split_path(filename).0, OpenFlags::O_DIRECTORY)
+                .map_err(from_vfs)?;
+            check_access(&self.task, &parent.inner, W_OK | X_OK)?;
+        }
         let file = dir.dentry_open(filename, flags).map_err(from_vfs)?;
+        // O_NOFOLLOW keeps the last symbolic link, it can't be opened.
+        if flags.contains(OpenFlags::O_NOFOLLOW)
+            && !flags.contains(OpenFlags::O_PATH)
+            && file.metadata().is_ok_and(|x| x.file_type == FileType::Link)
+        {
+            return Err(LinuxError::ELOOP);
+        }
+        let write = file.options.contains(FileOptions::W);
+        if write {
+            check_writable(&file.path().map_err(from_vfs)?)?;
+        }
+        if create {
+            init_owner(&self.task, &file.inner, Some(mode))?;
+        } else if !flags.contains(OpenFlags::O_PATH) {
+            let mut mask = match write {
+                true => W_OK,
+                false => 0,
+            };
+            if !flags.contains(OpenFlags::O_WRONLY) {
+                mask |= R_OK;
+            }
+            check_access(&self.task, &file.inner, mask)?;
+        }
+        let file = match flags.contains(OpenFlags::O_PATH) {
+            true => file,
+            false => file.open_device().map_err(from_vfs)?,
+        };
+        file.notify_open();
         let fd = self.task.alloc_fd().ok_or(LinuxError::EMFILE)?;
         self.task.set_fd(fd, file);
         debug!("sys_openat @ ret fd: {}", fd);
@@ -247,32 +704,127 @@ impl UserTaskContainer {
         mode: usize,
         flags: usize,
     ) -> SysResult {
-        let open_flags = OpenFlags::from_bits_truncate(flags);
         let filename = if filename.is_valid() {
             filename.get_cstr().map_err(|_| LinuxError::EINVAL)?
         } else {
             ""
         };
         debug!(
-            "sys_accessat @ fd: {}, filename: {}, flags: {:?}, mode: {}",
-            fd as isize, filename, open_flags, mode
+            "sys_accessat @ fd: {}, filename: {}, flags: {:#x}, mode: {}",
+            fd as isize, filename, flags, mode
         );
+        let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+            true => OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::NONE,
+        };
         let dir = to_node(&self.task, fd, filename)?;
-        let _node =
+        let node =
             dentry_open(dir.dentry.clone().unwrap(), filename, open_flags).map_err(from_vfs)?;
+        if mode == F_OK {
+            return Ok(0);
+        }
+        if mode & W_OK != 0 {
+            check_writable(&node.path())?;
+        }
+        check_access(&self.task, &node.node, mode & (R_OK | W_OK | X_OK))?;
         Ok(0)
     }
 
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_access(&self, filename: UserRef<i8>, mode: usize) -> SysResult {
+        self.sys_faccess_at(AT_CWD, filename, mode, 0).await
+    }
+
+    pub async fn sys_fchmodat(
+        &self,
+        dir_fd: usize,
+        path: UserRef<i8>,
+        mode: usize,
+        flags: usize,
+    ) -> SysResult {
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_fchmodat @ dir_fd: {}, path: {}, mode: {:#o}, flags: {:#x}",
+            dir_fd as isize, path, mode, flags
+        );
+        let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+            true => OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::NONE,
+        };
+        let file = to_node(&self.task, dir_fd, path)?
+            .dentry_open(path, open_flags)
+            .map_err(from_vfs)?;
+        chmod_file(&self.task, &file, mode)
+    }
+
+    pub async fn sys_fchmod(&self, fd: usize, mode: usize) -> SysResult {
+        debug!("sys_fchmod @ fd: {}, mode: {:#o}", fd, mode);
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        chmod_file(&self.task, &file, mode)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_chmod(&self, path: UserRef<i8>, mode: usize) -> SysResult {
+        self.sys_fchmodat(AT_CWD, path, mode, 0).await
+    }
+
+    pub async fn sys_fchownat(
+        &self,
+        dir_fd: usize,
+        path: UserRef<i8>,
+        uid: usize,
+        gid: usize,
+        flags: usize,
+    ) -> SysResult {
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_fchownat @ dir_fd: {}, path: {}, uid: {}, gid: {}, flags: {:#x}",
+            dir_fd as isize, path, uid as isize, gid as isize, flags
+        );
+        let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+            true => OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::NONE,
+        };
+        let file = to_node(&self.task, dir_fd, path)?
+            .dentry_open(path, open_flags)
+            .map_err(from_vfs)?;
+        chown_file(&self.task, &file, uid, gid)
+    }
+
+    pub async fn sys_fchown(&self, fd: usize, uid: usize, gid: usize) -> SysResult {
+        debug!(
+            "sys_fchown @ fd: {}, uid: {}, gid: {}",
+            fd, uid as isize, gid as isize
+        );
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        chown_file(&self.task, &file, uid, gid)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_chown(&self, path: UserRef<i8>, uid: usize, gid: usize) -> SysResult {
+        self.sys_fchownat(AT_CWD, path, uid, gid, 0).await
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_lchown(&self, path: UserRef<i8>, uid: usize, gid: usize) -> SysResult {
+        self.sys_fchownat(AT_CWD, path, uid, gid, AT_SYMLINK_NOFOLLOW)
+            .await
+    }
+
+    pub async fn sys_umask(&self, mask: usize) -> SysResult {
+        debug!("sys_umask @ mask: {:#o}", mask);
+        let old = self
+            .task
+            .inner_map(|pcb| core::mem::replace(&mut pcb.umask, mask as u32 & 0o777));
+        Ok(old as usize)
+    }
+
     pub async fn sys_fstat(&self, fd: usize, stat_ptr: UserRef<Stat>) -> SysResult {
         debug!("sys_fstat @ fd: {} stat_ptr: {}", fd, stat_ptr);
         let stat_ref = stat_ptr.get_mut();
 
         let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
         file.stat(stat_ref).map_err(from_vfs)?;
-        stat_ref.mode |= StatMode::OWNER_MASK;
-        if let Ok(metadata) = file.metadata() {
-            stat_ref.ino = metadata.filename.as_ptr() as _;
-        }
         Ok(0)
     }
 
@@ -281,10 +833,11 @@ impl UserTaskContainer {
         dir_fd: usize,
         path_ptr: UserRef<i8>,
         stat_ptr: UserRef<Stat>,
+        flags: usize,
     ) -> SysResult {
         debug!(
-            "sys_fstatat @ dir_fd: {}, path_ptr:{}, stat_ptr: {}",
-            dir_fd as isize, path_ptr, stat_ptr
+            "sys_fstatat @ dir_fd: {}, path_ptr:{}, stat_ptr: {}, flags: {:#x}",
+            dir_fd as isize, path_ptr, stat_ptr, flags
         );
         let path = path_ptr.get_cstr().map_err(|_| LinuxError::EINVAL)?;
         debug!(
@@ -293,24 +846,116 @@ impl UserTaskContainer {
         );
         let stat = stat_ptr.get_mut();
 
+        let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+            true => OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::NONE,
+        };
         let dir = to_node(&self.task, dir_fd, path)?;
-        dentry_open(dir.dentry.clone().unwrap(), &path, OpenFlags::NONE)
-            .map_err(from_vfs)?
-            .node
-            .stat(stat)
-            .map_err(from_vfs)?;
-        stat.mode |= StatMode::OWNER_MASK;
+        let dentry =
+            dentry_open(dir.dentry.clone().unwrap(), &path, open_flags).map_err(from_vfs)?;
+        dentry.node.stat(stat).map_err(from_vfs)?;
+        stat.dev = mount_dev(&dentry.path());
+        Ok(0)
+    }
+
+    /// The birth time and the attributes are filled if the filesystem keeps them.
+    pub async fn sys_statx(
+        &self,
+        dir_fd: usize,
+        path_ptr: UserRef<i8>,
+        flags: usize,
+        mask: u32,
+        statx_ptr: UserRef<Statx>,
+    ) -> SysResult {
+        let path = path_ptr.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_statx @ dir_fd: {}, path: {}, flags: {:#x}, mask: {:#x}, statx_ptr: {}",
+            dir_fd as isize, path, flags, mask, statx_ptr
+        );
+        let valid_flags =
+            AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH | AT_NO_AUTOMOUNT | AT_STATX_SYNC_TYPE;
+        if flags & !valid_flags != 0
+            || flags & AT_STATX_SYNC_TYPE == AT_STATX_SYNC_TYPE
+            || mask & STATX_RESERVED != 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        // the file without the path, like a pipe, has no mount point.
+        let (node, path) = match path.is_empty() {
+            true if flags & AT_EMPTY_PATH == 0 => return Err(LinuxError::ENOENT),
+            true => {
+                let file = to_node(&self.task, dir_fd, path)?;
+                (file.inner.clone(), file.path().ok())
+            }
+            false => {
+                let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+                    true => OpenFlags::O_NOFOLLOW,
+                    false => OpenFlags::NONE,
+                };
+                let dir = to_node(&self.task, dir_fd, path)?;
+                let dentry = dir.dentry.clone().ok_or(LinuxError::ENOTDIR)?;
+                let dentry = dentry_open(dentry, path, open_flags).map_err(from_vfs)?;
+                (dentry.node.clone(), Some(dentry.path()))
+            }
+        };
+        let stat = node_stat(&node)?;
+
+        let statx = statx_ptr.get_mut();
+        *statx = Statx {
+            mask: STATX_BASIC_STATS,
+            blksize: stat.blksize as _,
+            nlink: stat.nlink as _,
+            uid: stat.uid as _,
+            gid: stat.gid as _,
+            mode: stat.mode.bits() as _,
+            ino: stat.ino as _,
+            size: stat.size as _,
+            blocks: stat.blocks as _,
+            attributes_mask: STATX_ATTR_MOUNT_ROOT,
+            atime: statx_time(stat.atime),
+            ctime: statx_time(stat.ctime),
+            mtime: statx_time(stat.mtime),
+            rdev_major: major(stat.rdev as _),
+            rdev_minor: minor(stat.rdev as _),
+            dev_major: major(stat.dev as _),
+            dev_minor: minor(stat.dev as _),
+            ..Default::default()
+        };
+        if let Some(path) = path {
+            let (mnt_id, is_root) = mount_id(&path);
+            let dev = mount_dev(&path);
+            statx.mask |= STATX_MNT_ID;
+            statx.mnt_id = mnt_id as _;
+            statx.dev_major = major(dev);
+            statx.dev_minor = minor(dev);
+            if is_root {
+                statx.attributes |= STATX_ATTR_MOUNT_ROOT;
+            }
+        }
+        if let Ok(btime) = fs::birth_time(&node) {
+            statx.mask |= STATX_BTIME;
+            statx.btime = statx_time(btime);
+        }
+        if let Ok(attributes) = fs::attributes(&node) {
+            statx.attributes |= attributes;
+            statx.attributes_mask |= STATX_ATTR_COMPRESSED
+                | STATX_ATTR_IMMUTABLE
+                | STATX_ATTR_APPEND
+                | STATX_ATTR_NODUMP
+                | STATX_ATTR_ENCRYPTED;
+        }
         Ok(0)
     }
 
     #[cfg(target_arch = "x86_64")]
     pub async fn sys_stat(&self, path: UserRef<i8>, stat_ptr: UserRef<Stat>) -> SysResult {
-        self.sys_fstatat(AT_CWD, path, stat_ptr).await
+        self.sys_fstatat(AT_CWD, path, stat_ptr, 0).await
     }
 
     #[cfg(target_arch = "x86_64")]
     pub async fn sys_lstat(&self, path: UserRef<i8>, stat_ptr: UserRef<Stat>) -> SysResult {
-        self.sys_fstatat(AT_CWD, path, stat_ptr).await
+        self.sys_fstatat(AT_CWD, path, stat_ptr, AT_SYMLINK_NOFOLLOW)
+            .await
     }
 
     pub async fn sys_statfs(
@@ -390,35 +1035,77 @@ impl UserTaskContainer {
         dir: UserRef<i8>,
         fstype: UserRef<i8>,
         flags: usize,
-        data: usize,
+        data: UserRef<i8>,
     ) -> SysResult {
-        let special = special.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        // the source and the type are ignored by the remount and may be null.
+        let special = match special.is_valid() {
+            true => special.get_cstr().map_err(|_| LinuxError::EINVAL)?,
+            false => "",
+        };
         let dir = dir.get_cstr().map_err(|_| LinuxError::EINVAL)?;
-        let fstype = fstype.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        let fstype = match fstype.is_valid() {
+            true => fstype.get_cstr().map_err(|_| LinuxError::EINVAL)?,
+            false => "",
+        };
+        // the options of the filesystem, like lowerdir=<dir>,upperdir=<dir> of the overlay.
+        let data = match data.is_valid() {
+            true => data.get_cstr().map_err(|_| LinuxError::EINVAL)?,
+            false => "",
+        };
         debug!(
-            "sys_mount @ special: {}, dir: {}, fstype: {}, flags: {}, data: {:#x}",
+            "sys_mount @ special: {}, dir: {}, fstype: {}, flags: {}, data: {}",
             special, dir, fstype, flags, data
         );
 
-        let dev_node = FileItem::fs_open(special, OpenFlags::NONE).map_err(from_vfs)?;
-        dev_node.mount(dir).map_err(from_vfs)?;
+        let flags = MountFlags::from_bits_truncate(flags);
+        fs::mount::mount(special, dir, fstype, flags, data).map_err(|err| match err {
+            VfsError::NotSupported => LinuxError::ENODEV,
+            _ => from_vfs(err),
+        })?;
         Ok(0)
     }
 
     pub async fn sys_umount2(&self, special: UserRef<i8>, flags: usize) -> SysResult {
         let special = special.get_cstr().map_err(|_| LinuxError::EINVAL)?;
         debug!("sys_umount @ special: {}, flags: {}", special, flags);
-        match special.starts_with("/dev") {
-            true => {
-                todo!("unmount dev");
-                // let dev = dentry_open(dentry_root(), special, OpenFlags::NONE).map_err(from_vfs)?;
-                // dev.node.umount().map_err(from_vfs)?;
-            }
-            false => {
-                DentryNode::unmount(String::from(special)).map_err(from_vfs)?;
-            }
-        };
+        fs::mount::umount(special).map_err(|err| match err {
+            VfsError::NoMountedPoint => LinuxError::EINVAL,
+            VfsError::InvalidInput => LinuxError::EBUSY,
+            _ => from_vfs(err),
+        })?;
+        Ok(0)
+    }
 
+    pub async fn sys_fsync(&self, fd: usize) -> SysResult {
+        debug!("[task {}] sys_fsync @ fd: {}", self.tid, fd);
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        // the files in memory have nothing to write back.
+        match file.flush() {
+            Ok(()) | Err(VfsError::NotSupported) => Ok(0),
+            Err(err) => Err(from_vfs(err)),
+        }
+    }
+
+    /// The metadata is written with the data, fdatasync is the same as fsync.
+    pub async fn sys_fdatasync(&self, fd: usize) -> SysResult {
+        debug!("[task {}] sys_fdatasync @ fd: {}", self.tid, fd);
+        self.sys_fsync(fd).await
+    }
+
+    pub async fn sys_syncfs(&self, fd: usize) -> SysResult {
+        debug!("[task {}] sys_syncfs @ fd: {}", self.tid, fd);
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        // the files without path, like pipes, aren't in any filesystem.
+        match file.path() {
+            Ok(path) => fs::mount::sync_mount(&path).map_err(from_vfs)?,
+            Err(_) => fs::sync(),
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_sync(&self) -> SysResult {
+        debug!("[task {}] sys_sync", self.tid);
+        fs::sync();
         Ok(0)
     }
 
@@ -440,16 +1127,23 @@ impl UserTaskContainer {
             self.tid, fd, offset as isize, whence
         );
 
-        self.task
-            .get_fd(fd)
-            .ok_or(LinuxError::EBADF)?
-            .seek(match whence {
-                0 => SeekFrom::SET(offset),
-                1 => SeekFrom::CURRENT(offset as isize),
-                2 => SeekFrom::END(offset as isize),
-                _ => return Err(LinuxError::EINVAL),
-            })
-            .map_err(from_vfs)
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        // ENXIO if there isn't any data or hole after the offset.
+        let seek_sparse = |found: Result<Option<usize>, VfsError>| {
+            found.map_err(from_vfs)?.ok_or(LinuxError::ENXIO)
+        };
+        match whence {
+            SEEK_DATA => return seek_sparse(file.seek_data(offset)),
+            SEEK_HOLE => return seek_sparse(file.seek_hole(offset)),
+            _ => {}
+        }
+        file.seek(match whence {
+            0 => SeekFrom::SET(offset),
+            1 => SeekFrom::CURRENT(offset as isize),
+            2 => SeekFrom::END(offset as isize),
+            _ => return Err(LinuxError::EINVAL),
+        })
+        .map_err(from_vfs)
     }
 
     pub async fn sys_ioctl(
@@ -488,10 +1182,132 @@ impl UserTaskContainer {
                 self.task.set_fd(fd, file);
                 Ok(0)
             }
+            FcntlCmd::GETLK | FcntlCmd::OFD_GETLK => {
+                let flock = UserRef::<Flock>::from(arg).get_mut();
+                let owner = match cmd {
+                    FcntlCmd::GETLK => LockOwner::Process(self.task.process_id),
+                    _ => LockOwner::File(Arc::as_ptr(&file) as usize),
+                };
+                // the OFD lock requires l_pid to be zero.
+                if matches!(owner, LockOwner::File(_)) && flock.l_pid != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                let ltype = match flock.l_type {
+                    F_RDLCK => LockType::Read,
+                    F_WRLCK => LockType::Write,
+                    _ => return Err(LinuxError::EINVAL),
+                };
+                let (start, end) = lock_range(&file, flock)?;
+                let lock = FileLock {
+                    class: LockClass::Record,
+                    owner,
+                    ltype,
+                    start,
+                    end,
+                };
+                match test_lock(file.lock_key().map_err(from_vfs)?, &lock) {
+                    Some(x) => {
+                        flock.l_type = match x.ltype {
+                            LockType::Read => F_RDLCK,
+                            LockType::Write => F_WRLCK,
+                        };
+                        flock.l_whence = 0;
+                        flock.l_start = x.start as i64;
+                        flock.l_len = match x.end {
+                            u64::MAX => 0,
+                            end => (end - x.start) as i64,
+                        };
+                        flock.l_pid = match x.owner {
+                            LockOwner::Process(pid) => pid as i32,
+                            LockOwner::File(_) => -1,
+                        };
+                    }
+                    None => flock.l_type = F_UNLCK,
+                }
+                Ok(0)
+            }
+            FcntlCmd::SETLK | FcntlCmd::SETLKW | FcntlCmd::OFD_SETLK | FcntlCmd::OFD_SETLKW => {
+                let flock = UserRef::<Flock>::from(arg).get_ref();
+                let owner = match cmd {
+                    FcntlCmd::SETLK | FcntlCmd::SETLKW => LockOwner::Process(self.task.process_id),
+                    _ => LockOwner::File(Arc::as_ptr(&file) as usize),
+                };
+                // the OFD lock requires l_pid to be zero.
+                if matches!(owner, LockOwner::File(_)) && flock.l_pid != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                let (start, end) = lock_range(&file, flock)?;
+                let key = file.lock_key().map_err(from_vfs)?;
+                // the lock needs the file opened for reading or writing.
+                let readable = !file.flags.lock().contains(OpenFlags::O_WRONLY);
+                let writable = file
+                    .flags
+                    .lock()
+                    .intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR);
+                let ltype = match flock.l_type {
+                    F_RDLCK if readable => LockType::Read,
+                    F_WRLCK if writable => LockType::Write,
+                    F_RDLCK | F_WRLCK => return Err(LinuxError::EBADF),
+                    F_UNLCK => {
+                        unlock(key, LockClass::Record, owner, start, end);
+                        return Ok(0);
+                    }
+                    _ => return Err(LinuxError::EINVAL),
+                };
+                let lock = FileLock {
+                    class: LockClass::Record,
+                    owner,
+                    ltype,
+                    start,
+                    end,
+                };
+                let wait = matches!(cmd, FcntlCmd::SETLKW | FcntlCmd::OFD_SETLKW);
+                self.wait_lock(key, lock, wait).await
+            }
             _ => Ok(0),
         }
     }
 
+    /// Add the lock, wait in the queue of the file until the conflicting locks are released
+    /// if wait is set.
+    async fn wait_lock(&self, key: LockKey, lock: FileLock, wait: bool) -> SysResult {
+        loop {
+            match try_lock(key, lock, wait.then_some(self.tid)) {
+                Ok(()) => return Ok(0),
+                Err(LockError::Blocking) if wait => WaitLock(key, self.tid).await?,
+                Err(LockError::Blocking) => return Err(LinuxError::EAGAIN),
+                Err(LockError::Deadlock) => return Err(LinuxError::EDEADLK),
+            }
+        }
+    }
+
+    pub async fn sys_flock(&self, fd: usize, operation: usize) -> SysResult {
+        debug!(
+            "[task {}] sys_flock @ fd: {}, operation: {:#x}",
+            self.tid, fd, operation
+        );
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        let key = file.lock_key().map_err(from_vfs)?;
+        let owner = LockOwner::File(Arc::as_ptr(&file) as usize);
+        let ltype = match operation & !LOCK_NB {
+            LOCK_SH => LockType::Read,
+            LOCK_EX => LockType::Write,
+            LOCK_UN => {
+                unlock(key, LockClass::Flock, owner, 0, u64::MAX);
+                return Ok(0);
+            }
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let lock = FileLock {
+            class: LockClass::Flock,
+            owner,
+            ltype,
+            start: 0,
+            end: u64::MAX,
+        };
+        self.wait_lock(key, lock, operation & LOCK_NB == 0).await
+    }
+
     /// information source: https://man7.org/linux/man-pages/man2/utimensat.2.html
     ///
     /// Updated file timestamps are set to the greatest value supported
@@ -550,10 +1366,13 @@ impl UserTaskContainer {
             return Ok(0);
         }
 
-        dir.dentry_open(path, OpenFlags::O_RDONLY)
-            .map_err(from_vfs)?
-            .utimes(&mut times)
-            .map_err(from_vfs)?;
+        let open_flags = match flags & AT_SYMLINK_NOFOLLOW != 0 {
+            true => OpenFlags::O_RDONLY | OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::O_RDONLY,
+        };
+        let file = dir.dentry_open(path, open_flags).map_err(from_vfs)?;
+        check_writable(&file.path().map_err(from_vfs)?)?;
+        file.utimes(&mut times).map_err(from_vfs)?;
 
         Ok(0)
     }
@@ -573,23 +1392,15 @@ impl UserTaskContainer {
         let buffer = buffer.slice_mut_with_len(buffer_size);
         debug!("readlinkat @ filename: {}", filename);
 
-        let dir = to_node(&self.task, dir_fd, filename)?;
-
-        let ftype = dir
-            .open(filename, OpenFlags::NONE)
-            .map_err(from_vfs)?
-            .metadata()
-            .map_err(from_vfs)?
-            .file_type;
+        let file = to_node(&self.task, dir_fd, filename)?
+            .dentry_open(filename, OpenFlags::O_NOFOLLOW)
+            .map_err(from_vfs)?;
 
-        if FileType::Link != ftype {
+        if FileType::Link != file.metadata().map_err(from_vfs)?.file_type {
             return Err(LinuxError::EINVAL);
         }
 
-        let file_path = FileItem::fs_open(filename, OpenFlags::NONE)
-            .map_err(from_vfs)?
-            .resolve_link()
-            .map_err(from_vfs)?;
+        let file_path = file.resolve_link().map_err(from_vfs)?;
 
         let bytes = file_path.as_bytes();
 
@@ -878,6 +1689,118 @@ impl UserTaskContainer {
         Ok(0)
     }
 
+    pub async fn sys_fallocate(
+        &self,
+        fd: usize,
+        mode: usize,
+        offset: usize,
+        len: usize,
+    ) -> SysResult {
+        debug!(
+            "sys_fallocate @ fd: {}, mode: {:#x}, offset: {:#x}, len: {:#x}",
+            fd, mode, offset, len
+        );
+        if (offset as isize) < 0 || (len as isize) <= 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if mode & !(FALLOC_FL_KEEP_SIZE | FALLOC_FL_PUNCH_HOLE) != 0 {
+            return Err(LinuxError::EOPNOTSUPP);
+        }
+        let file = self.task.get_fd(fd).ok_or(LinuxError::EBADF)?;
+        match file.metadata().map(|x| x.file_type) {
+            Ok(FileType::File) => {}
+            Ok(FileType::Directory) => return Err(LinuxError::EISDIR),
+            Ok(_) => return Err(LinuxError::ENODEV),
+            // the pipes have no metadata.
+            Err(_) => return Err(LinuxError::ESPIPE),
+        }
+        let result = match mode & FALLOC_FL_PUNCH_HOLE != 0 {
+            // the size is never changed by punching a hole.
+            true if mode & FALLOC_FL_KEEP_SIZE == 0 => return Err(LinuxError::EOPNOTSUPP),
+            true => file.punch_hole(offset, len),
+            false => file.fallocate(offset, len, mode & FALLOC_FL_KEEP_SIZE != 0),
+        };
+        result.map_err(|err| match err {
+            VfsError::NotSupported => LinuxError::EOPNOTSUPP,
+            VfsError::StorageFull => LinuxError::ENOSPC,
+            _ => from_vfs(err),
+        })?;
+        Ok(0)
+    }
+
+    pub async fn sys_inotify_init1(&self, flags: usize) -> SysResult {
+        debug!("sys_inotify_init1 @ flags: {:#x}", flags);
+        let file = FileItem::new_dev(Inotify::new());
+        // IN_NONBLOCK is the same as O_NONBLOCK.
+        *file.flags.lock() = OpenFlags::from_bits_truncate(flags) & OpenFlags::O_NONBLOCK;
+        let fd = self.task.alloc_fd().ok_or(LinuxError::EMFILE)?;
+        self.task.set_fd(fd, file);
+        Ok(fd)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub async fn sys_inotify_init(&self) -> SysResult {
+        self.sys_inotify_init1(0).await
+    }
+
+    pub async fn sys_inotify_add_watch(
+        &self,
+        fd: usize,
+        path: UserRef<i8>,
+        mask: usize,
+    ) -> SysResult {
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_inotify_add_watch @ fd: {}, path: {}, mask: {:#x}",
+            fd, path, mask
+        );
+        let inotify = self
+            .task
+            .get_fd(fd)
+            .ok_or(LinuxError::EBADF)?
+            .inner
+            .clone()
+            .downcast_arc::<Inotify>()
+            .map_err(|_| LinuxError::EINVAL)?;
+        let mask = InotifyMask::from_bits_truncate(mask as u32);
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS)
+            || mask.contains(InotifyMask::IN_MASK_CREATE | InotifyMask::IN_MASK_ADD)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = match mask.contains(InotifyMask::IN_DONT_FOLLOW) {
+            true => OpenFlags::O_NOFOLLOW,
+            false => OpenFlags::NONE,
+        };
+        let file = to_node(&self.task, AT_CWD, path)?
+            .dentry_open(path, flags)
+            .map_err(from_vfs)?;
+        if mask.contains(InotifyMask::IN_ONLYDIR)
+            && file.metadata().map_err(from_vfs)?.file_type != FileType::Directory
+        {
+            return Err(LinuxError::ENOTDIR);
+        }
+        check_access(&self.task, &file.inner, R_OK)?;
+        let dentry = file.dentry.as_ref().ok_or(LinuxError::ENOENT)?;
+        let key = inotify::watch_key(dentry).map_err(from_vfs)?;
+        let wd = inotify.add_watch(key, mask).map_err(from_vfs)?;
+        Ok(wd as usize)
+    }
+
+    pub async fn sys_inotify_rm_watch(&self, fd: usize, wd: usize) -> SysResult {
+        debug!("sys_inotify_rm_watch @ fd: {}, wd: {}", fd, wd as i32);
+        let inotify = self
+            .task
+            .get_fd(fd)
+            .ok_or(LinuxError::EBADF)?
+            .inner
+            .clone()
+            .downcast_arc::<Inotify>()
+            .map_err(|_| LinuxError::EINVAL)?;
+        inotify.rm_watch(wd as i32).map_err(from_vfs)?;
+        Ok(0)
+    }
+
     pub async fn sys_epoll_create1(&self, flags: usize) -> SysResult {
         debug!("sys_epoll_create @ flags: {:#x}", flags);
         let file = Arc::new(EpollFile::new(flags));
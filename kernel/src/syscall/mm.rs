@@ -2,7 +2,7 @@ use core::ops::Add;
 
 use arch::USER_ADDR_MAX;
 use arch::{VirtAddr, VirtPage, PAGE_SIZE};
-use executor::MemArea;
+use executor::{MemArea, MemType};
 use frame_allocator::ceil_div;
 use log::debug;
 
@@ -76,11 +76,12 @@ impl UserTaskContainer {
                 .memset
                 .overlapping(addr.addr(), addr.addr() + len);
             if overlaped {
-                self.task.pcb.lock().memset.sub_area(
-                    addr.addr(),
-                    addr.addr() + len,
-                    &self.task.page_table,
-                );
+                self.task
+                    .pcb
+                    .lock()
+                    .memset
+                    .sub_area(addr.addr(), addr.addr() + len, &self.task.page_table)
+                    .map_err(from_vfs)?;
             }
         } else if self
             .task
@@ -92,6 +93,26 @@ impl UserTaskContainer {
             return Err(LinuxError::EINVAL);
         }
 
+        // the page cache is mapped at the first access, the private mapping copies it on write.
+        if let Some(file) = file.as_ref().filter(|x| x.page_cache().is_some()) {
+            if off % PAGE_SIZE != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            self.task.pcb.lock().memset.push(MemArea {
+                mtype: match flags.contains(MapFlags::MAP_SHARED) {
+                    true => MemType::ShareFile,
+                    false => MemType::Mmap,
+                },
+                mtrackers: vec![],
+                file: Some(file.get_bare_file()),
+                offset: off,
+                start: addr.addr(),
+                len,
+                rwx: prot.bits() as _,
+            });
+            return Ok(addr.into());
+        }
+
         if flags.contains(MapFlags::MAP_SHARED) {
             match &file {
                 Some(file) => self
@@ -149,6 +170,7 @@ impl UserTaskContainer {
                 offset: 0,
                 start: addr.addr(),
                 len,
+                rwx: 0b111,
             });
         };
 
@@ -161,10 +183,12 @@ impl UserTaskContainer {
 
     pub async fn sys_munmap(&self, start: usize, len: usize) -> SysResult {
         debug!("sys_munmap @ start: {:#x}, len: {:#x}", start, len);
-        self.task.inner_map(|pcb| {
-            pcb.memset
-                .sub_area(start, start + len, &self.task.page_table);
-        });
+        self.task
+            .inner_map(|pcb| {
+                pcb.memset
+                    .sub_area(start, start + len, &self.task.page_table)
+            })
+            .map_err(from_vfs)?;
         Ok(0)
     }
 
@@ -184,7 +208,15 @@ impl UserTaskContainer {
             "sys_msync @ addr: {:#x} len: {:#x} flags: {:?}",
             addr, len, flags
         );
-        // use it temporarily
+        // write back the shared file mappings in the range.
+        self.task
+            .pcb
+            .lock()
+            .memset
+            .iter()
+            .filter(|x| x.mtype == MemType::ShareFile && x.overlapping(addr, addr + len))
+            .try_for_each(|area| area.sync())
+            .map_err(from_vfs)?;
         Ok(0)
     }
 }
@@ -8,6 +8,7 @@ mod socket;
 mod sys;
 mod task;
 mod time;
+mod xattr;
 
 pub use socket::NET_SERVER;
 pub use task::exec_with_process;
@@ -16,7 +17,7 @@ use log::warn;
 
 use crate::user::UserTaskContainer;
 
-use self::consts::*;
+use self::{consts::*, xattr::XattrTarget};
 
 type SysResult = Result<usize, LinuxError>;
 
@@ -36,6 +37,10 @@ impl UserTaskContainer {
                 self.sys_mkdir_at(args[0] as _, args[1].into(), args[2] as _)
                     .await
             }
+            SYS_MKNODAT => {
+                self.sys_mknodat(args[0] as _, args[1].into(), args[2] as _, args[3] as _)
+                    .await
+            }
             SYS_READ => {
                 self.sys_read(args[0] as _, args[1].into(), args[2] as _)
                     .await
@@ -59,6 +64,20 @@ impl UserTaskContainer {
                 self.sys_unlinkat(args[0] as _, args[1].into(), args[2] as _)
                     .await
             }
+            SYS_SYMLINKAT => {
+                self.sys_symlinkat(args[0].into(), args[1] as _, args[2].into())
+                    .await
+            }
+            SYS_LINKAT => {
+                self.sys_linkat(
+                    args[0] as _,
+                    args[1].into(),
+                    args[2] as _,
+                    args[3].into(),
+                    args[4] as _,
+                )
+                .await
+            }
             SYS_FSTAT => self.sys_fstat(args[0] as _, args[1].into()).await,
             SYS_WAIT4 => {
                 self.sys_wait4(args[0] as _, args[1].into(), args[2] as _)
@@ -72,7 +91,7 @@ impl UserTaskContainer {
                     args[1].into(),
                     args[2].into(),
                     args[3] as _,
-                    args[4] as _,
+                    args[4].into(),
                 )
                 .await
             }
@@ -122,13 +141,25 @@ impl UserTaskContainer {
                     .await
             }
             SYS_FSTATAT => {
-                self.sys_fstatat(args[0] as _, args[1].into(), args[2].into())
+                self.sys_fstatat(args[0] as _, args[1].into(), args[2].into(), args[3] as _)
                     .await
             }
+            SYS_STATX => {
+                self.sys_statx(
+                    args[0],
+                    args[1].into(),
+                    args[2],
+                    args[3] as _,
+                    args[4].into(),
+                )
+                .await
+            }
             SYS_GETEUID => self.sys_geteuid().await,
             SYS_GETEGID => self.sys_getegid().await,
             SYS_GETGID => self.sys_getgid().await,
             SYS_GETUID => self.sys_getuid().await,
+            SYS_SETUID => self.sys_setuid(args[0]).await,
+            SYS_SETGID => self.sys_setgid(args[0]).await,
             SYS_GETPGID => self.sys_getpgid().await,
             SYS_IOCTL => {
                 self.sys_ioctl(
@@ -144,6 +175,7 @@ impl UserTaskContainer {
                 self.sys_fcntl(args[0] as _, args[1] as _, args[2] as _)
                     .await
             }
+            SYS_FLOCK => self.sys_flock(args[0] as _, args[1] as _).await,
             SYS_UTIMEAT => {
                 self.sys_utimensat(args[0] as _, args[1].into(), args[2].into(), args[3] as _)
                     .await
@@ -203,12 +235,83 @@ impl UserTaskContainer {
                 .await
             }
             SYS_KILL => self.sys_kill(args[0] as _, args[1] as _).await,
-            SYS_FSYNC => Ok(0),
+            SYS_SYNC => self.sys_sync().await,
+            SYS_FSYNC => self.sys_fsync(args[0] as _).await,
+            SYS_FDATASYNC => self.sys_fdatasync(args[0] as _).await,
+            SYS_SETXATTR => {
+                let target = XattrTarget::Path(args[0].into());
+                self.sys_setxattr(target, args[1].into(), args[2].into(), args[3], args[4])
+                    .await
+            }
+            SYS_LSETXATTR => {
+                let target = XattrTarget::LinkPath(args[0].into());
+                self.sys_setxattr(target, args[1].into(), args[2].into(), args[3], args[4])
+                    .await
+            }
+            SYS_FSETXATTR => {
+                let target = XattrTarget::Fd(args[0]);
+                self.sys_setxattr(target, args[1].into(), args[2].into(), args[3], args[4])
+                    .await
+            }
+            SYS_GETXATTR => {
+                let target = XattrTarget::Path(args[0].into());
+                self.sys_getxattr(target, args[1].into(), args[2].into(), args[3])
+                    .await
+            }
+            SYS_LGETXATTR => {
+                let target = XattrTarget::LinkPath(args[0].into());
+                self.sys_getxattr(target, args[1].into(), args[2].into(), args[3])
+                    .await
+            }
+            SYS_FGETXATTR => {
+                let target = XattrTarget::Fd(args[0]);
+                self.sys_getxattr(target, args[1].into(), args[2].into(), args[3])
+                    .await
+            }
+            SYS_LISTXATTR => {
+                let target = XattrTarget::Path(args[0].into());
+                self.sys_listxattr(target, args[1].into(), args[2]).await
+            }
+            SYS_LLISTXATTR => {
+                let target = XattrTarget::LinkPath(args[0].into());
+                self.sys_listxattr(target, args[1].into(), args[2]).await
+            }
+            SYS_FLISTXATTR => {
+                let target = XattrTarget::Fd(args[0]);
+                self.sys_listxattr(target, args[1].into(), args[2]).await
+            }
+            SYS_REMOVEXATTR => {
+                self.sys_removexattr(XattrTarget::Path(args[0].into()), args[1].into())
+                    .await
+            }
+            SYS_LREMOVEXATTR => {
+                self.sys_removexattr(XattrTarget::LinkPath(args[0].into()), args[1].into())
+                    .await
+            }
+            SYS_FREMOVEXATTR => {
+                self.sys_removexattr(XattrTarget::Fd(args[0]), args[1].into())
+                    .await
+            }
+            SYS_SYNCFS => self.sys_syncfs(args[0] as _).await,
             SYS_FACCESSAT => {
+                self.sys_faccess_at(args[0] as _, args[1].into(), args[2], 0)
+                    .await
+            }
+            SYS_FACCESSAT2 => {
                 self.sys_faccess_at(args[0] as _, args[1].into(), args[2], args[3])
                     .await
-            } // always be ok at now.
-            SYS_FACCESSAT2 => Ok(0),
+            }
+            SYS_FCHMODAT => {
+                self.sys_fchmodat(args[0] as _, args[1].into(), args[2], args[3])
+                    .await
+            }
+            SYS_FCHMOD => self.sys_fchmod(args[0], args[1]).await,
+            SYS_FCHOWNAT => {
+                self.sys_fchownat(args[0] as _, args[1].into(), args[2], args[3], args[4])
+                    .await
+            }
+            SYS_FCHOWN => self.sys_fchown(args[0], args[1], args[2]).await,
+            SYS_UMASK => self.sys_umask(args[0]).await,
             SYS_SOCKET => {
                 self.sys_socket(args[0] as _, args[1] as _, args[2] as _)
                     .await
@@ -264,6 +367,7 @@ impl UserTaskContainer {
             SYS_MSYNC => self.sys_msync(args[0], args[1], args[2] as _).await,
             SYS_EXIT_GROUP => self.sys_exit_group(args[0]),
             SYS_FTRUNCATE => self.sys_ftruncate(args[0], args[1]).await,
+            SYS_FALLOCATE => self.sys_fallocate(args[0], args[1], args[2], args[3]).await,
             SYS_SHMGET => {
                 self.sys_shmget(args[0] as _, args[1] as _, args[2] as _)
                     .await
@@ -320,6 +424,12 @@ impl UserTaskContainer {
                 self.sys_clock_nanosleep(args[0] as _, args[1] as _, args[2].into(), args[3].into())
                     .await
             }
+            SYS_INOTIFY_INIT1 => self.sys_inotify_init1(args[0] as _).await,
+            SYS_INOTIFY_ADD_WATCH => {
+                self.sys_inotify_add_watch(args[0] as _, args[1].into(), args[2] as _)
+                    .await
+            }
+            SYS_INOTIFY_RM_WATCH => self.sys_inotify_rm_watch(args[0] as _, args[1] as _).await,
             SYS_EPOLL_CREATE => self.sys_epoll_create1(args[0] as _).await,
             SYS_EPOLL_CTL => {
                 self.sys_epoll_ctl(args[0] as _, args[1] as _, args[2] as _, args[3].into())
@@ -403,6 +513,22 @@ impl UserTaskContainer {
             #[cfg(target_arch = "x86_64")]
             SYS_MKDIR => self.sys_mkdir(args[0].into(), args[1]).await,
             #[cfg(target_arch = "x86_64")]
+            SYS_MKNOD => self.sys_mknod(args[0].into(), args[1], args[2]).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_SYMLINK => self.sys_symlink(args[0].into(), args[1].into()).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_LINK => self.sys_link(args[0].into(), args[1].into()).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_INOTIFY_INIT => self.sys_inotify_init().await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_ACCESS => self.sys_access(args[0].into(), args[1]).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_CHMOD => self.sys_chmod(args[0].into(), args[1]).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_CHOWN => self.sys_chown(args[0].into(), args[1], args[2]).await,
+            #[cfg(target_arch = "x86_64")]
+            SYS_LCHOWN => self.sys_lchown(args[0].into(), args[1], args[2]).await,
+            #[cfg(target_arch = "x86_64")]
             SYS_READLINK => {
                 self.sys_readlink(args[0].into(), args[1].into(), args[2])
                     .await
@@ -1,7 +1,8 @@
+use devices::random;
 use log::{debug, warn};
 
 use crate::{
-    syscall::consts::{Rlimit, UTSname},
+    syscall::consts::{GetRandomFlags, LinuxError, Rlimit, UTSname},
     user::UserTaskContainer,
 };
 
@@ -69,19 +70,44 @@ impl UserTaskContainer {
     }
 
     pub async fn sys_geteuid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.inner_map(|pcb| pcb.uid) as usize)
     }
 
     pub async fn sys_getegid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.inner_map(|pcb| pcb.gid) as usize)
     }
 
     pub async fn sys_getgid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.inner_map(|pcb| pcb.gid) as usize)
     }
 
     pub async fn sys_getuid(&self) -> SysResult {
-        Ok(0)
+        Ok(self.task.inner_map(|pcb| pcb.uid) as usize)
+    }
+
+    /// The real and effective ids are the same, only root can change to another user.
+    pub async fn sys_setuid(&self, uid: usize) -> SysResult {
+        debug!("sys_setuid @ uid: {}", uid);
+        self.task
+            .inner_map(|pcb| match pcb.uid == 0 || pcb.uid == uid as u32 {
+                true => {
+                    pcb.uid = uid as u32;
+                    Ok(0)
+                }
+                false => Err(LinuxError::EPERM),
+            })
+    }
+
+    pub async fn sys_setgid(&self, gid: usize) -> SysResult {
+        debug!("sys_setgid @ gid: {}", gid);
+        self.task
+            .inner_map(|pcb| match pcb.uid == 0 || pcb.gid == gid as u32 {
+                true => {
+                    pcb.gid = gid as u32;
+                    Ok(0)
+                }
+                false => Err(LinuxError::EPERM),
+            })
     }
 
     pub async fn sys_getpgid(&self) -> SysResult {
@@ -134,21 +160,22 @@ impl UserTaskContainer {
             "sys_getrandom @ buf: {}, buf_len: {:#x}, flags: {:#x}",
             buf, buf_len, flags
         );
-        let buf = buf.slice_mut_with_len(buf_len);
-        static mut SEED: u64 = 0xdead_beef_cafe_babe;
-        for x in buf.iter_mut() {
-            unsafe {
-                // from musl
-                SEED = SEED.wrapping_mul(0x5851_f42d_4c95_7f2d);
-                *x = (SEED >> 33) as u8;
-            }
+        let flags = GetRandomFlags::from_bits(flags as _).ok_or(LinuxError::EINVAL)?;
+        if flags.contains(GetRandomFlags::GRND_RANDOM | GetRandomFlags::GRND_INSECURE) {
+            return Err(LinuxError::EINVAL);
+        }
+        // the pool is seeded at the boot, GRND_RANDOM doesn't block after that like linux.
+        if !random::is_initialized() && !flags.contains(GetRandomFlags::GRND_INSECURE) {
+            return Err(LinuxError::EAGAIN);
         }
+        let buf = buf.slice_mut_with_len(buf_len);
+        random::fill_random(buf);
         Ok(buf_len)
     }
 
     #[cfg(target_arch = "x86_64")]
     pub async fn sys_arch_prctl(&self, code: usize, addr: usize) -> SysResult {
-        use crate::syscall::consts::{ArchPrctlCode, LinuxError};
+        use crate::syscall::consts::ArchPrctlCode;
         use arch::ContextArgs;
         use num_traits::FromPrimitive;
 
@@ -1,4 +1,5 @@
-use crate::syscall::consts::{from_vfs, CloneFlags, Rusage};
+use crate::syscall::consts::{from_vfs, CloneFlags, Rusage, X_OK};
+use crate::syscall::fd::check_access;
 use crate::syscall::time::WaitUntilsec;
 use crate::tasks::elf::{init_task_stack, ElfExtra};
 use crate::tasks::{futex_requeue, futex_wake, WaitFutex, WaitPid};
@@ -16,6 +17,7 @@ use executor::{
 };
 use frame_allocator::{ceil_div, frame_alloc_much, FrameTracker};
 use fs::dentry::{dentry_open, dentry_root};
+use fs::mount::{mount_flags, MountFlags};
 use fs::TimeSpec;
 use hal::{current_nsec, TimeVal};
 use log::{debug, warn};
@@ -146,6 +148,7 @@ pub fn cache_task_template(path: &str) -> Result<(), LinuxError> {
                     offset: 0,
                     start: vpn * PAGE_SIZE,
                     len: page_count * PAGE_SIZE,
+                    rwx: 0b111,
                 })
             });
         if base > 0 {
@@ -206,11 +209,13 @@ pub async fn exec_with_process<'a>(
         );
 
         for area in &cache_task.maps {
-            user_task.inner_map(|pcb| {
-                pcb.memset
-                    .sub_area(area.start, area.start + area.len, &user_task.page_table);
-                pcb.memset.push(area.clone());
-            });
+            user_task
+                .inner_map(|pcb| {
+                    pcb.memset
+                        .sub_area(area.start, area.start + area.len, &user_task.page_table)
+                        .map(|_| pcb.memset.push(area.clone()))
+                })
+                .map_err(from_vfs)?;
             for mtracker in area.mtrackers.iter() {
                 user_task.map(mtracker.tracker.0, mtracker.vpn, MappingFlags::URX);
             }
@@ -355,6 +360,7 @@ impl UserTaskContainer {
 
         match new_dir.metadata().unwrap().file_type {
             fs::FileType::Directory => {
+                check_access(&self.task, &new_dir.inner, X_OK)?;
                 self.task.pcb.lock().curr_dir = new_dir;
                 Ok(0)
             }
@@ -418,7 +424,11 @@ impl UserTaskContainer {
             self.task.exit(0);
             return Ok(0);
         }
-        let _exec_file = FileItem::fs_open(filename, OpenFlags::O_RDONLY).map_err(from_vfs)?;
+        let exec_file = FileItem::fs_open(filename, OpenFlags::O_RDONLY).map_err(from_vfs)?;
+        if mount_flags(&exec_file.path().map_err(from_vfs)?).contains(MountFlags::MS_NOEXEC) {
+            return Err(LinuxError::EACCES);
+        }
+        check_access(&self.task, &exec_file.inner, X_OK)?;
         exec_with_process(self.task.clone(), filename, args, envp).await?;
         self.task.before_run();
         Ok(0)
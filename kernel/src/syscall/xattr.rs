@@ -0,0 +1,184 @@
+//! The extended attributes, they are kept by the filesystems which support them.
+//! The l* variants don't follow the symbolic link, the f* variants use the opened file.
+
+use alloc::{sync::Arc, vec::Vec};
+use executor::FileItem;
+use fs::{OpenFlags, VfsError, XattrMode};
+use log::debug;
+
+use crate::user::UserTaskContainer;
+
+use super::{
+    consts::{from_vfs, LinuxError, UserRef, AT_CWD, W_OK},
+    fd::{check_access, check_writable, to_node},
+    SysResult,
+};
+
+// the flags of setxattr(2).
+const XATTR_CREATE: usize = 1;
+const XATTR_REPLACE: usize = 2;
+
+/// The limits of the name and the value, the same as Linux.
+const XATTR_NAME_MAX: usize = 255;
+const XATTR_SIZE_MAX: usize = 65536;
+
+/// The namespaces of the attributes, the others aren't supported.
+const XATTR_PREFIXES: [&str; 4] = ["user.", "trusted.", "security.", "system."];
+
+/// The file which the attributes belong to.
+pub enum XattrTarget {
+    Path(UserRef<i8>),
+    /// The symbolic link itself.
+    LinkPath(UserRef<i8>),
+    Fd(usize),
+}
+
+fn from_xattr_err(err: VfsError) -> LinuxError {
+    match err {
+        VfsError::NotSupported => LinuxError::EOPNOTSUPP,
+        VfsError::FileNotFound => LinuxError::ENODATA,
+        _ => from_vfs(err),
+    }
+}
+
+fn check_name(name: &str) -> Result<(), LinuxError> {
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(LinuxError::ERANGE);
+    }
+    match XATTR_PREFIXES.iter().any(|x| name.starts_with(x)) {
+        true => Ok(()),
+        false => Err(LinuxError::EOPNOTSUPP),
+    }
+}
+
+impl UserTaskContainer {
+    fn xattr_file(&self, target: XattrTarget) -> Result<Arc<FileItem>, LinuxError> {
+        let (path, flags) = match target {
+            XattrTarget::Fd(fd) => return self.task.get_fd(fd).ok_or(LinuxError::EBADF),
+            XattrTarget::Path(path) => (path, OpenFlags::NONE),
+            XattrTarget::LinkPath(path) => (path, OpenFlags::O_NOFOLLOW),
+        };
+        let path = path.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        to_node(&self.task, AT_CWD, path)?
+            .dentry_open(path, flags)
+            .map_err(from_vfs)
+    }
+
+    /// The trusted attributes are only visible to root.
+    fn xattr_visible(&self, name: &str) -> bool {
+        !name.starts_with("trusted.") || self.task.inner_map(|pcb| pcb.uid) == 0
+    }
+
+    /// The attributes out of the user namespace can only be changed by root.
+    fn check_xattr_write(&self, file: &FileItem, name: &str) -> Result<(), LinuxError> {
+        check_writable(&file.path().map_err(from_vfs)?)?;
+        match name.starts_with("user.") {
+            true => check_access(&self.task, &file.inner, W_OK),
+            false if self.task.inner_map(|pcb| pcb.uid) != 0 => Err(LinuxError::EPERM),
+            false => Ok(()),
+        }
+    }
+
+    pub async fn sys_setxattr(
+        &self,
+        target: XattrTarget,
+        name: UserRef<i8>,
+        value: UserRef<u8>,
+        size: usize,
+        flags: usize,
+    ) -> SysResult {
+        let name = name.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_setxattr @ name: {}, value: {}, size: {:#x}, flags: {:#x}",
+            name, value, size, flags
+        );
+        let mode = match flags {
+            0 => XattrMode::Any,
+            XATTR_CREATE => XattrMode::Create,
+            XATTR_REPLACE => XattrMode::Replace,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        check_name(name)?;
+        if size > XATTR_SIZE_MAX {
+            return Err(LinuxError::E2BIG);
+        }
+        let file = self.xattr_file(target)?;
+        self.check_xattr_write(&file, name)?;
+        let value: &[u8] = match size {
+            0 => &[],
+            _ => &*value.slice_mut_with_len(size),
+        };
+        fs::setxattr(&file.inner, name, value, mode).map_err(from_xattr_err)?;
+        Ok(0)
+    }
+
+    /// Return the size of the value, the value isn't copied if the size is 0.
+    pub async fn sys_getxattr(
+        &self,
+        target: XattrTarget,
+        name: UserRef<i8>,
+        value: UserRef<u8>,
+        size: usize,
+    ) -> SysResult {
+        let name = name.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!(
+            "sys_getxattr @ name: {}, value: {}, size: {:#x}",
+            name, value, size
+        );
+        check_name(name)?;
+        let file = self.xattr_file(target)?;
+        if !self.xattr_visible(name) {
+            return Err(LinuxError::ENODATA);
+        }
+        let data = fs::getxattr(&file.inner, name).map_err(from_xattr_err)?;
+        if size == 0 {
+            return Ok(data.len());
+        }
+        if data.len() > size {
+            return Err(LinuxError::ERANGE);
+        }
+        value.slice_mut_with_len(data.len()).copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    /// Return the size of the names, each of them ends with a zero.
+    pub async fn sys_listxattr(
+        &self,
+        target: XattrTarget,
+        list: UserRef<u8>,
+        size: usize,
+    ) -> SysResult {
+        debug!("sys_listxattr @ list: {}, size: {:#x}", list, size);
+        let file = self.xattr_file(target)?;
+        // the filesystems without the attributes have an empty list.
+        let names = match fs::listxattr(&file.inner) {
+            Ok(names) => names,
+            Err(VfsError::NotSupported) => Vec::new(),
+            Err(err) => return Err(from_vfs(err)),
+        };
+        let mut buffer = Vec::new();
+        for name in names.iter().filter(|x| self.xattr_visible(x)) {
+            buffer.extend_from_slice(name.as_bytes());
+            buffer.push(0);
+        }
+        if size == 0 {
+            return Ok(buffer.len());
+        }
+        if buffer.len() > size {
+            return Err(LinuxError::ERANGE);
+        }
+        list.slice_mut_with_len(buffer.len())
+            .copy_from_slice(&buffer);
+        Ok(buffer.len())
+    }
+
+    pub async fn sys_removexattr(&self, target: XattrTarget, name: UserRef<i8>) -> SysResult {
+        let name = name.get_cstr().map_err(|_| LinuxError::EINVAL)?;
+        debug!("sys_removexattr @ name: {}", name);
+        check_name(name)?;
+        let file = self.xattr_file(target)?;
+        self.check_xattr_write(&file, name)?;
+        fs::removexattr(&file.inner, name).map_err(from_xattr_err)?;
+        Ok(0)
+    }
+}
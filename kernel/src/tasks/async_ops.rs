@@ -3,6 +3,7 @@ use core::{cmp, future::Future, pin::Pin, task::Poll};
 use alloc::{sync::Arc, vec::Vec};
 use arch::{get_time, time_to_usec};
 use executor::{current_user_task, FutexOps, FutexTable, UserTask};
+use fs::lock::{cancel_wait, is_waiting, LockKey};
 use sync::Mutex;
 
 use crate::syscall::consts::LinuxError;
@@ -88,6 +89,29 @@ impl Future for WaitFutex {
     }
 }
 
+/// Wait in the queue of the file until the locks of it are released.
+pub struct WaitLock(pub LockKey, pub usize);
+
+impl Future for WaitLock {
+    type Output = Result<(), LinuxError>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let signal = current_user_task().tcb.read().signal.clone();
+        match is_waiting(self.0, self.1) {
+            true if signal.has_signal() => Poll::Ready(Err(LinuxError::EINTR)),
+            true => Poll::Pending,
+            false => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// The waiter leaves the queue if it is interrupted or the task exits.
+impl Drop for WaitLock {
+    fn drop(&mut self) {
+        cancel_wait(self.0, self.1);
+    }
+}
+
 pub struct WaitHandleAbleSignal(pub Arc<UserTask>);
 
 impl Future for WaitHandleAbleSignal {
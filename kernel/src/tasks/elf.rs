@@ -1,6 +1,10 @@
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use arch::{Context, ContextArgs, VirtPage, PAGE_SIZE};
 use executor::{AsyncTask, MemType, UserTask};
+use fs::{
+    dentry::{dentry_open, dentry_root},
+    OpenFlags,
+};
 use log::warn;
 use xmas_elf::{
     program::Type,
@@ -141,6 +145,18 @@ pub fn init_task_stack(
         "PATH=/:/bin:/usr/bin",
         "UB_BINDIR=./",
     ];
+    // the stack of the other task can't be read, so /proc/[pid] uses a copy.
+    let exe = dentry_open(dentry_root(), path, OpenFlags::NONE)
+        .map(|x| x.path())
+        .unwrap_or(String::from(path));
+    user_task.inner_map(|pcb| {
+        pcb.exe = exe.clone();
+        pcb.cmdline = args.clone();
+        pcb.environ = envp
+            .iter()
+            .map(|x| String::from(x.trim_end_matches('\0')))
+            .collect();
+    });
     let envp: Vec<usize> = envp
         .into_iter()
         .rev()
@@ -152,7 +168,10 @@ pub fn init_task_stack(
         .map(|x| user_task.push_str(&x))
         .collect();
 
-    let random_ptr = user_task.push_arr(&[0u8; 16]);
+    // the 16 random bytes used by the libc as the stack canary and the pointer guard.
+    let mut random_bytes = [0u8; 16];
+    devices::random::fill_random(&mut random_bytes);
+    let random_ptr = user_task.push_arr(&random_bytes);
     let mut auxv = BTreeMap::new();
     auxv.insert(elf::AT_PLATFORM, user_task.push_str("riscv"));
     auxv.insert(elf::AT_EXECFN, user_task.push_str(path));
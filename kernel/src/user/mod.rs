@@ -2,14 +2,14 @@ use core::pin::Pin;
 
 use ::signal::SignalFlags;
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
-use arch::{get_time, run_user_task, Context, ContextArgs, MappingFlags, VirtPage};
+use arch::{add_cpu_time, get_time, run_user_task, Context, ContextArgs, MappingFlags, VirtPage};
 use executor::{AsyncTask, MapTrack, TaskId, UserTask};
 use frame_allocator::frame_alloc;
 use futures_lite::Future;
 use log::{debug, warn};
 
 use crate::{
-    syscall::consts::SYS_SIGRETURN,
+    syscall::consts::{MmapProt, SYS_SIGRETURN},
     tasks::{hexdump, UserTaskControlFlow},
 };
 
@@ -38,6 +38,7 @@ pub fn user_cow_int(task: Arc<UserTask>, _cx_ref: &mut Context, addr: usize) {
     let mut pcb = task.pcb.lock();
     let area = pcb.memset.iter_mut().find(|x| x.contains(addr));
     if let Some(area) = area {
+        let cached = area.page_cache().is_some();
         let finded = area.mtrackers.iter_mut().find(|x| x.vpn == vpn);
         let ppn = match finded {
             Some(map_track) => {
@@ -45,6 +46,28 @@ pub fn user_cow_int(task: Arc<UserTask>, _cx_ref: &mut Context, addr: usize) {
                     task.tcb.write().signal.add_signal(SignalFlags::SIGSEGV);
                     return;
                 }
+                // the page of the page cache is mapped read-only until the first write.
+                if cached {
+                    let prot = MmapProt::from_bits_truncate(map_track.rwx as _);
+                    if !prot.contains(MmapProt::PROT_WRITE) {
+                        task.tcb.write().signal.add_signal(SignalFlags::SIGSEGV);
+                        return;
+                    }
+                    let shared = area.mtype == executor::MemType::ShareFile;
+                    if !shared && Arc::strong_count(&map_track.tracker) > 1 {
+                        let dst_ppn = frame_alloc().expect("can't alloc @ user page fault");
+                        dst_ppn.0.copy_value_from_another(map_track.tracker.0);
+                        map_track.tracker = Arc::new(dst_ppn);
+                    }
+                    let ppn = map_track.tracker.0;
+                    if shared {
+                        area.mark_dirty(vpn);
+                    }
+                    drop(pcb);
+                    let flags: MappingFlags = prot.into();
+                    task.map(ppn, vpn, MappingFlags::U | flags);
+                    return;
+                }
                 // tips: this finded will consume a strong count.
                 debug!("strong count: {}", Arc::strong_count(&map_track.tracker));
                 if Arc::strong_count(&map_track.tracker) > 1 {
@@ -55,6 +78,23 @@ pub fn user_cow_int(task: Arc<UserTask>, _cx_ref: &mut Context, addr: usize) {
                 }
                 map_track.tracker.0
             }
+            None if cached => {
+                let Some(Ok(tracker)) = area.cache_page(vpn) else {
+                    task.tcb.write().signal.add_signal(SignalFlags::SIGBUS);
+                    return;
+                };
+                let flags: MappingFlags =
+                    (MmapProt::from_bits_truncate(area.rwx as _) - MmapProt::PROT_WRITE).into();
+                let ppn = tracker.0;
+                area.mtrackers.push(MapTrack {
+                    vpn,
+                    tracker,
+                    rwx: area.rwx,
+                });
+                drop(pcb);
+                task.map(ppn, vpn, MappingFlags::U | flags);
+                return;
+            }
             None => {
                 let tracker = Arc::new(frame_alloc().expect("can't alloc frame in cow_fork_int"));
                 let mtracker = MapTrack {
@@ -85,8 +125,9 @@ impl UserTaskContainer {
     pub async fn handle_syscall(&self, cx_ref: &mut Context) -> UserTaskControlFlow {
         let ustart = get_time();
         if let Some(()) = run_user_task(cx_ref) {
-            self.task
-                .inner_map(|inner| inner.tms.utime += (get_time() - ustart) as u64);
+            let utime = get_time() - ustart;
+            self.task.inner_map(|inner| inner.tms.utime += utime as u64);
+            add_cpu_time(utime, 0);
 
             let sstart = get_time();
             if cx_ref[ContextArgs::SYSCALL] == SYS_SIGRETURN {
@@ -108,8 +149,9 @@ impl UserTaskContainer {
             );
 
             cx_ref[ContextArgs::RET] = result;
-            self.task
-                .inner_map(|inner| inner.tms.stime += (get_time() - sstart) as u64);
+            let stime = get_time() - sstart;
+            self.task.inner_map(|inner| inner.tms.stime += stime as u64);
+            add_cpu_time(0, stime);
         }
 
         // let trap_type = trap_pre_handle(cx_ref);
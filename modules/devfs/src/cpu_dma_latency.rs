@@ -1,6 +1,16 @@
 use vfscore::{INodeInterface, Stat, StatMode, VfsResult};
 
-pub struct CpuDmaLatency;
+use crate::alloc_ino;
+
+pub struct CpuDmaLatency {
+    ino: u64,
+}
+
+impl CpuDmaLatency {
+    pub fn new() -> Self {
+        Self { ino: alloc_ino() }
+    }
+}
 
 impl INodeInterface for CpuDmaLatency {
     fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
@@ -17,11 +27,11 @@ impl INodeInterface for CpuDmaLatency {
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o600);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
@@ -0,0 +1,83 @@
+use core::mem::size_of;
+
+use alloc::sync::Arc;
+use devices::{device::InputDriver, makedev, INPUT_DEVICE_MAJOR, INPUT_EVENT_MINOR};
+use hal::TimeVal;
+use vfscore::{INodeInterface, PollEvent, Stat, StatMode, VfsError, VfsResult};
+
+use crate::alloc_ino;
+
+/// The struct input_event of linux.
+#[repr(C)]
+struct InputEvent {
+    time: TimeVal,
+    event_type: u16,
+    code: u16,
+    value: i32,
+}
+
+/// The event device input/eventN, the events are read as struct input_event.
+pub struct EventDevice {
+    ino: u64,
+    index: u32,
+    device: Arc<dyn InputDriver>,
+}
+
+impl EventDevice {
+    pub fn new(index: u32, device: Arc<dyn InputDriver>) -> Self {
+        Self {
+            ino: alloc_ino(),
+            index,
+            device,
+        }
+    }
+}
+
+impl INodeInterface for EventDevice {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let count = buffer.len() / size_of::<InputEvent>();
+        if count == 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        if self.device.is_empty() {
+            return Err(VfsError::Blocking);
+        }
+        let events = buffer.as_mut_ptr() as *mut InputEvent;
+        let mut rcount = 0;
+        while rcount < count && !self.device.is_empty() {
+            let event = self.device.read_event();
+            let event = InputEvent {
+                time: TimeVal::now(),
+                event_type: event as u16,
+                code: (event >> 16) as u16,
+                value: (event >> 32) as i32,
+            };
+            // the buffer of the user may not be aligned.
+            unsafe { events.add(rcount).write_unaligned(event) };
+            rcount += 1;
+        }
+        Ok(rcount * size_of::<InputEvent>())
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && !self.device.is_empty() {
+            res |= PollEvent::POLLIN;
+        }
+        Ok(res)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.dev = 0;
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o660);
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = makedev(INPUT_DEVICE_MAJOR, INPUT_EVENT_MINOR + self.index);
+        Ok(())
+    }
+}
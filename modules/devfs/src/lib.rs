@@ -3,13 +3,28 @@
 extern crate alloc;
 extern crate log;
 
-use alloc::{collections::BTreeMap, string::ToString, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use devices::{
+    makedev, ALL_DEVICES, INPUT_DEVICE_MAJOR, INPUT_EVENT_MINOR, MEM_DEVICE_MAJOR,
+    RTC_DEVICE_MAJOR, TTYAUX_DEVICE_MAJOR, TTY_DEVICE_MAJOR, TTY_SERIAL_MINOR,
+};
+use sync::Mutex;
 use vfscore::{DirEntry, FileSystem, FileType, INodeInterface, StatMode, VfsError, VfsResult};
 
 mod cpu_dma_latency;
+mod input;
 mod null;
 mod rtc;
 mod sdx;
+mod serial;
 mod shm;
 mod tty;
 mod urandom;
@@ -17,6 +32,24 @@ mod zero;
 
 pub use {sdx::Sdx, tty::Tty};
 
+/// The inode number of the root directory.
+const ROOT_INO: u64 = 1;
+
+/// Allocate the inode number of the device node.
+pub(crate) fn alloc_ino() -> u64 {
+    static NEXT_INO: AtomicU64 = AtomicU64::new(ROOT_INO + 1);
+    NEXT_INO.fetch_add(1, Ordering::Relaxed)
+}
+
+/// (The bits of the file type, the device number) -> the device node.
+/// The char and block devices may have the same number, the nodes made by mknod(2) open them.
+static DEVICES: Mutex<BTreeMap<(u32, u64), Arc<dyn INodeInterface>>> = Mutex::new(BTreeMap::new());
+
+/// Find the device node by the file type, char or block, and the device number.
+pub fn find_device(file_type: StatMode, rdev: u64) -> Option<Arc<dyn INodeInterface>> {
+    DEVICES.lock().get(&(file_type.bits(), rdev)).cloned()
+}
+
 pub struct DevFS {
     root_dir: Arc<DevDir>,
 }
@@ -48,7 +81,8 @@ impl FileSystem for DevFS {
 }
 
 pub struct DevDir {
-    map: BTreeMap<&'static str, Arc<dyn INodeInterface>>,
+    ino: u64,
+    map: Mutex<BTreeMap<String, Arc<dyn INodeInterface>>>,
 }
 
 pub struct DevDirContainer {
@@ -57,24 +91,111 @@ pub struct DevDirContainer {
 
 impl DevDir {
     pub fn new() -> Self {
-        let mut map: BTreeMap<&'static str, Arc<dyn INodeInterface>> = BTreeMap::new();
-        map.insert("stdout", Arc::new(Tty::new()));
-        map.insert("stderr", Arc::new(Tty::new()));
-        map.insert("stdin", Arc::new(Tty::new()));
-        map.insert("ttyv0", Arc::new(Tty::new()));
-        map.insert("null", Arc::new(null::Null));
-        map.insert("zero", Arc::new(zero::Zero));
-        map.insert("shm", Arc::new(shm::Shm));
-        map.insert("rtc", Arc::new(rtc::Rtc));
-        map.insert("urandom", Arc::new(urandom::Urandom));
-        map.insert("cpu_dma_latency", Arc::new(cpu_dma_latency::CpuDmaLatency));
+        let dir = Self::empty(ROOT_INO);
+        dir.add("stdout", Arc::new(Tty::new()));
+        dir.add("stderr", Arc::new(Tty::new()));
+        dir.add("stdin", Arc::new(Tty::new()));
+        dir.add("ttyv0", Arc::new(Tty::new()));
+        let console = makedev(TTYAUX_DEVICE_MAJOR, 1);
+        dir.add_char_device("console", console, Arc::new(Tty::new()));
+        let mem = |minor| makedev(MEM_DEVICE_MAJOR, minor);
+        dir.add_char_device("null", mem(3), Arc::new(null::Null::new()));
+        dir.add_char_device("zero", mem(5), Arc::new(zero::Zero::new()));
+        dir.add_char_device("random", mem(8), Arc::new(urandom::Urandom::new(8)));
+        dir.add_char_device("urandom", mem(9), Arc::new(urandom::Urandom::new(9)));
+        dir.add("shm", Arc::new(shm::Shm::new()));
+        dir.add(
+            "cpu_dma_latency",
+            Arc::new(cpu_dma_latency::CpuDmaLatency::new()),
+        );
         // map.insert("tty", Arc::new(stdout::Stdout));
 
-        Self { map }
+        let all_devices = ALL_DEVICES.lock();
+        for (index, uart) in all_devices.uart.iter().enumerate() {
+            let rdev = makedev(TTY_DEVICE_MAJOR, TTY_SERIAL_MINOR + index as u32);
+            let serial = serial::Serial::new(index as _, uart.clone());
+            dir.add_char_device(&format!("ttyS{}", index), rdev, Arc::new(serial));
+        }
+        for (index, device) in all_devices.input.iter().enumerate() {
+            let rdev = makedev(INPUT_DEVICE_MAJOR, INPUT_EVENT_MINOR + index as u32);
+            let event = input::EventDevice::new(index as _, device.clone());
+            dir.add_char_device(&format!("input/event{}", index), rdev, Arc::new(event));
+        }
+        for (index, device) in all_devices.rtc.iter().enumerate() {
+            let rtc = Arc::new(rtc::Rtc::new(index as _, Some(device.clone())));
+            let rdev = makedev(RTC_DEVICE_MAJOR, index as _);
+            dir.add_char_device(&format!("rtc{}", index), rdev, rtc.clone());
+            // rtc is the first one like the symbolic link in linux.
+            if index == 0 {
+                dir.add("rtc", rtc);
+            }
+        }
+        // the programs open rtc even if there isn't the device.
+        if all_devices.rtc.is_empty() {
+            dir.add("rtc", Arc::new(rtc::Rtc::new(0, None)));
+        }
+
+        dir
+    }
+
+    fn empty(ino: u64) -> Self {
+        Self {
+            ino,
+            map: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add the node, the directories in the path are created if they don't exist.
+    pub fn add(&self, path: &str, node: Arc<dyn INodeInterface>) {
+        let mut map = self.map.lock();
+        match path.split_once('/') {
+            Some((name, rest)) => {
+                let dir = map.entry(name.to_string()).or_insert_with(|| {
+                    Arc::new(DevDirContainer {
+                        inner: Arc::new(DevDir::empty(alloc_ino())),
+                    })
+                });
+                dir.downcast_ref::<DevDirContainer>()
+                    .expect("can't add the device node to a file")
+                    .inner
+                    .add(rest, node);
+            }
+            None => {
+                map.insert(path.to_string(), node);
+            }
+        }
+    }
+
+    /// Add the node of the device, it can be found by the file type and the device number.
+    pub fn add_device(
+        &self,
+        path: &str,
+        file_type: StatMode,
+        rdev: u64,
+        node: Arc<dyn INodeInterface>,
+    ) {
+        DEVICES
+            .lock()
+            .insert((file_type.bits(), rdev), node.clone());
+        self.add(path, node);
     }
 
-    pub fn add(&mut self, path: &'static str, node: Arc<dyn INodeInterface>) {
-        self.map.insert(path, node);
+    /// The devices made by devfs are char devices except the disks.
+    fn add_char_device(&self, path: &str, rdev: u64, node: Arc<dyn INodeInterface>) {
+        self.add_device(path, StatMode::CHAR, rdev, node)
+    }
+}
+
+impl DevDirContainer {
+    /// Make the node of the device which exists, like mdev does.
+    pub fn mknod(&self, name: &str, file_type: StatMode, rdev: u64) -> VfsResult<()> {
+        let node = find_device(file_type, rdev).ok_or(VfsError::FileNotFound)?;
+        let mut map = self.inner.map.lock();
+        if map.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        map.insert(name.to_string(), node);
+        Ok(())
     }
 }
 
@@ -82,6 +203,7 @@ impl INodeInterface for DevDirContainer {
     fn open(&self, name: &str, _flags: vfscore::OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
         self.inner
             .map
+            .lock()
             .get(name)
             .map(|x| x.clone())
             .ok_or(VfsError::FileNotFound)
@@ -91,20 +213,35 @@ impl INodeInterface for DevDirContainer {
         Ok(self
             .inner
             .map
+            .lock()
             .iter()
-            .map(|(name, _)| DirEntry {
+            .map(|(name, node)| DirEntry {
                 filename: name.to_string(),
                 len: 0,
-                file_type: FileType::Device,
+                file_type: match node.is::<DevDirContainer>() {
+                    true => FileType::Directory,
+                    false => FileType::Device,
+                },
             })
             .collect())
     }
 
+    fn remove(&self, name: &str) -> VfsResult<()> {
+        match self.inner.map.lock().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(VfsError::FileNotFound),
+        }
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()> {
+        self.remove(name)
+    }
+
     fn stat(&self, stat: &mut vfscore::Stat) -> VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::DIR; // TODO: add access mode
-        stat.nlink = 1;
+        stat.ino = self.inner.ino;
+        stat.mode = StatMode::DIR | StatMode::from_bits_truncate(0o755);
+        stat.nlink = 2;
         stat.uid = 0;
         stat.gid = 0;
         stat.size = 0;
@@ -120,7 +257,7 @@ impl INodeInterface for DevDirContainer {
             inode: 0,
             file_type: FileType::Directory,
             size: 0,
-            childrens: self.inner.map.len(),
+            childrens: self.inner.map.lock().len(),
         })
     }
 }
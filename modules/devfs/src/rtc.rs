@@ -1,6 +1,25 @@
+use alloc::sync::Arc;
+use devices::{device::RtcDriver, makedev, RTC_DEVICE_MAJOR};
 use vfscore::{INodeInterface, StatMode, VfsResult};
 
-pub struct Rtc;
+use crate::alloc_ino;
+
+pub struct Rtc {
+    ino: u64,
+    minor: u32,
+    /// The time is always zero without the device.
+    device: Option<Arc<dyn RtcDriver>>,
+}
+
+impl Rtc {
+    pub fn new(minor: u32, device: Option<Arc<dyn RtcDriver>>) -> Self {
+        Self {
+            ino: alloc_ino(),
+            minor,
+            device,
+        }
+    }
+}
 
 pub struct RtcTime {
     sec: u32,
@@ -17,26 +36,49 @@ pub struct RtcTime {
 impl INodeInterface for Rtc {
     fn stat(&self, stat: &mut vfscore::Stat) -> vfscore::VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o644);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = makedev(RTC_DEVICE_MAJOR, self.minor);
         Ok(())
     }
 
     fn ioctl(&self, _command: usize, arg: usize) -> VfsResult<usize> {
         let rtc_time = unsafe { (arg as *mut RtcTime).as_mut().unwrap() };
-        rtc_time.sec = 0;
-        rtc_time.min = 0;
-        rtc_time.hour = 0;
-        rtc_time.mday = 0;
-        rtc_time.mon = 0;
-        rtc_time.year = 0;
+        // the same as struct tm, the year is from 1900 and the month is from 0.
+        let (year, mon, mday, secs) = match &self.device {
+            Some(device) => {
+                let timestamp = device.read_timestamp();
+                let (year, mon, mday) = civil_from_days((timestamp / 86400) as i64);
+                (year - 1900, mon - 1, mday, (timestamp % 86400) as u32)
+            }
+            None => (0, 0, 0, 0),
+        };
+        rtc_time.sec = secs % 60;
+        rtc_time.min = secs / 60 % 60;
+        rtc_time.hour = secs / 3600;
+        rtc_time.mday = mday;
+        rtc_time.mon = mon;
+        rtc_time.year = year;
         Ok(0)
     }
 }
+
+/// Convert the days since 1970-01-01 to (year, month, day).
+fn civil_from_days(days: i64) -> (u32, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year as u32, month as u32, day as u32)
+}
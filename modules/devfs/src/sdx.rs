@@ -1,8 +1,15 @@
 use alloc::{string::String, vec::Vec};
+use devices::{
+    makedev,
+    partition::{blk_device_minor, BLK_DEVICE_MAJOR},
+};
 use sync::Mutex;
 use vfscore::{INodeInterface, StatMode, VfsError};
 
+use crate::alloc_ino;
+
 pub struct Sdx {
+    ino: u64,
     device_id: usize,
     mount_fn: fn(usize, &str) -> Result<(), VfsError>,
     umount_fn: fn(usize, &str) -> Result<(), VfsError>,
@@ -16,6 +23,7 @@ impl Sdx {
         umount_fn: fn(usize, &str) -> Result<(), VfsError>,
     ) -> Self {
         Self {
+            ino: alloc_ino(),
             device_id,
             mount_fn,
             umount_fn,
@@ -42,15 +50,15 @@ impl INodeInterface for Sdx {
 
     fn stat(&self, stat: &mut vfscore::Stat) -> vfscore::VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::BLOCK | StatMode::from_bits_truncate(0o660);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = makedev(BLK_DEVICE_MAJOR, blk_device_minor(self.device_id));
         Ok(())
     }
 }
@@ -0,0 +1,80 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use devices::{device::UartDriver, makedev, TTY_DEVICE_MAJOR, TTY_SERIAL_MINOR};
+use sync::Mutex;
+use vfscore::{INodeInterface, PollEvent, Stat, StatMode, VfsError, VfsResult};
+
+use crate::alloc_ino;
+
+/// The serial port ttySn, it reads and writes the uart directly.
+pub struct Serial {
+    ino: u64,
+    index: u32,
+    uart: Arc<dyn UartDriver>,
+    /// The characters got by poll, they are read first.
+    buffer: Mutex<VecDeque<u8>>,
+}
+
+impl Serial {
+    pub fn new(index: u32, uart: Arc<dyn UartDriver>) -> Self {
+        Self {
+            ino: alloc_ino(),
+            index,
+            uart,
+            buffer: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl INodeInterface for Serial {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let mut self_buffer = self.buffer.lock();
+        let mut rlen = 0;
+        while rlen < buffer.len() {
+            match self_buffer.pop_front().or_else(|| self.uart.get()) {
+                Some(c) => buffer[rlen] = c,
+                None => break,
+            }
+            rlen += 1;
+        }
+        match rlen {
+            0 if !buffer.is_empty() => Err(VfsError::Blocking),
+            _ => Ok(rlen),
+        }
+    }
+
+    fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        buffer.iter().for_each(|c| self.uart.put(*c));
+        Ok(buffer.len())
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) {
+            let mut buffer = self.buffer.lock();
+            if buffer.is_empty() {
+                buffer.extend(self.uart.get());
+            }
+            if !buffer.is_empty() {
+                res |= PollEvent::POLLIN;
+            }
+        }
+        if events.contains(PollEvent::POLLOUT) {
+            res |= PollEvent::POLLOUT;
+        }
+        Ok(res)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.dev = 0;
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o660);
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = makedev(TTY_DEVICE_MAJOR, TTY_SERIAL_MINOR + self.index);
+        Ok(())
+    }
+}
@@ -1,15 +1,25 @@
 use vfscore::{INodeInterface, StatMode};
 
-pub struct Shm;
+use crate::alloc_ino;
+
+pub struct Shm {
+    ino: u64,
+}
+
+impl Shm {
+    pub fn new() -> Self {
+        Self { ino: alloc_ino() }
+    }
+}
 
 impl INodeInterface for Shm {
     fn stat(&self, stat: &mut vfscore::Stat) -> vfscore::VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::DIR; // TODO: add access mode
-        stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.ino = self.ino;
+        stat.mode = StatMode::DIR | StatMode::from_bits_retain(0o1777);
+        stat.nlink = 2;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
@@ -2,12 +2,17 @@ use core::cmp;
 
 use alloc::collections::VecDeque;
 use bitflags::bitflags;
+use devices::{makedev, TTYAUX_DEVICE_MAJOR};
 use logging::{get_char, puts};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use sync::Mutex;
 use vfscore::{INodeInterface, PollEvent, Stat, StatMode, VfsError, VfsResult};
+
+use crate::alloc_ino;
+
 pub struct Tty {
+    ino: u64,
     buffer: Mutex<VecDeque<u8>>,
     termios: Mutex<Termios>,
     pgid: Mutex<u32>,
@@ -17,6 +22,7 @@ pub struct Tty {
 impl Tty {
     pub fn new() -> Tty {
         Tty {
+            ino: alloc_ino(),
             buffer: Mutex::new(VecDeque::new()),
             termios: Default::default(),
             pgid: Default::default(),
@@ -48,15 +54,15 @@ impl INodeInterface for Tty {
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
         stat.dev = 1;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o666);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 15;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = makedev(TTYAUX_DEVICE_MAJOR, 1);
         Ok(())
     }
 
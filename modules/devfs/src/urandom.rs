@@ -1,28 +1,46 @@
+use devices::{makedev, random, MEM_DEVICE_MAJOR};
 use vfscore::{INodeInterface, PollEvent, Stat, StatMode, VfsResult};
 
-pub struct Urandom;
+use crate::alloc_ino;
+
+/// The /dev/random and /dev/urandom, both read from the pool after it is seeded at the boot.
+pub struct Urandom {
+    ino: u64,
+    minor: u32,
+}
+
+impl Urandom {
+    pub fn new(minor: u32) -> Self {
+        Self {
+            ino: alloc_ino(),
+            minor,
+        }
+    }
+}
 
 impl INodeInterface for Urandom {
     fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
-        buffer.fill(0);
+        random::fill_random(buffer);
         Ok(buffer.len())
     }
 
+    /// The written bytes are mixed into the pool like linux.
     fn writeat(&self, _offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        random::add_randomness(buffer);
         Ok(buffer.len())
     }
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o666);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = makedev(MEM_DEVICE_MAJOR, self.minor);
         Ok(())
     }
 
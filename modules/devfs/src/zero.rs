@@ -1,6 +1,17 @@
+use devices::{makedev, MEM_DEVICE_MAJOR};
 use vfscore::{INodeInterface, Stat, StatMode, VfsResult};
 
-pub struct Zero;
+use crate::alloc_ino;
+
+pub struct Zero {
+    ino: u64,
+}
+
+impl Zero {
+    pub fn new() -> Self {
+        Self { ino: alloc_ino() }
+    }
+}
 
 impl INodeInterface for Zero {
     fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
@@ -14,15 +25,15 @@ impl INodeInterface for Zero {
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
         stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.ino = self.ino;
+        stat.mode = StatMode::CHAR | StatMode::from_bits_truncate(0o666);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = makedev(MEM_DEVICE_MAJOR, 5);
         Ok(())
     }
 }
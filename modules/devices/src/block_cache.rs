@@ -0,0 +1,225 @@
+//! The sector buffer cache shared by all the users of the block devices.
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use sync::Mutex;
+
+use crate::device::{BlkDriver, DeviceType, Driver};
+
+const SECTOR_SIZE: usize = 0x200;
+/// The number of sectors cached by each device.
+const CACHE_CAPACITY: usize = 2048;
+
+/// All the caches, the sync writes back all of them.
+static BLOCK_CACHES: Mutex<Vec<Arc<BlockCache>>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub writebacks: usize,
+    pub cached: usize,
+    pub dirty: usize,
+}
+
+struct CacheEntry {
+    data: Vec<u8>,
+    dirty: bool,
+    stamp: u64,
+}
+
+struct CacheInner {
+    entries: BTreeMap<usize, CacheEntry>,
+    /// stamp -> sector, the first one is the least recently used.
+    lru: BTreeMap<u64, usize>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+/// Cache the sectors of the device, the writes are kept until the sync
+/// or the eviction.
+pub struct BlockCache {
+    device: Arc<dyn BlkDriver>,
+    inner: Mutex<CacheInner>,
+}
+
+impl BlockCache {
+    pub fn new(device: Arc<dyn BlkDriver>) -> Arc<Self> {
+        let cache = Arc::new(Self {
+            device,
+            inner: Mutex::new(CacheInner {
+                entries: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+                stats: CacheStats::default(),
+            }),
+        });
+        BLOCK_CACHES.lock().push(cache.clone());
+        cache
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock();
+        CacheStats {
+            cached: inner.entries.len(),
+            dirty: inner.entries.values().filter(|x| x.dirty).count(),
+            ..inner.stats
+        }
+    }
+
+    /// Write back all the dirty sectors, the continuous sectors are written together.
+    pub fn sync(&self) {
+        let mut inner = self.inner.lock();
+        let dirty: Vec<usize> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(sector, _)| *sector)
+            .collect();
+        let mut index = 0;
+        while index < dirty.len() {
+            let start = dirty[index];
+            let mut count = 1;
+            while index + count < dirty.len() && dirty[index + count] == start + count {
+                count += 1;
+            }
+            let mut buffer = vec![0u8; count * SECTOR_SIZE];
+            for i in 0..count {
+                let entry = inner.entries.get_mut(&(start + i)).unwrap();
+                buffer[i * SECTOR_SIZE..(i + 1) * SECTOR_SIZE].copy_from_slice(&entry.data);
+                entry.dirty = false;
+            }
+            self.device.write_blocks(start, &buffer);
+            inner.stats.writebacks += count;
+            index += count;
+        }
+    }
+}
+
+impl CacheInner {
+    /// Mark the sector as the most recently used.
+    fn touch(&mut self, sector: usize) {
+        self.clock += 1;
+        let entry = self.entries.get_mut(&sector).unwrap();
+        self.lru.remove(&entry.stamp);
+        entry.stamp = self.clock;
+        self.lru.insert(self.clock, sector);
+    }
+
+    fn insert(&mut self, device: &Arc<dyn BlkDriver>, sector: usize, data: &[u8], dirty: bool) {
+        if self.entries.len() >= CACHE_CAPACITY {
+            self.evict(device);
+        }
+        self.clock += 1;
+        self.entries.insert(
+            sector,
+            CacheEntry {
+                data: data.to_vec(),
+                dirty,
+                stamp: self.clock,
+            },
+        );
+        self.lru.insert(self.clock, sector);
+    }
+
+    /// Drop the least recently used sector, write it back if it is dirty.
+    fn evict(&mut self, device: &Arc<dyn BlkDriver>) {
+        if let Some((_, sector)) = self.lru.pop_first() {
+            let entry = self.entries.remove(&sector).unwrap();
+            if entry.dirty {
+                device.write_blocks(sector, &entry.data);
+                self.stats.writebacks += 1;
+            }
+        }
+    }
+}
+
+impl Driver for BlockCache {
+    fn get_id(&self) -> &str {
+        self.device.get_id()
+    }
+
+    fn get_device_wrapper(self: Arc<Self>) -> DeviceType {
+        DeviceType::BLOCK(self.clone())
+    }
+}
+
+impl BlkDriver for BlockCache {
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) {
+        let mut inner = self.inner.lock();
+        let count = buf.len() / SECTOR_SIZE;
+        let mut index = 0;
+        while index < count {
+            let sector = block_id + index;
+            if inner.entries.contains_key(&sector) {
+                inner.stats.hits += 1;
+                inner.touch(sector);
+                buf[index * SECTOR_SIZE..(index + 1) * SECTOR_SIZE]
+                    .copy_from_slice(&inner.entries[&sector].data);
+                index += 1;
+                continue;
+            }
+            // read the missed sectors together.
+            let mut missed = 1;
+            while index + missed < count && !inner.entries.contains_key(&(sector + missed)) {
+                missed += 1;
+            }
+            let range = index * SECTOR_SIZE..(index + missed) * SECTOR_SIZE;
+            self.device.read_blocks(sector, &mut buf[range.clone()]);
+            inner.stats.misses += missed;
+            for (i, data) in buf[range].chunks_exact(SECTOR_SIZE).enumerate() {
+                inner.insert(&self.device, sector + i, data, false);
+            }
+            index += missed;
+        }
+    }
+
+    fn write_blocks(&self, block_id: usize, buf: &[u8]) {
+        let mut inner = self.inner.lock();
+        for (i, data) in buf.chunks_exact(SECTOR_SIZE).enumerate() {
+            let sector = block_id + i;
+            match inner.entries.get_mut(&sector) {
+                Some(entry) => {
+                    entry.data.copy_from_slice(data);
+                    entry.dirty = true;
+                    inner.touch(sector);
+                }
+                None => inner.insert(&self.device, sector, data, true),
+            }
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.device.capacity()
+    }
+
+    fn flush(&self) {
+        self.sync()
+    }
+}
+
+/// Write back the dirty sectors of all the block devices, the statistics are reported after it.
+pub fn sync_all() {
+    // sync outside the registry lock, the device may be slow.
+    let caches = BLOCK_CACHES.lock().clone();
+    caches.iter().for_each(|cache| cache.sync());
+    let stats = cache_stats();
+    info!(
+        "block cache: {} hits, {} misses, {} writebacks, {} sectors cached",
+        stats.hits, stats.misses, stats.writebacks, stats.cached
+    );
+}
+
+/// The sum of the statistics of all the caches.
+pub fn cache_stats() -> CacheStats {
+    BLOCK_CACHES
+        .lock()
+        .iter()
+        .map(|cache| cache.stats())
+        .fold(CacheStats::default(), |acc, x| CacheStats {
+            hits: acc.hits + x.hits,
+            misses: acc.misses + x.misses,
+            writebacks: acc.writebacks + x.writebacks,
+            cached: acc.cached + x.cached,
+            dirty: acc.dirty + x.dirty,
+        })
+}
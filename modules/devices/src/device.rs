@@ -1,6 +1,6 @@
 use alloc::{sync::Arc, vec::Vec};
 
-use crate::{INT_DEVICE, MAIN_UART};
+use crate::{block_cache::BlockCache, INT_DEVICE, MAIN_UART};
 
 pub enum DeviceType {
     RTC(Arc<dyn RtcDriver>),
@@ -9,6 +9,8 @@ pub enum DeviceType {
     INPUT(Arc<dyn InputDriver>),
     INT(Arc<dyn IntDriver>),
     UART(Arc<dyn UartDriver>),
+    P9(Arc<dyn P9Driver>),
+    RNG(Arc<dyn RngDriver>),
     None,
 }
 
@@ -18,6 +20,8 @@ pub struct DeviceSet {
     pub net: Vec<Arc<dyn NetDriver>>,
     pub uart: Vec<Arc<dyn UartDriver>>,
     pub input: Vec<Arc<dyn InputDriver>>,
+    pub p9: Vec<Arc<dyn P9Driver>>,
+    pub rng: Vec<Arc<dyn RngDriver>>,
 }
 
 impl DeviceSet {
@@ -28,13 +32,15 @@ impl DeviceSet {
             net: vec![],
             uart: vec![],
             input: vec![],
+            p9: vec![],
+            rng: vec![],
         }
     }
 
     pub fn add_device(&mut self, device: Arc<dyn Driver>) {
         match device.get_device_wrapper() {
             DeviceType::RTC(device) => self.rtc.push(device),
-            DeviceType::BLOCK(device) => self.blk.push(device),
+            DeviceType::BLOCK(device) => self.blk.push(BlockCache::new(device)),
             DeviceType::NET(device) => self.net.push(device),
             DeviceType::INPUT(device) => self.input.push(device),
             DeviceType::INT(device) => INT_DEVICE.init_by(device),
@@ -44,6 +50,8 @@ impl DeviceSet {
                 }
                 self.uart.push(device)
             }
+            DeviceType::P9(device) => self.p9.push(device),
+            DeviceType::RNG(device) => self.rng.push(device),
             DeviceType::None => {}
         }
     }
@@ -71,6 +79,14 @@ pub trait RtcDriver: Driver {
 pub trait BlkDriver: Driver {
     fn read_blocks(&self, block_id: usize, buf: &mut [u8]);
     fn write_blocks(&self, block_id: usize, buf: &[u8]);
+
+    /// The number of the 512-byte sectors, 0 if it isn't known.
+    fn capacity(&self) -> usize {
+        0
+    }
+
+    /// Write back the cached data to the device.
+    fn flush(&self) {}
 }
 
 #[derive(Debug)]
@@ -88,6 +104,7 @@ pub trait IntDriver: Driver {
 }
 
 pub trait InputDriver: Driver {
+    /// Pop the event packed as type | code << 16 | value << 32, the same layout as virtio.
     fn read_event(&self) -> u64;
     fn handle_irq(&self);
     fn is_empty(&self) -> bool;
@@ -98,6 +115,20 @@ pub trait UartDriver: Driver {
     fn get(&self) -> Option<u8>;
 }
 
+/// The transport of the 9P protocol, like virtio-9p.
+pub trait P9Driver: Driver {
+    /// The tag used to mount the shared directory.
+    fn mount_tag(&self) -> &str;
+    /// Send the request and wait for the response, return the length of the response.
+    fn request(&self, req: &[u8], resp: &mut [u8]) -> usize;
+}
+
+/// The hardware random number generator, like virtio-rng.
+pub trait RngDriver: Driver {
+    /// Fill the buffer with the random bytes, return the number of the bytes filled.
+    fn read_random(&self, buf: &mut [u8]) -> usize;
+}
+
 pub struct UnsupportedDriver;
 
 impl Driver for UnsupportedDriver {
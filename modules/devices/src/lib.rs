@@ -9,12 +9,15 @@ extern crate log;
 #[macro_use]
 extern crate alloc;
 
+pub mod block_cache;
 pub mod device;
 pub mod memory;
+pub mod partition;
+pub mod random;
 // pub mod virtio;
 
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
-use device::{BlkDriver, DeviceSet, Driver, IntDriver, NetDriver, UartDriver};
+use device::{BlkDriver, DeviceSet, Driver, IntDriver, NetDriver, P9Driver, RngDriver, UartDriver};
 use fdt::{self, node::FdtNode, Fdt};
 use kheader::macros::link_define;
 use sync::{LazyInit, Mutex};
@@ -32,6 +35,37 @@ link_define! {
     pub static DRIVERS_INIT: [fn() -> Option<Arc<dyn Driver>>] = [..];
 }
 
+/// Encode the device number like the makedev in the libc.
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xfffff000) << 32)
+        | ((major & 0xfff) << 8)
+        | ((minor & 0xffffff00) << 12)
+        | (minor & 0xff)
+}
+
+/// The major number of the device number, the inverse of makedev.
+pub const fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xfffff000) | ((dev >> 8) & 0xfff)) as u32
+}
+
+/// The minor number of the device number, the inverse of makedev.
+pub const fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffffff00) | (dev & 0xff)) as u32
+}
+
+/// The major device numbers of the character devices, the same as linux.
+pub const MEM_DEVICE_MAJOR: u32 = 1;
+pub const TTY_DEVICE_MAJOR: u32 = 4;
+pub const TTYAUX_DEVICE_MAJOR: u32 = 5;
+pub const INPUT_DEVICE_MAJOR: u32 = 13;
+pub const RTC_DEVICE_MAJOR: u32 = 254;
+
+/// The minor number of the first serial port ttyS0.
+pub const TTY_SERIAL_MINOR: u32 = 64;
+/// The minor number of the first input event device event0.
+pub const INPUT_EVENT_MINOR: u32 = 64;
+
 #[inline]
 pub fn get_blk_device(id: usize) -> Option<Arc<dyn BlkDriver>> {
     let all_device = ALL_DEVICES.lock();
@@ -47,6 +81,16 @@ pub fn get_blk_devices() -> Vec<Arc<dyn BlkDriver>> {
     ALL_DEVICES.lock().blk.clone()
 }
 
+#[inline]
+pub fn get_p9_devices() -> Vec<Arc<dyn P9Driver>> {
+    ALL_DEVICES.lock().p9.clone()
+}
+
+#[inline]
+pub fn get_rng_devices() -> Vec<Arc<dyn RngDriver>> {
+    ALL_DEVICES.lock().rng.clone()
+}
+
 #[inline]
 pub fn get_int_device() -> Arc<dyn IntDriver> {
     INT_DEVICE.try_get().expect("can't find int device").clone()
@@ -0,0 +1,248 @@
+//! MBR and GPT partition tables, each partition is exposed as a block device.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use sync::Mutex;
+
+use crate::{
+    device::{BlkDriver, DeviceType, Driver},
+    get_blk_devices, ALL_DEVICES,
+};
+
+const SECTOR_SIZE: usize = 0x200;
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_TYPE_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+/// Stop walking the broken extended partition chain.
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+
+/// The block device id of the partition -> (disk id, partition number).
+static PARTITIONS: Mutex<BTreeMap<usize, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+/// A partition of the disk, the sectors are translated by the start offset.
+pub struct Partition {
+    name: String,
+    disk: Arc<dyn BlkDriver>,
+    start: usize,
+    sectors: usize,
+}
+
+impl Partition {
+    /// The filesystem on the broken partition may go past the end, the I/O fails then.
+    fn check_range(&self, block_id: usize, len: usize) -> bool {
+        let in_range = block_id
+            .checked_add(len.div_ceil(SECTOR_SIZE))
+            .is_some_and(|end| end <= self.sectors);
+        if !in_range {
+            error!(
+                "can't access the sector {} out of the partition {}",
+                block_id, self.name
+            );
+        }
+        in_range
+    }
+}
+
+impl Driver for Partition {
+    fn get_id(&self) -> &str {
+        &self.name
+    }
+
+    fn get_device_wrapper(self: Arc<Self>) -> DeviceType {
+        DeviceType::BLOCK(self.clone())
+    }
+}
+
+impl BlkDriver for Partition {
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) {
+        match self.check_range(block_id, buf.len()) {
+            true => self.disk.read_blocks(self.start + block_id, buf),
+            false => buf.fill(0),
+        }
+    }
+
+    /// The write out of the partition is dropped, the other partitions are kept safe.
+    fn write_blocks(&self, block_id: usize, buf: &[u8]) {
+        if self.check_range(block_id, buf.len()) {
+            self.disk.write_blocks(self.start + block_id, buf)
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.sectors
+    }
+
+    fn flush(&self) {
+        self.disk.flush()
+    }
+}
+
+#[inline]
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline]
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Read the GPT entries, return (start, sectors) of the partitions.
+fn parse_gpt(disk: &Arc<dyn BlkDriver>) -> Option<Vec<(usize, usize)>> {
+    let mut header = vec![0u8; SECTOR_SIZE];
+    disk.read_blocks(1, &mut header);
+    if &header[..8] != GPT_SIGNATURE {
+        return None;
+    }
+    let entries_lba = read_u64(&header, 0x48) as usize;
+    let entries_count = read_u32(&header, 0x50) as usize;
+    let entry_size = read_u32(&header, 0x54) as usize;
+    if entry_size < 0x30 || entry_size > SECTOR_SIZE || entries_count > 1024 {
+        warn!(
+            "ignore the gpt with {} entries of {} bytes",
+            entries_count, entry_size
+        );
+        return None;
+    }
+
+    let mut entries = vec![0u8; (entries_count * entry_size).div_ceil(SECTOR_SIZE) * SECTOR_SIZE];
+    disk.read_blocks(entries_lba, &mut entries);
+    Some(
+        entries
+            .chunks_exact(entry_size)
+            .take(entries_count)
+            .map(|entry| match entry[..16].iter().all(|x| *x == 0) {
+                true => (0, 0),
+                false => {
+                    let first = read_u64(entry, 0x20) as usize;
+                    let last = read_u64(entry, 0x28) as usize;
+                    (first, (last + 1).saturating_sub(first))
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Read the MBR, return (start, sectors) of the partitions.
+/// The primary partitions are 1-4 and the logical partitions start from 5.
+fn parse_mbr(disk: &Arc<dyn BlkDriver>, mbr: &[u8]) -> Vec<(usize, usize)> {
+    let entry = |sector: &[u8], index: usize| {
+        let offset = MBR_TABLE_OFFSET + index * 16;
+        (
+            sector[offset + 4],
+            read_u32(sector, offset + 8) as usize,
+            read_u32(sector, offset + 12) as usize,
+        )
+    };
+
+    let mut partitions = Vec::new();
+    let mut extended = None;
+    for index in 0..4 {
+        let (ptype, start, sectors) = entry(mbr, index);
+        match ptype {
+            0 => partitions.push((0, 0)),
+            _ if MBR_TYPE_EXTENDED.contains(&ptype) => {
+                // the extended partition itself isn't usable.
+                extended.get_or_insert(start);
+                partitions.push((0, 0));
+            }
+            _ => partitions.push((start, sectors)),
+        }
+    }
+
+    // walk the chain of the EBRs, the offsets in them are relative.
+    if let Some(extended) = extended {
+        let mut ebr = vec![0u8; SECTOR_SIZE];
+        let mut current = extended;
+        for _ in 0..MAX_LOGICAL_PARTITIONS {
+            disk.read_blocks(current, &mut ebr);
+            if ebr[510..512] != [0x55, 0xaa] {
+                break;
+            }
+            let (ptype, start, sectors) = entry(&ebr, 0);
+            if ptype != 0 && sectors != 0 {
+                partitions.push((current + start, sectors));
+            }
+            let (ptype, next, _) = entry(&ebr, 1);
+            if !MBR_TYPE_EXTENDED.contains(&ptype) || next == 0 {
+                break;
+            }
+            current = extended + next;
+        }
+    }
+    partitions
+}
+
+/// Parse the partition table of the disk, return (start, sectors) of the partitions.
+fn parse_partitions(disk: &Arc<dyn BlkDriver>) -> Vec<(usize, usize)> {
+    let mut mbr = vec![0u8; SECTOR_SIZE];
+    disk.read_blocks(0, &mut mbr);
+    // the boot sector of the FAT on the whole disk has the signature too,
+    // but the boot indicators of the boot code are rarely valid.
+    let valid = mbr[510..512] == [0x55, 0xaa]
+        && (0..4).all(|i| matches!(mbr[MBR_TABLE_OFFSET + i * 16], 0 | 0x80));
+    if !valid {
+        return Vec::new();
+    }
+    let is_gpt = (0..4).any(|i| mbr[MBR_TABLE_OFFSET + i * 16 + 4] == MBR_TYPE_GPT_PROTECTIVE);
+    match is_gpt {
+        true => parse_gpt(disk).unwrap_or_default(),
+        false => parse_mbr(disk, &mbr),
+    }
+}
+
+/// Scan the partition tables of the disks, add the partitions to the block devices.
+/// It should be called once after all the drivers are ready.
+pub fn scan_partitions() {
+    for (disk_id, disk) in get_blk_devices().into_iter().enumerate() {
+        let disk_name = blk_device_name(disk_id);
+        for (index, (start, sectors)) in parse_partitions(&disk).into_iter().enumerate() {
+            // the unused slot keeps the number of the following partitions.
+            if start == 0 || sectors == 0 {
+                continue;
+            }
+            let name = format!("{}{}", disk_name, index + 1);
+            info!(
+                "partition {}: start {:#x}, {:#x} sectors",
+                name, start, sectors
+            );
+            let partition = Arc::new(Partition {
+                name,
+                disk: disk.clone(),
+                start,
+                sectors,
+            });
+            // the disk is cached already, don't add the partition through add_device.
+            let mut all_devices = ALL_DEVICES.lock();
+            all_devices.blk.push(partition);
+            PARTITIONS
+                .lock()
+                .insert(all_devices.blk.len() - 1, (disk_id, index + 1));
+        }
+    }
+}
+
+/// The name of the block device in the devfs, sda for the disk and sda1 for the partition.
+pub fn blk_device_name(device_id: usize) -> String {
+    let disk_name = |disk_id: usize| format!("sd{}", (b'a' + disk_id as u8) as char);
+    match PARTITIONS.lock().get(&device_id) {
+        Some((disk_id, index)) => format!("{}{}", disk_name(*disk_id), index),
+        None => disk_name(device_id),
+    }
+}
+
+/// The disk id and the partition number if the block device is a partition.
+pub fn blk_device_partition(device_id: usize) -> Option<(usize, usize)> {
+    PARTITIONS.lock().get(&device_id).cloned()
+}
+
+/// The major device number of the disks, the same as the scsi disks in linux.
+pub const BLK_DEVICE_MAJOR: u32 = 8;
+
+/// The minor device number of the block device, each disk reserves 16 minors for its partitions.
+pub fn blk_device_minor(device_id: usize) -> u32 {
+    match PARTITIONS.lock().get(&device_id) {
+        Some((disk_id, index)) => (disk_id * 16 + index) as u32,
+        None => (device_id * 16) as u32,
+    }
+}
@@ -0,0 +1,237 @@
+//! The entropy pool and the ChaCha20 generator of /dev/urandom, /dev/random and getrandom(2).
+//! The pool is fed by the timer jitter, the interrupt timing, the rtc and the hardware rngs.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use alloc::vec::Vec;
+use arch::{get_time, time_to_usec};
+use sync::Mutex;
+
+use crate::{get_rng_devices, ALL_DEVICES};
+
+/// "expand 32-byte k"
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+/// The nonces of the blocks used to absorb the entropy and to reseed the key.
+const ABSORB_NONCE: u64 = u64::MAX;
+const RESEED_NONCE: u64 = u64::MAX - 1;
+/// The output of one key, a new key is made after every request like fast key erasure.
+const MAX_REQUEST: usize = 64 << 10;
+/// The hardware rngs are read again after the interval.
+const HWRNG_INTERVAL_USEC: usize = 60_000_000;
+/// The rounds of the timer jitter at the boot.
+const JITTER_ROUNDS: usize = 256;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const FAST_SLOT: AtomicU32 = AtomicU32::new(0);
+/// The interrupt timing is mixed without the lock, the interrupt may come when the lock is held.
+static FAST_POOL: [AtomicU32; 8] = [FAST_SLOT; 8];
+static FAST_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// The time of the last read of the hardware rngs in usec.
+static HWRNG_TIME: AtomicUsize = AtomicUsize::new(0);
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+static CRNG: Mutex<Crng> = Mutex::new(Crng::new());
+
+#[inline]
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// The ChaCha20 block with the 64-bit counter and the 64-bit nonce.
+fn chacha20_block(key: &[u32; 8], counter: u64, nonce: u64) -> [u32; 16] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+    state[14] = nonce as u32;
+    state[15] = (nonce >> 32) as u32;
+    let mut block = state;
+    for _ in 0..10 {
+        quarter_round(&mut block, 0, 4, 8, 12);
+        quarter_round(&mut block, 1, 5, 9, 13);
+        quarter_round(&mut block, 2, 6, 10, 14);
+        quarter_round(&mut block, 3, 7, 11, 15);
+        quarter_round(&mut block, 0, 5, 10, 15);
+        quarter_round(&mut block, 1, 6, 11, 12);
+        quarter_round(&mut block, 2, 7, 8, 13);
+        quarter_round(&mut block, 3, 4, 9, 14);
+    }
+    block
+        .iter_mut()
+        .zip(state)
+        .for_each(|(x, origin)| *x = x.wrapping_add(origin));
+    block
+}
+
+fn first_key(block: &[u32; 16]) -> [u32; 8] {
+    let mut key = [0u32; 8];
+    key.copy_from_slice(&block[..8]);
+    key
+}
+
+struct Crng {
+    key: [u32; 8],
+    /// The entropy absorbed since the last reseed.
+    pool: [u32; 8],
+    dirty: bool,
+    /// The nonce of the next request, the counter starts from 0 in every request.
+    generation: u64,
+}
+
+impl Crng {
+    const fn new() -> Self {
+        Self {
+            key: [0; 8],
+            pool: [0; 8],
+            dirty: false,
+            generation: 0,
+        }
+    }
+
+    /// Absorb the bytes 32 bytes a time, the pool is the key of the ChaCha20 permutation.
+    fn absorb(&mut self, data: &[u8]) {
+        for chunk in data.chunks(32) {
+            let mut input = self.pool;
+            for (i, byte) in chunk.iter().enumerate() {
+                input[i / 4] ^= (*byte as u32) << (i % 4 * 8);
+            }
+            self.pool = first_key(&chacha20_block(&input, chunk.len() as _, ABSORB_NONCE));
+        }
+        self.dirty = true;
+    }
+
+    /// Mix the pool into the key, the old key can't be got from the new one.
+    fn reseed(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let mut input = self.key;
+        input
+            .iter_mut()
+            .zip(self.pool)
+            .for_each(|(x, pool)| *x ^= pool);
+        self.key = first_key(&chacha20_block(&input, self.generation, RESEED_NONCE));
+        self.pool = [0; 8];
+        self.dirty = false;
+    }
+
+    /// Return the key and the nonce of the request, the key is replaced by the block 0.
+    fn request(&mut self) -> ([u32; 8], u64) {
+        let (key, nonce) = (self.key, self.generation);
+        self.generation = self.generation.wrapping_add(1);
+        self.key = first_key(&chacha20_block(&key, 0, nonce));
+        (key, nonce)
+    }
+}
+
+/// Mix the time and the irq into the fast pool, it can be called in the interrupt.
+pub fn add_interrupt_randomness(irq: u32) {
+    let count = FAST_COUNT.fetch_add(1, Ordering::Relaxed);
+    let time = get_time() as u64;
+    let value = (time as u32) ^ ((time >> 32) as u32).rotate_left(13) ^ irq.rotate_left(24);
+    let slot = &FAST_POOL[count % FAST_POOL.len()];
+    let old = slot.load(Ordering::Relaxed);
+    slot.store(old.rotate_left(7) ^ value, Ordering::Relaxed);
+}
+
+/// Add the bytes to the pool, they are used at the next request.
+pub fn add_randomness(data: &[u8]) {
+    CRNG.lock().absorb(data);
+}
+
+/// Take the interrupt timing and the current time.
+fn take_fast_pool() -> [u8; 48] {
+    let mut bytes = [0u8; 48];
+    for (i, slot) in FAST_POOL.iter().enumerate() {
+        let value = slot.swap(0, Ordering::Relaxed);
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let count = FAST_COUNT.load(Ordering::Relaxed);
+    bytes[32..40].copy_from_slice(&(count as u64).to_le_bytes());
+    bytes[40..48].copy_from_slice(&(get_time() as u64).to_le_bytes());
+    bytes
+}
+
+/// Read 32 bytes from every hardware rng, the device lock isn't held when the pool is locked.
+fn read_hwrngs() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for rng in get_rng_devices() {
+        let mut buffer = [0u8; 32];
+        let rlen = rng.read_random(&mut buffer);
+        bytes.extend_from_slice(&buffer[..rlen]);
+    }
+    bytes
+}
+
+/// The time of the loop varies with the caches, the pipeline and the interrupts.
+fn timer_jitter() -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(JITTER_ROUNDS);
+    let mut value = 0usize;
+    for round in 0..JITTER_ROUNDS {
+        let start = get_time();
+        for i in 0..(round % 7 + 1) * 16 {
+            value = core::hint::black_box(value.rotate_left(5) ^ i);
+        }
+        bytes.push((get_time() - start) as u8 ^ value as u8);
+    }
+    bytes
+}
+
+/// Seed the pool with the timer jitter, the rtcs and the hardware rngs.
+/// It is called after the devices are probed.
+pub fn init() {
+    let mut seed = timer_jitter();
+    for rtc in ALL_DEVICES.lock().rtc.iter() {
+        seed.extend_from_slice(&rtc.read().to_le_bytes());
+        seed.extend_from_slice(&rtc.read_timestamp().to_le_bytes());
+    }
+    let hwrngs = read_hwrngs();
+    HWRNG_TIME.store(time_to_usec(get_time()), Ordering::Relaxed);
+    info!(
+        "seed the random pool with {} bytes, {} from the hardware rngs",
+        seed.len() + hwrngs.len(),
+        hwrngs.len()
+    );
+    seed.extend_from_slice(&hwrngs);
+    let mut crng = CRNG.lock();
+    crng.absorb(&seed);
+    crng.reseed();
+    INITIALIZED.store(true, Ordering::Release);
+}
+
+/// Whether the pool is seeded, getrandom(2) fails with EAGAIN before it.
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Acquire)
+}
+
+/// Fill the buffer with the random bytes.
+pub fn fill_random(buffer: &mut [u8]) {
+    let now = time_to_usec(get_time());
+    if now.saturating_sub(HWRNG_TIME.load(Ordering::Relaxed)) >= HWRNG_INTERVAL_USEC {
+        HWRNG_TIME.store(now, Ordering::Relaxed);
+        add_randomness(&read_hwrngs());
+    }
+    for request in buffer.chunks_mut(MAX_REQUEST) {
+        let (key, nonce) = {
+            let mut crng = CRNG.lock();
+            crng.absorb(&take_fast_pool());
+            crng.reseed();
+            crng.request()
+        };
+        // the block 0 is the next key, the output starts from the block 1.
+        for (counter, chunk) in request.chunks_mut(64).enumerate() {
+            let block = chacha20_block(&key, counter as u64 + 1, nonce);
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (block[i / 4] >> (i % 4 * 8)) as u8;
+            }
+        }
+    }
+}
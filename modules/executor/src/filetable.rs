@@ -1,16 +1,21 @@
 use core::{
     mem::size_of,
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use alloc::{string::String, sync::Arc, vec::Vec};
 use fs::{
     dentry::{self, dentry_open, dentry_root, DentryNode},
+    inotify::{self, InotifyMask},
+    lock::{release_owner, LockKey, LockOwner},
+    mount::mount_dev,
+    page_cache::{get_page_cache, PageCache},
     INodeInterface, VfsError, WaitBlockingRead, WaitBlockingWrite,
 };
 use sync::Mutex;
 use vfscore::{
-    DirEntry, Dirent64, Metadata, OpenFlags, PollEvent, SeekFrom, Stat, StatFS, TimeSpec,
+    DirEntry, Dirent64, FileType, Metadata, OpenFlags, PollEvent, SeekFrom, Stat, StatFS, TimeSpec,
 };
 
 const FILE_MAX: usize = 255;
@@ -73,6 +78,8 @@ pub struct FileItem {
     pub options: FileOptions,
     pub offset: Mutex<usize>,
     pub flags: Mutex<OpenFlags>,
+    /// Set if it is opened by open(2), the close event is reported when it is dropped.
+    opened: AtomicBool,
 }
 
 impl<'a> FileItem {
@@ -87,6 +94,7 @@ impl<'a> FileItem {
             dentry,
             offset: Mutex::new(0),
             flags: Mutex::new(OpenFlags::NONE),
+            opened: AtomicBool::new(false),
         })
     }
 
@@ -107,6 +115,7 @@ impl<'a> FileItem {
             dentry: None,
             options: FileOptions::default(),
             flags: Mutex::new(OpenFlags::NONE),
+            opened: AtomicBool::new(false),
         })
     }
 
@@ -114,6 +123,11 @@ impl<'a> FileItem {
         self.inner.clone()
     }
 
+    /// The page cache of the file, None if the file isn't cached.
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        get_page_cache(&self.inner)
+    }
+
     pub fn fs_open(path: &str, open_flags: OpenFlags) -> Result<Arc<Self>, VfsError> {
         let mut options = FileOptions::R | FileOptions::X;
         if open_flags.contains(OpenFlags::O_WRONLY)
@@ -134,6 +148,7 @@ impl<'a> FileItem {
             dentry: Some(dentry_node),
             offset: Mutex::new(offset),
             flags: Mutex::new(open_flags),
+            opened: AtomicBool::new(false),
         }))
     }
 
@@ -153,10 +168,27 @@ impl<'a> FileItem {
                 offset: Mutex::new(0),
                 flags: Mutex::new(flags.clone()),
                 options,
+                opened: AtomicBool::new(false),
             })
         })
     }
 
+    /// Open the device of the device node made by mknod(2), the other files are kept.
+    pub fn open_device(self: Arc<Self>) -> Result<Arc<Self>, VfsError> {
+        let inner = match fs::open_device(&self.inner)? {
+            Some(inner) => inner,
+            None => return Ok(self),
+        };
+        Ok(Arc::new(Self {
+            inner,
+            dentry: self.dentry.clone(),
+            options: self.options.clone(),
+            offset: Mutex::new(0),
+            flags: Mutex::new(self.flags.lock().clone()),
+            opened: AtomicBool::new(false),
+        }))
+    }
+
     #[inline(always)]
     fn check_writeable(&self) -> Result<(), VfsError> {
         if self.options.contains(FileOptions::W) {
@@ -166,6 +198,19 @@ impl<'a> FileItem {
         }
     }
 
+    /// Report the inotify event of the file.
+    pub fn notify(&self, mask: InotifyMask) {
+        if let Some(dentry) = &self.dentry {
+            inotify::notify(dentry, mask);
+        }
+    }
+
+    /// Mark the file opened by open(2), IN_OPEN is reported.
+    pub fn notify_open(&self) {
+        self.opened.store(true, Ordering::Relaxed);
+        self.notify(InotifyMask::IN_OPEN);
+    }
+
     pub fn path(&self) -> Result<String, VfsError> {
         // Ok(&self.path)
         // dentry_open(dentry, path, flags)
@@ -216,29 +261,47 @@ impl<'a> FileItem {
 }
 
 impl FileItem {
+    /// Drop the cached dentry of the file in the directory, it is changed without the dentry.
+    pub fn invalidate(&self, name: &str) {
+        if let Some(dentry) = &self.dentry {
+            dentry.invalidate(name);
+        }
+    }
+
     pub fn mkdir(&self, name: &str) -> Result<Arc<dyn INodeInterface>, VfsError> {
-        self.inner.mkdir(name)
+        let node = self.inner.mkdir(name)?;
+        self.invalidate(name);
+        Ok(node)
     }
 
     pub fn rmdir(&self, name: &str) -> Result<(), VfsError> {
-        self.inner.rmdir(name)
+        self.inner.rmdir(name)?;
+        self.invalidate(name);
+        Ok(())
     }
 
     pub fn remove(&self, name: &str) -> Result<(), VfsError> {
-        self.inner.remove(name)
+        self.inner.remove(name)?;
+        self.invalidate(name);
+        Ok(())
     }
 
     pub fn moveto(&self, _path: &str) -> Result<Self, VfsError> {
         todo!("Move the file? to other location")
     }
 
+    /// Remove the file from its directory, the directory is removed by rmdir.
     pub fn remove_self(&self) -> Result<(), VfsError> {
         match &self.dentry {
             Some(dentry) => {
                 let filename = &dentry.filename;
+                let is_dir = self.metadata()?.file_type == FileType::Directory;
                 if let Some(parent) = dentry.parent.upgrade() {
-                    parent.node.remove(filename)?;
-                    parent.children.lock().retain(|x| &x.filename != filename);
+                    inotify::notify_delete(dentry, || match is_dir {
+                        true => parent.node.rmdir(filename),
+                        false => parent.node.remove(filename),
+                    })?;
+                    parent.invalidate(filename);
                 }
                 Ok(())
             }
@@ -247,7 +310,9 @@ impl FileItem {
     }
 
     pub fn touch(&self, name: &str) -> Result<Arc<dyn INodeInterface>, VfsError> {
-        self.inner.touch(name)
+        let node = self.inner.touch(name)?;
+        self.invalidate(name);
+        Ok(node)
     }
 
     pub fn read_dir(&self) -> Result<Vec<DirEntry>, VfsError> {
@@ -276,10 +341,34 @@ impl FileItem {
         // if *offset > size {
         //     *offset = size;
         // }
-        self.inner.truncate(size)
+        self.inner.truncate(size)?;
+        if let Some(cache) = self.page_cache() {
+            cache.truncate(size);
+        }
+        self.notify(InotifyMask::IN_MODIFY);
+        Ok(())
+    }
+
+    /// Allocate the space of the range, the file is extended unless keep_size is set.
+    pub fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> Result<(), VfsError> {
+        self.check_writeable()?;
+        fs::fallocate(&self.inner, offset, len, keep_size)?;
+        self.notify(InotifyMask::IN_MODIFY);
+        Ok(())
+    }
+
+    /// Free the space of the range, it is read as zero after it.
+    pub fn punch_hole(&self, offset: usize, len: usize) -> Result<(), VfsError> {
+        self.check_writeable()?;
+        fs::punch_hole(&self.inner, offset, len)?;
+        self.notify(InotifyMask::IN_MODIFY);
+        Ok(())
     }
 
     pub fn flush(&self) -> Result<(), VfsError> {
+        if let Some(cache) = self.page_cache() {
+            cache.sync()?;
+        }
         self.inner.flush()
     }
 
@@ -288,19 +377,41 @@ impl FileItem {
     }
 
     pub fn link(&self, name: &str, src: Arc<dyn INodeInterface>) -> Result<(), VfsError> {
-        self.inner.link(name, src)
+        self.inner.link(name, src)?;
+        self.invalidate(name);
+        if let Some(dentry) = self
+            .dentry
+            .clone()
+            .and_then(|x| x.open(name, OpenFlags::NONE))
+        {
+            inotify::notify(&dentry, InotifyMask::IN_CREATE);
+        }
+        Ok(())
     }
 
     pub fn unlink(&self, name: &str) -> Result<(), VfsError> {
-        self.inner.unlink(name)
+        self.inner.unlink(name)?;
+        self.invalidate(name);
+        Ok(())
     }
 
     pub fn stat(&self, stat: &mut Stat) -> Result<(), VfsError> {
         self.inner.stat(stat)?;
-        stat.dev = 0;
+        // the files without path, like pipes, are not in any filesystem.
+        stat.dev = match &self.dentry {
+            Some(dentry) => mount_dev(&dentry.path()),
+            None => 0,
+        };
         Ok(())
     }
 
+    /// The file is locked by its device number and inode number.
+    pub fn lock_key(&self) -> Result<LockKey, VfsError> {
+        let mut stat = Stat::default();
+        self.stat(&mut stat)?;
+        Ok((stat.dev, stat.ino))
+    }
+
     pub fn mount(&self, path: &str) -> Result<(), VfsError> {
         self.inner.mount(path)
     }
@@ -322,9 +433,25 @@ impl FileItem {
     }
 }
 
+impl Drop for FileItem {
+    fn drop(&mut self) {
+        // the flock locks and the OFD locks are released when the open file is closed.
+        release_owner(LockOwner::File(self as *const FileItem as usize));
+        if self.opened.load(Ordering::Relaxed) {
+            self.notify(match self.options.contains(FileOptions::W) {
+                true => InotifyMask::IN_CLOSE_WRITE,
+                false => InotifyMask::IN_CLOSE_NOWRITE,
+            });
+        }
+    }
+}
+
 impl FileItem {
     pub fn readat(&self, offset: usize, buffer: &mut [u8]) -> Result<usize, VfsError> {
-        self.inner.readat(offset, buffer)
+        match self.page_cache() {
+            Some(cache) => cache.read(offset, buffer),
+            None => self.inner.readat(offset, buffer),
+        }
     }
 
     pub fn writeat(&self, offset: usize, buffer: &[u8]) -> Result<usize, VfsError> {
@@ -332,12 +459,17 @@ impl FileItem {
         if buffer.len() == 0 {
             return Ok(0);
         }
-        self.inner.writeat(offset, buffer)
+        let wsize = match self.page_cache() {
+            Some(cache) => cache.write(offset, buffer),
+            None => self.inner.writeat(offset, buffer),
+        }?;
+        self.notify(InotifyMask::IN_MODIFY);
+        Ok(wsize)
     }
 
     pub fn read(&self, buffer: &mut [u8]) -> Result<usize, VfsError> {
         let offset = *self.offset.lock();
-        self.inner.readat(offset, buffer).map(|x| {
+        self.readat(offset, buffer).map(|x| {
             *self.offset.lock() += x;
             x
         })
@@ -349,7 +481,7 @@ impl FileItem {
             return Ok(0);
         }
         let offset = *self.offset.lock();
-        self.inner.writeat(offset, buffer).map(|x| {
+        self.writeat(offset, buffer).map(|x| {
             *self.offset.lock() += x;
             x
         })
@@ -357,8 +489,9 @@ impl FileItem {
 
     pub async fn async_read(&self, buffer: &mut [u8]) -> Result<usize, VfsError> {
         let offset = *self.offset.lock();
-        if self.flags.lock().contains(OpenFlags::O_NONBLOCK) {
-            self.inner.readat(offset, buffer)
+        // the cached files never block.
+        if self.flags.lock().contains(OpenFlags::O_NONBLOCK) || self.page_cache().is_some() {
+            self.readat(offset, buffer)
         } else {
             WaitBlockingRead(self.inner.clone(), buffer, offset).await
         }
@@ -374,12 +507,32 @@ impl FileItem {
             return Ok(0);
         }
         let offset = *self.offset.lock();
-        WaitBlockingWrite(self.inner.clone(), &buffer, offset)
-            .await
-            .map(|x| {
-                *self.offset.lock() += x;
-                x
-            })
+        let wsize = match self.page_cache() {
+            Some(cache) => cache.write(offset, buffer),
+            None => WaitBlockingWrite(self.inner.clone(), &buffer, offset).await,
+        }?;
+        *self.offset.lock() += wsize;
+        self.notify(InotifyMask::IN_MODIFY);
+        Ok(wsize)
+    }
+
+    /// Move to the first data at or after the offset, lseek(2) with SEEK_DATA.
+    /// The offset isn't changed if there is only the hole after it.
+    pub fn seek_data(&self, offset: usize) -> Result<Option<usize>, VfsError> {
+        let found = fs::seek_data(&self.inner, offset)?;
+        if let Some(new_off) = found {
+            *self.offset.lock() = new_off;
+        }
+        Ok(found)
+    }
+
+    /// Move to the first hole at or after the offset, lseek(2) with SEEK_HOLE.
+    pub fn seek_hole(&self, offset: usize) -> Result<Option<usize>, VfsError> {
+        let found = fs::seek_hole(&self.inner, offset)?;
+        if let Some(new_off) = found {
+            *self.offset.lock() = new_off;
+        }
+        Ok(found)
     }
 
     pub fn seek(&self, seek_from: SeekFrom) -> Result<usize, VfsError> {
@@ -12,6 +12,7 @@ mod executor;
 mod filetable;
 mod memset;
 mod ops;
+mod process;
 pub mod shm;
 pub mod signal;
 mod task;
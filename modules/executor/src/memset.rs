@@ -1,12 +1,16 @@
 use alloc::{sync::Arc, vec::Vec};
-use arch::{PageTable, VirtPage, PAGE_SIZE};
+use arch::{MappingFlags, PageTable, VirtPage, PAGE_SIZE};
 use core::{
     cmp::min,
     fmt::Debug,
     ops::{Deref, DerefMut},
 };
 use frame_allocator::{frame_alloc, FrameTracker};
-use fs::File;
+use fs::{
+    page_cache::{get_page_cache, PageCache},
+    File,
+};
+use vfscore::{VfsError, VfsResult};
 
 /// Memory set for storing the memory and its map relation.
 #[derive(Debug)]
@@ -37,7 +41,13 @@ impl<'a> MemSet {
         self.0.iter().find(|x| x.overlapping(start, end)).is_some()
     }
 
-    pub fn sub_area(&mut self, start: usize, end: usize, pt: &PageTable) {
+    /// Unmap the range, the shared file pages in it are written back first.
+    /// Nothing is unmapped if the write back fails.
+    pub fn sub_area(&mut self, start: usize, end: usize, pt: &PageTable) -> VfsResult<()> {
+        self.0
+            .iter()
+            .filter(|x| x.overlapping(start, end))
+            .try_for_each(|x| x.write_back(start, end))?;
         let mut new_set = Vec::new();
         self.0.retain_mut(|area| {
             let res = area.sub(start, end, pt);
@@ -47,6 +57,7 @@ impl<'a> MemSet {
             area.len != 0
         });
         self.0.extend(new_set);
+        Ok(())
     }
 
     pub fn clear(&mut self) {
@@ -89,6 +100,8 @@ pub struct MemArea {
     pub offset: usize,
     pub start: usize,
     pub len: usize,
+    /// The protection of the pages mapped at the first access.
+    pub rwx: u8,
 }
 
 impl Debug for MemArea {
@@ -111,6 +124,7 @@ impl MemArea {
             start,
             offset: 0,
             len,
+            rwx: 0b111,
         }
     }
     pub fn map(&mut self, vpn: VirtPage, tracker: Arc<FrameTracker>) {
@@ -151,14 +165,91 @@ impl MemArea {
         res
     }
 
+    /// The page cache of the mapped file, the pages are shared with it.
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.file.as_ref().and_then(get_page_cache)
+    }
+
+    /// The page of the page cache at the virtual page, None if the area isn't backed by it.
+    /// It fails after the end of the file.
+    pub fn cache_page(&self, vpn: VirtPage) -> Option<VfsResult<Arc<FrameTracker>>> {
+        let cache = self.page_cache()?;
+        let index = self.page_index(vpn);
+        let page = match cache.size() {
+            Ok(size) if index * PAGE_SIZE < size => cache.get_page(index),
+            Ok(_) => Err(VfsError::InvalidInput),
+            Err(err) => Err(err),
+        };
+        Some(page)
+    }
+
+    /// The index of the page in the mapped file.
+    fn page_index(&self, vpn: VirtPage) -> usize {
+        (vpn.to_addr() + self.offset - self.start) / PAGE_SIZE
+    }
+
+    /// The flags to map the page, the page of the page cache is read-only until the write fault.
+    pub fn map_flags(&self, map_track: &MapTrack) -> MappingFlags {
+        if self.page_cache().is_none() {
+            return MappingFlags::URWX;
+        }
+        let mut flags = MappingFlags::U;
+        if map_track.rwx & 0b001 != 0 {
+            flags |= MappingFlags::R;
+        }
+        if map_track.rwx & 0b010 != 0
+            && self.mtype != MemType::ShareFile
+            && Arc::strong_count(&map_track.tracker) == 1
+        {
+            flags |= MappingFlags::W;
+        }
+        if map_track.rwx & 0b100 != 0 {
+            flags |= MappingFlags::X;
+        }
+        flags
+    }
+
+    /// Mark the page dirty at the first write through the shared file mapping.
+    pub fn mark_dirty(&self, vpn: VirtPage) {
+        if let Some(cache) = self.page_cache() {
+            cache.mark_dirty(self.page_index(vpn));
+        }
+    }
+
+    /// write back the pages of the shared file mapping.
+    pub fn sync(&self) -> VfsResult<()> {
+        match self.page_cache() {
+            Some(cache) => cache.sync(),
+            None => self.mtrackers.iter().try_for_each(|x| self.write_page(x)),
+        }
+    }
+
+    /// write back the pages of the shared file mapping in the range.
+    pub fn write_back(&self, start: usize, end: usize) -> VfsResult<()> {
+        if self.mtype != MemType::ShareFile || self.file.is_none() {
+            return Ok(());
+        }
+        if let Some(cache) = self.page_cache() {
+            return cache.sync();
+        }
+        self.mtrackers
+            .iter()
+            .filter(|x| (start..end).contains(&x.vpn.to_addr()))
+            .try_for_each(|x| self.write_page(x))
+    }
+
     /// write page to file
-    pub fn write_page(&self, mtracker: &MapTrack) {
+    pub fn write_page(&self, mtracker: &MapTrack) -> VfsResult<()> {
         assert!(self.file.is_some());
+        // the page is marked dirty when it is written.
+        if let Some(cache) = self.page_cache() {
+            return cache.sync();
+        }
         if let Some(file) = &self.file {
             let offset = mtracker.vpn.to_addr() + self.offset - self.start;
-            file.writeat(offset, mtracker.tracker.0.get_buffer())
-                .expect("can't write data back to mapped file.");
+            file.writeat(offset, mtracker.tracker.0.get_buffer())?;
         }
+        Ok(())
     }
 
     /// Sub the memory from this memory area.
@@ -174,14 +265,6 @@ impl MemArea {
             self.len = start - self.start;
             let new_area_range = end..range.end;
 
-            if let Some(_file) = &self.file {
-                self.mtrackers
-                    .iter()
-                    .filter(|x| jrange.contains(&x.vpn.to_addr()))
-                    .for_each(|x| {
-                        self.write_page(x);
-                    });
-            };
             // drop the sub memory area pages.
             self.mtrackers
                 .retain(|x| !new_area_range.contains(&x.vpn.to_addr()));
@@ -193,8 +276,9 @@ impl MemArea {
                     .collect(),
                 file: self.file.clone(),
                 start: end,
-                offset: end - self.start,
+                offset: self.offset + end - self.start,
                 len: new_area_range.len(),
+                rwx: self.rwx,
             });
         }
 
@@ -202,14 +286,6 @@ impl MemArea {
             self.len = 0;
             // TIPS: This area will be remove outside this function.
             // So return the None.
-            if let Some(_file) = &self.file {
-                self.mtrackers
-                    .iter()
-                    .filter(|x| jrange.contains(&x.vpn.to_addr()))
-                    .for_each(|x| {
-                        self.write_page(x);
-                    });
-            };
             self.mtrackers.retain(|x| {
                 pt.unmap(x.vpn);
                 false
@@ -222,16 +298,9 @@ impl MemArea {
             self.len = start - self.start;
         } else if jrange.contains(&self.start) {
             self.len = self.start + self.len - end;
+            self.offset += end - self.start;
             self.start = end;
         }
-        if let Some(_file) = &self.file {
-            self.mtrackers
-                .iter()
-                .filter(|x| jrange.contains(&x.vpn.to_addr()))
-                .for_each(|x| {
-                    self.write_page(x);
-                });
-        };
         // drop the sub memory area pages.
         let new_self_rang = self.start..self.start + self.len;
         self.mtrackers
@@ -251,6 +320,11 @@ impl MemArea {
 impl Drop for MemArea {
     fn drop(&mut self) {
         match &self.mtype {
+            MemType::ShareFile if self.page_cache().is_some() => {
+                if let Err(err) = self.sync() {
+                    log::warn!("can't write back the shared file mapping: {:?}", err);
+                }
+            }
             MemType::ShareFile => {
                 let start = self.start;
                 let len = self.len;
@@ -0,0 +1,183 @@
+//! The tasks shown in /proc/[pid], procfs asks them through ProcessOps.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use arch::{time_to_usec, PAGE_SIZE};
+use fs::{
+    pipe::{PipeReceiver, PipeSender},
+    Stat,
+};
+use procfs::{ProcessInfo, ProcessMap, ProcessOps};
+
+use crate::{FileItem, MemType, TaskId, UserTask, CURRENT_TASK, TASK_QUEUE};
+
+/// The clock ticks per second of the times in /proc/[pid]/stat.
+const USER_HZ: usize = 100;
+
+fn current_thread() -> Option<Arc<UserTask>> {
+    CURRENT_TASK.lock().clone()?.as_user_task()
+}
+
+/// The user tasks in the task queue, and the exited children which aren't waited.
+fn user_tasks() -> BTreeMap<TaskId, Arc<UserTask>> {
+    fn add_task(tasks: &mut BTreeMap<TaskId, Arc<UserTask>>, task: Arc<UserTask>) {
+        let children = task.pcb.lock().children.clone();
+        tasks.insert(task.task_id, task);
+        for child in children {
+            if !tasks.contains_key(&child.task_id) {
+                add_task(tasks, child);
+            }
+        }
+    }
+    let queue: Vec<_> = TASK_QUEUE.lock().iter().cloned().collect();
+    let mut tasks = BTreeMap::new();
+    for task in queue.into_iter().filter_map(|x| x.as_user_task()) {
+        add_task(&mut tasks, task);
+    }
+    if let Some(task) = current_thread() {
+        add_task(&mut tasks, task);
+    }
+    tasks
+}
+
+fn find_task(tid: usize) -> Option<Arc<UserTask>> {
+    user_tasks().remove(&tid)
+}
+
+/// The files without the path are named by the type like Linux.
+fn fd_target(file: &FileItem) -> String {
+    if let Ok(path) = file.path() {
+        return path;
+    }
+    let id = Arc::as_ptr(&file.inner) as *const u8 as usize;
+    match file.inner.is::<PipeReceiver>() || file.inner.is::<PipeSender>() {
+        true => format!("pipe:[{}]", id),
+        false => format!("anon_inode:[{}]", id),
+    }
+}
+
+struct ProcessOpsImpl;
+
+#[crate_interface::impl_interface]
+impl ProcessOps for ProcessOpsImpl {
+    fn processes() -> Vec<usize> {
+        let pids: BTreeSet<_> = user_tasks().values().map(|x| x.process_id).collect();
+        pids.into_iter().collect()
+    }
+
+    fn threads(pid: usize) -> Vec<usize> {
+        user_tasks()
+            .into_values()
+            .filter(|x| x.process_id == pid)
+            .map(|x| x.task_id)
+            .collect()
+    }
+
+    fn current() -> Option<(usize, usize)> {
+        current_thread().map(|x| (x.process_id, x.task_id))
+    }
+
+    fn info(tid: usize) -> Option<ProcessInfo> {
+        let task = find_task(tid)?;
+        let is_current = current_thread().is_some_and(|x| x.task_id == tid);
+        let thread_exited = task.tcb.read().thread_exit_code.is_some();
+        let ppid = task.parent.read().upgrade().map(|x| x.get_task_id());
+        let pcb = task.pcb.lock();
+        let state = match (is_current, thread_exited || pcb.exit_code.is_some()) {
+            (true, _) => 'R',
+            (false, true) => 'Z',
+            (false, false) => 'S',
+        };
+        let vsize = pcb.memset.iter().map(|x| x.len).sum::<usize>()
+            + pcb.shms.iter().map(|x| x.size).sum::<usize>();
+        let pages = pcb.memset.iter().map(|x| x.mtrackers.len()).sum::<usize>()
+            + pcb.shms.iter().map(|x| x.mem.trackers.len()).sum::<usize>();
+        let ticks = |time: u64| (time_to_usec(time as _) / (1_000_000 / USER_HZ)) as u64;
+        Some(ProcessInfo {
+            pid: task.process_id,
+            tid,
+            ppid: ppid.unwrap_or(0),
+            state,
+            uid: pcb.uid,
+            gid: pcb.gid,
+            umask: pcb.umask,
+            threads: pcb.threads.iter().filter(|x| x.strong_count() > 0).count(),
+            utime: ticks(pcb.tms.utime),
+            stime: ticks(pcb.tms.stime),
+            vsize,
+            rss: pages * PAGE_SIZE,
+            fd_size: pcb.fd_table.len(),
+            cmdline: pcb.cmdline.clone(),
+            environ: pcb.environ.clone(),
+            exe: pcb.exe.clone(),
+            cwd: pcb.curr_dir.path().unwrap_or_default(),
+        })
+    }
+
+    fn maps(tid: usize) -> Vec<ProcessMap> {
+        let task = match find_task(tid) {
+            Some(task) => task,
+            None => return Vec::new(),
+        };
+        let pcb = task.pcb.lock();
+        let mut maps: Vec<ProcessMap> = pcb
+            .memset
+            .iter()
+            .map(|x| {
+                let mut stat = Stat::default();
+                let ino = match &x.file {
+                    Some(file) if file.stat(&mut stat).is_ok() => stat.ino,
+                    _ => 0,
+                };
+                ProcessMap {
+                    start: x.start,
+                    end: x.start + x.len,
+                    perms: match x.mtype {
+                        MemType::Shared | MemType::ShareFile => "rwxs",
+                        _ => "rwxp",
+                    },
+                    offset: x.offset,
+                    ino,
+                    name: match x.mtype {
+                        MemType::Stack => String::from("[stack]"),
+                        _ => String::new(),
+                    },
+                }
+            })
+            .collect();
+        maps.extend(pcb.shms.iter().map(|x| ProcessMap {
+            start: x.start,
+            end: x.start + x.size,
+            perms: "rw-s",
+            offset: 0,
+            ino: x.key as _,
+            name: format!("/SYSV{:08x}", x.key),
+        }));
+        maps.sort_by_key(|x| x.start);
+        maps
+    }
+
+    fn fds(tid: usize) -> Vec<(usize, String)> {
+        let task = match find_task(tid) {
+            Some(task) => task,
+            None => return Vec::new(),
+        };
+        let files: Vec<_> = task
+            .pcb
+            .lock()
+            .fd_table
+            .iter()
+            .cloned()
+            .enumerate()
+            .collect();
+        files
+            .into_iter()
+            .filter_map(|(fd, file)| Some((fd, fd_target(&file?))))
+            .collect()
+    }
+}
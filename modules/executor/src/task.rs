@@ -3,6 +3,7 @@ use core::{future::Future, mem::size_of, ops::Add};
 use alloc::{
     boxed::Box,
     collections::BTreeMap,
+    string::String,
     sync::{Arc, Weak},
     vec::Vec,
 };
@@ -10,7 +11,10 @@ use arch::{
     Context, ContextArgs, MappingFlags, PageTable, PhysPage, VirtAddr, VirtPage, PAGE_SIZE,
 };
 use frame_allocator::{ceil_div, frame_alloc_much, FrameTracker};
-use fs::File;
+use fs::{
+    lock::{release_owner, unlock, LockClass, LockOwner},
+    File,
+};
 use log::{debug, warn};
 use signal::REAL_TIME_SIGNAL_NUM;
 pub use signal::{SigAction, SigProcMask, SignalFlags};
@@ -83,6 +87,15 @@ pub struct ProcessControlBlock {
     pub timer: [ProcessTimer; 3],
     pub threads: Vec<Weak<UserTask>>,
     pub exit_code: Option<usize>,
+    /// The credential used by the access checks of the files.
+    pub uid: u32,
+    pub gid: u32,
+    /// The permission bits cleared from the new files.
+    pub umask: u32,
+    /// The executable, the arguments and the environment shown in /proc/[pid].
+    pub exe: String,
+    pub cmdline: Vec<String>,
+    pub environ: Vec<String>,
 }
 
 pub struct ThreadControlBlock {
@@ -143,6 +156,12 @@ impl UserTask {
             timer: [Default::default(); 3],
             exit_code: None,
             threads: Vec::new(),
+            uid: 0,
+            gid: 0,
+            umask: 0o022,
+            exe: String::new(),
+            cmdline: Vec::new(),
+            environ: Vec::new(),
         };
 
         let tcb = RwLock::new(ThreadControlBlock {
@@ -236,6 +255,7 @@ impl UserTask {
                     offset: 0,
                     start: 0x7000_0000,
                     len: 0x1000_0000,
+                    rwx: 0b111,
                 });
             }
         } else {
@@ -246,6 +266,7 @@ impl UserTask {
                 offset,
                 start,
                 len,
+                rwx: 0b111,
             });
         }
         drop(inner);
@@ -310,6 +331,7 @@ impl UserTask {
         if Arc::strong_count(&self.pcb) == 1 {
             self.pcb.lock().memset.clear();
             self.pcb.lock().fd_table.clear();
+            release_owner(LockOwner::Process(self.process_id));
             self.pcb.lock().children.clear();
         }
 
@@ -353,6 +375,7 @@ impl UserTask {
         if Arc::strong_count(&self.pcb) == 1 {
             self.pcb.lock().memset.clear();
             self.pcb.lock().fd_table.clear();
+            release_owner(LockOwner::Process(self.process_id));
             self.pcb.lock().children.clear();
             self.pcb.lock().exit_code = Some(exit_code);
         }
@@ -406,6 +429,10 @@ impl UserTask {
         new_tcb_writer.cx = self.tcb.read().cx.clone();
         new_tcb_writer.cx[ContextArgs::RET] = 0;
         new_pcb.curr_dir = pcb.curr_dir.clone();
+        (new_pcb.uid, new_pcb.gid, new_pcb.umask) = (pcb.uid, pcb.gid, pcb.umask);
+        new_pcb.exe = pcb.exe.clone();
+        new_pcb.cmdline = pcb.cmdline.clone();
+        new_pcb.environ = pcb.environ.clone();
 
         pcb.children.push(new_task.clone());
         new_pcb.shms = pcb.shms.clone();
@@ -413,7 +440,8 @@ impl UserTask {
         pcb.memset.iter().for_each(|x| {
             let map_area = x.fork();
             map_area.mtrackers.iter().for_each(|map_track| {
-                new_task.map(map_track.tracker.0, map_track.vpn, MappingFlags::URWX);
+                let flags = map_area.map_flags(map_track);
+                new_task.map(map_track.tracker.0, map_track.vpn, flags);
             });
 
             new_task.pcb.lock().memset.push(map_area);
@@ -458,6 +486,10 @@ impl UserTask {
         new_tcb_writer.cx = self.tcb.read().cx.clone();
         new_tcb_writer.cx[ContextArgs::RET] = 0;
         new_pcb.curr_dir = pcb.curr_dir.clone();
+        (new_pcb.uid, new_pcb.gid, new_pcb.umask) = (pcb.uid, pcb.gid, pcb.umask);
+        new_pcb.exe = pcb.exe.clone();
+        new_pcb.cmdline = pcb.cmdline.clone();
+        new_pcb.environ = pcb.environ.clone();
         pcb.children.push(new_task.clone());
         new_pcb.shms = pcb.shms.clone();
         drop(new_pcb);
@@ -611,17 +643,34 @@ impl UserTask {
 
     pub fn set_fd(&self, index: usize, value: Arc<FileItem>) {
         let mut pcb = self.pcb.lock();
-        match index >= pcb.rlimits[7] {
-            true => {}
-            false => pcb.fd_table.0[index] = Some(value),
+        let old = match index >= pcb.rlimits[7] {
+            true => None,
+            false => pcb.fd_table.0[index].replace(value.clone()),
+        };
+        drop(pcb);
+        // dup2(2) to the same file descriptor doesn't close it.
+        if let Some(old) = old.filter(|x| !Arc::ptr_eq(x, &value)) {
+            self.close_file(&old);
         }
     }
 
     pub fn clear_fd(&self, index: usize) {
         let mut pcb = self.pcb.lock();
-        match index >= pcb.fd_table.len() {
-            true => {}
-            false => pcb.fd_table.0[index] = None,
+        let old = match index >= pcb.fd_table.len() {
+            true => None,
+            false => pcb.fd_table.0[index].take(),
+        };
+        drop(pcb);
+        if let Some(old) = old {
+            self.close_file(&old);
+        }
+    }
+
+    /// Any close of the file releases the record locks of the process on it.
+    fn close_file(&self, file: &FileItem) {
+        if let Ok(key) = file.lock_key() {
+            let owner = LockOwner::Process(self.process_id);
+            unlock(key, LockClass::Record, owner, 0, u64::MAX);
         }
     }
 
@@ -229,18 +229,20 @@ pub fn add_frame_map(mm_start: usize, mm_end: usize) {
     }
     let phys_end = floor(end as usize, PAGE_SIZE) * PAGE_SIZE;
     // let phys_end = floor(end as usize - VIRT_ADDR_START, PAGE_SIZE) * PAGE_SIZE;
-    if phys_end > mm_start && phys_end < mm_end {
-        info!("add frame memory region {:#x} - {:#x}", phys_end, mm_end);
+    // the memory before the end of the kernel isn't used.
+    let mm_start = mm_start.max(phys_end);
+    if mm_start < mm_end {
+        info!("add frame memory region {:#x} - {:#x}", mm_start, mm_end);
         unsafe {
             core::slice::from_raw_parts_mut(
-                phys_end as *mut u128,
-                (mm_end - phys_end) / size_of::<u128>(),
+                mm_start as *mut u128,
+                (mm_end - mm_start) / size_of::<u128>(),
             )
             .fill(0);
         };
         FRAME_ALLOCATOR
             .lock()
-            .add_memory_region(phys_end - VIRT_ADDR_START, mm_end - VIRT_ADDR_START);
+            .add_memory_region(mm_start - VIRT_ADDR_START, mm_end - VIRT_ADDR_START);
     }
 }
 
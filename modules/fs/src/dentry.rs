@@ -1,18 +1,102 @@
-use core::fmt::Debug;
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
 use alloc::{
+    collections::BTreeMap,
+    format,
     string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
 };
+use ramfs::RamDir;
 use sync::{LazyInit, Mutex};
-use vfscore::{INodeInterface, OpenFlags, VfsError};
+use vfscore::{FileType, INodeInterface, OpenFlags, Stat, VfsError};
+
+use crate::{
+    ext4::Ext4Node,
+    fatfs_shim::FatDir,
+    inotify::{self, InotifyMask},
+    overlay::OverlayNode,
+};
+
+/// The max number of the cached entries, the unused ones are evicted when it is exceeded.
+const MAX_DENTRIES: usize = 4096;
+
+/// The number of the dentry nodes and the negative entries.
+static DENTRIES: AtomicUsize = AtomicUsize::new(0);
+static NEGATIVES: AtomicUsize = AtomicUsize::new(0);
+
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static NEGATIVE_HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+static EVICTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of the slots of the directory generations.
+const GENERATION_SLOTS: usize = 256;
+
+/// The tick of the last change of the directories, indexed by the hash of the identity.
+/// The directories in the same slot are changed together, they are only checked again.
+static GENERATIONS: Mutex<[u64; GENERATION_SLOTS]> = Mutex::new([0; GENERATION_SLOTS]);
+
+/// The clock of the cache, it is increased on every use of an entry.
+fn tick() -> u64 {
+    static TICK: AtomicU64 = AtomicU64::new(0);
+    TICK.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The slots of the directory, the dentries of the same directory share them.
+/// The overlay is changed through its upper and lower directories.
+fn generation_slots(node: &Arc<dyn INodeInterface>) -> Vec<usize> {
+    let key = if let Some(dir) = node.downcast_ref::<RamDir>() {
+        dir.id() as u64
+    } else if let Some(dir) = node.downcast_ref::<Ext4Node>() {
+        let (dev, ino) = dir.dev_ino();
+        dev.rotate_left(32) ^ ino
+    } else if let Some(dir) = node.downcast_ref::<FatDir>() {
+        let (dev, ino) = dir.dev_ino();
+        dev.rotate_left(32) ^ ino
+    } else if let Some(dir) = node.downcast_ref::<OverlayNode>() {
+        return dir.layers().iter().flat_map(generation_slots).collect();
+    } else {
+        return Vec::new();
+    };
+    let hash = key.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    vec![(hash >> 32) as usize % GENERATION_SLOTS]
+}
+
+/// The tick of the last change of the directory, 0 if it isn't changed or can't be cached.
+fn generation(node: &Arc<dyn INodeInterface>) -> u64 {
+    let generations = GENERATIONS.lock();
+    generation_slots(node)
+        .into_iter()
+        .map(|x| generations[x])
+        .max()
+        .unwrap_or(0)
+}
 
 pub struct DentryNode {
     pub filename: String,
     pub node: Arc<dyn INodeInterface>,
     pub parent: Weak<DentryNode>,
     pub children: Mutex<Vec<Arc<DentryNode>>>,
+    /// The names which aren't in the directory -> the tick of the last use.
+    negatives: Mutex<BTreeMap<String, u64>>,
+    last_used: AtomicU64,
+    /// The tick when the cached entries are known to match the directory.
+    synced: AtomicU64,
+    /// The root of a mounted fs, it is never evicted.
+    mounted: bool,
+    /// Shared by the dentries of the mounted fs, the opened files keep it busy.
+    users: Option<Arc<()>>,
+}
+
+impl Drop for DentryNode {
+    fn drop(&mut self) {
+        DENTRIES.fetch_sub(1, Ordering::Relaxed);
+        NEGATIVES.fetch_sub(self.negatives.get_mut().len(), Ordering::Relaxed);
+    }
 }
 
 impl Debug for DentryNode {
@@ -27,85 +111,170 @@ impl Debug for DentryNode {
 
 impl DentryNode {
     pub fn new(filename: String, node: Arc<dyn INodeInterface>, parent: Weak<DentryNode>) -> Self {
+        let users = parent.upgrade().and_then(|x| x.users.clone());
+        Self::new_with_mounted(filename, node, parent, false, users)
+    }
+
+    fn new_with_mounted(
+        filename: String,
+        node: Arc<dyn INodeInterface>,
+        parent: Weak<DentryNode>,
+        mounted: bool,
+        users: Option<Arc<()>>,
+    ) -> Self {
+        DENTRIES.fetch_add(1, Ordering::Relaxed);
         Self {
             filename,
             node,
             parent,
             children: Mutex::new(Vec::new()),
+            negatives: Mutex::new(BTreeMap::new()),
+            last_used: AtomicU64::new(tick()),
+            synced: AtomicU64::new(tick()),
+            mounted,
+            users,
         }
     }
 
     /// Mount a fs to DentryTree, return Some if successfully mounted.
     /// path: The mounted path.
     /// node: fs root directory node.
-    pub fn mount(path: String, node: Arc<dyn INodeInterface>) -> Result<(), VfsError> {
-        let paths = path.split("/").into_iter();
-        let mut dentry = DENTRY_TREE.lock().clone();
-
-        for x in paths {
-            dentry = match x {
-                "." | "" => dentry,
-                ".." => dentry.parent.upgrade().unwrap_or(dentry),
-                filename => {
-                    let finded = dentry
-                        .children
-                        .lock()
-                        .iter()
-                        .find(|x| x.filename == *filename)
-                        .cloned();
-                    log::debug!("open: {}", filename);
-                    match finded {
-                        Some(new_dentry) => new_dentry,
-                        None => dentry.node.open(filename, OpenFlags::NONE).map(|x| {
-                            Arc::new(DentryNode::new(
-                                filename.to_string(),
-                                x,
-                                Arc::downgrade(&dentry),
-                            ))
-                        })?,
-                    }
-                }
-            }
-        }
-        dentry
-            .parent
-            .upgrade()
-            .unwrap()
-            .children
-            .lock()
-            .push(Arc::new(DentryNode::new(
-                dentry.filename.clone(),
-                node,
-                dentry.parent.clone(),
-            )));
+    /// users: shared by the dentries in it, umount checks whether it is busy by it.
+    pub fn mount(
+        path: String,
+        node: Arc<dyn INodeInterface>,
+        users: Arc<()>,
+    ) -> Result<(), VfsError> {
+        let dentry = dentry_open(dentry_root(), &path, OpenFlags::NONE)?;
+        let parent = dentry.parent.upgrade().ok_or(VfsError::InvalidInput)?;
+        let mut children = parent.children.lock();
+        // replace the cached directory, it is covered by the mounted fs.
+        children.retain(|x| x.filename != dentry.filename);
+        children.push(Arc::new(DentryNode::new_with_mounted(
+            dentry.filename.clone(),
+            node,
+            dentry.parent.clone(),
+            true,
+            Some(users),
+        )));
         Ok(())
     }
 
-    pub fn unmount(_path: String) -> Result<(), VfsError> {
-        todo!("unmount in dentry node");
+    /// Remove the mounted fs from DentryTree, the directory under it will be opened again.
+    pub fn unmount(path: String) -> Result<(), VfsError> {
+        let dentry = dentry_open(dentry_root(), &path, OpenFlags::NONE)?;
+        let parent = dentry.parent.upgrade().ok_or(VfsError::InvalidInput)?;
+        parent.children.lock().retain(|x| !Arc::ptr_eq(x, &dentry));
+        Ok(())
+    }
+
+    /// The number of the dentries cached in the mounted fs, None if any of them is used.
+    pub fn cached_dentries(path: String) -> Result<Option<usize>, VfsError> {
+        fn count(dentry: &Arc<DentryNode>, holders: usize) -> Option<usize> {
+            if Arc::strong_count(dentry) > holders {
+                return None;
+            }
+            let children = dentry.children.lock();
+            children
+                .iter()
+                .try_fold(1, |sum, x| Some(sum + count(x, 1)?))
+        }
+        let dentry = dentry_open(dentry_root(), &path, OpenFlags::NONE)?;
+        // the mounted dentry is held by the parent and here.
+        Ok(count(&dentry, 2))
     }
 
     pub fn open(self: Arc<DentryNode>, name: &str, flags: OpenFlags) -> Option<Arc<DentryNode>> {
         let mut children = self.children.lock();
+        self.sync(&mut children);
         if let Some(dnode) = children.iter().find(|x| x.filename == name) {
-            Some(dnode.clone())
-        } else {
-            match self.node.open(name, flags) {
-                Ok(node) => {
-                    // add node to dentry node children.
-                    let child_dentry = Arc::new(DentryNode::new(
-                        name.to_string(),
-                        node,
-                        Arc::downgrade(&self),
-                    ));
-                    children.push(child_dentry.clone());
-                    Some(child_dentry)
-                }
-                Err(_) => None,
+            HITS.fetch_add(1, Ordering::Relaxed);
+            dnode.last_used.store(tick(), Ordering::Relaxed);
+            return Some(dnode.clone());
+        }
+        if let Some(last_used) = self.negatives.lock().get_mut(name) {
+            NEGATIVE_HITS.fetch_add(1, Ordering::Relaxed);
+            *last_used = tick();
+            return None;
+        }
+        MISSES.fetch_add(1, Ordering::Relaxed);
+        let child = match self.node.open(name, flags) {
+            Ok(node) => {
+                let child_dentry = Arc::new(DentryNode::new(
+                    name.to_string(),
+                    node,
+                    Arc::downgrade(&self),
+                ));
+                children.push(child_dentry.clone());
+                Some(child_dentry)
+            }
+            Err(VfsError::FileNotFound) if is_stable(&self.node) => {
+                self.negatives.lock().insert(name.to_string(), tick());
+                NEGATIVES.fetch_add(1, Ordering::Relaxed);
+                None
             }
+            Err(_) => None,
+        };
+        drop(children);
+        shrink();
+        child
+    }
+
+    /// Add the created file to the cache, the negative entry of it is replaced.
+    pub fn add_child(self: &Arc<Self>, name: &str, node: Arc<dyn INodeInterface>) -> Arc<Self> {
+        self.invalidate(name);
+        let child = Arc::new(DentryNode::new(
+            name.to_string(),
+            node,
+            Arc::downgrade(self),
+        ));
+        self.children.lock().push(child.clone());
+        shrink();
+        child
+    }
+
+    /// Drop the cached entry of the name, it must be called when the file is
+    /// created, removed or renamed without the dentry. The mount points are kept.
+    /// The other dentries of the directory check their entries again.
+    pub fn invalidate(&self, name: &str) {
+        let mut children = self.children.lock();
+        let synced = generation(&self.node) <= self.synced.load(Ordering::Relaxed);
+        let changed = tick();
+        let mut generations = GENERATIONS.lock();
+        for slot in generation_slots(&self.node) {
+            generations[slot] = changed;
+        }
+        drop(generations);
+        if synced {
+            self.synced.store(tick(), Ordering::Relaxed);
+        }
+        children.retain(|x| x.filename != name || x.mounted);
+        if self.negatives.lock().remove(name).is_some() {
+            NEGATIVES.fetch_sub(1, Ordering::Relaxed);
         }
     }
 
+    /// Check the cached entries again if the directory is changed through another dentry,
+    /// like a bind mount, another mount of the same fs or the upper directory of an overlay.
+    fn sync(&self, children: &mut Vec<Arc<DentryNode>>) {
+        if generation(&self.node) <= self.synced.load(Ordering::Relaxed) {
+            return;
+        }
+        self.synced.store(tick(), Ordering::Relaxed);
+        let mut negatives = self.negatives.lock();
+        NEGATIVES.fetch_sub(negatives.len(), Ordering::Relaxed);
+        negatives.clear();
+        children.retain(|x| x.mounted || is_current(&self.node, x));
+    }
+
+    /// The entry can be evicted if it is only used by the cache.
+    fn is_unused(self: &Arc<Self>) -> bool {
+        !self.mounted
+            && Arc::strong_count(self) == 1
+            && self.children.lock().is_empty()
+            && self.negatives.lock().is_empty()
+    }
+
     pub fn path(&self) -> String {
         if let Some(_) = self.parent.upgrade() {
             let mut path = String::from("/") + &self.filename.clone();
@@ -125,13 +294,34 @@ impl DentryNode {
 
 pub static DENTRY_TREE: LazyInit<Mutex<Arc<DentryNode>>> = LazyInit::new();
 
+/// The max number of the symbolic links followed in a path, the same as Linux.
+const MAX_SYMLINKS: usize = 40;
+
 /// dentry_open function will open the dentry node by path and dentry.
 /// path should will be rebuild.
-/// flags not be used at now.
+/// The symbolic links are followed, the last one is kept if O_NOFOLLOW is set.
+/// NotLinkFile is only used for too many symbolic links,
+/// resolving a node which isn't a link is InvalidInput.
 pub fn dentry_open(
+    dentry: Arc<DentryNode>,
+    path: &str,
+    flags: OpenFlags,
+) -> Result<Arc<DentryNode>, VfsError> {
+    walk_path(dentry, path, flags, &mut 0)
+}
+
+fn is_symlink(dentry: &DentryNode) -> bool {
+    dentry
+        .node
+        .metadata()
+        .is_ok_and(|x| x.file_type == FileType::Link)
+}
+
+fn walk_path(
     mut dentry: Arc<DentryNode>,
     path: &str,
     flags: OpenFlags,
+    links: &mut usize,
 ) -> Result<Arc<DentryNode>, VfsError> {
     if path.starts_with("/") {
         dentry = DENTRY_TREE.lock().clone();
@@ -144,7 +334,27 @@ pub fn dentry_open(
             x => dentry.clone().open(x, flags.clone()),
         };
         if let Some(new_dentry) = new_dentry {
-            dentry = new_dentry;
+            let is_last = path_peeker.peek().is_none();
+            if !is_symlink(&new_dentry) || (is_last && flags.contains(OpenFlags::O_NOFOLLOW)) {
+                dentry = new_dentry;
+                continue;
+            }
+            *links += 1;
+            if *links > MAX_SYMLINKS {
+                return Err(VfsError::NotLinkFile);
+            }
+            // the link exists already, it isn't replaced by the exclusive creation.
+            if is_last && flags.contains(OpenFlags::O_CREAT | OpenFlags::O_EXCL) {
+                return Err(VfsError::AlreadyExists);
+            }
+            // only the plain open creates the missing target, mkdir doesn't.
+            let mut link_flags = flags.difference(OpenFlags::O_NOFOLLOW);
+            if !is_last || flags.contains(OpenFlags::O_DIRECTORY) {
+                link_flags.remove(OpenFlags::O_CREAT | OpenFlags::O_EXCL);
+            }
+            // the relative target starts from the directory which contains the link.
+            let target = new_dentry.node.resolve_link()?;
+            dentry = walk_path(dentry, &target, link_flags, links)?;
         } else if flags.contains(OpenFlags::O_CREAT) {
             // is not the last item
             let node = if path_peeker.peek().is_some() || flags.contains(OpenFlags::O_DIRECTORY) {
@@ -152,12 +362,8 @@ pub fn dentry_open(
             } else {
                 dentry.node.touch(filename)?
             };
-            let new_dentry = Arc::new(DentryNode::new(
-                filename.to_string(),
-                node,
-                Arc::downgrade(&dentry),
-            ));
-            dentry.children.lock().push(new_dentry.clone());
+            let new_dentry = dentry.add_child(filename, node);
+            inotify::notify(&new_dentry, InotifyMask::IN_CREATE);
             dentry = new_dentry;
         } else {
             return Err(VfsError::FileNotFound);
@@ -180,3 +386,123 @@ pub fn dentry_init(root_node: Arc<dyn INodeInterface>) {
 pub fn dentry_root() -> Arc<DentryNode> {
     DENTRY_TREE.lock().clone()
 }
+
+/// The missing files are cached only if the directory can't be changed without the dentry.
+/// The files in procfs, devfs and 9p come and go by themselves.
+fn is_stable(node: &Arc<dyn INodeInterface>) -> bool {
+    node.is::<RamDir>() || node.is::<FatDir>() || node.is::<Ext4Node>() || node.is::<OverlayNode>()
+}
+
+/// Whether the cached entry is still the file which has the name in the directory.
+fn is_current(dir: &Arc<dyn INodeInterface>, dentry: &DentryNode) -> bool {
+    let ino = |node: &Arc<dyn INodeInterface>| {
+        let mut stat = Stat::default();
+        node.stat(&mut stat).map(|_| stat.ino)
+    };
+    dir.open(&dentry.filename, OpenFlags::NONE)
+        .and_then(|node| ino(&node))
+        .is_ok_and(|x| ino(&dentry.node).is_ok_and(|y| x == y))
+}
+
+/// An evictable entry, (last used, the directory, the name, is negative).
+type Victim = (u64, Arc<DentryNode>, String, bool);
+
+/// Collect the entries which can be evicted under the directory.
+fn collect_unused(dir: &Arc<DentryNode>, victims: &mut Vec<Victim>) {
+    for (name, last_used) in dir.negatives.lock().iter() {
+        victims.push((*last_used, dir.clone(), name.clone(), true));
+    }
+    for child in dir.children.lock().iter() {
+        if child.is_unused() {
+            let last_used = child.last_used.load(Ordering::Relaxed);
+            victims.push((last_used, dir.clone(), child.filename.clone(), false));
+        } else {
+            collect_unused(child, victims);
+        }
+    }
+}
+
+/// Evict the least recently used entries until a quarter of the cache is free.
+fn shrink() {
+    if DENTRIES.load(Ordering::Relaxed) + NEGATIVES.load(Ordering::Relaxed) <= MAX_DENTRIES {
+        return;
+    }
+    let mut victims = Vec::new();
+    collect_unused(&dentry_root(), &mut victims);
+    victims.sort_unstable_by_key(|x| x.0);
+    for (_, dir, name, negative) in victims {
+        if DENTRIES.load(Ordering::Relaxed) + NEGATIVES.load(Ordering::Relaxed)
+            <= MAX_DENTRIES * 3 / 4
+        {
+            break;
+        }
+        let evicted = match negative {
+            true => dir.negatives.lock().remove(&name).is_some(),
+            false => {
+                let mut children = dir.children.lock();
+                let len = children.len();
+                // it may be used again after it is collected.
+                children.retain(|x| x.filename != name || !x.is_unused());
+                children.len() != len
+            }
+        };
+        if evicted {
+            if negative {
+                NEGATIVES.fetch_sub(1, Ordering::Relaxed);
+            }
+            EVICTIONS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The statistics of the dentry cache.
+pub struct DentryStats {
+    pub dentries: usize,
+    pub unused: usize,
+    pub negatives: usize,
+    pub hits: usize,
+    pub negative_hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+}
+
+impl DentryStats {
+    /// The percentage of the lookups which are found in the cache.
+    pub fn hit_rate(&self) -> usize {
+        let hits = self.hits + self.negative_hits;
+        match hits + self.misses {
+            0 => 0,
+            total => hits * 100 / total,
+        }
+    }
+}
+
+pub fn dentry_stats() -> DentryStats {
+    let mut victims = Vec::new();
+    collect_unused(&dentry_root(), &mut victims);
+    DentryStats {
+        dentries: DENTRIES.load(Ordering::Relaxed),
+        unused: victims.iter().filter(|x| !x.3).count(),
+        negatives: NEGATIVES.load(Ordering::Relaxed),
+        hits: HITS.load(Ordering::Relaxed),
+        negative_hits: NEGATIVE_HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        evictions: EVICTIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// Generate the content of /proc/sys/fs/dentry-state.
+/// The fields of Linux are followed by the hits, misses, evictions and the hit rate.
+pub fn render_dentry_state() -> String {
+    let stats = dentry_stats();
+    format!(
+        "{}\t{}\t45\t0\t{}\t0\t{}\t{}\t{}\t{}\n",
+        stats.dentries,
+        stats.unused,
+        stats.negatives,
+        stats.hits + stats.negative_hits,
+        stats.misses,
+        stats.evictions,
+        stats.hit_rate()
+    )
+}
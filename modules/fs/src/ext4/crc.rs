@@ -0,0 +1,53 @@
+//! Checksums used by ext4 metadata.
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0x82f6_3b78,
+                _ => crc >> 1,
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut j = 0;
+        while j < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xa001,
+                _ => crc >> 1,
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// crc32c without the final inversion, the same as `ext4_chksum` in linux.
+pub fn crc32c(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// crc16 used by the old uninit_bg group descriptor checksum.
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC16_TABLE[((crc ^ byte as u16) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
@@ -0,0 +1,594 @@
+//! Linear and hash tree indexed directories.
+
+use alloc::{string::String, vec::Vec};
+use vfscore::{VfsError, VfsResult};
+
+use super::{
+    crc::crc32c,
+    fs::{BlockMap, Ext4Inner},
+    hash::{dirhash, DX_HASH_TEA},
+    layout::*,
+};
+
+/// The size of the fake dirent holds the checksum at the end of the leaf block.
+const DIRENT_TAIL_SIZE: usize = 12;
+
+/// A entry parsed from the directory block.
+pub struct RawDirent {
+    pub offset: usize,
+    pub inode: u32,
+    pub rec_len: usize,
+    pub name_len: usize,
+    pub file_type: u8,
+}
+
+impl RawDirent {
+    pub fn name<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.offset + 8..self.offset + 8 + self.name_len]
+    }
+}
+
+#[inline]
+const fn rec_len(name_len: usize) -> usize {
+    (8 + name_len + 3) & !3
+}
+
+pub struct DirentIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    filetype: bool,
+}
+
+impl<'a> Iterator for DirentIter<'a> {
+    type Item = RawDirent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        if offset + 8 > self.data.len() {
+            return None;
+        }
+        let rec_len = read_u16(self.data, offset + 4) as usize;
+        let (name_len, file_type) = match self.filetype {
+            true => (self.data[offset + 6] as usize, self.data[offset + 7]),
+            false => (read_u16(self.data, offset + 6) as usize, FT_UNKNOWN),
+        };
+        // stop at the broken entry.
+        if rec_len < 8 || offset + rec_len > self.data.len() || 8 + name_len > rec_len {
+            return None;
+        }
+        self.offset += rec_len;
+        Some(RawDirent {
+            offset,
+            inode: read_u32(self.data, offset),
+            rec_len,
+            name_len,
+            file_type,
+        })
+    }
+}
+
+/// The index node of the hash tree, the root block or a interior node.
+struct DxNode {
+    pblk: u64,
+    data: Vec<u8>,
+    /// The offset of the count and limit.
+    base: usize,
+    /// The entry chosen for the hash.
+    position: usize,
+}
+
+impl DxNode {
+    #[inline]
+    fn limit(&self) -> usize {
+        read_u16(&self.data, self.base) as usize
+    }
+
+    #[inline]
+    fn count(&self) -> usize {
+        read_u16(&self.data, self.base + 2) as usize
+    }
+
+    #[inline]
+    fn set_count(&mut self, count: usize) {
+        write_u16(&mut self.data, self.base + 2, count as u16)
+    }
+
+    /// The hash of entry 0 is not stored, it covers from zero.
+    #[inline]
+    fn hash(&self, index: usize) -> u32 {
+        match index {
+            0 => 0,
+            _ => read_u32(&self.data, self.base + index * 8),
+        }
+    }
+
+    #[inline]
+    fn block(&self, index: usize) -> u32 {
+        read_u32(&self.data, self.base + index * 8 + 4)
+    }
+
+    fn insert(&mut self, index: usize, hash: u32, block: u32) {
+        let count = self.count();
+        let offset = self.base + index * 8;
+        self.data
+            .copy_within(offset..self.base + count * 8, offset + 8);
+        write_u32(&mut self.data, offset, hash);
+        write_u32(&mut self.data, offset + 4, block);
+        self.set_count(count + 1);
+    }
+}
+
+/// A entry collected when the leaf block is split.
+struct SplitEntry {
+    hash: u32,
+    inode: u32,
+    file_type: u8,
+    name: Vec<u8>,
+}
+
+impl Ext4Inner {
+    #[inline]
+    pub fn dirents<'a>(&self, data: &'a [u8]) -> DirentIter<'a> {
+        DirentIter {
+            data,
+            offset: 0,
+            filetype: self.sb.has_incompat(INCOMPAT_FILETYPE),
+        }
+    }
+
+    /// The space of the leaf block can be used by dirents.
+    #[inline]
+    fn leaf_space(&self) -> usize {
+        match self.metadata_csum {
+            true => self.block_size - DIRENT_TAIL_SIZE,
+            false => self.block_size,
+        }
+    }
+
+    fn write_dirent(
+        &self,
+        data: &mut [u8],
+        offset: usize,
+        rec: usize,
+        ino: u32,
+        name: &[u8],
+        ft: u8,
+    ) {
+        write_u32(data, offset, ino);
+        write_u16(data, offset + 4, rec as u16);
+        match self.sb.has_incompat(INCOMPAT_FILETYPE) {
+            true => {
+                data[offset + 6] = name.len() as u8;
+                data[offset + 7] = ft;
+            }
+            false => write_u16(data, offset + 6, name.len() as u16),
+        }
+        data[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+    }
+
+    /// Write the leaf block, the checksum is stored in the fake dirent at the end.
+    fn write_leaf(&self, dir: &Inode, pblk: u64, data: &mut [u8]) -> VfsResult<()> {
+        if self.metadata_csum {
+            let tail = self.block_size - DIRENT_TAIL_SIZE;
+            data[tail..].fill(0);
+            write_u16(data, tail + 4, DIRENT_TAIL_SIZE as u16);
+            data[tail + 7] = FT_DIR_CSUM;
+            let csum = crc32c(self.inode_csum_seed(dir), &data[..tail]);
+            write_u32(data, tail + 8, csum);
+        }
+        self.write_block(pblk, data)
+    }
+
+    fn write_dx_node(&self, dir: &Inode, node: &mut DxNode) -> VfsResult<()> {
+        if self.metadata_csum {
+            let tail = node.base + node.limit() * 8;
+            let mut csum = crc32c(
+                self.inode_csum_seed(dir),
+                &node.data[..node.base + node.count() * 8],
+            );
+            csum = crc32c(csum, &node.data[tail..tail + 4]);
+            csum = crc32c(csum, &[0; 4]);
+            write_u32(&mut node.data, tail + 4, csum);
+        }
+        self.write_block(node.pblk, &node.data)
+    }
+
+    fn dir_block(&self, dir: &Inode, lblk: u32) -> VfsResult<(u64, Vec<u8>)> {
+        match self.map_block(dir, lblk)? {
+            BlockMap::Mapped {
+                pblk,
+                uninit: false,
+                ..
+            } => Ok((pblk, self.read_block(pblk)?)),
+            _ => Err(VfsError::InvalidData),
+        }
+    }
+
+    #[inline]
+    fn dir_blocks(&self, dir: &Inode) -> u32 {
+        (dir.size() / self.block_size as u64) as u32
+    }
+
+    #[inline]
+    fn is_indexed(&self, dir: &Inode) -> bool {
+        dir.flags() & INODE_INDEX_FL != 0 && self.sb.has_compat(COMPAT_DIR_INDEX)
+    }
+
+    /// All the entries except "." and "..", return (name, ino, file type).
+    pub fn read_dir_entries(&self, dir: &Inode) -> VfsResult<Vec<(String, u32, u8)>> {
+        let mut entries = Vec::new();
+        for lblk in 0..self.dir_blocks(dir) {
+            let (_, data) = self.dir_block(dir, lblk)?;
+            for dirent in self.dirents(&data) {
+                let name = dirent.name(&data);
+                if dirent.inode == 0 || name == b"." || name == b".." {
+                    continue;
+                }
+                entries.push((
+                    String::from_utf8_lossy(name).into_owned(),
+                    dirent.inode,
+                    dirent.file_type,
+                ));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn find_in_block(&self, data: &[u8], name: &[u8]) -> Option<RawDirent> {
+        self.dirents(data)
+            .find(|x| x.inode != 0 && x.name(data) == name)
+    }
+
+    /// The hash version used by the directory, the unsigned variants are chosen by the superblock.
+    fn dx_hash(&self, root: &[u8], name: &[u8]) -> Option<u32> {
+        let mut version = root[0x1c];
+        if version <= DX_HASH_TEA && self.sb.flags() & FLAGS_UNSIGNED_HASH != 0 {
+            version += 3;
+        }
+        dirhash(name, version, &self.sb.hash_seed())
+    }
+
+    /// Walk the hash tree, return the index nodes from the root and the hash.
+    /// None if the tree can't be used, the directory is searched linearly then.
+    fn dx_path(&self, dir: &Inode, name: &[u8]) -> VfsResult<Option<(Vec<DxNode>, u32)>> {
+        let (pblk, data) = self.dir_block(dir, 0)?;
+        let info_length = data[0x1d] as usize;
+        let levels = data[0x1e] as usize;
+        let max_levels = match self.sb.has_incompat(INCOMPAT_LARGEDIR) {
+            true => 3,
+            false => 2,
+        };
+        let hash = match self.dx_hash(&data, name) {
+            Some(hash) if info_length == 8 && levels < max_levels => hash,
+            _ => return Ok(None),
+        };
+
+        let mut path = Vec::new();
+        let mut node = DxNode {
+            pblk,
+            data,
+            base: 0x18 + info_length,
+            position: 0,
+        };
+        loop {
+            if node.count() == 0 || node.count() > node.limit() {
+                return Ok(None);
+            }
+            node.position = (1..node.count())
+                .take_while(|x| node.hash(*x) <= hash)
+                .last()
+                .unwrap_or(0);
+            let next = node.block(node.position);
+            path.push(node);
+            if path.len() > levels {
+                return Ok(Some((path, hash)));
+            }
+            let (pblk, data) = self.dir_block(dir, next)?;
+            node = DxNode {
+                pblk,
+                data,
+                base: 8,
+                position: 0,
+            };
+        }
+    }
+
+    /// Find the entry, return (ino, file type).
+    pub fn dir_lookup(&self, dir: &Inode, name: &str) -> VfsResult<Option<(u32, u8)>> {
+        let name = name.as_bytes();
+        if self.is_indexed(dir) {
+            if let Some((path, hash)) = self.dx_path(dir, name)? {
+                let node = path.last().unwrap();
+                let mut position = node.position;
+                loop {
+                    let (_, data) = self.dir_block(dir, node.block(position))?;
+                    if let Some(dirent) = self.find_in_block(&data, name) {
+                        return Ok(Some((dirent.inode, dirent.file_type)));
+                    }
+                    // names with the same hash may continue in the next block.
+                    position += 1;
+                    if position >= node.count() || node.hash(position) != hash | 1 {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        for lblk in 0..self.dir_blocks(dir) {
+            let (_, data) = self.dir_block(dir, lblk)?;
+            if let Some(dirent) = self.find_in_block(&data, name) {
+                return Ok(Some((dirent.inode, dirent.file_type)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Insert the entry to the leaf block, return false if there is no space.
+    fn insert_into_block(&self, data: &mut [u8], name: &[u8], ino: u32, ft: u8) -> bool {
+        let needed = rec_len(name.len());
+        let space = self.leaf_space();
+        let found = self.dirents(&data[..space]).find(|x| {
+            let used = match x.inode {
+                0 => 0,
+                _ => rec_len(x.name_len),
+            };
+            x.rec_len - used >= needed
+        });
+        match found {
+            Some(dirent) => {
+                let (offset, rec) = match dirent.inode {
+                    0 => (dirent.offset, dirent.rec_len),
+                    _ => {
+                        let used = rec_len(dirent.name_len);
+                        write_u16(data, dirent.offset + 4, used as u16);
+                        (dirent.offset + used, dirent.rec_len - used)
+                    }
+                };
+                self.write_dirent(data, offset, rec, ino, name, ft);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Append a new leaf block to the directory.
+    fn append_dir_block(&mut self, dir: &mut Inode) -> VfsResult<(u32, u64, Vec<u8>)> {
+        let lblk = self.dir_blocks(dir);
+        let (pblk, _, _) = self.get_blocks_for_write(dir, lblk, 1)?;
+        dir.set_size(dir.size() + self.block_size as u64);
+        let mut data = vec![0u8; self.block_size];
+        write_u16(&mut data, 4, self.leaf_space() as u16);
+        Ok((lblk, pblk, data))
+    }
+
+    pub fn dir_add_entry(
+        &mut self,
+        dir: &mut Inode,
+        name: &str,
+        ino: u32,
+        ft: u8,
+    ) -> VfsResult<()> {
+        let name = name.as_bytes();
+        if name.len() > 255 {
+            return Err(VfsError::InvalidInput);
+        }
+        if self.is_indexed(dir) {
+            return self.dx_add_entry(dir, name, ino, ft);
+        }
+        for lblk in 0..self.dir_blocks(dir) {
+            let (pblk, mut data) = self.dir_block(dir, lblk)?;
+            if self.insert_into_block(&mut data, name, ino, ft) {
+                self.write_leaf(dir, pblk, &mut data)?;
+                return self.write_inode(dir);
+            }
+        }
+        let (_, pblk, mut data) = self.append_dir_block(dir)?;
+        self.insert_into_block(&mut data, name, ino, ft);
+        self.write_leaf(dir, pblk, &mut data)?;
+        self.write_inode(dir)
+    }
+
+    fn dx_add_entry(&mut self, dir: &mut Inode, name: &[u8], ino: u32, ft: u8) -> VfsResult<()> {
+        loop {
+            let (mut path, hash) = self.dx_path(dir, name)?.ok_or(VfsError::NotSupported)?;
+            let leaf_lblk = path.last().map(|x| x.block(x.position)).unwrap();
+            let (pblk, mut data) = self.dir_block(dir, leaf_lblk)?;
+            if self.insert_into_block(&mut data, name, ino, ft) {
+                self.write_leaf(dir, pblk, &mut data)?;
+                return self.write_inode(dir);
+            }
+
+            // the index needs a free slot before the leaf can be split.
+            let level = path.len() - 1;
+            if path[level].count() >= path[level].limit() {
+                self.dx_make_room(dir, &mut path)?;
+                continue;
+            }
+
+            let (new_lblk, new_pblk, mut new_data) = self.append_dir_block(dir)?;
+            let mut entries: Vec<SplitEntry> = self
+                .dirents(&data[..self.leaf_space()])
+                .filter(|x| x.inode != 0)
+                .map(|x| SplitEntry {
+                    hash: self.dx_hash(&path[0].data, x.name(&data)).unwrap_or(0),
+                    inode: x.inode,
+                    file_type: x.file_type,
+                    name: x.name(&data).to_vec(),
+                })
+                .collect();
+            entries.push(SplitEntry {
+                hash,
+                inode: ino,
+                file_type: ft,
+                name: name.to_vec(),
+            });
+            entries.sort_by_key(|x| x.hash);
+
+            // move the upper half by size to the new block.
+            let total: usize = entries.iter().map(|x| rec_len(x.name.len())).sum();
+            let mut size = 0;
+            let mut split = 0;
+            while split < entries.len() - 1
+                && size + rec_len(entries[split].name.len()) <= total / 2
+            {
+                size += rec_len(entries[split].name.len());
+                split += 1;
+            }
+            let split = split.max(1);
+            let mut split_hash = entries[split].hash;
+            if entries[split - 1].hash == split_hash {
+                split_hash |= 1;
+            }
+
+            self.fill_leaf(&mut data, &entries[..split]);
+            self.fill_leaf(&mut new_data, &entries[split..]);
+            self.write_leaf(dir, pblk, &mut data)?;
+            self.write_leaf(dir, new_pblk, &mut new_data)?;
+
+            let node = path.last_mut().unwrap();
+            node.insert(node.position + 1, split_hash, new_lblk);
+            self.write_dx_node(dir, node)?;
+            return self.write_inode(dir);
+        }
+    }
+
+    /// Rewrite the leaf block with the entries.
+    fn fill_leaf(&self, data: &mut [u8], entries: &[SplitEntry]) {
+        data.fill(0);
+        let mut offset = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            let rec = match i == entries.len() - 1 {
+                true => self.leaf_space() - offset,
+                false => rec_len(entry.name.len()),
+            };
+            self.write_dirent(data, offset, rec, entry.inode, &entry.name, entry.file_type);
+            offset += rec;
+        }
+    }
+
+    /// Make room in the bottom index node, add a level or split the interior node.
+    fn dx_make_room(&mut self, dir: &mut Inode, path: &mut [DxNode]) -> VfsResult<()> {
+        let node_limit = (self.block_size - 8) / 8 - self.metadata_csum as usize;
+        let (lblk, pblk, _) = self.append_dir_block(dir)?;
+        let mut new_node = DxNode {
+            pblk,
+            data: vec![0u8; self.block_size],
+            base: 8,
+            position: 0,
+        };
+        // the fake dirent covers the whole interior node.
+        write_u16(&mut new_node.data, 4, self.block_size as u16);
+        write_u16(&mut new_node.data, 8, node_limit as u16);
+
+        if path.len() == 1 {
+            // move all the entries of the root to the new node.
+            let root = &mut path[0];
+            let count = root.count();
+            new_node.data[8 + 4..8 + count * 8]
+                .copy_from_slice(&root.data[root.base + 4..root.base + count * 8]);
+            new_node.set_count(count);
+            self.write_dx_node(dir, &mut new_node)?;
+
+            root.set_count(1);
+            write_u32(&mut root.data, root.base + 4, lblk);
+            root.data[0x1e] += 1;
+            return self.write_dx_node(dir, root);
+        }
+
+        let level = path.len() - 1;
+        if path[level - 1].count() >= path[level - 1].limit() || level > 1 {
+            return Err(VfsError::StorageFull);
+        }
+        // move the upper half of the interior node to the new node.
+        let node = &mut path[level];
+        let count = node.count();
+        let half = count / 2;
+        let split_hash = node.hash(half);
+        new_node.data[8 + 4..8 + (count - half) * 8]
+            .copy_from_slice(&node.data[node.base + half * 8 + 4..node.base + count * 8]);
+        new_node.set_count(count - half);
+        node.set_count(half);
+        self.write_dx_node(dir, &mut new_node)?;
+        self.write_dx_node(dir, node)?;
+
+        let parent = &mut path[level - 1];
+        parent.insert(parent.position + 1, split_hash, lblk);
+        self.write_dx_node(dir, parent)?;
+        self.write_inode(dir)
+    }
+
+    /// Remove the entry, return the inode number.
+    pub fn dir_remove_entry(&mut self, dir: &mut Inode, name: &str) -> VfsResult<u32> {
+        let name = name.as_bytes();
+        for lblk in 0..self.dir_blocks(dir) {
+            let (pblk, mut data) = self.dir_block(dir, lblk)?;
+            let mut prev: Option<RawDirent> = None;
+            for dirent in self.dirents(&data) {
+                if dirent.inode != 0 && dirent.name(&data) == name {
+                    let ino = dirent.inode;
+                    match prev {
+                        // merge the space to the previous entry.
+                        Some(prev) => write_u16(
+                            &mut data,
+                            prev.offset + 4,
+                            (prev.rec_len + dirent.rec_len) as u16,
+                        ),
+                        None => write_u32(&mut data, dirent.offset, 0),
+                    }
+                    self.write_leaf(dir, pblk, &mut data)?;
+                    return Ok(ino);
+                }
+                prev = Some(dirent);
+            }
+        }
+        Err(VfsError::FileNotFound)
+    }
+
+    /// Point the entry to another inode, return the inode number it pointed to.
+    pub fn dir_replace_entry(
+        &mut self,
+        dir: &Inode,
+        name: &str,
+        ino: u32,
+        ft: u8,
+    ) -> VfsResult<u32> {
+        let name = name.as_bytes();
+        for lblk in 0..self.dir_blocks(dir) {
+            let (pblk, mut data) = self.dir_block(dir, lblk)?;
+            if let Some(dirent) = self.find_in_block(&data, name) {
+                write_u32(&mut data, dirent.offset, ino);
+                if self.sb.has_incompat(INCOMPAT_FILETYPE) {
+                    data[dirent.offset + 7] = ft;
+                }
+                self.write_leaf(dir, pblk, &mut data)?;
+                return Ok(dirent.inode);
+            }
+        }
+        Err(VfsError::FileNotFound)
+    }
+
+    /// Create the first block with "." and "..".
+    pub fn init_dir(&mut self, dir: &mut Inode, parent: u32) -> VfsResult<()> {
+        let (_, pblk, mut data) = self.append_dir_block(dir)?;
+        self.write_dirent(&mut data, 0, 12, dir.ino, b".", FT_DIR);
+        self.write_dirent(&mut data, 12, self.leaf_space() - 12, parent, b"..", FT_DIR);
+        self.write_leaf(dir, pblk, &mut data)
+    }
+
+    /// Point ".." to the new parent.
+    pub fn set_dir_parent(&mut self, dir: &Inode, parent: u32) -> VfsResult<()> {
+        let (pblk, mut data) = self.dir_block(dir, 0)?;
+        write_u32(&mut data, 12, parent);
+        match self.is_indexed(dir) {
+            true => {
+                let mut root = DxNode {
+                    pblk,
+                    base: 0x18 + data[0x1d] as usize,
+                    data,
+                    position: 0,
+                };
+                self.write_dx_node(dir, &mut root)
+            }
+            false => self.write_leaf(dir, pblk, &mut data),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Extent tree and the legacy block map.
+
+use alloc::vec::Vec;
+use vfscore::{VfsError, VfsResult};
+
+use super::{
+    crc::crc32c,
+    fs::{BlockMap, Ext4Inner},
+    layout::*,
+};
+
+const EXTENT_MAGIC: u16 = 0xf30a;
+/// The length of the extent larger than it is uninitialized.
+const EXT_INIT_MAX_LEN: u32 = 32768;
+const EXT_MAX_DEPTH: usize = 5;
+
+/// A node of the extent tree, the root is stored in the inode.
+struct PathNode {
+    block: Option<u64>,
+    data: Vec<u8>,
+    /// The entry chosen for the logical block.
+    index: usize,
+}
+
+impl PathNode {
+    #[inline]
+    fn entries(&self) -> usize {
+        read_u16(&self.data, 2) as usize
+    }
+
+    #[inline]
+    fn set_entries(&mut self, entries: usize) {
+        write_u16(&mut self.data, 2, entries as u16)
+    }
+
+    #[inline]
+    fn max(&self) -> usize {
+        read_u16(&self.data, 4) as usize
+    }
+
+    #[inline]
+    fn depth(&self) -> u16 {
+        read_u16(&self.data, 6)
+    }
+
+    #[inline]
+    fn full(&self) -> bool {
+        self.entries() >= self.max()
+    }
+
+    #[inline]
+    fn key(&self, index: usize) -> u32 {
+        read_u32(&self.data, 12 + index * 12)
+    }
+
+    /// The child block of the index entry.
+    #[inline]
+    fn child(&self, index: usize) -> u64 {
+        let offset = 12 + index * 12;
+        read_u32(&self.data, offset + 4) as u64 | (read_u16(&self.data, offset + 8) as u64) << 32
+    }
+
+    /// Return (logical block, length, physical block, uninit) of the leaf entry.
+    #[inline]
+    fn extent(&self, index: usize) -> (u32, u32, u64, bool) {
+        parse_extent(&self.data[12 + index * 12..])
+    }
+}
+
+#[inline]
+fn parse_extent(data: &[u8]) -> (u32, u32, u64, bool) {
+    let len = read_u16(data, 4) as u32;
+    let pblk = read_u32(data, 8) as u64 | (read_u16(data, 6) as u64) << 32;
+    match len > EXT_INIT_MAX_LEN {
+        true => (read_u32(data, 0), len - EXT_INIT_MAX_LEN, pblk, true),
+        false => (read_u32(data, 0), len, pblk, false),
+    }
+}
+
+fn build_extent(lblk: u32, len: u32, pblk: u64, uninit: bool) -> [u8; 12] {
+    let mut entry = [0u8; 12];
+    let len = match uninit {
+        true => len + EXT_INIT_MAX_LEN,
+        false => len,
+    };
+    write_u32(&mut entry, 0, lblk);
+    write_u16(&mut entry, 4, len as u16);
+    write_u16(&mut entry, 6, (pblk >> 32) as u16);
+    write_u32(&mut entry, 8, pblk as u32);
+    entry
+}
+
+fn build_index(lblk: u32, child: u64) -> [u8; 12] {
+    let mut entry = [0u8; 12];
+    write_u32(&mut entry, 0, lblk);
+    write_u32(&mut entry, 4, child as u32);
+    write_u16(&mut entry, 8, (child >> 32) as u16);
+    entry
+}
+
+impl Ext4Inner {
+    /// Make i_block an empty extent tree.
+    pub fn init_extent_root(inode: &mut Inode) {
+        let root = inode.block_mut();
+        root.fill(0);
+        write_u16(root, 0, EXTENT_MAGIC);
+        write_u16(root, 4, 4);
+        inode.set_flags(inode.flags() | INODE_EXTENTS_FL);
+    }
+
+    fn extent_path(&self, inode: &Inode, lblk: u32) -> VfsResult<Vec<PathNode>> {
+        let mut path = Vec::new();
+        let mut node = PathNode {
+            block: None,
+            data: inode.block().to_vec(),
+            index: 0,
+        };
+        loop {
+            if read_u16(&node.data, 0) != EXTENT_MAGIC || path.len() >= EXT_MAX_DEPTH {
+                return Err(VfsError::InvalidData);
+            }
+            let index = (1..node.entries())
+                .take_while(|x| node.key(*x) <= lblk)
+                .last()
+                .unwrap_or(0);
+            node.index = index;
+            if node.depth() == 0 || node.entries() == 0 {
+                path.push(node);
+                return Ok(path);
+            }
+            let child = node.child(node.index);
+            path.push(node);
+            node = PathNode {
+                block: Some(child),
+                data: self.read_block(child)?,
+                index: 0,
+            };
+        }
+    }
+
+    /// Map the logical block through direct and indirect blocks.
+    fn map_indirect(&self, inode: &Inode, lblk: u32) -> VfsResult<BlockMap> {
+        let per_block = (self.block_size / 4) as u64;
+        let mut lblk = lblk as u64;
+        let (mut pblk, levels) = if lblk < 12 {
+            (read_u32(inode.block(), lblk as usize * 4), 0)
+        } else if lblk - 12 < per_block {
+            lblk -= 12;
+            (read_u32(inode.block(), 12 * 4), 1)
+        } else if lblk - 12 - per_block < per_block * per_block {
+            lblk -= 12 + per_block;
+            (read_u32(inode.block(), 13 * 4), 2)
+        } else {
+            lblk -= 12 + per_block + per_block * per_block;
+            (read_u32(inode.block(), 14 * 4), 3)
+        };
+        for level in (0..levels).rev() {
+            if pblk == 0 {
+                break;
+            }
+            let data = self.read_block(pblk as u64)?;
+            let index = (lblk / per_block.pow(level)) % per_block;
+            pblk = read_u32(&data, index as usize * 4);
+        }
+        Ok(match pblk {
+            0 => BlockMap::Hole { len: 1 },
+            _ => BlockMap::Mapped {
+                pblk: pblk as u64,
+                len: 1,
+                uninit: false,
+            },
+        })
+    }
+
+    pub fn map_block(&self, inode: &Inode, lblk: u32) -> VfsResult<BlockMap> {
+        if inode.flags() & INODE_EXTENTS_FL == 0 {
+            return self.map_indirect(inode, lblk);
+        }
+        let path = self.extent_path(inode, lblk)?;
+        let leaf = path.last().unwrap();
+        if leaf.entries() == 0 {
+            return Ok(BlockMap::Hole {
+                len: u32::MAX - lblk,
+            });
+        }
+        let (start, len, pblk, uninit) = leaf.extent(leaf.index);
+        if start <= lblk && lblk - start < len {
+            return Ok(BlockMap::Mapped {
+                pblk: pblk + (lblk - start) as u64,
+                len: start + len - lblk,
+                uninit,
+            });
+        }
+        // the hole ends at the next extent.
+        let next = if start > lblk {
+            Some(start)
+        } else {
+            path.iter()
+                .rev()
+                .find(|x| x.index + 1 < x.entries())
+                .map(|x| x.key(x.index + 1))
+        };
+        Ok(BlockMap::Hole {
+            len: next.unwrap_or(u32::MAX) - lblk,
+        })
+    }
+
+    fn write_node(&self, inode: &mut Inode, node: &mut PathNode) -> VfsResult<()> {
+        match node.block {
+            None => {
+                inode.block_mut().copy_from_slice(&node.data[..60]);
+                Ok(())
+            }
+            Some(block) => {
+                if self.metadata_csum {
+                    let tail = 12 + node.max() * 12;
+                    let csum = crc32c(self.inode_csum_seed(inode), &node.data[..tail]);
+                    write_u32(&mut node.data, tail, csum);
+                }
+                self.write_block(block, &node.data)
+            }
+        }
+    }
+
+    /// Insert the entry to the node which is not full.
+    fn node_insert(
+        &mut self,
+        inode: &mut Inode,
+        path: &mut [PathNode],
+        level: usize,
+        entry: [u8; 12],
+    ) -> VfsResult<()> {
+        let key = read_u32(&entry, 0);
+        let node = &mut path[level];
+        let entries = node.entries();
+        let pos = (0..entries).find(|x| node.key(*x) > key).unwrap_or(entries);
+        let offset = 12 + pos * 12;
+        node.data
+            .copy_within(offset..12 + entries * 12, offset + 12);
+        node.data[offset..offset + 12].copy_from_slice(&entry);
+        node.set_entries(entries + 1);
+        self.write_node(inode, node)?;
+
+        // the index key is the first logical block of the child.
+        if pos == 0 {
+            for parent in path[..level].iter_mut().rev() {
+                write_u32(&mut parent.data, 12 + parent.index * 12, key);
+                self.write_node(inode, parent)?;
+                if parent.index != 0 {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the entries of the root to a new block, the tree grows one level.
+    fn grow_root(&mut self, inode: &mut Inode, root: &mut PathNode) -> VfsResult<()> {
+        let goal = self.group_first_block(self.inode_group(inode.ino));
+        let (block, _) = self.alloc_blocks(goal, 1)?;
+        inode.add_sectors(self.block_size, 1);
+
+        let entries = root.entries();
+        let mut child = PathNode {
+            block: Some(block),
+            data: vec![0u8; self.block_size],
+            index: 0,
+        };
+        child.data[..12 + entries * 12].copy_from_slice(&root.data[..12 + entries * 12]);
+        write_u16(&mut child.data, 4, ((self.block_size - 12) / 12) as u16);
+        self.write_node(inode, &mut child)?;
+
+        let first = match entries {
+            0 => 0,
+            _ => root.key(0),
+        };
+        root.data[12..].fill(0);
+        root.data[12..24].copy_from_slice(&build_index(first, block));
+        let depth = root.depth() + 1;
+        root.set_entries(1);
+        write_u16(&mut root.data, 6, depth);
+        self.write_node(inode, root)
+    }
+
+    /// Split the full node into two, the parent gets a new index entry.
+    fn split_node(
+        &mut self,
+        inode: &mut Inode,
+        path: &mut [PathNode],
+        level: usize,
+    ) -> VfsResult<()> {
+        if level == 0 {
+            return self.grow_root(inode, &mut path[0]);
+        }
+        if path[level - 1].full() {
+            // the caller looks up the path again and retries.
+            return self.split_node(inode, path, level - 1);
+        }
+        let goal = path[level].block.unwrap_or(0);
+        let (block, _) = self.alloc_blocks(goal, 1)?;
+        inode.add_sectors(self.block_size, 1);
+
+        let node = &mut path[level];
+        let entries = node.entries();
+        let half = entries / 2;
+        let mut sibling = PathNode {
+            block: Some(block),
+            data: vec![0u8; self.block_size],
+            index: 0,
+        };
+        sibling.data[..12].copy_from_slice(&node.data[..12]);
+        sibling.data[12..12 + (entries - half) * 12]
+            .copy_from_slice(&node.data[12 + half * 12..12 + entries * 12]);
+        sibling.set_entries(entries - half);
+        node.set_entries(half);
+        self.write_node(inode, &mut sibling)?;
+        self.write_node(inode, node)?;
+
+        let key = sibling.key(0);
+        self.node_insert(inode, path, level - 1, build_index(key, block))
+    }
+
+    fn extent_insert(
+        &mut self,
+        inode: &mut Inode,
+        lblk: u32,
+        len: u32,
+        pblk: u64,
+        uninit: bool,
+    ) -> VfsResult<()> {
+        loop {
+            let mut path = self.extent_path(inode, lblk)?;
+            let leaf = path.len() - 1;
+            let node = &mut path[leaf];
+            if node.entries() > 0 {
+                // extend the previous extent if they are continuous.
+                let (start, plen, ppblk, puninit) = node.extent(node.index);
+                if start <= lblk
+                    && start + plen == lblk
+                    && ppblk + plen as u64 == pblk
+                    && puninit == uninit
+                    && plen + len <= EXT_INIT_MAX_LEN - uninit as u32
+                {
+                    let entry = build_extent(start, plen + len, ppblk, uninit);
+                    let offset = 12 + node.index * 12;
+                    node.data[offset..offset + 12].copy_from_slice(&entry);
+                    return self.write_node(inode, node);
+                }
+            }
+            if !node.full() {
+                let entry = build_extent(lblk, len, pblk, uninit);
+                return self.node_insert(inode, &mut path, leaf, entry);
+            }
+            self.split_node(inode, &mut path, leaf)?;
+        }
+    }
+
+    /// Write zero to the uninitialized extent which contains lblk and mark it initialized.
+    fn convert_uninit(&mut self, inode: &mut Inode, lblk: u32) -> VfsResult<()> {
+        let mut path = self.extent_path(inode, lblk)?;
+        let node = path.last_mut().unwrap();
+        let (start, len, pblk, _) = node.extent(node.index);
+        let zero = vec![0u8; self.block_size];
+        for block in pblk..pblk + len as u64 {
+            self.write_block(block, &zero)?;
+        }
+        let offset = 12 + node.index * 12;
+        node.data[offset..offset + 12].copy_from_slice(&build_extent(start, len, pblk, false));
+        self.write_node(inode, node)
+    }
+
+    /// Get the blocks to write at lblk, allocate them if it is a hole.
+    /// Return the physical block, the continuous length and whether the blocks are new.
+    pub fn get_blocks_for_write(
+        &mut self,
+        inode: &mut Inode,
+        lblk: u32,
+        count: u32,
+    ) -> VfsResult<(u64, u32, bool)> {
+        loop {
+            match self.map_block(inode, lblk)? {
+                BlockMap::Mapped {
+                    pblk,
+                    len,
+                    uninit: false,
+                } => return Ok((pblk, len.min(count), false)),
+                BlockMap::Mapped { uninit: true, .. } => self.convert_uninit(inode, lblk)?,
+                BlockMap::Hole { len } => {
+                    if inode.flags() & INODE_EXTENTS_FL == 0 {
+                        return Err(VfsError::NotSupported);
+                    }
+                    let goal = match lblk.checked_sub(1).map(|x| self.map_block(inode, x)) {
+                        Some(Ok(BlockMap::Mapped { pblk, .. })) => pblk + 1,
+                        _ => self.group_first_block(self.inode_group(inode.ino)),
+                    };
+                    let count = len.min(count).min(EXT_INIT_MAX_LEN);
+                    let (pblk, allocated) = self.alloc_blocks(goal, count)?;
+                    inode.add_sectors(self.block_size, allocated as i64);
+                    self.extent_insert(inode, lblk, allocated, pblk, false)?;
+                    return Ok((pblk, allocated, true));
+                }
+            }
+        }
+    }
+
+    fn collect_extents(
+        &self,
+        data: &[u8],
+        extents: &mut Vec<(u32, u32, u64, bool)>,
+        nodes: &mut Vec<u64>,
+    ) -> VfsResult<()> {
+        if read_u16(data, 0) != EXTENT_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let entries = read_u16(data, 2) as usize;
+        let depth = read_u16(data, 6);
+        for i in 0..entries {
+            let entry = &data[12 + i * 12..];
+            if depth == 0 {
+                extents.push(parse_extent(entry));
+            } else {
+                let child = read_u32(entry, 4) as u64 | (read_u16(entry, 8) as u64) << 32;
+                nodes.push(child);
+                self.collect_extents(&self.read_block(child)?, extents, nodes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Free the blocks of the legacy block map, level 0 is a data block.
+    fn free_indirect(&mut self, inode: &mut Inode, block: u64, level: u32) -> VfsResult<()> {
+        if block == 0 {
+            return Ok(());
+        }
+        if level > 0 {
+            let data = self.read_block(block)?;
+            for i in 0..self.block_size / 4 {
+                self.free_indirect(inode, read_u32(&data, i * 4) as u64, level - 1)?;
+            }
+        }
+        self.free_blocks(block, 1)?;
+        inode.add_sectors(self.block_size, -1);
+        Ok(())
+    }
+
+    /// Free the blocks from the logical block keep to the end.
+    pub fn truncate_blocks(&mut self, inode: &mut Inode, keep: u32) -> VfsResult<()> {
+        if inode.flags() & INODE_EXTENTS_FL == 0 {
+            // only dropping the whole block map is supported.
+            if keep != 0 {
+                return Err(VfsError::NotSupported);
+            }
+            for i in 0..15 {
+                let level = i.saturating_sub(11) as u32;
+                let block = read_u32(inode.block(), i * 4) as u64;
+                self.free_indirect(inode, block, level)?;
+            }
+            Self::init_extent_root(inode);
+            return Ok(());
+        }
+
+        let mut extents = Vec::new();
+        let mut nodes = Vec::new();
+        self.collect_extents(inode.block(), &mut extents, &mut nodes)?;
+        if extents.iter().all(|x| x.0 + x.1 <= keep) {
+            return Ok(());
+        }
+
+        // rebuild the tree with the extents left.
+        for block in nodes {
+            self.free_blocks(block, 1)?;
+            inode.add_sectors(self.block_size, -1);
+        }
+        Self::init_extent_root(inode);
+        for (start, len, pblk, uninit) in extents {
+            let kept = keep.saturating_sub(start).min(len);
+            if kept < len {
+                self.free_blocks(pblk + kept as u64, (len - kept) as u64)?;
+                inode.add_sectors(self.block_size, -((len - kept) as i64));
+            }
+            if kept > 0 {
+                self.extent_insert(inode, start, kept, pblk, uninit)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,688 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
+use devices::{device::BlkDriver, get_blk_device};
+use vfscore::{VfsError, VfsResult};
+
+use super::{
+    crc::{crc16, crc32c},
+    layout::*,
+};
+
+#[inline]
+pub fn test_bit(bitmap: &[u8], bit: usize) -> bool {
+    bitmap[bit / 8] & (1 << (bit % 8)) != 0
+}
+
+#[inline]
+pub fn set_bit(bitmap: &mut [u8], bit: usize) {
+    bitmap[bit / 8] |= 1 << (bit % 8);
+}
+
+#[inline]
+pub fn clear_bit(bitmap: &mut [u8], bit: usize) {
+    bitmap[bit / 8] &= !(1 << (bit % 8));
+}
+
+/// The mounted ext4 state, all the metadata is written through.
+pub struct Ext4Inner {
+    pub device: Arc<dyn BlkDriver>,
+    pub sb: SuperBlock,
+    pub groups: Vec<GroupDesc>,
+    pub block_size: usize,
+    pub inode_size: usize,
+    pub group_count: u32,
+    pub csum_seed: u32,
+    pub metadata_csum: bool,
+    pub readonly: bool,
+    /// The number of live nodes of each inode.
+    pub handles: BTreeMap<u32, usize>,
+    /// Inodes which are unlinked but still opened, freed when the last node is dropped.
+    pub orphans: BTreeSet<u32>,
+}
+
+impl Ext4Inner {
+    /// Read the superblock, return None if it isn't a ext4 we can mount.
+    pub fn read_super(device: &Arc<dyn BlkDriver>) -> Option<SuperBlock> {
+        let mut raw = vec![0u8; SUPERBLOCK_SIZE];
+        device.read_blocks(SUPERBLOCK_OFFSET / 512, &mut raw);
+        let sb = SuperBlock { raw };
+        let supported = sb.magic() == EXT4_MAGIC
+            && sb.feature_incompat() & !INCOMPAT_SUPPORTED == 0
+            && sb.block_size() <= 65536
+            && sb.blocks_per_group() != 0
+            && sb.inodes_per_group() != 0
+            && sb.inode_size() >= GOOD_OLD_INODE_SIZE;
+        supported.then_some(sb)
+    }
+
+    pub fn new(device_id: usize) -> VfsResult<Self> {
+        let device = get_blk_device(device_id).ok_or(VfsError::Io)?;
+        let sb = Self::read_super(&device).ok_or(VfsError::InvalidData)?;
+
+        let metadata_csum = sb.has_ro_compat(RO_COMPAT_METADATA_CSUM);
+        let csum_seed = match sb.has_incompat(INCOMPAT_CSUM_SEED) {
+            true => sb.checksum_seed(),
+            false => crc32c(!0, sb.uuid()),
+        };
+        // we don't replay the journal and only write extent-mapped files.
+        let readonly = sb.feature_ro_compat() & !RO_COMPAT_WRITABLE != 0
+            || sb.has_incompat(INCOMPAT_RECOVER)
+            || !sb.has_incompat(INCOMPAT_EXTENTS);
+        if readonly {
+            log::warn!("ext4 on block device {} is mounted read-only", device_id);
+        }
+
+        let group_count = (sb.blocks_count() - sb.first_data_block() as u64)
+            .div_ceil(sb.blocks_per_group() as u64) as u32;
+        let mut inner = Self {
+            device,
+            block_size: sb.block_size(),
+            inode_size: sb.inode_size(),
+            sb,
+            groups: Vec::new(),
+            group_count,
+            csum_seed,
+            metadata_csum,
+            readonly,
+            handles: BTreeMap::new(),
+            orphans: BTreeSet::new(),
+        };
+
+        let desc_size = inner.sb.desc_size();
+        let descs_per_block = inner.block_size / desc_size;
+        for index in 0..group_count.div_ceil(descs_per_block as u32) {
+            let block = inner.read_block(inner.desc_block(index * descs_per_block as u32))?;
+            inner.groups.extend(
+                block
+                    .chunks_exact(desc_size)
+                    .take((group_count as usize).saturating_sub(inner.groups.len()))
+                    .map(|x| GroupDesc { raw: x.to_vec() }),
+            );
+        }
+        Ok(inner)
+    }
+
+    pub fn read_block(&self, block: u64) -> VfsResult<Vec<u8>> {
+        let mut buffer = vec![0u8; self.block_size];
+        self.read_block_into(block, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub fn read_block_into(&self, block: u64, buffer: &mut [u8]) -> VfsResult<()> {
+        if block >= self.sb.blocks_count() {
+            return Err(VfsError::InvalidData);
+        }
+        self.device
+            .read_blocks(block as usize * (self.block_size / 512), buffer);
+        Ok(())
+    }
+
+    pub fn write_block(&self, block: u64, buffer: &[u8]) -> VfsResult<()> {
+        if self.readonly {
+            return Err(VfsError::NotWriteable);
+        }
+        if block >= self.sb.blocks_count() || block == 0 {
+            return Err(VfsError::InvalidData);
+        }
+        self.device
+            .write_blocks(block as usize * (self.block_size / 512), buffer);
+        Ok(())
+    }
+
+    pub fn write_super(&mut self) -> VfsResult<()> {
+        if self.readonly {
+            return Err(VfsError::NotWriteable);
+        }
+        if self.metadata_csum {
+            let csum = crc32c(!0, &self.sb.raw[..0x3fc]);
+            write_u32(&mut self.sb.raw, 0x3fc, csum);
+        }
+        self.device
+            .write_blocks(SUPERBLOCK_OFFSET / 512, &self.sb.raw);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn group_first_block(&self, group: u32) -> u64 {
+        self.sb.first_data_block() as u64 + group as u64 * self.sb.blocks_per_group() as u64
+    }
+
+    #[inline]
+    pub fn blocks_in_group(&self, group: u32) -> u32 {
+        let start = self.group_first_block(group);
+        (self.sb.blocks_count() - start).min(self.sb.blocks_per_group() as u64) as u32
+    }
+
+    #[inline]
+    pub fn inode_group(&self, ino: u32) -> u32 {
+        (ino - 1) / self.sb.inodes_per_group()
+    }
+
+    /// Whether the group contains a backup of the superblock.
+    fn has_super(&self, group: u32) -> bool {
+        let is_power_of = |mut x: u32, base: u32| {
+            while x % base == 0 {
+                x /= base;
+            }
+            x == 1
+        };
+        group <= 1
+            || !self.sb.has_ro_compat(RO_COMPAT_SPARSE_SUPER)
+            || is_power_of(group, 3)
+            || is_power_of(group, 5)
+            || is_power_of(group, 7)
+    }
+
+    #[inline]
+    fn gdt_blocks(&self) -> u64 {
+        let descs_per_block = (self.block_size / self.sb.desc_size()) as u64;
+        (self.group_count as u64).div_ceil(descs_per_block)
+    }
+
+    #[inline]
+    fn itable_blocks(&self) -> u64 {
+        (self.sb.inodes_per_group() as u64 * self.inode_size as u64)
+            .div_ceil(self.block_size as u64)
+    }
+
+    /// The block which contains the descriptor of the group.
+    fn desc_block(&self, group: u32) -> u64 {
+        let descs_per_block = (self.block_size / self.sb.desc_size()) as u32;
+        let index = group / descs_per_block;
+        if !self.sb.has_incompat(INCOMPAT_META_BG) || index < self.sb.first_meta_bg() {
+            self.sb.first_data_block() as u64 + 1 + index as u64
+        } else {
+            let first = index * descs_per_block;
+            self.group_first_block(first) + self.has_super(first) as u64
+        }
+    }
+
+    fn desc_checksum(&self, group: u32) -> u16 {
+        let desc = &self.groups[group as usize].raw;
+        let group = group.to_le_bytes();
+        if self.metadata_csum {
+            let mut csum = crc32c(self.csum_seed, &group);
+            csum = crc32c(csum, &desc[..0x1e]);
+            csum = crc32c(csum, &[0, 0]);
+            csum = crc32c(csum, &desc[0x20..]);
+            csum as u16
+        } else {
+            let mut csum = crc16(!0, self.sb.uuid());
+            csum = crc16(csum, &group);
+            csum = crc16(csum, &desc[..0x1e]);
+            crc16(csum, &desc[0x20..])
+        }
+    }
+
+    pub fn write_desc(&mut self, group: u32) -> VfsResult<()> {
+        if self.metadata_csum || self.sb.has_ro_compat(RO_COMPAT_GDT_CSUM) {
+            let csum = self.desc_checksum(group);
+            self.groups[group as usize].set_checksum(csum);
+        }
+        let desc_size = self.sb.desc_size();
+        let descs_per_block = (self.block_size / desc_size) as u32;
+        let block = self.desc_block(group);
+        let offset = (group % descs_per_block) as usize * desc_size;
+        let mut data = self.read_block(block)?;
+        data[offset..offset + desc_size].copy_from_slice(&self.groups[group as usize].raw);
+        self.write_block(block, &data)
+    }
+
+    /// Read the block bitmap, build it if the group is not initialized.
+    fn read_block_bitmap(&self, group: u32) -> VfsResult<Vec<u8>> {
+        let desc = &self.groups[group as usize];
+        if desc.flags() & BG_BLOCK_UNINIT == 0 {
+            return self.read_block(desc.block_bitmap());
+        }
+
+        let mut bitmap = vec![0u8; self.block_size];
+        let start = self.group_first_block(group);
+        let blocks = self.blocks_in_group(group) as u64;
+        let mut mark = |block: u64| {
+            if block >= start && block - start < blocks {
+                set_bit(&mut bitmap, (block - start) as usize);
+            }
+        };
+        if self.has_super(group) {
+            let reserved = 1 + self.gdt_blocks() + self.sb.reserved_gdt_blocks() as u64;
+            (0..reserved).for_each(|x| mark(start + x));
+        }
+        // flex_bg places the metadata of groups together.
+        for desc in self.groups.iter() {
+            mark(desc.block_bitmap());
+            mark(desc.inode_bitmap());
+            (0..self.itable_blocks()).for_each(|x| mark(desc.inode_table() + x));
+        }
+        (blocks as usize..self.block_size * 8).for_each(|x| set_bit(&mut bitmap, x));
+        Ok(bitmap)
+    }
+
+    fn write_block_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult<()> {
+        let block = self.groups[group as usize].block_bitmap();
+        self.write_block(block, bitmap)?;
+        let desc = &mut self.groups[group as usize];
+        if self.metadata_csum {
+            let size = self.sb.blocks_per_group() as usize / 8;
+            desc.set_block_bitmap_csum(crc32c(self.csum_seed, &bitmap[..size]));
+        }
+        desc.set_flags(desc.flags() & !BG_BLOCK_UNINIT);
+        Ok(())
+    }
+
+    fn read_inode_bitmap(&self, group: u32) -> VfsResult<Vec<u8>> {
+        let desc = &self.groups[group as usize];
+        if desc.flags() & BG_INODE_UNINIT == 0 {
+            return self.read_block(desc.inode_bitmap());
+        }
+        let mut bitmap = vec![0u8; self.block_size];
+        (self.sb.inodes_per_group() as usize..self.block_size * 8)
+            .for_each(|x| set_bit(&mut bitmap, x));
+        Ok(bitmap)
+    }
+
+    fn write_inode_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult<()> {
+        let block = self.groups[group as usize].inode_bitmap();
+        self.write_block(block, bitmap)?;
+        let desc = &mut self.groups[group as usize];
+        if self.metadata_csum {
+            let size = self.sb.inodes_per_group() as usize / 8;
+            desc.set_inode_bitmap_csum(crc32c(self.csum_seed, &bitmap[..size]));
+        }
+        desc.set_flags(desc.flags() & !BG_INODE_UNINIT);
+        Ok(())
+    }
+
+    /// Allocate at most count continuous blocks near the goal.
+    /// Return the first block and the number of blocks allocated.
+    pub fn alloc_blocks(&mut self, goal: u64, count: u32) -> VfsResult<(u64, u32)> {
+        if self.readonly {
+            return Err(VfsError::NotWriteable);
+        }
+        let first_data_block = self.sb.first_data_block() as u64;
+        let goal = goal.clamp(first_data_block, self.sb.blocks_count() - 1);
+        let goal_group = ((goal - first_data_block) / self.sb.blocks_per_group() as u64) as u32;
+
+        for i in 0..self.group_count {
+            let group = (goal_group + i) % self.group_count;
+            let desc = &self.groups[group as usize];
+            // the uninit bitmap can't be built without parsing meta_bg.
+            if desc.free_blocks_count() == 0
+                || (desc.flags() & BG_BLOCK_UNINIT != 0 && self.sb.has_incompat(INCOMPAT_META_BG))
+            {
+                continue;
+            }
+            let mut bitmap = self.read_block_bitmap(group)?;
+            let len = self.blocks_in_group(group) as usize;
+            let start = match i {
+                0 => (goal - self.group_first_block(group)) as usize,
+                _ => 0,
+            };
+            let first = match (start..len)
+                .chain(0..start)
+                .find(|x| !test_bit(&bitmap, *x))
+            {
+                Some(first) => first,
+                None => continue,
+            };
+            let mut allocated = 0;
+            while allocated < count
+                && first + (allocated as usize) < len
+                && !test_bit(&bitmap, first + allocated as usize)
+            {
+                set_bit(&mut bitmap, first + allocated as usize);
+                allocated += 1;
+            }
+            self.write_block_bitmap(group, &bitmap)?;
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_blocks_count(desc.free_blocks_count().saturating_sub(allocated));
+            self.write_desc(group)?;
+            let free = self.sb.free_blocks_count();
+            self.sb
+                .set_free_blocks_count(free.saturating_sub(allocated as u64));
+            self.write_super()?;
+            return Ok((self.group_first_block(group) + first as u64, allocated));
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_blocks(&mut self, start: u64, count: u64) -> VfsResult<()> {
+        let first_data_block = self.sb.first_data_block() as u64;
+        let blocks_per_group = self.sb.blocks_per_group() as u64;
+        let mut block = start;
+        while block < start + count {
+            let group = ((block - first_data_block) / blocks_per_group) as u32;
+            let offset = block - self.group_first_block(group);
+            let len = (start + count - block).min(blocks_per_group - offset);
+            let mut bitmap = self.read_block_bitmap(group)?;
+            (offset..offset + len).for_each(|x| clear_bit(&mut bitmap, x as usize));
+            self.write_block_bitmap(group, &bitmap)?;
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_blocks_count(desc.free_blocks_count() + len as u32);
+            self.write_desc(group)?;
+            block += len;
+        }
+        let free = self.sb.free_blocks_count();
+        self.sb.set_free_blocks_count(free + count);
+        self.write_super()
+    }
+
+    /// Allocate a inode number, try the group of the parent first.
+    pub fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> VfsResult<u32> {
+        if self.readonly {
+            return Err(VfsError::NotWriteable);
+        }
+        let inodes_per_group = self.sb.inodes_per_group();
+        let goal_group = self.inode_group(parent);
+        for i in 0..self.group_count {
+            let group = (goal_group + i) % self.group_count;
+            if self.groups[group as usize].free_inodes_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_inode_bitmap(group)?;
+            let first_ino = self.sb.first_ino();
+            let index = match (0..inodes_per_group).find(|x| {
+                !test_bit(&bitmap, *x as usize) && group * inodes_per_group + x + 1 >= first_ino
+            }) {
+                Some(index) => index,
+                None => continue,
+            };
+            set_bit(&mut bitmap, index as usize);
+            self.write_inode_bitmap(group, &bitmap)?;
+
+            let has_unused = self.metadata_csum || self.sb.has_ro_compat(RO_COMPAT_GDT_CSUM);
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_inodes_count(desc.free_inodes_count() - 1);
+            if is_dir {
+                desc.set_used_dirs_count(desc.used_dirs_count() + 1);
+            }
+            if has_unused && index >= inodes_per_group - desc.itable_unused() {
+                desc.set_itable_unused(inodes_per_group - index - 1);
+            }
+            self.write_desc(group)?;
+            let free = self.sb.free_inodes_count();
+            self.sb.set_free_inodes_count(free - 1);
+            self.write_super()?;
+            return Ok(group * inodes_per_group + index + 1);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult<()> {
+        let group = self.inode_group(ino);
+        let index = (ino - 1) % self.sb.inodes_per_group();
+        let mut bitmap = self.read_inode_bitmap(group)?;
+        clear_bit(&mut bitmap, index as usize);
+        self.write_inode_bitmap(group, &bitmap)?;
+        let desc = &mut self.groups[group as usize];
+        desc.set_free_inodes_count(desc.free_inodes_count() + 1);
+        if is_dir {
+            desc.set_used_dirs_count(desc.used_dirs_count().saturating_sub(1));
+        }
+        self.write_desc(group)?;
+        let free = self.sb.free_inodes_count();
+        self.sb.set_free_inodes_count(free + 1);
+        self.write_super()
+    }
+
+    fn inode_location(&self, ino: u32) -> VfsResult<(u64, usize)> {
+        if ino == 0 || ino > self.sb.inodes_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let group = self.inode_group(ino);
+        let offset = ((ino - 1) % self.sb.inodes_per_group()) as u64 * self.inode_size as u64;
+        let table = self.groups[group as usize].inode_table();
+        Ok((
+            table + offset / self.block_size as u64,
+            (offset % self.block_size as u64) as usize,
+        ))
+    }
+
+    pub fn read_inode(&self, ino: u32) -> VfsResult<Inode> {
+        let (block, offset) = self.inode_location(ino)?;
+        let data = self.read_block(block)?;
+        Ok(Inode {
+            ino,
+            raw: data[offset..offset + self.inode_size].to_vec(),
+        })
+    }
+
+    /// The seed of the checksums of the blocks belong to the inode.
+    pub fn inode_csum_seed(&self, inode: &Inode) -> u32 {
+        let csum = crc32c(self.csum_seed, &inode.ino.to_le_bytes());
+        crc32c(csum, &inode.generation().to_le_bytes())
+    }
+
+    fn inode_checksum(&self, inode: &Inode) -> u32 {
+        let raw = &inode.raw;
+        let mut csum = crc32c(self.inode_csum_seed(inode), &raw[..0x7c]);
+        csum = crc32c(csum, &[0, 0]);
+        csum = crc32c(csum, &raw[0x7e..GOOD_OLD_INODE_SIZE]);
+        if raw.len() > GOOD_OLD_INODE_SIZE {
+            csum = crc32c(csum, &raw[GOOD_OLD_INODE_SIZE..0x82]);
+            let mut offset = 0x82;
+            if inode.extra_isize() >= 4 {
+                csum = crc32c(csum, &[0, 0]);
+                offset += 2;
+            }
+            csum = crc32c(csum, &raw[offset..]);
+        }
+        csum
+    }
+
+    pub fn write_inode(&self, inode: &mut Inode) -> VfsResult<()> {
+        if self.metadata_csum {
+            let csum = self.inode_checksum(inode);
+            write_u16(&mut inode.raw, 0x7c, csum as u16);
+            if inode.extra_isize() >= 4 {
+                write_u16(&mut inode.raw, 0x82, (csum >> 16) as u16);
+            }
+        }
+        let (block, offset) = self.inode_location(inode.ino)?;
+        let mut data = self.read_block(block)?;
+        data[offset..offset + self.inode_size].copy_from_slice(&inode.raw);
+        self.write_block(block, &data)
+    }
+
+    /// Allocate and initialize a new inode, the caller links it to a directory.
+    pub fn new_inode(&mut self, parent: u32, mode: u16) -> VfsResult<Inode> {
+        let ino = self.alloc_inode(parent, mode & S_IFMT == S_IFDIR)?;
+        let mut inode = Inode {
+            ino,
+            raw: vec![0u8; self.inode_size],
+        };
+        inode.set_mode(mode);
+        inode.set_extra_isize((self.inode_size - GOOD_OLD_INODE_SIZE).min(32) as u16);
+        if mode & S_IFMT != S_IFLNK {
+            Self::init_extent_root(&mut inode);
+        }
+        Ok(inode)
+    }
+
+    /// The target of the fast symlink is stored in i_block.
+    pub fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let xattr_sectors = match inode.file_acl() {
+            0 => 0,
+            _ => (self.block_size / 512) as u64,
+        };
+        inode.is_link() && inode.sectors(self.block_size) == xattr_sectors
+    }
+
+    /// Drop the reference of the extended attribute block.
+    fn release_xattr_block(&mut self, inode: &mut Inode) -> VfsResult<()> {
+        let block = inode.file_acl();
+        if block == 0 {
+            return Ok(());
+        }
+        let mut data = self.read_block(block)?;
+        let refcount = read_u32(&data, 0x4);
+        if refcount <= 1 {
+            self.free_blocks(block, 1)?;
+        } else {
+            write_u32(&mut data, 0x4, refcount - 1);
+            if self.metadata_csum {
+                write_u32(&mut data, 0x10, 0);
+                let csum = crc32c(self.csum_seed, &block.to_le_bytes());
+                write_u32(&mut data, 0x10, crc32c(csum, &data));
+            }
+            self.write_block(block, &data)?;
+        }
+        inode.add_sectors(self.block_size, -1);
+        write_u32(&mut inode.raw, 0x68, 0);
+        write_u16(&mut inode.raw, 0x76, 0);
+        Ok(())
+    }
+
+    /// Free the inode and its blocks, the links count must be zero.
+    pub fn release_inode(&mut self, ino: u32) -> VfsResult<()> {
+        let mut inode = self.read_inode(ino)?;
+        if !self.is_fast_symlink(&inode) {
+            self.truncate_blocks(&mut inode, 0)?;
+        }
+        self.release_xattr_block(&mut inode)?;
+        // there is no clock here, dtime only needs to be non-zero.
+        inode.set_dtime(1);
+        self.write_inode(&mut inode)?;
+        self.free_inode(ino, inode.is_dir())
+    }
+
+    pub fn get_handle(&mut self, ino: u32) {
+        *self.handles.entry(ino).or_insert(0) += 1;
+    }
+
+    /// Drop a handle of the inode, the orphan inode is freed with the last handle.
+    pub fn put_handle(&mut self, ino: u32) -> VfsResult<()> {
+        let count = self.handles.get_mut(&ino).ok_or(VfsError::InvalidData)?;
+        *count -= 1;
+        if *count == 0 {
+            self.handles.remove(&ino);
+            if self.orphans.remove(&ino) {
+                self.release_inode(ino)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrease the links count, free the inode if nobody uses it.
+    pub fn drop_link(&mut self, inode: &mut Inode) -> VfsResult<()> {
+        let links = match inode.is_dir() {
+            // the directory is linked by itself through ".".
+            true => 0,
+            false => inode.links_count().saturating_sub(1),
+        };
+        inode.set_links_count(links);
+        self.write_inode(inode)?;
+        if links == 0 {
+            match self.handles.contains_key(&inode.ino) {
+                true => {
+                    self.orphans.insert(inode.ino);
+                }
+                false => self.release_inode(inode.ino)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read data of the regular file or the symlink.
+    pub fn read_data(&self, inode: &Inode, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let size = inode.size() as usize;
+        if offset >= size {
+            return Ok(0);
+        }
+        let rlen = buffer.len().min(size - offset);
+        let mut block_data = vec![0u8; self.block_size];
+        let mut readed = 0;
+        while readed < rlen {
+            let pos = offset + readed;
+            let lblk = (pos / self.block_size) as u32;
+            let block_off = pos % self.block_size;
+            let len = (self.block_size - block_off).min(rlen - readed);
+            match self.map_block(inode, lblk)? {
+                BlockMap::Mapped {
+                    pblk,
+                    uninit: false,
+                    ..
+                } => {
+                    self.read_block_into(pblk, &mut block_data)?;
+                    buffer[readed..readed + len]
+                        .copy_from_slice(&block_data[block_off..block_off + len]);
+                }
+                _ => buffer[readed..readed + len].fill(0),
+            }
+            readed += len;
+        }
+        Ok(rlen)
+    }
+
+    /// Write data to the regular file, allocate blocks for holes.
+    pub fn write_data(
+        &mut self,
+        inode: &mut Inode,
+        offset: usize,
+        buffer: &[u8],
+    ) -> VfsResult<usize> {
+        let mut written = 0;
+        while written < buffer.len() {
+            let pos = offset + written;
+            let lblk = (pos / self.block_size) as u32;
+            let blocks = (pos % self.block_size + buffer.len() - written).div_ceil(self.block_size);
+            let (pblk, len, fresh) = self.get_blocks_for_write(inode, lblk, blocks as u32)?;
+            for i in 0..len as u64 {
+                let pos = offset + written;
+                let block_off = pos % self.block_size;
+                let wlen = (self.block_size - block_off).min(buffer.len() - written);
+                if wlen == self.block_size {
+                    self.write_block(pblk + i, &buffer[written..written + wlen])?;
+                } else {
+                    let mut data = match fresh {
+                        true => vec![0u8; self.block_size],
+                        false => self.read_block(pblk + i)?,
+                    };
+                    data[block_off..block_off + wlen]
+                        .copy_from_slice(&buffer[written..written + wlen]);
+                    self.write_block(pblk + i, &data)?;
+                }
+                written += wlen;
+            }
+        }
+        if (offset + written) as u64 > inode.size() {
+            inode.set_size((offset + written) as u64);
+        }
+        self.write_inode(inode)?;
+        Ok(written)
+    }
+
+    /// Change the size of the file, free the blocks after the end.
+    pub fn truncate(&mut self, inode: &mut Inode, size: u64) -> VfsResult<()> {
+        let block_size = self.block_size as u64;
+        if size < inode.size() {
+            self.truncate_blocks(inode, size.div_ceil(block_size) as u32)?;
+            // the tail of the last block must read as zero once the file grows again.
+            if size % block_size != 0 {
+                let lblk = (size / block_size) as u32;
+                if let BlockMap::Mapped {
+                    pblk,
+                    uninit: false,
+                    ..
+                } = self.map_block(inode, lblk)?
+                {
+                    let mut data = self.read_block(pblk)?;
+                    data[(size % block_size) as usize..].fill(0);
+                    self.write_block(pblk, &data)?;
+                }
+            }
+        }
+        inode.set_size(size);
+        self.write_inode(inode)
+    }
+}
+
+/// The mapping of a logical block.
+pub enum BlockMap {
+    /// The block is mapped to pblk, len blocks after it are continuous.
+    Mapped { pblk: u64, len: u32, uninit: bool },
+    /// The block is not allocated, len blocks after it are holes too.
+    Hole { len: u32 },
+}
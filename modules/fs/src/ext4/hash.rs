@@ -0,0 +1,166 @@
+//! Directory index hash, the port of fs/ext4/hash.c.
+
+pub const DX_HASH_LEGACY: u8 = 0;
+pub const DX_HASH_HALF_MD4: u8 = 1;
+pub const DX_HASH_TEA: u8 = 2;
+pub const DX_HASH_LEGACY_UNSIGNED: u8 = 3;
+pub const DX_HASH_HALF_MD4_UNSIGNED: u8 = 4;
+pub const DX_HASH_TEA_UNSIGNED: u8 = 5;
+
+const HTREE_EOF_32BIT: u32 = 0x7fff_ffff;
+
+fn dx_hack_hash(name: &[u8], signed: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3_fe2du32, 0x37ab_e8f9u32);
+    for &c in name {
+        let c = match signed {
+            true => c as i8 as i32 as u32,
+            false => c as u32,
+        };
+        let mut hash = hash1.wrapping_add(hash0 ^ c.wrapping_mul(7152373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+fn str2hashbuf(msg: &[u8], buf: &mut [u32], signed: bool) {
+    let len = msg.len() as u32;
+    let mut num = buf.len() as isize;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+
+    let mut val = pad;
+    let mut index = 0;
+    for (i, &c) in msg.iter().take(buf.len() * 4).enumerate() {
+        let c = match signed {
+            true => c as i8 as i32 as u32,
+            false => c as u32,
+        };
+        val = c.wrapping_add(val << 8);
+        if i % 4 == 3 {
+            buf[index] = val;
+            index += 1;
+            val = pad;
+            num -= 1;
+        }
+    }
+    num -= 1;
+    if num >= 0 {
+        buf[index] = val;
+        index += 1;
+    }
+    while index < buf.len() {
+        buf[index] = pad;
+        index += 1;
+    }
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32]) {
+    let mut sum = 0u32;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let (a, b, c, d) = (input[0], input[1], input[2], input[3]);
+
+    for _ in 0..16 {
+        sum = sum.wrapping_add(0x9e37_79b9);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32]) {
+    const K2: u32 = 0x5a82_7999;
+    const K3: u32 = 0x6ed9_eba1;
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let round = |func: &dyn Fn(u32, u32, u32) -> u32,
+                 a: &mut u32,
+                 b: u32,
+                 c: u32,
+                 d: u32,
+                 x: u32,
+                 s: u32| {
+        *a = a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s);
+    };
+
+    let (mut a, mut b, mut c, mut d) = (buf[0], buf[1], buf[2], buf[3]);
+
+    round(&f, &mut a, b, c, d, input[0], 3);
+    round(&f, &mut d, a, b, c, input[1], 7);
+    round(&f, &mut c, d, a, b, input[2], 11);
+    round(&f, &mut b, c, d, a, input[3], 19);
+    round(&f, &mut a, b, c, d, input[4], 3);
+    round(&f, &mut d, a, b, c, input[5], 7);
+    round(&f, &mut c, d, a, b, input[6], 11);
+    round(&f, &mut b, c, d, a, input[7], 19);
+
+    round(&g, &mut a, b, c, d, input[1].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[3].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[5].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[7].wrapping_add(K2), 13);
+    round(&g, &mut a, b, c, d, input[0].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[2].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[4].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round(&h, &mut a, b, c, d, input[3].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[7].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[2].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[6].wrapping_add(K3), 15);
+    round(&h, &mut a, b, c, d, input[1].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[5].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[0].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+/// Calculate the major hash of the name, return None if the version is unknown.
+pub fn dirhash(name: &[u8], version: u8, seed: &[u32; 4]) -> Option<u32> {
+    let mut buf = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    if seed.iter().any(|x| *x != 0) {
+        buf = *seed;
+    }
+
+    let hash = match version {
+        DX_HASH_LEGACY => dx_hack_hash(name, true),
+        DX_HASH_LEGACY_UNSIGNED => dx_hack_hash(name, false),
+        DX_HASH_HALF_MD4 | DX_HASH_HALF_MD4_UNSIGNED => {
+            let signed = version == DX_HASH_HALF_MD4;
+            let mut input = [0u32; 8];
+            for offset in (0..name.len()).step_by(32) {
+                str2hashbuf(&name[offset..], &mut input, signed);
+                half_md4_transform(&mut buf, &input);
+            }
+            buf[1]
+        }
+        DX_HASH_TEA | DX_HASH_TEA_UNSIGNED => {
+            let signed = version == DX_HASH_TEA;
+            let mut input = [0u32; 4];
+            for offset in (0..name.len()).step_by(16) {
+                str2hashbuf(&name[offset..], &mut input, signed);
+                tea_transform(&mut buf, &input);
+            }
+            buf[0]
+        }
+        _ => return None,
+    };
+
+    let hash = hash & !1;
+    match hash == HTREE_EOF_32BIT << 1 {
+        true => Some((HTREE_EOF_32BIT - 1) << 1),
+        false => Some(hash),
+    }
+}
@@ -0,0 +1,518 @@
+//! On-disk structures of ext4, accessed through little endian helpers.
+
+use alloc::vec::Vec;
+
+pub const EXT4_MAGIC: u16 = 0xef53;
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+pub const ROOT_INO: u32 = 2;
+pub const GOOD_OLD_INODE_SIZE: usize = 128;
+
+pub const COMPAT_DIR_INDEX: u32 = 0x20;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_META_BG: u32 = 0x10;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_MMP: u32 = 0x100;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+/// Incompatible features we understand, the others can't be mounted.
+pub const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_META_BG
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_MMP
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_BTREE_DIR: u32 = 0x4;
+pub const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+pub const RO_COMPAT_ORPHAN_PRESENT: u32 = 0x10000;
+/// Read-only compatible features we keep consistent when writing.
+pub const RO_COMPAT_WRITABLE: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_BTREE_DIR
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM
+    | RO_COMPAT_ORPHAN_PRESENT;
+
+pub const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+pub const BG_INODE_UNINIT: u16 = 0x1;
+pub const BG_BLOCK_UNINIT: u16 = 0x2;
+
+pub const INODE_COMPR_FL: u32 = 0x4;
+pub const INODE_IMMUTABLE_FL: u32 = 0x10;
+pub const INODE_APPEND_FL: u32 = 0x20;
+pub const INODE_NODUMP_FL: u32 = 0x40;
+pub const INODE_ENCRYPT_FL: u32 = 0x800;
+pub const INODE_INDEX_FL: u32 = 0x1000;
+pub const INODE_HUGE_FILE_FL: u32 = 0x4_0000;
+pub const INODE_EXTENTS_FL: u32 = 0x8_0000;
+pub const INODE_INLINE_DATA_FL: u32 = 0x1000_0000;
+
+pub const S_IFMT: u16 = 0o170000;
+pub const S_IFSOCK: u16 = 0o140000;
+pub const S_IFLNK: u16 = 0o120000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFBLK: u16 = 0o060000;
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFCHR: u16 = 0o020000;
+pub const S_IFIFO: u16 = 0o010000;
+
+pub const FT_UNKNOWN: u8 = 0;
+pub const FT_REG_FILE: u8 = 1;
+pub const FT_DIR: u8 = 2;
+pub const FT_CHRDEV: u8 = 3;
+pub const FT_BLKDEV: u8 = 4;
+pub const FT_FIFO: u8 = 5;
+pub const FT_SOCK: u8 = 6;
+pub const FT_SYMLINK: u8 = 7;
+/// The file type of the fake dirent which holds the leaf block checksum.
+pub const FT_DIR_CSUM: u8 = 0xde;
+
+#[inline]
+pub fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+pub fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+#[inline]
+pub fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+pub fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Convert the mode of the inode to the file type stored in the dirent.
+pub const fn mode_to_ft(mode: u16) -> u8 {
+    match mode & S_IFMT {
+        S_IFREG => FT_REG_FILE,
+        S_IFDIR => FT_DIR,
+        S_IFCHR => FT_CHRDEV,
+        S_IFBLK => FT_BLKDEV,
+        S_IFIFO => FT_FIFO,
+        S_IFSOCK => FT_SOCK,
+        S_IFLNK => FT_SYMLINK,
+        _ => FT_UNKNOWN,
+    }
+}
+
+pub struct SuperBlock {
+    pub raw: Vec<u8>,
+}
+
+impl SuperBlock {
+    pub fn magic(&self) -> u16 {
+        read_u16(&self.raw, 0x38)
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x0)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        let lo = read_u32(&self.raw, 0x4) as u64;
+        match self.has_incompat(INCOMPAT_64BIT) {
+            true => lo | (read_u32(&self.raw, 0x150) as u64) << 32,
+            false => lo,
+        }
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        let lo = read_u32(&self.raw, 0xc) as u64;
+        match self.has_incompat(INCOMPAT_64BIT) {
+            true => lo | (read_u32(&self.raw, 0x158) as u64) << 32,
+            false => lo,
+        }
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        write_u32(&mut self.raw, 0xc, count as u32);
+        if self.has_incompat(INCOMPAT_64BIT) {
+            write_u32(&mut self.raw, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        write_u32(&mut self.raw, 0x10, count)
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        read_u32(&self.raw, 0x14)
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << read_u32(&self.raw, 0x18)
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        read_u32(&self.raw, 0x20)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        read_u32(&self.raw, 0x28)
+    }
+
+    pub fn rev_level(&self) -> u32 {
+        read_u32(&self.raw, 0x4c)
+    }
+
+    pub fn first_ino(&self) -> u32 {
+        match self.rev_level() {
+            0 => 11,
+            _ => read_u32(&self.raw, 0x54),
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        match self.rev_level() {
+            0 => GOOD_OLD_INODE_SIZE,
+            _ => read_u16(&self.raw, 0x58) as usize,
+        }
+    }
+
+    pub fn feature_compat(&self) -> u32 {
+        read_u32(&self.raw, 0x5c)
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        read_u32(&self.raw, 0x60)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    #[inline]
+    pub fn has_compat(&self, feature: u32) -> bool {
+        self.feature_compat() & feature != 0
+    }
+
+    #[inline]
+    pub fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat() & feature != 0
+    }
+
+    #[inline]
+    pub fn has_ro_compat(&self, feature: u32) -> bool {
+        self.feature_ro_compat() & feature != 0
+    }
+
+    pub fn uuid(&self) -> &[u8] {
+        &self.raw[0x68..0x78]
+    }
+
+    pub fn reserved_gdt_blocks(&self) -> u32 {
+        read_u16(&self.raw, 0xce) as u32
+    }
+
+    pub fn hash_seed(&self) -> [u32; 4] {
+        [
+            read_u32(&self.raw, 0xec),
+            read_u32(&self.raw, 0xf0),
+            read_u32(&self.raw, 0xf4),
+            read_u32(&self.raw, 0xf8),
+        ]
+    }
+
+    pub fn desc_size(&self) -> usize {
+        match self.has_incompat(INCOMPAT_64BIT) {
+            true => read_u16(&self.raw, 0xfe) as usize,
+            false => 32,
+        }
+    }
+
+    pub fn first_meta_bg(&self) -> u32 {
+        read_u32(&self.raw, 0x104)
+    }
+
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.raw, 0x160)
+    }
+
+    pub fn checksum_seed(&self) -> u32 {
+        read_u32(&self.raw, 0x270)
+    }
+
+    pub fn volume_name(&self) -> &[u8] {
+        &self.raw[0x78..0x88]
+    }
+}
+
+pub struct GroupDesc {
+    pub raw: Vec<u8>,
+}
+
+impl GroupDesc {
+    #[inline]
+    fn read_lo_hi32(&self, lo: usize, hi: usize) -> u64 {
+        let value = read_u32(&self.raw, lo) as u64;
+        match self.raw.len() >= 64 {
+            true => value | (read_u32(&self.raw, hi) as u64) << 32,
+            false => value,
+        }
+    }
+
+    #[inline]
+    fn read_lo_hi16(&self, lo: usize, hi: usize) -> u32 {
+        let value = read_u16(&self.raw, lo) as u32;
+        match self.raw.len() >= 64 {
+            true => value | (read_u16(&self.raw, hi) as u32) << 16,
+            false => value,
+        }
+    }
+
+    #[inline]
+    fn write_lo_hi16(&mut self, lo: usize, hi: usize, value: u32) {
+        write_u16(&mut self.raw, lo, value as u16);
+        if self.raw.len() >= 64 {
+            write_u16(&mut self.raw, hi, (value >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.read_lo_hi32(0x0, 0x20)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.read_lo_hi32(0x4, 0x24)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.read_lo_hi32(0x8, 0x28)
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.read_lo_hi16(0xc, 0x2c)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u32) {
+        self.write_lo_hi16(0xc, 0x2c, count)
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.read_lo_hi16(0xe, 0x2e)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        self.write_lo_hi16(0xe, 0x2e, count)
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.read_lo_hi16(0x10, 0x30)
+    }
+
+    pub fn set_used_dirs_count(&mut self, count: u32) {
+        self.write_lo_hi16(0x10, 0x30, count)
+    }
+
+    pub fn flags(&self) -> u16 {
+        read_u16(&self.raw, 0x12)
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        write_u16(&mut self.raw, 0x12, flags)
+    }
+
+    pub fn set_block_bitmap_csum(&mut self, csum: u32) {
+        self.write_lo_hi16(0x18, 0x38, csum)
+    }
+
+    pub fn set_inode_bitmap_csum(&mut self, csum: u32) {
+        self.write_lo_hi16(0x1a, 0x3a, csum)
+    }
+
+    pub fn itable_unused(&self) -> u32 {
+        self.read_lo_hi16(0x1c, 0x32)
+    }
+
+    pub fn set_itable_unused(&mut self, count: u32) {
+        self.write_lo_hi16(0x1c, 0x32, count)
+    }
+
+    pub fn set_checksum(&mut self, csum: u16) {
+        write_u16(&mut self.raw, 0x1e, csum)
+    }
+}
+
+pub struct Inode {
+    pub ino: u32,
+    pub raw: Vec<u8>,
+}
+
+impl Inode {
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.raw, 0x0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        write_u16(&mut self.raw, 0x0, mode)
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.mode() & S_IFMT == S_IFDIR
+    }
+
+    #[inline]
+    pub fn is_link(&self) -> bool {
+        self.mode() & S_IFMT == S_IFLNK
+    }
+
+    pub fn uid(&self) -> u32 {
+        read_u16(&self.raw, 0x2) as u32 | (read_u16(&self.raw, 0x78) as u32) << 16
+    }
+
+    pub fn gid(&self) -> u32 {
+        read_u16(&self.raw, 0x18) as u32 | (read_u16(&self.raw, 0x7a) as u32) << 16
+    }
+
+    pub fn set_uid(&mut self, uid: u32) {
+        write_u16(&mut self.raw, 0x2, uid as u16);
+        write_u16(&mut self.raw, 0x78, (uid >> 16) as u16);
+    }
+
+    pub fn set_gid(&mut self, gid: u32) {
+        write_u16(&mut self.raw, 0x18, gid as u16);
+        write_u16(&mut self.raw, 0x7a, (gid >> 16) as u16);
+    }
+
+    pub fn size(&self) -> u64 {
+        read_u32(&self.raw, 0x4) as u64 | (read_u32(&self.raw, 0x6c) as u64) << 32
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        write_u32(&mut self.raw, 0x4, size as u32);
+        write_u32(&mut self.raw, 0x6c, (size >> 32) as u32);
+    }
+
+    pub fn atime(&self) -> u32 {
+        read_u32(&self.raw, 0x8)
+    }
+
+    pub fn ctime(&self) -> u32 {
+        read_u32(&self.raw, 0xc)
+    }
+
+    pub fn mtime(&self) -> u32 {
+        read_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_atime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 0x8, time)
+    }
+
+    pub fn set_ctime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 0xc, time)
+    }
+
+    pub fn set_mtime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 0x10, time)
+    }
+
+    pub fn set_dtime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 0x14, time)
+    }
+
+    pub fn links_count(&self) -> u16 {
+        read_u16(&self.raw, 0x1a)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        write_u16(&mut self.raw, 0x1a, count)
+    }
+
+    /// The number of 512 bytes sectors used by the inode.
+    pub fn sectors(&self, block_size: usize) -> u64 {
+        let count = read_u32(&self.raw, 0x1c) as u64 | (read_u16(&self.raw, 0x74) as u64) << 32;
+        match self.flags() & INODE_HUGE_FILE_FL != 0 {
+            true => count * (block_size / 512) as u64,
+            false => count,
+        }
+    }
+
+    /// Add or remove the blocks used by the inode, never set the huge file flag.
+    pub fn add_sectors(&mut self, block_size: usize, blocks: i64) {
+        let sectors = self.sectors(block_size) as i64 + blocks * (block_size / 512) as i64;
+        self.set_flags(self.flags() & !INODE_HUGE_FILE_FL);
+        write_u32(&mut self.raw, 0x1c, sectors as u32);
+        write_u16(&mut self.raw, 0x74, (sectors >> 32) as u16);
+    }
+
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        write_u32(&mut self.raw, 0x20, flags)
+    }
+
+    /// i_block, the extent tree root, block map or fast symlink target.
+    pub fn block(&self) -> &[u8] {
+        &self.raw[0x28..0x64]
+    }
+
+    pub fn block_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[0x28..0x64]
+    }
+
+    pub fn generation(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    pub fn file_acl(&self) -> u64 {
+        read_u32(&self.raw, 0x68) as u64 | (read_u16(&self.raw, 0x76) as u64) << 32
+    }
+
+    pub fn extra_isize(&self) -> usize {
+        match self.raw.len() > GOOD_OLD_INODE_SIZE {
+            true => read_u16(&self.raw, 0x80) as usize,
+            false => 0,
+        }
+    }
+
+    pub fn set_extra_isize(&mut self, size: u16) {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            write_u16(&mut self.raw, 0x80, size)
+        }
+    }
+
+    /// The creation time, only exists in the large inode.
+    pub fn crtime(&self) -> Option<u32> {
+        match self.extra_isize() >= 0x14 {
+            true => Some(read_u32(&self.raw, 0x90)),
+            false => None,
+        }
+    }
+}
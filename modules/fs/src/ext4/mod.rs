@@ -0,0 +1,498 @@
+//! A native ext2/3/4 filesystem, the metadata is written through without the journal.
+
+mod crc;
+mod dir;
+mod extent;
+mod fs;
+mod hash;
+mod layout;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use devices::{
+    get_blk_device, makedev,
+    partition::{blk_device_minor, BLK_DEVICE_MAJOR},
+};
+use sync::{Mutex, MutexGuard};
+use vfscore::{
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, OpenFlags, Stat, StatFS, StatMode,
+    TimeSpec, VfsError, VfsResult, UTIME_OMIT,
+};
+
+use self::{fs::Ext4Inner, layout::*};
+
+pub struct Ext4FileSystem {
+    inner: Mutex<Ext4Inner>,
+    /// The device number of the block device, the same as the mount table.
+    dev: u64,
+}
+
+impl Ext4FileSystem {
+    pub fn new(device_id: usize) -> VfsResult<Arc<Self>> {
+        Ok(Arc::new(Self {
+            inner: Mutex::new(Ext4Inner::new(device_id)?),
+            dev: makedev(BLK_DEVICE_MAJOR, blk_device_minor(device_id)),
+        }))
+    }
+
+    pub fn probe(device_id: usize) -> bool {
+        get_blk_device(device_id)
+            .and_then(|device| Ext4Inner::read_super(&device))
+            .is_some()
+    }
+
+    pub fn open(device_id: usize) -> VfsResult<Arc<dyn FileSystem>> {
+        Ok(Self::new(device_id)?)
+    }
+
+    /// Get the node of the inode, the lock must not be held.
+    fn node(&'static self, ino: u32, filename: &str) -> Arc<Ext4Node> {
+        self.inner.lock().get_handle(ino);
+        Arc::new(Ext4Node {
+            fs: self,
+            ino,
+            filename: String::from(filename),
+        })
+    }
+
+    /// Lock the fs for the modification.
+    fn writable(&self) -> VfsResult<MutexGuard<'_, Ext4Inner>> {
+        let inner = self.inner.lock();
+        match inner.readonly {
+            true => Err(VfsError::NotWriteable),
+            false => Ok(inner),
+        }
+    }
+}
+
+impl FileSystem for Ext4FileSystem {
+    fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
+        self.node(ROOT_INO, "")
+    }
+
+    fn name(&self) -> &str {
+        "ext4"
+    }
+
+    fn flush(&self) -> VfsResult<()> {
+        // all the metadata is written through, only the block cache is left.
+        self.inner.lock().device.flush();
+        Ok(())
+    }
+}
+
+pub struct Ext4Node {
+    fs: &'static Ext4FileSystem,
+    ino: u32,
+    filename: String,
+}
+
+impl Drop for Ext4Node {
+    fn drop(&mut self) {
+        if let Err(err) = self.fs.inner.lock().put_handle(self.ino) {
+            log::error!("can't put ext4 inode {}: {:?}", self.ino, err);
+        }
+    }
+}
+
+/// The fifo isn't a regular file, vfscore has no fifo type so it is a special file like devices.
+const fn mode_to_type(mode: u16) -> FileType {
+    match mode & S_IFMT {
+        S_IFDIR => FileType::Directory,
+        S_IFLNK => FileType::Link,
+        S_IFCHR | S_IFBLK | S_IFIFO => FileType::Device,
+        S_IFSOCK => FileType::Socket,
+        _ => FileType::File,
+    }
+}
+
+const fn ft_to_type(ft: u8) -> FileType {
+    match ft {
+        FT_DIR => FileType::Directory,
+        FT_SYMLINK => FileType::Link,
+        FT_CHRDEV | FT_BLKDEV | FT_FIFO => FileType::Device,
+        FT_SOCK => FileType::Socket,
+        _ => FileType::File,
+    }
+}
+
+impl Ext4Node {
+    /// The device number and the inode number, the same file opened twice has the same ones.
+    pub fn dev_ino(&self) -> (u64, u64) {
+        (self.fs.dev, self.ino as u64)
+    }
+
+    fn dir(&self, inner: &Ext4Inner) -> VfsResult<Inode> {
+        let inode = inner.read_inode(self.ino)?;
+        match inode.is_dir() {
+            true => Ok(inode),
+            false => Err(VfsError::NotDir),
+        }
+    }
+
+    /// Create a inode and link it to this directory.
+    fn create(&self, name: &str, mode: u16) -> VfsResult<Arc<dyn INodeInterface>> {
+        let ino = {
+            let mut inner = self.fs.writable()?;
+            let mut dir = self.dir(&inner)?;
+            if inner.dir_lookup(&dir, name)?.is_some() {
+                return Err(VfsError::AlreadyExists);
+            }
+            let mut inode = inner.new_inode(self.ino, mode)?;
+            if inode.is_dir() {
+                inode.set_links_count(2);
+                inner.init_dir(&mut inode, self.ino)?;
+            } else {
+                inode.set_links_count(1);
+            }
+            inner.write_inode(&mut inode)?;
+            inner.dir_add_entry(&mut dir, name, inode.ino, mode_to_ft(mode))?;
+            if inode.is_dir() {
+                // ".." of the new directory links to this one.
+                let mut dir = inner.read_inode(self.ino)?;
+                dir.set_links_count(dir.links_count() + 1);
+                inner.write_inode(&mut dir)?;
+            }
+            inode.ino
+        };
+        Ok(self.fs.node(ino, name))
+    }
+
+    /// Remove the entry, rmdir only removes the empty directories and unlink never removes them.
+    /// vfscore has no error for EISDIR, NotFile is returned like readat on the directory.
+    fn remove_entry(&self, name: &str, rmdir: bool) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut dir = self.dir(&inner)?;
+        let (ino, _) = inner
+            .dir_lookup(&dir, name)?
+            .ok_or(VfsError::FileNotFound)?;
+        let mut inode = inner.read_inode(ino)?;
+        match (inode.is_dir(), rmdir) {
+            (true, false) => return Err(VfsError::NotFile),
+            (false, true) => return Err(VfsError::NotDir),
+            (true, true) if !inner.read_dir_entries(&inode)?.is_empty() => {
+                return Err(VfsError::DirectoryNotEmpty)
+            }
+            _ => {}
+        }
+        inner.dir_remove_entry(&mut dir, name)?;
+        if inode.is_dir() {
+            dir.set_links_count(dir.links_count().saturating_sub(1));
+            inner.write_inode(&mut dir)?;
+        }
+        inner.drop_link(&mut inode)
+    }
+
+    /// Move the entry to the new directory, the entry which has the new name is replaced.
+    pub fn rename(&self, name: &str, new_dir: &Ext4Node, new_name: &str) -> VfsResult<()> {
+        if !core::ptr::eq(self.fs, new_dir.fs) {
+            return Err(VfsError::NotSupported);
+        }
+        let mut inner = self.fs.writable()?;
+        let dir = self.dir(&inner)?;
+        let (ino, ft) = inner
+            .dir_lookup(&dir, name)?
+            .ok_or(VfsError::FileNotFound)?;
+        let inode = inner.read_inode(ino)?;
+        let mut target = new_dir.dir(&inner)?;
+        match inner.dir_lookup(&target, new_name)? {
+            // the both names are the links of the same file.
+            Some((old_ino, _)) if old_ino == ino => return Ok(()),
+            Some((old_ino, _)) => {
+                let mut old = inner.read_inode(old_ino)?;
+                match (inode.is_dir(), old.is_dir()) {
+                    (true, false) => return Err(VfsError::NotDir),
+                    (false, true) => return Err(VfsError::NotFile),
+                    (true, true) if !inner.read_dir_entries(&old)?.is_empty() => {
+                        return Err(VfsError::DirectoryNotEmpty)
+                    }
+                    _ => {}
+                }
+                // the new name always exists, it points to the moved inode at once.
+                inner.dir_replace_entry(&target, new_name, ino, ft)?;
+                if old.is_dir() {
+                    target.set_links_count(target.links_count().saturating_sub(1));
+                    inner.write_inode(&mut target)?;
+                }
+                inner.drop_link(&mut old)?;
+            }
+            None => inner.dir_add_entry(&mut target, new_name, ino, ft)?,
+        }
+        // the directory is read again, it is changed if it is the new directory.
+        let mut dir = self.dir(&inner)?;
+        inner.dir_remove_entry(&mut dir, name)?;
+        if inode.is_dir() && self.ino != new_dir.ino {
+            inner.set_dir_parent(&inode, new_dir.ino)?;
+            dir.set_links_count(dir.links_count().saturating_sub(1));
+            inner.write_inode(&mut dir)?;
+            let mut target = new_dir.dir(&inner)?;
+            target.set_links_count(target.links_count() + 1);
+            inner.write_inode(&mut target)?;
+        }
+        Ok(())
+    }
+
+    /// Change the permission bits, the file type is kept.
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut inode = inner.read_inode(self.ino)?;
+        inode.set_mode((inode.mode() & S_IFMT) | (mode as u16 & 0o7777));
+        inner.write_inode(&mut inode)
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut inode = inner.read_inode(self.ino)?;
+        if let Some(uid) = uid {
+            inode.set_uid(uid);
+        }
+        if let Some(gid) = gid {
+            inode.set_gid(gid);
+        }
+        inner.write_inode(&mut inode)
+    }
+
+    /// The creation time, the small inode doesn't have it.
+    pub fn birth_time(&self) -> VfsResult<TimeSpec> {
+        let inode = self.fs.inner.lock().read_inode(self.ino)?;
+        let sec = inode.crtime().ok_or(VfsError::NotSupported)?;
+        Ok(TimeSpec {
+            sec: sec as _,
+            nsec: 0,
+        })
+    }
+
+    /// The flags shown by statx(2), they have the same bits as the attributes of statx.
+    pub fn attributes(&self) -> VfsResult<u64> {
+        let inode = self.fs.inner.lock().read_inode(self.ino)?;
+        let mask = INODE_COMPR_FL
+            | INODE_IMMUTABLE_FL
+            | INODE_APPEND_FL
+            | INODE_NODUMP_FL
+            | INODE_ENCRYPT_FL;
+        Ok((inode.flags() & mask) as u64)
+    }
+
+    /// Create a symbolic link, the short target is stored in i_block.
+    pub fn symlink(&self, name: &str, target: &str) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut dir = self.dir(&inner)?;
+        if inner.dir_lookup(&dir, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut inode = inner.new_inode(self.ino, S_IFLNK | 0o777)?;
+        inode.set_links_count(1);
+        match target.len() < 60 {
+            true => {
+                inode.block_mut()[..target.len()].copy_from_slice(target.as_bytes());
+                inode.set_size(target.len() as u64);
+                inner.write_inode(&mut inode)?;
+            }
+            false => {
+                Ext4Inner::init_extent_root(&mut inode);
+                inner.write_data(&mut inode, 0, target.as_bytes())?;
+            }
+        }
+        inner.dir_add_entry(&mut dir, name, inode.ino, mode_to_ft(S_IFLNK))
+    }
+}
+
+impl INodeInterface for Ext4Node {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let inner = self.fs.inner.lock();
+        let inode = inner.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        inner.read_data(&inode, offset, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        let mut inner = self.fs.writable()?;
+        let mut inode = inner.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        inner.write_data(&mut inode, offset, buffer)
+    }
+
+    fn truncate(&self, size: usize) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut inode = inner.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        inner.truncate(&mut inode, size as u64)
+    }
+
+    fn flush(&self) -> VfsResult<()> {
+        FileSystem::flush(self.fs)
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        let inner = self.fs.inner.lock();
+        let inode = inner.read_inode(self.ino)?;
+        let childrens = match inode.is_dir() {
+            true => inner.read_dir_entries(&inode)?.len(),
+            false => 0,
+        };
+        Ok(Metadata {
+            filename: &self.filename,
+            inode: self.ino as usize,
+            file_type: mode_to_type(inode.mode()),
+            size: inode.size() as usize,
+            childrens,
+        })
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let inner = self.fs.inner.lock();
+        let inode = inner.read_inode(self.ino)?;
+        stat.dev = self.fs.dev;
+        stat.ino = self.ino as u64;
+        stat.mode = StatMode::from_bits_retain(inode.mode() as _);
+        stat.nlink = inode.links_count() as _;
+        stat.uid = inode.uid() as _;
+        stat.gid = inode.gid() as _;
+        stat.size = inode.size() as _;
+        stat.blksize = inner.block_size as _;
+        stat.blocks = inode.sectors(inner.block_size) as _;
+        stat.rdev = 0;
+        stat.atime = TimeSpec {
+            sec: inode.atime() as _,
+            nsec: 0,
+        };
+        stat.mtime = TimeSpec {
+            sec: inode.mtime() as _,
+            nsec: 0,
+        };
+        stat.ctime = TimeSpec {
+            sec: inode.ctime() as _,
+            nsec: 0,
+        };
+        Ok(())
+    }
+
+    fn statfs(&self, statfs: &mut StatFS) -> VfsResult<()> {
+        let inner = self.fs.inner.lock();
+        statfs.ftype = EXT4_MAGIC as _;
+        statfs.bsize = inner.block_size as _;
+        statfs.blocks = inner.sb.blocks_count() as _;
+        statfs.bfree = inner.sb.free_blocks_count() as _;
+        statfs.bavail = inner.sb.free_blocks_count() as _;
+        statfs.files = inner.sb.inodes_count() as _;
+        statfs.ffree = inner.sb.free_inodes_count() as _;
+        statfs.fsid = 0;
+        statfs.namelen = 255;
+        Ok(())
+    }
+
+    fn utimes(&self, times: &mut [TimeSpec]) -> VfsResult<()> {
+        let mut inner = self.fs.writable()?;
+        let mut inode = inner.read_inode(self.ino)?;
+        if times[0].nsec != UTIME_OMIT {
+            inode.set_atime(times[0].sec as _);
+        }
+        if times[1].nsec != UTIME_OMIT {
+            inode.set_mtime(times[1].sec as _);
+        }
+        inner.write_inode(&mut inode)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        let ino = {
+            let inner = self.fs.inner.lock();
+            let dir = self.dir(&inner)?;
+            let (ino, _) = inner
+                .dir_lookup(&dir, name)?
+                .ok_or(VfsError::FileNotFound)?;
+            ino
+        };
+        Ok(self.fs.node(ino, name))
+    }
+
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.lookup(name)
+    }
+
+    fn touch(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.create(name, S_IFREG | 0o644)
+    }
+
+    fn mkdir(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.create(name, S_IFDIR | 0o755)
+    }
+
+    fn rmdir(&self, name: &str) -> VfsResult<()> {
+        self.remove_entry(name, true)
+    }
+
+    fn remove(&self, name: &str) -> VfsResult<()> {
+        self.remove_entry(name, false)
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()> {
+        self.remove_entry(name, false)
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        let inner = self.fs.inner.lock();
+        let dir = self.dir(&inner)?;
+        inner
+            .read_dir_entries(&dir)?
+            .into_iter()
+            .map(|(filename, ino, ft)| {
+                let inode = inner.read_inode(ino)?;
+                // the file type isn't stored in the dirent without the filetype feature.
+                let file_type = match ft {
+                    FT_UNKNOWN => mode_to_type(inode.mode()),
+                    _ => ft_to_type(ft),
+                };
+                Ok(DirEntry {
+                    filename,
+                    len: inode.size() as usize,
+                    file_type,
+                })
+            })
+            .collect()
+    }
+
+    fn link(&self, name: &str, src: Arc<dyn INodeInterface>) -> VfsResult<()> {
+        let src = src
+            .downcast_arc::<Ext4Node>()
+            .map_err(|_| VfsError::NotSupported)?;
+        if !core::ptr::eq(src.fs, self.fs) {
+            return Err(VfsError::NotSupported);
+        }
+        let mut inner = self.fs.writable()?;
+        let mut dir = self.dir(&inner)?;
+        let mut inode = inner.read_inode(src.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        if inner.dir_lookup(&dir, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        inner.dir_add_entry(&mut dir, name, inode.ino, mode_to_ft(inode.mode()))?;
+        inode.set_links_count(inode.links_count() + 1);
+        inner.write_inode(&mut inode)
+    }
+
+    fn resolve_link(&self) -> VfsResult<String> {
+        let inner = self.fs.inner.lock();
+        let inode = inner.read_inode(self.ino)?;
+        if !inode.is_link() {
+            return Err(VfsError::InvalidInput);
+        }
+        let size = inode.size() as usize;
+        let target = match inner.is_fast_symlink(&inode) {
+            true => inode.block()[..size.min(60)].to_vec(),
+            false => {
+                let mut buffer = vec![0u8; size];
+                let len = inner.read_data(&inode, 0, &mut buffer)?;
+                buffer.truncate(len);
+                buffer
+            }
+        };
+        Ok(String::from_utf8_lossy(&target).into_owned())
+    }
+}
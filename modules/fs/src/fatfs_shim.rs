@@ -2,14 +2,17 @@ use core::cmp::{self, min};
 
 use alloc::string::String;
 use alloc::sync::Arc;
-use devices::get_blk_device;
-use fatfs::{Dir, Error, File, LossyOemCpConverter, NullTimeProvider};
+use devices::{
+    get_blk_device, makedev,
+    partition::{blk_device_minor, BLK_DEVICE_MAJOR},
+};
+use fatfs::{DateTime, Dir, Error, File, FileAttributes, LossyOemCpConverter, NullTimeProvider};
 use fatfs::{Read, Seek, SeekFrom, Write};
 use log::debug;
 use sync::Mutex;
 use vfscore::{
-    DirEntry, FileSystem, FileType, INodeInterface, Metadata, Stat, StatFS, StatMode, VfsError,
-    VfsResult,
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, Stat, StatFS, StatMode, TimeSpec,
+    VfsError, VfsResult,
 };
 
 pub trait DiskOperation {
@@ -19,6 +22,8 @@ pub trait DiskOperation {
 
 pub struct Fat32FileSystem {
     inner: fatfs::FileSystem<DiskCursor, NullTimeProvider, LossyOemCpConverter>,
+    /// The device number of the block device, the same as the mount table.
+    dev: u64,
 }
 
 unsafe impl Send for Fat32FileSystem {}
@@ -33,6 +38,9 @@ impl FileSystem for Fat32FileSystem {
     fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
         Arc::new(FatDir {
             filename: String::from(""),
+            dev: self.dev,
+            ino: FAT_ROOT_INO,
+            created: None,
             inner: self.inner.root_dir(),
         })
     }
@@ -43,14 +51,43 @@ impl FileSystem for Fat32FileSystem {
 }
 
 impl Fat32FileSystem {
-    pub fn new(device_id: usize) -> Arc<Self> {
+    pub fn new(device_id: usize) -> VfsResult<Arc<Self>> {
         let cursor: DiskCursor = DiskCursor {
             sector: 0,
             offset: 0,
             device_id,
         };
-        let inner = fatfs::FileSystem::new(cursor, fatfs::FsOptions::new()).expect("open fs wrong");
-        Arc::new(Self { inner })
+        let inner =
+            fatfs::FileSystem::new(cursor, fatfs::FsOptions::new()).map_err(as_vfs_err)?;
+        Ok(Arc::new(Self {
+            inner,
+            dev: makedev(BLK_DEVICE_MAJOR, blk_device_minor(device_id)),
+        }))
+    }
+
+    /// Check the boot sector, only the BPB fields fatfs depends on.
+    pub fn probe(device_id: usize) -> bool {
+        let device = match get_blk_device(device_id) {
+            Some(device) => device,
+            None => return false,
+        };
+        let mut sector = vec![0u8; 512];
+        device.read_blocks(0, &mut sector);
+
+        let bytes_per_sector = u16::from_le_bytes([sector[11], sector[12]]);
+        let sectors_per_cluster = sector[13];
+        let reserved_sectors = u16::from_le_bytes([sector[14], sector[15]]);
+        let fats = sector[16];
+        sector[510..512] == [0x55, 0xaa]
+            && matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            && sectors_per_cluster.is_power_of_two()
+            && reserved_sectors != 0
+            && fats != 0
+            && (&sector[54..57] == b"FAT" || &sector[82..87] == b"FAT32")
+    }
+
+    pub fn open(device_id: usize) -> VfsResult<Arc<dyn FileSystem>> {
+        Ok(Self::new(device_id)?)
     }
 }
 
@@ -62,6 +99,11 @@ pub struct FatFileInner {
 #[allow(dead_code)]
 pub struct FatFile {
     filename: String,
+    dev: u64,
+    ino: u64,
+    attrs: FileAttributes,
+    /// The creation time in the directory entry, None for the file created now.
+    created: Option<DateTime>,
     inner: Mutex<FatFileInner>,
 }
 
@@ -69,8 +111,47 @@ pub struct FatFile {
 unsafe impl Sync for FatFile {}
 unsafe impl Send for FatFile {}
 
+impl FatFile {
+    /// The device number and the inode number, the same file opened twice has the same ones.
+    pub fn dev_ino(&self) -> (u64, u64) {
+        (self.dev, self.ino)
+    }
+
+    pub fn birth_time(&self) -> VfsResult<TimeSpec> {
+        self.created.map(fat_time).ok_or(VfsError::NotSupported)
+    }
+
+    /// Allocate the clusters by writing zeros after the end of the file.
+    /// The clusters after the end can't be kept, so keep_size is only allowed in the file.
+    pub fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> VfsResult<()> {
+        let mut inner = self.inner.lock();
+        let end = offset + len;
+        if end <= inner.size {
+            return Ok(());
+        }
+        if keep_size {
+            return Err(VfsError::NotSupported);
+        }
+        let size = inner.size;
+        inner
+            .inner
+            .seek(SeekFrom::Start(size as u64))
+            .map_err(as_vfs_err)?;
+        let zeros = vec![0u8; 512];
+        while inner.size < end {
+            let wlen = cmp::min(end - inner.size, zeros.len());
+            inner.inner.write_all(&zeros[..wlen]).map_err(as_vfs_err)?;
+            inner.size += wlen;
+        }
+        Ok(())
+    }
+}
+
 pub struct FatDir {
     filename: String,
+    dev: u64,
+    ino: u64,
+    created: Option<DateTime>,
     inner: Dir<'static, DiskCursor, NullTimeProvider, LossyOemCpConverter>,
 }
 
@@ -78,6 +159,95 @@ pub struct FatDir {
 unsafe impl Sync for FatDir {}
 unsafe impl Send for FatDir {}
 
+impl FatDir {
+    /// The device number and the inode number, the same directory opened twice has the same ones.
+    pub fn dev_ino(&self) -> (u64, u64) {
+        (self.dev, self.ino)
+    }
+
+    pub fn birth_time(&self) -> VfsResult<TimeSpec> {
+        self.created.map(fat_time).ok_or(VfsError::NotSupported)
+    }
+
+    /// Move the entry to the new directory, the entry which has the new name is replaced.
+    /// Fat can't replace the entry, so the old one is renamed aside and removed after the move.
+    pub fn rename(&self, name: &str, new_dir: &FatDir, new_name: &str) -> VfsResult<()> {
+        if self.dev != new_dir.dev {
+            return Err(VfsError::NotSupported);
+        }
+        let find = |dir: &FatDir, name: &str| {
+            dir.inner
+                .iter()
+                .filter_map(|x| x.ok())
+                .find(|x| x.file_name() == name)
+        };
+        let is_dir = find(self, name).ok_or(VfsError::FileNotFound)?.is_dir();
+        let Some(old) = find(new_dir, new_name) else {
+            return self
+                .inner
+                .rename(name, &new_dir.inner, new_name)
+                .map_err(as_vfs_err);
+        };
+        let empty = || {
+            old.to_dir()
+                .iter()
+                .filter_map(|x| x.ok())
+                .all(|x| x.file_name() == "." || x.file_name() == "..")
+        };
+        match (is_dir, old.is_dir()) {
+            (true, false) => return Err(VfsError::NotDir),
+            (false, true) => return Err(VfsError::NotFile),
+            (true, true) if !empty() => return Err(VfsError::DirectoryNotEmpty),
+            _ => {}
+        }
+        let aside = format!(".rename-{:x}", fat_ino(new_dir.ino, new_name));
+        new_dir
+            .inner
+            .rename(new_name, &new_dir.inner, &aside)
+            .map_err(as_vfs_err)?;
+        if let Err(err) = self.inner.rename(name, &new_dir.inner, new_name) {
+            // put the old entry back, nothing is changed.
+            new_dir
+                .inner
+                .rename(&aside, &new_dir.inner, new_name)
+                .map_err(as_vfs_err)?;
+            return Err(as_vfs_err(err));
+        }
+        new_dir.inner.remove(&aside).map_err(as_vfs_err)
+    }
+}
+
+const FAT_ROOT_INO: u64 = 1;
+
+/// Fat has no inode, the inode number is made from the path by FNV-1a.
+/// The entries are not linked, so the path is the identity of the file.
+fn fat_ino(parent: u64, name: &str) -> u64 {
+    parent
+        .to_le_bytes()
+        .iter()
+        .chain(name.as_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3)
+        })
+}
+
+/// Convert the time in the directory entry, it is the local time and taken as UTC.
+fn fat_time(time: DateTime) -> TimeSpec {
+    // the days since 1970-01-01, the year starts from March to put the leap day at the end.
+    let (month, day) = (time.date.month as i64, time.date.day as i64);
+    let year = time.date.year as i64 - (month <= 2) as i64;
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let secs = time.time.hour as i64 * 3600 + time.time.min as i64 * 60 + time.time.sec as i64;
+    TimeSpec {
+        sec: (days * 86400 + secs) as _,
+        nsec: (time.time.millis as u32 * 1_000_000) as _,
+    }
+}
+
 impl INodeInterface for FatFile {
     fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
         let mut inner = self.inner.lock();
@@ -155,8 +325,13 @@ impl INodeInterface for FatFile {
     }
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::FILE; // TODO: add access mode
+        // fat has no owner and mode, the read-only attribute clears the write bits.
+        stat.dev = self.dev;
+        stat.ino = self.ino;
+        stat.mode = match self.attrs.contains(FileAttributes::READ_ONLY) {
+            true => StatMode::FILE | StatMode::from_bits_truncate(0o555),
+            false => StatMode::FILE | StatMode::from_bits_truncate(0o755),
+        };
         stat.nlink = 1;
         stat.uid = 0;
         stat.gid = 0;
@@ -182,6 +357,9 @@ impl INodeInterface for FatDir {
             .map(|dir| -> Arc<dyn INodeInterface> {
                 Arc::new(FatDir {
                     filename: String::from(name),
+                    dev: self.dev,
+                    ino: fat_ino(self.ino, name),
+                    created: None,
                     inner: dir,
                 })
             })
@@ -194,6 +372,10 @@ impl INodeInterface for FatDir {
             .map(|file| -> Arc<dyn INodeInterface> {
                 Arc::new(FatFile {
                     filename: String::from(name),
+                    dev: self.dev,
+                    ino: fat_ino(self.ino, name),
+                    attrs: FileAttributes::ARCHIVE,
+                    created: None,
                     inner: Mutex::new(FatFileInner {
                         inner: file,
                         size: 0,
@@ -216,11 +398,18 @@ impl INodeInterface for FatDir {
         if file.is_dir() {
             Ok(Arc::new(FatDir {
                 filename: String::from(name),
+                dev: self.dev,
+                ino: fat_ino(self.ino, name),
+                created: Some(file.created()),
                 inner: file.to_dir(),
             }))
         } else if file.is_file() {
             Ok(Arc::new(FatFile {
                 filename: String::from(name),
+                dev: self.dev,
+                ino: fat_ino(self.ino, name),
+                attrs: file.attributes(),
+                created: Some(file.created()),
                 inner: Mutex::new(FatFileInner {
                     inner: file.to_file(),
                     size: file.len() as usize,
@@ -277,8 +466,9 @@ impl INodeInterface for FatDir {
     }
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::DIR; // TODO: add access mode
+        stat.dev = self.dev;
+        stat.ino = self.ino;
+        stat.mode = StatMode::DIR | StatMode::from_bits_truncate(0o755);
         stat.nlink = 1;
         stat.uid = 0;
         stat.gid = 0;
@@ -447,6 +637,7 @@ impl fatfs::Write for DiskCursor {
     }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
+        get_blk_device(self.device_id).unwrap().flush();
         Ok(())
     }
 }
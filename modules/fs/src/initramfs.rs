@@ -0,0 +1,161 @@
+//! Unpack the initramfs, a cpio archive in the newc format, into the root directory.
+//! It is linked into the kernel or passed by the bootloader.
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use vfscore::{INodeInterface, OpenFlags, VfsError, VfsResult};
+
+use crate::{chmod, chown, symlink};
+
+const CPIO_MAGIC: &[u8] = b"070701";
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+#[cfg(feature = "initramfs")]
+core::arch::global_asm!(
+    "
+    .section .rodata
+    .global initramfs_start
+    .global initramfs_end
+    .align 16
+    initramfs_start:
+    .incbin \"./initramfs.cpio\"
+    initramfs_end:
+"
+);
+
+/// The initramfs linked into the kernel, it is built with the feature initramfs.
+pub fn builtin_initramfs() -> Option<&'static [u8]> {
+    #[cfg(feature = "initramfs")]
+    {
+        extern "C" {
+            fn initramfs_start();
+            fn initramfs_end();
+        }
+        let len = initramfs_end as usize - initramfs_start as usize;
+        Some(unsafe { core::slice::from_raw_parts(initramfs_start as *const u8, len) })
+    }
+    #[cfg(not(feature = "initramfs"))]
+    None
+}
+
+#[inline]
+fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+/// The fields of the header are 8 hex digits.
+fn parse_hex(field: &[u8]) -> VfsResult<u32> {
+    core::str::from_utf8(field)
+        .ok()
+        .and_then(|x| u32::from_str_radix(x, 16).ok())
+        .ok_or(VfsError::InvalidInput)
+}
+
+/// Find the directory which contains the path, the missing directories are created.
+fn parent_dir<'a>(
+    root: &Arc<dyn INodeInterface>,
+    path: &'a str,
+) -> VfsResult<(Arc<dyn INodeInterface>, &'a str)> {
+    let (dir_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let mut dir = root.clone();
+    for name in dir_path.split('/').filter(|x| !x.is_empty() && *x != ".") {
+        dir = dir
+            .open(name, OpenFlags::NONE)
+            .or_else(|_| dir.mkdir(name))?;
+    }
+    Ok((dir, name))
+}
+
+fn write_all(file: &Arc<dyn INodeInterface>, content: &[u8]) -> VfsResult<()> {
+    let mut written = 0;
+    while written < content.len() {
+        written += file.writeat(written, &content[written..])?;
+    }
+    Ok(())
+}
+
+/// Unpack the archive into the directory, return the number of the files.
+/// The special files like the devices are skipped, they are provided by devfs.
+pub fn unpack(root: &Arc<dyn INodeInterface>, data: &[u8]) -> VfsResult<usize> {
+    // the inode number in the archive -> the file, the hard links share the inode number.
+    let mut links: BTreeMap<u32, Arc<dyn INodeInterface>> = BTreeMap::new();
+    let mut offset = 0;
+    let mut count = 0;
+    loop {
+        let header = data
+            .get(offset..offset + CPIO_HEADER_SIZE)
+            .ok_or(VfsError::InvalidInput)?;
+        if !header.starts_with(CPIO_MAGIC) {
+            return Err(VfsError::InvalidInput);
+        }
+        let field = |index: usize| parse_hex(&header[6 + index * 8..14 + index * 8]);
+        let (ino, mode, uid, gid, nlink) = (field(0)?, field(1)?, field(2)?, field(3)?, field(4)?);
+        let filesize = field(6)? as usize;
+        let namesize = field(11)? as usize;
+
+        let name_start = offset + CPIO_HEADER_SIZE;
+        let name = data
+            .get(name_start..name_start + namesize)
+            .ok_or(VfsError::InvalidInput)?;
+        // the name ends with a zero.
+        let name = core::str::from_utf8(&name[..namesize.saturating_sub(1)])
+            .map_err(|_| VfsError::InvalidInput)?;
+        let data_start = align4(name_start + namesize);
+        let content = data
+            .get(data_start..data_start + filesize)
+            .ok_or(VfsError::InvalidInput)?;
+        offset = align4(data_start + filesize);
+
+        if name == CPIO_TRAILER {
+            break;
+        }
+        let path = name.trim_start_matches('/');
+        let node = match path {
+            "" | "." => root.clone(),
+            _ => {
+                let (dir, filename) = parent_dir(root, path)?;
+                match mode & S_IFMT {
+                    S_IFDIR => dir
+                        .open(filename, OpenFlags::NONE)
+                        .or_else(|_| dir.mkdir(filename))?,
+                    S_IFREG => {
+                        let file = match links.get(&ino) {
+                            Some(file) => {
+                                dir.link(filename, file.clone())?;
+                                file.clone()
+                            }
+                            None => dir.touch(filename)?,
+                        };
+                        if nlink > 1 {
+                            links.insert(ino, file.clone());
+                        }
+                        // the content of the hard links is in the last one.
+                        write_all(&file, content)?;
+                        file
+                    }
+                    S_IFLNK => {
+                        let target =
+                            core::str::from_utf8(content).map_err(|_| VfsError::InvalidInput)?;
+                        symlink(&dir, filename, target)?;
+                        count += 1;
+                        continue;
+                    }
+                    _ => {
+                        log::warn!("skip the special file {} in the initramfs", path);
+                        continue;
+                    }
+                }
+            }
+        };
+        // the filesystem may not support the permissions.
+        let _ = chmod(&node, mode & 0o7777);
+        let _ = chown(&node, Some(uid), Some(gid));
+        count += 1;
+    }
+    Ok(count)
+}
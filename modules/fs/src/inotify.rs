@@ -0,0 +1,351 @@
+//! The inotify instances, the events are reported by the paths of the dentry tree.
+//! The watches are identified by the device number and the inode number,
+//! so they work for all the filesystems.
+
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicI32, AtomicU32, Ordering},
+};
+
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use bitflags::bitflags;
+use sync::Mutex;
+use vfscore::{FileType, INodeInterface, PollEvent, Stat, VfsError, VfsResult};
+
+use crate::{dentry::DentryNode, mount::mount_dev};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InotifyMask: u32 {
+        const IN_ACCESS = 0x1;
+        const IN_MODIFY = 0x2;
+        const IN_ATTRIB = 0x4;
+        const IN_CLOSE_WRITE = 0x8;
+        const IN_CLOSE_NOWRITE = 0x10;
+        const IN_OPEN = 0x20;
+        const IN_MOVED_FROM = 0x40;
+        const IN_MOVED_TO = 0x80;
+        const IN_CREATE = 0x100;
+        const IN_DELETE = 0x200;
+        const IN_DELETE_SELF = 0x400;
+        const IN_MOVE_SELF = 0x800;
+        const IN_Q_OVERFLOW = 0x4000;
+        const IN_IGNORED = 0x8000;
+        const IN_ONLYDIR = 0x100_0000;
+        const IN_DONT_FOLLOW = 0x200_0000;
+        const IN_EXCL_UNLINK = 0x400_0000;
+        const IN_MASK_CREATE = 0x1000_0000;
+        const IN_MASK_ADD = 0x2000_0000;
+        const IN_ISDIR = 0x4000_0000;
+        const IN_ONESHOT = 0x8000_0000;
+    }
+}
+
+impl InotifyMask {
+    /// The events which can be watched.
+    pub const IN_ALL_EVENTS: InotifyMask = InotifyMask::from_bits_truncate(0xfff);
+    /// The events only reported to the directory which contains the file.
+    const CHILD_ONLY: InotifyMask = InotifyMask::IN_MOVED_FROM
+        .union(InotifyMask::IN_MOVED_TO)
+        .union(InotifyMask::IN_CREATE)
+        .union(InotifyMask::IN_DELETE);
+    /// The events only reported to the file itself.
+    const SELF_ONLY: InotifyMask = InotifyMask::IN_DELETE_SELF.union(InotifyMask::IN_MOVE_SELF);
+}
+
+/// The file is watched by its device number and inode number.
+pub type WatchKey = (u64, u64);
+
+/// The max number of the queued events, the same as Linux.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+struct Watch {
+    wd: i32,
+    key: WatchKey,
+    mask: InotifyMask,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct InotifyEvent {
+    wd: i32,
+    mask: InotifyMask,
+    cookie: u32,
+    name: String,
+}
+
+impl InotifyEvent {
+    /// The size of the struct inotify_event, the name is padded with zeros.
+    fn len(&self) -> usize {
+        size_of::<i32>() + size_of::<u32>() * 3 + self.name_len()
+    }
+
+    fn name_len(&self) -> usize {
+        match self.name.is_empty() {
+            true => 0,
+            false => (self.name.len() + 1 + 3) & !3,
+        }
+    }
+}
+
+pub struct Inotify {
+    watches: Mutex<Vec<Watch>>,
+    events: Mutex<VecDeque<InotifyEvent>>,
+    next_wd: AtomicI32,
+}
+
+/// The inotify instances alive, the events are only generated if there are watches.
+static INSTANCES: Mutex<Vec<Weak<Inotify>>> = Mutex::new(Vec::new());
+
+impl Inotify {
+    pub fn new() -> Arc<Self> {
+        let inotify = Arc::new(Self {
+            watches: Mutex::new(Vec::new()),
+            events: Mutex::new(VecDeque::new()),
+            next_wd: AtomicI32::new(1),
+        });
+        INSTANCES.lock().push(Arc::downgrade(&inotify));
+        inotify
+    }
+
+    /// Add the watch of the file or update its mask, return the watch descriptor.
+    pub fn add_watch(&self, key: WatchKey, mask: InotifyMask) -> VfsResult<i32> {
+        let mut watches = self.watches.lock();
+        if let Some(watch) = watches.iter_mut().find(|x| x.key == key) {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(VfsError::AlreadyExists);
+            }
+            watch.mask = match mask.contains(InotifyMask::IN_MASK_ADD) {
+                true => watch.mask | mask,
+                false => mask,
+            };
+            return Ok(watch.wd);
+        }
+        let wd = self.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch { wd, key, mask });
+        Ok(wd)
+    }
+
+    /// Remove the watch, IN_IGNORED is reported for it.
+    pub fn rm_watch(&self, wd: i32) -> VfsResult<()> {
+        let mut watches = self.watches.lock();
+        let index = watches
+            .iter()
+            .position(|x| x.wd == wd)
+            .ok_or(VfsError::InvalidInput)?;
+        watches.remove(index);
+        drop(watches);
+        self.push(wd, InotifyMask::IN_IGNORED, 0, "");
+        Ok(())
+    }
+
+    fn push(&self, wd: i32, mask: InotifyMask, cookie: u32, name: &str) {
+        let mut events = self.events.lock();
+        let event = InotifyEvent {
+            wd,
+            mask,
+            cookie,
+            name: name.to_string(),
+        };
+        // the same event as the last one is merged.
+        if events.back() == Some(&event) {
+            return;
+        }
+        if events.len() >= MAX_QUEUED_EVENTS {
+            if events
+                .back()
+                .is_some_and(|x| x.mask != InotifyMask::IN_Q_OVERFLOW)
+            {
+                events.push_back(InotifyEvent {
+                    wd: -1,
+                    mask: InotifyMask::IN_Q_OVERFLOW,
+                    cookie: 0,
+                    name: String::new(),
+                });
+            }
+            return;
+        }
+        events.push_back(event);
+    }
+
+    /// Report the event to the watches of the file.
+    fn report(&self, key: WatchKey, mask: InotifyMask, cookie: u32, name: &str) {
+        let mut ignored = Vec::new();
+        let mut matched = Vec::new();
+        self.watches.lock().retain(|x| {
+            if x.key != key || !x.mask.intersects(mask) {
+                return true;
+            }
+            matched.push(x.wd);
+            // the oneshot watch is removed after the first event.
+            if x.mask.contains(InotifyMask::IN_ONESHOT) {
+                ignored.push(x.wd);
+                return false;
+            }
+            true
+        });
+        for wd in matched {
+            self.push(wd, mask, cookie, name);
+        }
+        for wd in ignored {
+            self.push(wd, InotifyMask::IN_IGNORED, 0, "");
+        }
+    }
+
+    /// Remove the watches of the deleted file, IN_IGNORED is reported for them.
+    fn forget(&self, key: WatchKey) {
+        let mut ignored = Vec::new();
+        self.watches.lock().retain(|x| match x.key == key {
+            true => {
+                ignored.push(x.wd);
+                false
+            }
+            false => true,
+        });
+        for wd in ignored {
+            self.push(wd, InotifyMask::IN_IGNORED, 0, "");
+        }
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        INSTANCES.lock().retain(|x| x.strong_count() > 0);
+    }
+}
+
+impl INodeInterface for Inotify {
+    fn readat(&self, _offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let mut events = self.events.lock();
+        if events.is_empty() {
+            return Err(VfsError::Blocking);
+        }
+        // the buffer should hold the first event at least.
+        if events[0].len() > buffer.len() {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut rlen = 0;
+        while let Some(event) = events.front()
+            && rlen + event.len() <= buffer.len()
+        {
+            let data = &mut buffer[rlen..rlen + event.len()];
+            data.fill(0);
+            data[0..4].copy_from_slice(&event.wd.to_ne_bytes());
+            data[4..8].copy_from_slice(&event.mask.bits().to_ne_bytes());
+            data[8..12].copy_from_slice(&event.cookie.to_ne_bytes());
+            data[12..16].copy_from_slice(&(event.name_len() as u32).to_ne_bytes());
+            data[16..16 + event.name.len()].copy_from_slice(event.name.as_bytes());
+            rlen += event.len();
+            events.pop_front();
+        }
+        Ok(rlen)
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        let mut res = PollEvent::NONE;
+        if events.contains(PollEvent::POLLIN) && !self.events.lock().is_empty() {
+            res |= PollEvent::POLLIN;
+        }
+        Ok(res)
+    }
+}
+
+/// The inotify instances, None if nothing is watched.
+fn instances() -> Option<Vec<Arc<Inotify>>> {
+    let instances: Vec<_> = INSTANCES.lock().iter().filter_map(Weak::upgrade).collect();
+    match instances.is_empty() {
+        true => None,
+        false => Some(instances),
+    }
+}
+
+/// The key of the file in the dentry tree.
+pub fn watch_key(dentry: &DentryNode) -> VfsResult<WatchKey> {
+    let mut stat = Stat::default();
+    dentry.node.stat(&mut stat)?;
+    Ok((mount_dev(&dentry.path()), stat.ino))
+}
+
+fn is_dir(dentry: &DentryNode) -> bool {
+    dentry
+        .node
+        .metadata()
+        .is_ok_and(|x| x.file_type == FileType::Directory)
+}
+
+fn notify_with(instances: &[Arc<Inotify>], dentry: &DentryNode, mask: InotifyMask, cookie: u32) {
+    let mask = match is_dir(dentry) {
+        true => mask | InotifyMask::IN_ISDIR,
+        false => mask,
+    };
+    if let Ok(key) = watch_key(dentry)
+        && !mask.difference(InotifyMask::CHILD_ONLY).is_empty()
+    {
+        let mask = mask.difference(InotifyMask::CHILD_ONLY);
+        instances
+            .iter()
+            .for_each(|x| x.report(key, mask, cookie, ""));
+    }
+    if let Some(parent) = dentry.parent.upgrade()
+        && let Ok(key) = watch_key(&parent)
+        && !mask.difference(InotifyMask::SELF_ONLY).is_empty()
+    {
+        let mask = mask.difference(InotifyMask::SELF_ONLY);
+        instances
+            .iter()
+            .for_each(|x| x.report(key, mask, cookie, &dentry.filename));
+    }
+}
+
+/// Report the event of the file to its watches and the watches of its directory.
+pub fn notify(dentry: &DentryNode, mask: InotifyMask) {
+    if let Some(instances) = instances() {
+        notify_with(&instances, dentry, mask, 0);
+    }
+}
+
+/// Report the rename, the two events share the same cookie.
+pub fn notify_rename(old: &DentryNode, new: &DentryNode) {
+    static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+    if let Some(instances) = instances() {
+        let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+        notify_with(&instances, old, InotifyMask::IN_MOVED_FROM, cookie);
+        notify_with(&instances, new, InotifyMask::IN_MOVED_TO, cookie);
+        notify_with(&instances, old, InotifyMask::IN_MOVE_SELF, 0);
+    }
+}
+
+/// Remove the file and report the deletion.
+/// The file is deleted if its last link is removed, the watches of it are removed too.
+pub fn notify_delete(dentry: &DentryNode, remove: impl FnOnce() -> VfsResult<()>) -> VfsResult<()> {
+    let Some(instances) = instances() else {
+        return remove();
+    };
+    // the key is unknown after the file is removed.
+    let key = watch_key(dentry).ok();
+    let mut stat = Stat::default();
+    let mask = match is_dir(dentry) {
+        true => InotifyMask::IN_DELETE | InotifyMask::IN_ISDIR,
+        false => InotifyMask::IN_DELETE,
+    };
+    let deleted = mask.contains(InotifyMask::IN_ISDIR)
+        || dentry
+            .node
+            .stat(&mut stat)
+            .map_or(true, |_| stat.nlink <= 1);
+    remove()?;
+    notify_with(&instances, dentry, mask, 0);
+    if let Some(key) = key
+        && deleted
+    {
+        instances.iter().for_each(|x| {
+            x.report(key, InotifyMask::IN_DELETE_SELF, 0, "");
+            x.forget(key);
+        });
+    }
+    Ok(())
+}
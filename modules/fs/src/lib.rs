@@ -10,21 +10,28 @@ use core::{
     usize,
 };
 
-use alloc::{
-    string::{String, ToString},
-    sync::Arc,
-    vec::Vec,
+use alloc::{string::String, sync::Arc, vec::Vec};
+use arch::PAGE_SIZE;
+use devfs::{find_device, DevDir, DevDirContainer, DevFS, Sdx};
+use devices::{
+    get_blk_devices, makedev,
+    partition::{blk_device_minor, blk_device_name, BLK_DEVICE_MAJOR},
 };
-use devfs::{DevDir, DevFS, Sdx};
-use devices::get_blk_devices;
+use frame_allocator::{add_frame_map, ceil_div};
 use procfs::ProcFS;
-use ramfs::RamFs;
+use ramfs::{RamDir, RamFile, RamFs, RamLink, RamNode, RamSymlink, RamXattrs};
 use sync::LazyInit;
+use sysfs::SysFS;
 use vfscore::{FileSystem, VfsResult};
 
 use crate::{
-    dentry::{dentry_init, DentryNode},
-    fatfs_shim::Fat32FileSystem,
+    dentry::{dentry_init, render_dentry_state},
+    ext4::{Ext4FileSystem, Ext4Node},
+    fatfs_shim::{Fat32FileSystem, FatDir, FatFile},
+    mount::{add_mount, render_filesystems, render_mounts, source_dev, MountFlags},
+    overlay::OverlayNode,
+    p9::P9Node,
+    probe::{device_fs_type, flush_device_fs, open_device_fs, register_fs, FsProbe},
 };
 
 #[macro_use]
@@ -33,59 +40,124 @@ extern crate alloc;
 extern crate logging;
 
 pub mod dentry;
+mod ext4;
 mod fatfs_shim;
+pub mod initramfs;
+pub mod inotify;
+pub mod lock;
+pub mod mount;
+pub mod overlay;
+mod p9;
+pub mod page_cache;
 pub mod pipe;
+pub mod probe;
 
 pub type File = Arc<dyn INodeInterface>;
 
+pub use ramfs::XattrMode;
 pub use vfscore::{
     FileType, INodeInterface, OpenFlags, PollEvent, PollFd, SeekFrom, Stat, StatFS, StatMode,
     TimeSpec, VfsError, UTIME_NOW, UTIME_OMIT,
 };
 pub static FILESYSTEMS: LazyInit<Vec<Arc<dyn FileSystem>>> = LazyInit::new();
 
-pub fn build_devfs(filesystems: &Vec<(Arc<dyn FileSystem>, &str)>) -> Arc<DevFS> {
-    let dev_sdxs: Vec<_> = filesystems
-        .iter()
-        .enumerate()
-        .map(|(i, _x)| {
+pub fn build_devfs() -> Arc<DevFS> {
+    let dev_dir = DevDir::new();
+
+    for device_id in 0..get_blk_devices().len() {
+        let name = blk_device_name(device_id);
+        let rdev = makedev(BLK_DEVICE_MAJOR, blk_device_minor(device_id));
+        dev_dir.add_device(
+            &name,
+            StatMode::BLOCK,
+            rdev,
             Arc::new(Sdx::new(
-                i,
-                |fs_id, path| {
-                    DentryNode::mount(String::from(path), get_filesystem(fs_id).root_dir())
+                device_id,
+                |device_id, path| {
+                    let source = format!("/dev/{}", blk_device_name(device_id));
+                    mount::mount(&source, path, "auto", MountFlags::empty(), "")
                 },
-                |_fs_id: usize, path| DentryNode::unmount(String::from(path)),
-            ))
-        })
-        .collect();
-    let mut dev_dir = DevDir::new();
-
-    // TODO: add fs normal, not fixed.
-    dev_dir.add("sda", dev_sdxs[0].clone());
+                |_device_id: usize, path| mount::umount(path),
+            )),
+        );
+    }
 
     DevFS::new_with_dir(dev_dir)
 }
 
+/// Register the filesystems which can be found on block devices.
+fn register_filesystems() {
+    register_fs(FsProbe {
+        names: &["vfat", "fat32", "fat", "msdos"],
+        probe: Fat32FileSystem::probe,
+        open: Fat32FileSystem::open,
+    });
+    register_fs(FsProbe {
+        names: &["ext4", "ext3", "ext2"],
+        probe: Ext4FileSystem::probe,
+        open: Ext4FileSystem::open,
+    });
+}
+
 pub fn init() {
     info!("fs module initialized");
 
-    // TODO: Identify the filesystem at the device.
-    let mut filesystems: Vec<(Arc<dyn FileSystem>, &str)> = Vec::new();
-    if get_blk_devices().len() > 0 {
-        filesystems.push((Fat32FileSystem::new(0), "/"));
-    } else {
-        filesystems.push((RamFs::new(), "/"));
-    }
-    filesystems.push((build_devfs(&filesystems), "/dev"));
-    filesystems.push((RamFs::new(), "/tmp"));
-    filesystems.push((RamFs::new(), "/dev/shm"));
-    filesystems.push((RamFs::new(), "/home"));
-    filesystems.push((RamFs::new(), "/var"));
-    filesystems.push((ProcFS::new(), "/proc"));
+    register_filesystems();
+
+    // use the first block device which contains a known filesystem as root.
+    let root_device = (0..get_blk_devices().len())
+        .find_map(|device_id| Some((device_id, open_device_fs(device_id, None).ok()?)));
+    let (rootfs, root_source, root_type): (Arc<dyn FileSystem>, String, &str) = match root_device {
+        Some((device_id, fs)) => (
+            fs,
+            format!("/dev/{}", blk_device_name(device_id)),
+            device_fs_type(device_id).unwrap_or("auto"),
+        ),
+        None => {
+            log::warn!("can't find a filesystem on block devices, use ramfs as root");
+            (RamFs::new(), String::from("rootfs"), "ramfs")
+        }
+    };
+    // keep the image unchanged, the changes are in the memory and lost after reboot.
+    #[cfg(feature = "overlay")]
+    let (rootfs, root_type): (Arc<dyn FileSystem>, &str) = (
+        overlay::OverlayFs::new(
+            mount::leak(RamFs::new()).root_dir(),
+            mount::leak(rootfs).root_dir(),
+        ),
+        "overlay",
+    );
+
+    // (filesystem, mount point, source, type)
+    let mut filesystems: Vec<(Arc<dyn FileSystem>, &str, &str, &str)> = Vec::new();
+    filesystems.push((rootfs, "/", &root_source, root_type));
+    filesystems.push((build_devfs(), "/dev", "devtmpfs", "devtmpfs"));
+    filesystems.push((RamFs::new(), "/tmp", "tmpfs", "tmpfs"));
+    filesystems.push((RamFs::new(), "/dev/shm", "tmpfs", "tmpfs"));
+    filesystems.push((RamFs::new(), "/home", "tmpfs", "tmpfs"));
+    filesystems.push((RamFs::new(), "/var", "tmpfs", "tmpfs"));
+    let proc_dev = source_dev("proc");
+    filesystems.push((
+        ProcFS::new(
+            proc_dev,
+            render_mounts,
+            render_dentry_state,
+            render_filesystems,
+        ),
+        "/proc",
+        "proc",
+        "proc",
+    ));
+    filesystems.push((SysFS::new(), "/sys", "sysfs", "sysfs"));
     // filesystems.push((RamFs::new(), "/bin"));
 
     // mount to FILESYSTEMS
-    FILESYSTEMS.init_by(filesystems.iter().map(|(fs, _)| fs.clone()).collect());
+    FILESYSTEMS.init_by(filesystems.iter().map(|(fs, ..)| fs.clone()).collect());
+
+    // the initramfs is only used if there isn't a root filesystem on the block devices.
+    if root_source == "rootfs" {
+        unpack_initramfs();
+    }
 
     // init mount points
     info!("create fatfs mount file");
@@ -94,26 +166,338 @@ pub fn init() {
         // let fs = &filesystems[0].0;
         // let rootfs = filesystems[0].0.root_dir();
         let rootfs = get_filesystem(0).root_dir();
-        let dev = rootfs.mkdir("dev").expect("can't create devfs dir");
-        dev.mkdir("shm").expect("can't create shm dir");
-        rootfs.mkdir("tmp").expect("can't create tmp dir");
+        // the directories may be in the image already.
+        let mkdir = |dir: &Arc<dyn INodeInterface>, name: &str| {
+            dir.open(name, OpenFlags::NONE)
+                .or_else(|_| dir.mkdir(name))
+                .expect(&format!("can't create {} dir", name))
+        };
+        let dev = mkdir(&rootfs, "dev");
+        mkdir(&dev, "shm");
+        mkdir(&rootfs, "tmp");
         // rootfs.mkdir("lib").expect("can't create lib dir");
-        rootfs.mkdir("home").expect("can't create home dir");
-        rootfs.mkdir("var").expect("can't create var dir");
-        rootfs.mkdir("proc").expect("can't create proc dir");
-        rootfs.mkdir("bin").expect("can't create var dir");
+        mkdir(&rootfs, "home");
+        mkdir(&rootfs, "var");
+        mkdir(&rootfs, "proc");
+        mkdir(&rootfs, "sys");
+        mkdir(&rootfs, "bin");
     }
-    for (i, (_, mount_point)) in filesystems.iter().enumerate() {
-        // mount(mount_point.to_string(), i).expect(&format!("can't mount fs_{i} {mount_point}"));
+    for (i, (_, mount_point, source, fstype)) in filesystems.iter().enumerate() {
+        let root = get_filesystem(i).root_dir();
         if *mount_point == "/" {
-            dentry_init(get_filesystem(i).root_dir())
-        } else {
-            DentryNode::mount(mount_point.to_string(), get_filesystem(i).root_dir())
-                .expect(&format!("can't mount fs_{i} {mount_point}"));
+            dentry_init(root.clone())
+        }
+        let dev = match *fstype {
+            "proc" => proc_dev,
+            _ => source_dev(source),
+        };
+        add_mount(source, mount_point, fstype, MountFlags::empty(), dev, root)
+            .expect(&format!("can't mount fs_{i} {mount_point}"));
+    }
+}
+
+/// Unpack the initramfs passed by the bootloader or linked into the kernel into the root.
+fn unpack_initramfs() {
+    let initrd = arch::get_initrd();
+    let data = match &initrd {
+        Some(initrd) => unsafe {
+            core::slice::from_raw_parts(initrd.start as *const u8, initrd.end - initrd.start)
+        },
+        None => match initramfs::builtin_initramfs() {
+            Some(data) => data,
+            None => return,
+        },
+    };
+    match initramfs::unpack(&get_filesystem(0).root_dir(), data) {
+        Ok(count) => info!("unpack {} files from the initramfs", count),
+        Err(err) => log::error!("can't unpack the initramfs: {:?}", err),
+    }
+    // the pages of the initrd are free after unpacking.
+    if let Some(initrd) = initrd {
+        add_frame_map(
+            initrd.start / PAGE_SIZE * PAGE_SIZE,
+            ceil_div(initrd.end, PAGE_SIZE) * PAGE_SIZE,
+        );
+    }
+}
+
+/// Create a symbolic link in the directory, the target isn't checked.
+pub fn symlink(dir: &Arc<dyn INodeInterface>, name: &str, target: &str) -> VfsResult<()> {
+    if let Some(dir) = dir.downcast_ref::<RamDir>() {
+        dir.symlink(name, target)
+    } else if let Some(dir) = dir.downcast_ref::<Ext4Node>() {
+        dir.symlink(name, target)
+    } else if let Some(dir) = dir.downcast_ref::<OverlayNode>() {
+        dir.symlink(name, target)
+    } else if let Some(dir) = dir.downcast_ref::<P9Node>() {
+        dir.symlink(name, target)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Move the entry to the new directory at once, the entry which has the new name is replaced.
+/// NotSupported if the filesystem can't move it, the directories may be in different filesystems.
+pub fn rename(
+    old_dir: &Arc<dyn INodeInterface>,
+    old_name: &str,
+    new_dir: &Arc<dyn INodeInterface>,
+    new_name: &str,
+) -> VfsResult<()> {
+    if let (Some(old_dir), Some(new_dir)) = (
+        old_dir.downcast_ref::<RamDir>(),
+        new_dir.downcast_ref::<RamDir>(),
+    ) {
+        old_dir.rename(old_name, new_dir, new_name)
+    } else if let (Some(old_dir), Some(new_dir)) = (
+        old_dir.downcast_ref::<Ext4Node>(),
+        new_dir.downcast_ref::<Ext4Node>(),
+    ) {
+        old_dir.rename(old_name, new_dir, new_name)
+    } else if let (Some(old_dir), Some(new_dir)) = (
+        old_dir.downcast_ref::<FatDir>(),
+        new_dir.downcast_ref::<FatDir>(),
+    ) {
+        old_dir.rename(old_name, new_dir, new_name)
+    } else if let (Some(old_dir), Some(new_dir)) = (
+        old_dir.downcast_ref::<P9Node>(),
+        new_dir.downcast_ref::<P9Node>(),
+    ) {
+        old_dir.rename(old_name, new_dir, new_name)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Make the special file, the devfs only makes the nodes of the devices which exist.
+pub fn mknod(
+    dir: &Arc<dyn INodeInterface>,
+    name: &str,
+    file_type: StatMode,
+    rdev: u64,
+) -> VfsResult<()> {
+    if let Some(dir) = dir.downcast_ref::<RamDir>() {
+        dir.mknod(name, file_type, rdev)
+    } else if let Some(dir) = dir.downcast_ref::<DevDirContainer>() {
+        match file_type == StatMode::CHAR || file_type == StatMode::BLOCK {
+            true => dir.mknod(name, file_type, rdev),
+            false => Err(VfsError::NotSupported),
         }
+    } else {
+        Err(VfsError::NotSupported)
     }
 }
 
+/// The device opened by the device node made by mknod(2), None if the node isn't a device node.
+pub fn open_device(node: &Arc<dyn INodeInterface>) -> VfsResult<Option<Arc<dyn INodeInterface>>> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        return open_device(link.link_file());
+    }
+    match node.downcast_ref::<RamNode>().map(|x| x.device()) {
+        Some((file_type, rdev)) if file_type == StatMode::CHAR || file_type == StatMode::BLOCK => {
+            find_device(file_type, rdev)
+                .map(Some)
+                .ok_or(VfsError::FileNotFound)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Change the permission bits of the file, the symbolic link can't be changed.
+pub fn chmod(node: &Arc<dyn INodeInterface>, mode: u32) -> VfsResult<()> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        chmod(link.link_file(), mode)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        file.chmod(mode)
+    } else if let Some(dir) = node.downcast_ref::<RamDir>() {
+        dir.chmod(mode)
+    } else if let Some(node) = node.downcast_ref::<RamNode>() {
+        node.chmod(mode)
+    } else if let Some(node) = node.downcast_ref::<Ext4Node>() {
+        node.chmod(mode)
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.chmod(mode)
+    } else if let Some(node) = node.downcast_ref::<P9Node>() {
+        node.chmod(mode)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Change the owner and the group of the file, None keeps the current one.
+pub fn chown(node: &Arc<dyn INodeInterface>, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        chown(link.link_file(), uid, gid)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        file.chown(uid, gid)
+    } else if let Some(dir) = node.downcast_ref::<RamDir>() {
+        dir.chown(uid, gid)
+    } else if let Some(symlink) = node.downcast_ref::<RamSymlink>() {
+        symlink.chown(uid, gid)
+    } else if let Some(node) = node.downcast_ref::<RamNode>() {
+        node.chown(uid, gid)
+    } else if let Some(node) = node.downcast_ref::<Ext4Node>() {
+        node.chown(uid, gid)
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.chown(uid, gid)
+    } else if let Some(node) = node.downcast_ref::<P9Node>() {
+        node.chown(uid, gid)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Allocate the space of the range, the file is extended unless keep_size is set.
+pub fn fallocate(
+    node: &Arc<dyn INodeInterface>,
+    offset: usize,
+    len: usize,
+    keep_size: bool,
+) -> VfsResult<()> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        fallocate(link.link_file(), offset, len, keep_size)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        file.fallocate(offset, len, keep_size)
+    } else if let Some(file) = node.downcast_ref::<FatFile>() {
+        file.fallocate(offset, len, keep_size)
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.fallocate(offset, len, keep_size)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Free the space of the range, it is read as zero and the size isn't changed.
+pub fn punch_hole(node: &Arc<dyn INodeInterface>, offset: usize, len: usize) -> VfsResult<()> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        punch_hole(link.link_file(), offset, len)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        file.punch_hole(offset, len)
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.punch_hole(offset, len)
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// The offset of the first data at or after the offset, None if there isn't any.
+/// The files without holes are all data.
+pub fn seek_data(node: &Arc<dyn INodeInterface>, offset: usize) -> VfsResult<Option<usize>> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        seek_data(link.link_file(), offset)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        Ok(file.seek_data(offset))
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.seek_data(offset)
+    } else {
+        Ok((offset < node.metadata()?.size).then_some(offset))
+    }
+}
+
+/// The offset of the first hole at or after the offset, None if it is after the end.
+/// The end of the file is a hole.
+pub fn seek_hole(node: &Arc<dyn INodeInterface>, offset: usize) -> VfsResult<Option<usize>> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        seek_hole(link.link_file(), offset)
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        Ok(file.seek_hole(offset))
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.seek_hole(offset)
+    } else {
+        let size = node.metadata()?.size;
+        Ok((offset < size).then_some(size))
+    }
+}
+
+/// The extended attributes kept in memory, the others don't support them now.
+fn ram_xattrs(node: &Arc<dyn INodeInterface>) -> VfsResult<&RamXattrs> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        ram_xattrs(link.link_file())
+    } else if let Some(file) = node.downcast_ref::<RamFile>() {
+        Ok(file.xattrs())
+    } else if let Some(dir) = node.downcast_ref::<RamDir>() {
+        Ok(dir.xattrs())
+    } else if let Some(symlink) = node.downcast_ref::<RamSymlink>() {
+        Ok(symlink.xattrs())
+    } else if let Some(node) = node.downcast_ref::<RamNode>() {
+        Ok(node.xattrs())
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Get the extended attribute, FileNotFound if the file doesn't have it.
+pub fn getxattr(node: &Arc<dyn INodeInterface>, name: &str) -> VfsResult<Vec<u8>> {
+    match node.downcast_ref::<OverlayNode>() {
+        Some(node) => node.getxattr(name),
+        None => ram_xattrs(node)?.get(name),
+    }
+}
+
+pub fn setxattr(
+    node: &Arc<dyn INodeInterface>,
+    name: &str,
+    value: &[u8],
+    mode: XattrMode,
+) -> VfsResult<()> {
+    match node.downcast_ref::<OverlayNode>() {
+        Some(node) => node.setxattr(name, value, mode),
+        None => ram_xattrs(node)?.set(name, value, mode),
+    }
+}
+
+/// The names of the extended attributes of the file.
+pub fn listxattr(node: &Arc<dyn INodeInterface>) -> VfsResult<Vec<String>> {
+    match node.downcast_ref::<OverlayNode>() {
+        Some(node) => node.listxattr(),
+        None => Ok(ram_xattrs(node)?.list()),
+    }
+}
+
+pub fn removexattr(node: &Arc<dyn INodeInterface>, name: &str) -> VfsResult<()> {
+    match node.downcast_ref::<OverlayNode>() {
+        Some(node) => node.removexattr(name),
+        None => ram_xattrs(node)?.remove(name),
+    }
+}
+
+/// The creation time of the file, NotSupported if the filesystem doesn't keep it.
+pub fn birth_time(node: &Arc<dyn INodeInterface>) -> VfsResult<TimeSpec> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        birth_time(link.link_file())
+    } else if let Some(file) = node.downcast_ref::<FatFile>() {
+        file.birth_time()
+    } else if let Some(dir) = node.downcast_ref::<FatDir>() {
+        dir.birth_time()
+    } else if let Some(node) = node.downcast_ref::<Ext4Node>() {
+        node.birth_time()
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.birth_time()
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// The attributes like immutable and append-only, in the bits of statx(2).
+pub fn attributes(node: &Arc<dyn INodeInterface>) -> VfsResult<u64> {
+    if let Some(link) = node.downcast_ref::<RamLink>() {
+        attributes(link.link_file())
+    } else if let Some(node) = node.downcast_ref::<Ext4Node>() {
+        node.attributes()
+    } else if let Some(node) = node.downcast_ref::<OverlayNode>() {
+        node.attributes()
+    } else {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Write back all the filesystems and the cached blocks to the devices.
+pub fn sync() {
+    page_cache::sync_all();
+    flush_device_fs();
+    devices::block_cache::sync_all();
+}
+
 pub fn get_filesystem(id: usize) -> &'static Arc<dyn FileSystem> {
     &FILESYSTEMS[id]
 }
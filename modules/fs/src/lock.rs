@@ -0,0 +1,208 @@
+//! The advisory locks of the files, they are used by flock(2) and fcntl(2).
+//! The locks don't stop reading or writing, they only conflict with each other.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use sync::Mutex;
+
+/// The file is identified by the device number and the inode number.
+pub type LockKey = (u64, u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    Read,
+    Write,
+}
+
+/// The flock locks and the record locks are independent of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockClass {
+    Flock,
+    Record,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockOwner {
+    /// The flock locks and the OFD locks belong to the open file.
+    File(usize),
+    /// The traditional record locks belong to the process.
+    Process(usize),
+}
+
+/// Why the lock isn't added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+    /// It conflicts with the locks of others, the waiter is queued if it is given.
+    Blocking,
+    /// The owners of the conflicting locks wait for the locks of this owner.
+    Deadlock,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FileLock {
+    pub class: LockClass,
+    pub owner: LockOwner,
+    pub ltype: LockType,
+    /// The locked range [start, end), end is u64::MAX if it locks to the end of the file.
+    pub start: u64,
+    pub end: u64,
+}
+
+impl FileLock {
+    #[inline]
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    #[inline]
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.class == other.class
+            && self.owner != other.owner
+            && (self.ltype == LockType::Write || other.ltype == LockType::Write)
+            && self.overlaps(other.start, other.end)
+    }
+}
+
+/// The locks of the file and the tasks waiting for them.
+#[derive(Default)]
+struct LockList {
+    locks: Vec<FileLock>,
+    /// The id of the waiter and the lock it waits for, it is removed from here to wake it up.
+    waiters: Vec<(usize, FileLock)>,
+}
+
+static LOCK_TABLE: Mutex<BTreeMap<LockKey, LockList>> = Mutex::new(BTreeMap::new());
+
+/// Remove the range of the owner from the locks, the lock across the range is split.
+fn remove_range(
+    locks: &mut Vec<FileLock>,
+    class: LockClass,
+    owner: LockOwner,
+    start: u64,
+    end: u64,
+) {
+    let mut rest = Vec::new();
+    locks.retain(|x| {
+        if x.class != class || x.owner != owner || !x.overlaps(start, end) {
+            return true;
+        }
+        if x.start < start {
+            rest.push(FileLock { end: start, ..*x });
+        }
+        if x.end > end {
+            rest.push(FileLock { start: end, ..*x });
+        }
+        false
+    });
+    locks.extend(rest);
+}
+
+/// Whether the owner of the lock waits for itself, the owners of the conflicting locks wait for
+/// the locks of it through the other waiters.
+fn would_deadlock(table: &BTreeMap<LockKey, LockList>, key: LockKey, lock: &FileLock) -> bool {
+    let mut pending: Vec<LockOwner> = table[&key]
+        .locks
+        .iter()
+        .filter(|x| x.conflicts(lock))
+        .map(|x| x.owner)
+        .collect();
+    let mut visited = Vec::new();
+    while let Some(owner) = pending.pop() {
+        if owner == lock.owner {
+            return true;
+        }
+        if visited.contains(&owner) {
+            continue;
+        }
+        visited.push(owner);
+        for list in table.values() {
+            for (_, wait) in list.waiters.iter().filter(|(_, x)| x.owner == owner) {
+                pending.extend(
+                    list.locks
+                        .iter()
+                        .filter(|x| x.conflicts(wait))
+                        .map(|x| x.owner),
+                );
+            }
+        }
+    }
+    false
+}
+
+/// Find the lock which stops the new lock.
+pub fn test_lock(key: LockKey, lock: &FileLock) -> Option<FileLock> {
+    LOCK_TABLE
+        .lock()
+        .get(&key)?
+        .locks
+        .iter()
+        .find(|x| x.conflicts(lock))
+        .cloned()
+}
+
+/// Add the lock, the old locks of the owner in the range are replaced.
+/// The waiter is queued if it conflicts with others, it is woken up when the locks are released.
+pub fn try_lock(key: LockKey, lock: FileLock, waiter: Option<usize>) -> Result<(), LockError> {
+    let mut table = LOCK_TABLE.lock();
+    let conflicts = table
+        .get(&key)
+        .is_some_and(|x| x.locks.iter().any(|x| x.conflicts(&lock)));
+    if !conflicts {
+        let locks = &mut table.entry(key).or_default().locks;
+        remove_range(locks, lock.class, lock.owner, lock.start, lock.end);
+        locks.push(lock);
+        return Ok(());
+    }
+    let Some(waiter) = waiter else {
+        return Err(LockError::Blocking);
+    };
+    // only the record locks of the processes are checked for the deadlock, like Linux.
+    if matches!(lock.owner, LockOwner::Process(_)) && would_deadlock(&table, key, &lock) {
+        return Err(LockError::Deadlock);
+    }
+    table.get_mut(&key).unwrap().waiters.push((waiter, lock));
+    Err(LockError::Blocking)
+}
+
+/// Whether the waiter is still in the queue of the file.
+pub fn is_waiting(key: LockKey, waiter: usize) -> bool {
+    LOCK_TABLE
+        .lock()
+        .get(&key)
+        .is_some_and(|x| x.waiters.iter().any(|(id, _)| *id == waiter))
+}
+
+/// Remove the waiter from the queue, it is interrupted or exits.
+pub fn cancel_wait(key: LockKey, waiter: usize) {
+    let mut table = LOCK_TABLE.lock();
+    if let Some(list) = table.get_mut(&key) {
+        list.waiters.retain(|(id, _)| *id != waiter);
+        if list.locks.is_empty() && list.waiters.is_empty() {
+            table.remove(&key);
+        }
+    }
+}
+
+/// Release the locks of the owner in the range, the waiters of the file try again.
+pub fn unlock(key: LockKey, class: LockClass, owner: LockOwner, start: u64, end: u64) {
+    let mut table = LOCK_TABLE.lock();
+    if let Some(list) = table.get_mut(&key) {
+        remove_range(&mut list.locks, class, owner, start, end);
+        list.waiters.clear();
+        if list.locks.is_empty() {
+            table.remove(&key);
+        }
+    }
+}
+
+/// Release all the locks of the owner, it is called when the file is closed or the process exits.
+pub fn release_owner(owner: LockOwner) {
+    let mut table = LOCK_TABLE.lock();
+    table.values_mut().for_each(|list| {
+        let count = list.locks.len();
+        list.locks.retain(|x| x.owner != owner);
+        if list.locks.len() != count {
+            list.waiters.clear();
+        }
+    });
+    table.retain(|_, x| !x.locks.is_empty() || !x.waiters.is_empty());
+}
@@ -0,0 +1,470 @@
+//! The mount table, it is updated by mount(2) and umount2(2) and rendered by /proc/mounts.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use bitflags::bitflags;
+use devices::{
+    get_blk_devices, makedev,
+    partition::{blk_device_minor, blk_device_name, BLK_DEVICE_MAJOR},
+};
+use procfs::ProcFS;
+use ramfs::{RamDir, RamFs};
+use sync::Mutex;
+use sysfs::SysFS;
+use vfscore::{FileSystem, FileType, INodeInterface, OpenFlags, VfsError, VfsResult};
+
+use crate::{
+    build_devfs,
+    dentry::{dentry_open, dentry_root, render_dentry_state, DentryNode},
+    overlay::OverlayFs,
+    p9::P9FileSystem,
+    page_cache,
+    probe::{close_device_fs, device_fs_type, flush_device, open_device_fs, FS_PROBES},
+};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MountFlags: usize {
+        const MS_RDONLY = 1;
+        const MS_NOSUID = 1 << 1;
+        const MS_NODEV = 1 << 2;
+        const MS_NOEXEC = 1 << 3;
+        const MS_SYNCHRONOUS = 1 << 4;
+        const MS_REMOUNT = 1 << 5;
+        const MS_NOATIME = 1 << 10;
+        const MS_BIND = 1 << 12;
+        const MS_REC = 1 << 14;
+    }
+}
+
+/// The flags kept by the mount point, the others are the actions of mount(2).
+const MOUNT_OPTIONS: MountFlags = MountFlags::MS_RDONLY
+    .union(MountFlags::MS_NOSUID)
+    .union(MountFlags::MS_NODEV)
+    .union(MountFlags::MS_NOEXEC)
+    .union(MountFlags::MS_SYNCHRONOUS)
+    .union(MountFlags::MS_NOATIME);
+
+pub struct MountPoint {
+    /// The unique id reported by statx(2), it isn't reused after umount.
+    pub id: u32,
+    pub source: String,
+    pub target: String,
+    pub fstype: String,
+    pub flags: MountFlags,
+    /// The device number reported by stat(2) for the files in it.
+    pub dev: u64,
+    pub root: Arc<dyn INodeInterface>,
+    /// The filesystem is dropped with the last mount of it, None for the ones of the boot.
+    fs: Option<Arc<dyn FileSystem>>,
+    /// Shared by the dentries in the mount point, the opened files keep them.
+    users: Arc<()>,
+    /// The directories used by the overlay, the filesystems of them are kept busy.
+    #[allow(dead_code)]
+    layers: Vec<Arc<DentryNode>>,
+}
+
+/// The mount points in the mount order, the later one covers the former one.
+static MOUNT_TABLE: Mutex<Vec<MountPoint>> = Mutex::new(Vec::new());
+
+/// Check whether the absolute path is in the mount point.
+fn is_under(path: &str, target: &str) -> bool {
+    target == "/"
+        || path
+            .strip_prefix(target)
+            .is_some_and(|x| x.is_empty() || x.starts_with('/'))
+}
+
+/// Find the mount point which contains the path.
+fn with_mount<T>(path: &str, f: impl FnOnce(&MountPoint) -> T) -> Option<T> {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .filter(|x| is_under(path, &x.target))
+        .max_by_key(|x| x.target.len())
+        .map(f)
+}
+
+/// The flags of the mount point which contains the path.
+pub fn mount_flags(path: &str) -> MountFlags {
+    with_mount(path, |x| x.flags).unwrap_or(MountFlags::empty())
+}
+
+/// The device number of the mount point which contains the path.
+pub fn mount_dev(path: &str) -> u64 {
+    with_mount(path, |x| x.dev).unwrap_or(0)
+}
+
+/// The id of the mount point which contains the path, and whether the path is its root.
+pub fn mount_id(path: &str) -> (u32, bool) {
+    with_mount(path, |x| (x.id, x.target == path)).unwrap_or((0, false))
+}
+
+/// Find the block device by its device file, like /dev/sda1.
+fn find_blk_device(source: &str) -> Option<usize> {
+    let name = source.strip_prefix("/dev/")?;
+    (0..get_blk_devices().len()).find(|x| blk_device_name(*x) == name)
+}
+
+/// The device number of the new filesystem, the virtual one gets an anonymous number.
+pub(crate) fn source_dev(source: &str) -> u64 {
+    static NEXT_ANON_MINOR: AtomicU32 = AtomicU32::new(1);
+    match find_blk_device(source) {
+        Some(device_id) => makedev(BLK_DEVICE_MAJOR, blk_device_minor(device_id)),
+        None => makedev(0, NEXT_ANON_MINOR.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+/// The filesystems of the boot are never dropped, the root directory needs the static reference.
+pub(crate) fn leak(fs: Arc<dyn FileSystem>) -> &'static Arc<dyn FileSystem> {
+    Box::leak(Box::new(fs))
+}
+
+/// The root directory of the filesystem owned by the mount point.
+fn owned_root(fs: &Arc<dyn FileSystem>) -> Arc<dyn INodeInterface> {
+    // SAFETY: umount refuses the busy mount point, the nodes are dropped before the filesystem.
+    let fs: &'static dyn FileSystem = unsafe { &*Arc::as_ptr(fs) };
+    fs.root_dir()
+}
+
+/// Open the directory in the dentry tree for the options of mount(2).
+fn open_dir(path: &str) -> VfsResult<Arc<DentryNode>> {
+    let dentry = dentry_open(dentry_root(), path, OpenFlags::NONE)?;
+    if dentry.node.metadata()?.file_type != FileType::Directory {
+        return Err(VfsError::NotDir);
+    }
+    Ok(dentry)
+}
+
+/// Find the option like key=value in the data of mount(2).
+fn mount_option<'a>(data: &'a str, key: &str) -> Option<&'a str> {
+    data.split(',').find_map(|x| x.strip_prefix(key))
+}
+
+/// Parse the size option of tmpfs, the suffix k, m and g are supported.
+fn parse_size(value: &str) -> VfsResult<usize> {
+    let (number, shift) = match value.as_bytes().last() {
+        Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+        Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+        Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|x| x.checked_mul(1 << shift))
+        .ok_or(VfsError::InvalidInput)
+}
+
+fn open_tmpfs(data: &str) -> VfsResult<Arc<dyn FileSystem>> {
+    let size = match mount_option(data, "size=") {
+        Some(size) => parse_size(size)?,
+        None => 0,
+    };
+    Ok(RamFs::new_with_size(size))
+}
+
+/// Create the overlay filesystem by the options lowerdir=<dir>,upperdir=<dir>,
+/// return it and the dentries of the layers.
+/// The upper directory is a new tmpfs if it isn't given.
+fn open_overlay(data: &str) -> VfsResult<(Arc<dyn FileSystem>, Vec<Arc<DentryNode>>)> {
+    let option = |key: &str| mount_option(data, key);
+    let lowerdir = option("lowerdir=").ok_or(VfsError::InvalidInput)?;
+    // only one lower layer is supported.
+    if lowerdir.contains(':') {
+        return Err(VfsError::InvalidInput);
+    }
+    let mut layers = vec![open_dir(lowerdir)?];
+    let upper = match option("upperdir=") {
+        Some(upperdir) => {
+            layers.push(open_dir(upperdir)?);
+            layers[1].node.clone()
+        }
+        // the nodes of ramfs don't borrow the filesystem, it can be dropped.
+        None => owned_root(&RamFs::new()),
+    };
+    let lower = layers[0].node.clone();
+    Ok((OverlayFs::new(upper, lower), layers))
+}
+
+/// The filesystems which don't need a block device.
+const NODEV_FILESYSTEMS: [&str; 7] = [
+    "tmpfs", "ramfs", "proc", "sysfs", "devtmpfs", "overlay", "9p",
+];
+
+/// Create the filesystem and add it to the mount table.
+fn mount_fs(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+    data: &str,
+    dev: u64,
+) -> VfsResult<()> {
+    let mut layers = Vec::new();
+    let mut fstype = fstype;
+    let fs = match fstype {
+        "tmpfs" | "ramfs" => open_tmpfs(data)?,
+        "proc" => ProcFS::new(dev, render_mounts, render_dentry_state, render_filesystems),
+        "sysfs" => SysFS::new(),
+        "devtmpfs" => build_devfs(),
+        "overlay" => {
+            let (fs, dirs) = open_overlay(data)?;
+            layers = dirs;
+            fs
+        }
+        // the source is the mount tag of the device.
+        "9p" => {
+            let aname = mount_option(data, "aname=").unwrap_or("");
+            P9FileSystem::new(source, aname)?
+        }
+        _ => {
+            let device_id = find_blk_device(source).ok_or(VfsError::FileNotFound)?;
+            let fs = open_device_fs(device_id, Some(fstype).filter(|x| !x.is_empty()))?;
+            fstype = device_fs_type(device_id).unwrap_or(fstype);
+            fs
+        }
+    };
+    insert_mount(MountPoint {
+        id: 0,
+        source: String::from(source),
+        target: String::from(target),
+        fstype: String::from(fstype),
+        flags: flags & MOUNT_OPTIONS,
+        dev,
+        root: owned_root(&fs),
+        fs: Some(fs),
+        users: Arc::new(()),
+        layers,
+    })
+}
+
+/// Add the mounted root directory to the dentry tree and the mount table.
+pub(crate) fn add_mount(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+    dev: u64,
+    root: Arc<dyn INodeInterface>,
+) -> VfsResult<()> {
+    insert_mount(MountPoint {
+        id: 0,
+        source: String::from(source),
+        target: String::from(target),
+        fstype: String::from(fstype),
+        flags: flags & MOUNT_OPTIONS,
+        dev,
+        root,
+        fs: None,
+        users: Arc::new(()),
+        layers: Vec::new(),
+    })
+}
+
+/// Give the mount point a new id and add it, the ids start from 1 like Linux.
+fn insert_mount(mut mount_point: MountPoint) -> VfsResult<()> {
+    static NEXT_MOUNT_ID: AtomicU32 = AtomicU32::new(1);
+    if mount_point.target != "/" {
+        DentryNode::mount(
+            mount_point.target.clone(),
+            mount_point.root.clone(),
+            mount_point.users.clone(),
+        )?;
+    }
+    mount_point.id = NEXT_MOUNT_ID.fetch_add(1, Ordering::Relaxed);
+    MOUNT_TABLE.lock().push(mount_point);
+    Ok(())
+}
+
+/// Mount the filesystem to the target directory.
+/// source: the block device, the directory to bind, or anything for the virtual filesystems.
+/// data: the options of the filesystem, like the layers of the overlay filesystem.
+pub fn mount(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+    data: &str,
+) -> VfsResult<()> {
+    let target_dentry = dentry_open(dentry_root(), target, OpenFlags::NONE)?;
+    let target = target_dentry.path();
+
+    if flags.contains(MountFlags::MS_REMOUNT) {
+        let mut table = MOUNT_TABLE.lock();
+        let mount_point = table
+            .iter_mut()
+            .rev()
+            .find(|x| x.target == target)
+            .ok_or(VfsError::NoMountedPoint)?;
+        // the size of tmpfs can be changed by remounting.
+        if let Some(size) = mount_option(data, "size=") {
+            let root = mount_point.root.downcast_ref::<RamDir>();
+            let root = root.ok_or(VfsError::InvalidInput)?;
+            root.set_size(parse_size(size)?)?;
+        }
+        mount_point.flags = flags & MOUNT_OPTIONS;
+        return Ok(());
+    }
+
+    if flags.contains(MountFlags::MS_BIND) {
+        let dentry = dentry_open(dentry_root(), source, OpenFlags::NONE)?;
+        let path = dentry.path();
+        // the bind mount shares the files, so it shares the device number and the filesystem.
+        let (source, fstype, dev, fs) = with_mount(&path, |x| {
+            (x.source.clone(), x.fstype.clone(), x.dev, x.fs.clone())
+        })
+        .unwrap_or((path.clone(), String::from("none"), 0, None));
+        return insert_mount(MountPoint {
+            id: 0,
+            source,
+            target,
+            fstype,
+            flags: flags & MOUNT_OPTIONS,
+            dev,
+            root: dentry.node.clone(),
+            fs,
+            users: Arc::new(()),
+            layers: Vec::new(),
+        });
+    }
+
+    if target_dentry.node.metadata()?.file_type != FileType::Directory {
+        return Err(VfsError::NotDir);
+    }
+    let dev = source_dev(source);
+    mount_fs(source, &target, fstype, flags, data, dev)
+}
+
+/// Write back the filesystem mounted from the source.
+fn sync_source(source: &str) -> VfsResult<()> {
+    // the page caches aren't grouped by the filesystem.
+    page_cache::sync_all();
+    match find_blk_device(source) {
+        Some(device_id) => flush_device(device_id),
+        None => Ok(()),
+    }
+}
+
+/// Write back the filesystem which contains the path, it is used by syncfs(2).
+pub fn sync_mount(path: &str) -> VfsResult<()> {
+    let source = with_mount(path, |x| x.source.clone()).ok_or(VfsError::NoMountedPoint)?;
+    sync_source(&source)
+}
+
+/// Unmount the filesystem, the path can be the mount point or the source.
+/// The filesystem covered by it is visible again.
+/// The dirty data is written back first, the device may be removed after it.
+/// It is busy if the files in it are still opened, the filesystem is dropped with the last mount.
+pub fn umount(path: &str) -> VfsResult<()> {
+    let target = dentry_open(dentry_root(), path, OpenFlags::NONE)
+        .map(|x| x.path())
+        .unwrap_or(String::from(path));
+    let source = MOUNT_TABLE
+        .lock()
+        .iter()
+        .rev()
+        .find(|x| x.target == target || x.source == target)
+        .map(|x| x.source.clone());
+    if let Some(source) = source {
+        sync_source(&source)?;
+    }
+    let (target, users) = {
+        let table = MOUNT_TABLE.lock();
+        let index = table
+            .iter()
+            .rposition(|x| x.target == target || x.source == target)
+            .ok_or(VfsError::NoMountedPoint)?;
+        let target = table[index].target.clone();
+        // the root can't be unmounted, and the mount point is busy if others are mounted in it.
+        let busy = table[index + 1..]
+            .iter()
+            .any(|x| x.target != target && is_under(&x.target, &target));
+        if target == "/" || busy {
+            return Err(VfsError::InvalidInput);
+        }
+        (target, table[index].users.clone())
+    };
+    // the opened files keep the dentries, the ones out of the tree are found by the count.
+    // the mount table and this function hold it too.
+    let cached = DentryNode::cached_dentries(target.clone())?;
+    if cached.map_or(true, |x| Arc::strong_count(&users) > x + 2) {
+        return Err(VfsError::InvalidInput);
+    }
+
+    let mut table = MOUNT_TABLE.lock();
+    let index = table
+        .iter()
+        .position(|x| Arc::ptr_eq(&x.users, &users))
+        .ok_or(VfsError::NoMountedPoint)?;
+    // the device is closed when the last mount of it is gone, like the bind mounts.
+    let source = table[index].source.clone();
+    let device_unused = table.iter().filter(|x| x.source == source).count() == 1;
+    // the mapped files aren't in the dentry tree, they are found by the page cache.
+    if device_unused && page_cache::device_in_use(table[index].dev) {
+        return Err(VfsError::InvalidInput);
+    }
+    // the dentry tree doesn't use the mount table, drop the dentries before the filesystem.
+    DentryNode::unmount(target.clone())?;
+    let mount_point = table.remove(index);
+    let covered = table
+        .iter()
+        .rev()
+        .find(|x| x.target == target)
+        .map(|x| (x.root.clone(), x.users.clone()));
+    drop(table);
+    drop(mount_point);
+    if let Some(device_id) = find_blk_device(&source).filter(|_| device_unused) {
+        close_device_fs(device_id);
+    }
+
+    if let Some((root, users)) = covered {
+        DentryNode::mount(target, root, users)?;
+    }
+    Ok(())
+}
+
+/// Generate the content of /proc/filesystems.
+pub fn render_filesystems() -> String {
+    let mut text = String::new();
+    for name in NODEV_FILESYSTEMS {
+        text += "nodev\t";
+        text += name;
+        text += "\n";
+    }
+    for probe in FS_PROBES.lock().iter() {
+        for name in probe.names {
+            text += "\t";
+            text += name;
+            text += "\n";
+        }
+    }
+    text
+}
+
+/// Generate the content of /proc/mounts.
+pub fn render_mounts() -> String {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .map(|x| {
+            let mut options = String::from(match x.flags.contains(MountFlags::MS_RDONLY) {
+                true => "ro",
+                false => "rw",
+            });
+            for (flag, name) in [
+                (MountFlags::MS_NOSUID, ",nosuid"),
+                (MountFlags::MS_NODEV, ",nodev"),
+                (MountFlags::MS_NOEXEC, ",noexec"),
+                (MountFlags::MS_SYNCHRONOUS, ",sync"),
+                (MountFlags::MS_NOATIME, ",noatime"),
+            ] {
+                if x.flags.contains(flag) {
+                    options.push_str(name);
+                }
+            }
+            format!("{} {} {} {} 0 0\n", x.source, x.target, x.fstype, options)
+        })
+        .collect()
+}
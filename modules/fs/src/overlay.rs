@@ -0,0 +1,419 @@
+//! The overlay filesystem, a writable upper directory stacked over a read-only lower one.
+//! The lower files are copied up before they are changed, the lower files removed are hidden
+//! by the whiteouts in the upper directory, they are the empty files named .wh.<name>.
+
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use arch::PAGE_SIZE;
+use ramfs::XattrMode;
+use sync::Mutex;
+use vfscore::{
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, OpenFlags, PollEvent, Stat, StatFS,
+    TimeSpec, VfsError, VfsResult,
+};
+
+/// The prefix of the whiteouts, the files with it are hidden.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// The lower directory isn't merged if the upper directory contains it.
+const OPAQUE_NAME: &str = ".wh..wh..opq";
+
+fn whiteout(name: &str) -> String {
+    format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+fn is_dir(node: &Arc<dyn INodeInterface>) -> bool {
+    node.metadata()
+        .is_ok_and(|x| x.file_type == FileType::Directory)
+}
+
+pub struct OverlayFs {
+    root: Arc<OverlayNode>,
+}
+
+impl OverlayFs {
+    pub fn new(upper: Arc<dyn INodeInterface>, lower: Arc<dyn INodeInterface>) -> Arc<Self> {
+        Arc::new(Self {
+            root: OverlayNode::new(String::new(), None, Some(upper), Some(lower)),
+        })
+    }
+}
+
+impl FileSystem for OverlayFs {
+    fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
+        self.root.clone()
+    }
+
+    fn name(&self) -> &str {
+        "overlay"
+    }
+}
+
+pub struct OverlayNode {
+    name: String,
+    this: Weak<OverlayNode>,
+    parent: Option<Arc<OverlayNode>>,
+    /// The upper file, it is created when the lower file is copied up.
+    upper: Mutex<Option<Arc<dyn INodeInterface>>>,
+    lower: Option<Arc<dyn INodeInterface>>,
+    /// The opened children, the same file is always the same node while it is used.
+    children: Mutex<BTreeMap<String, Weak<OverlayNode>>>,
+}
+
+impl OverlayNode {
+    fn new(
+        name: String,
+        parent: Option<Arc<OverlayNode>>,
+        upper: Option<Arc<dyn INodeInterface>>,
+        lower: Option<Arc<dyn INodeInterface>>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            name,
+            this: this.clone(),
+            parent,
+            upper: Mutex::new(upper),
+            lower,
+            children: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    #[inline]
+    fn upper(&self) -> Option<Arc<dyn INodeInterface>> {
+        self.upper.lock().clone()
+    }
+
+    /// The upper and the lower directory, the entries of the overlay come from them.
+    pub fn layers(&self) -> Vec<Arc<dyn INodeInterface>> {
+        self.upper().into_iter().chain(self.lower.clone()).collect()
+    }
+
+    /// The visible file, the upper one covers the lower one.
+    #[inline]
+    fn node(&self) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.upper()
+            .or_else(|| self.lower.clone())
+            .ok_or(VfsError::FileNotFound)
+    }
+
+    fn add_child(
+        &self,
+        name: &str,
+        upper: Option<Arc<dyn INodeInterface>>,
+        lower: Option<Arc<dyn INodeInterface>>,
+    ) -> Arc<OverlayNode> {
+        let child = OverlayNode::new(String::from(name), self.this.upgrade(), upper, lower);
+        self.children
+            .lock()
+            .insert(String::from(name), Arc::downgrade(&child));
+        child
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<OverlayNode>> {
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::FileNotFound);
+        }
+        if let Some(child) = self.children.lock().get(name).and_then(Weak::upgrade) {
+            return Ok(child);
+        }
+        let upper_dir = self.upper();
+        let upper = upper_dir
+            .as_ref()
+            .and_then(|x| x.open(name, OpenFlags::NONE).ok());
+        // the upper file covers the lower one, only the directories are merged.
+        let hidden = upper.as_ref().is_some_and(|x| !is_dir(x))
+            || upper_dir.as_ref().is_some_and(|x| {
+                x.open(&whiteout(name), OpenFlags::NONE).is_ok()
+                    || x.open(OPAQUE_NAME, OpenFlags::NONE).is_ok()
+            });
+        let lower = match hidden {
+            true => None,
+            false => self
+                .lower
+                .as_ref()
+                .and_then(|x| x.open(name, OpenFlags::NONE).ok())
+                .filter(|x| upper.is_none() || is_dir(x)),
+        };
+        if upper.is_none() && lower.is_none() {
+            return Err(VfsError::FileNotFound);
+        }
+        Ok(self.add_child(name, upper, lower))
+    }
+
+    /// Copy the file to the upper directory, the parent directories are copied first.
+    fn copy_up(&self) -> VfsResult<Arc<dyn INodeInterface>> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        let dir = self
+            .parent
+            .as_ref()
+            .ok_or(VfsError::NotSupported)?
+            .copy_up()?;
+        let lower = self.lower.clone().ok_or(VfsError::FileNotFound)?;
+        let upper = match lower.metadata()?.file_type {
+            FileType::Directory => dir.mkdir(&self.name)?,
+            FileType::Link => {
+                crate::symlink(&dir, &self.name, &lower.resolve_link()?)?;
+                dir.open(&self.name, OpenFlags::NONE)?
+            }
+            _ => {
+                let file = dir.touch(&self.name)?;
+                let mut buffer = vec![0u8; PAGE_SIZE];
+                let mut offset = 0;
+                loop {
+                    let rsize = lower.readat(offset, &mut buffer)?;
+                    if rsize == 0 {
+                        break;
+                    }
+                    file.writeat(offset, &buffer[..rsize])?;
+                    offset += rsize;
+                }
+                file
+            }
+        };
+        // keep the mode and the owner if the upper filesystem supports them.
+        let mut stat = Stat::default();
+        if lower.stat(&mut stat).is_ok() {
+            crate::chmod(&upper, (stat.mode.bits() & 0o7777) as _).ok();
+            crate::chown(&upper, Some(stat.uid as _), Some(stat.gid as _)).ok();
+        }
+        for name in crate::listxattr(&lower).unwrap_or_default() {
+            if let Ok(value) = crate::getxattr(&lower, &name) {
+                crate::setxattr(&upper, &name, &value, XattrMode::Any).ok();
+            }
+        }
+        *self.upper.lock() = Some(upper.clone());
+        Ok(upper)
+    }
+
+    /// Get the upper directory to create the new file in it, the whiteout of the name is removed.
+    /// Return true if there was a whiteout, the lower file with the same name was removed.
+    fn prepare_create(&self, name: &str) -> VfsResult<(Arc<dyn INodeInterface>, bool)> {
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::InvalidInput);
+        }
+        if self.lookup(name).is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let dir = self.copy_up()?;
+        let whiteout = dir.remove(&whiteout(name)).is_ok();
+        Ok((dir, whiteout))
+    }
+
+    /// Remove the child, a whiteout is created if the lower file exists.
+    fn remove_child(&self, name: &str, child: &OverlayNode) -> VfsResult<()> {
+        if child.upper().is_some() {
+            let dir = self.upper().ok_or(VfsError::FileNotFound)?;
+            match child.node().is_ok_and(|x| is_dir(&x)) {
+                true => dir.rmdir(name)?,
+                false => dir.remove(name)?,
+            }
+        }
+        if child.lower.is_some() {
+            self.copy_up()?.touch(&whiteout(name))?;
+        }
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    /// Create a symbolic link in the upper directory.
+    pub fn symlink(&self, name: &str, target: &str) -> VfsResult<()> {
+        let (dir, _) = self.prepare_create(name)?;
+        crate::symlink(&dir, name, target)
+    }
+
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        crate::chmod(&self.copy_up()?, mode)
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        crate::chown(&self.copy_up()?, uid, gid)
+    }
+
+    pub fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> VfsResult<()> {
+        crate::fallocate(&self.copy_up()?, offset, len, keep_size)
+    }
+
+    pub fn punch_hole(&self, offset: usize, len: usize) -> VfsResult<()> {
+        crate::punch_hole(&self.copy_up()?, offset, len)
+    }
+
+    pub fn seek_data(&self, offset: usize) -> VfsResult<Option<usize>> {
+        crate::seek_data(&self.node()?, offset)
+    }
+
+    pub fn seek_hole(&self, offset: usize) -> VfsResult<Option<usize>> {
+        crate::seek_hole(&self.node()?, offset)
+    }
+
+    pub fn getxattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        crate::getxattr(&self.node()?, name)
+    }
+
+    pub fn setxattr(&self, name: &str, value: &[u8], mode: XattrMode) -> VfsResult<()> {
+        crate::setxattr(&self.copy_up()?, name, value, mode)
+    }
+
+    pub fn listxattr(&self) -> VfsResult<Vec<String>> {
+        crate::listxattr(&self.node()?)
+    }
+
+    pub fn removexattr(&self, name: &str) -> VfsResult<()> {
+        crate::removexattr(&self.copy_up()?, name)
+    }
+
+    pub fn birth_time(&self) -> VfsResult<TimeSpec> {
+        crate::birth_time(&self.node()?)
+    }
+
+    pub fn attributes(&self) -> VfsResult<u64> {
+        crate::attributes(&self.node()?)
+    }
+}
+
+impl INodeInterface for OverlayNode {
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        Ok(self.lookup(name)?)
+    }
+
+    fn touch(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        let (dir, _) = self.prepare_create(name)?;
+        let upper = dir.touch(name)?;
+        Ok(self.add_child(name, Some(upper), None))
+    }
+
+    fn mkdir(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        let (dir, whiteout) = self.prepare_create(name)?;
+        let upper = dir.mkdir(name)?;
+        // the removed lower directory isn't merged into the new one.
+        if whiteout {
+            upper.touch(OPAQUE_NAME)?;
+        }
+        Ok(self.add_child(name, Some(upper), None))
+    }
+
+    fn rmdir(&self, name: &str) -> VfsResult<()> {
+        let child = self.lookup(name)?;
+        if !child.node().is_ok_and(|x| is_dir(&x)) {
+            return Err(VfsError::NotDir);
+        }
+        if !child.read_dir()?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        // the whiteouts are the only files left in the upper directory.
+        if let Some(upper) = child.upper() {
+            for entry in upper.read_dir()? {
+                upper.remove(&entry.filename)?;
+            }
+        }
+        self.remove_child(name, &child)
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        let mut entries = match self.upper() {
+            Some(upper) => upper.read_dir()?,
+            None => Vec::new(),
+        };
+        let opaque = entries.iter().any(|x| x.filename == OPAQUE_NAME);
+        let whiteouts: Vec<String> = entries
+            .iter()
+            .filter_map(|x| x.filename.strip_prefix(WHITEOUT_PREFIX))
+            .map(String::from)
+            .collect();
+        entries.retain(|x| !x.filename.starts_with(WHITEOUT_PREFIX));
+        if let Some(lower) = &self.lower
+            && !opaque
+        {
+            for entry in lower.read_dir()? {
+                if !whiteouts.contains(&entry.filename)
+                    && !entries.iter().any(|x| x.filename == entry.filename)
+                {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn remove(&self, name: &str) -> VfsResult<()> {
+        let child = self.lookup(name)?;
+        if child.node().is_ok_and(|x| is_dir(&x)) {
+            return self.rmdir(name);
+        }
+        self.remove_child(name, &child)
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()> {
+        self.remove(name)
+    }
+
+    fn link(&self, name: &str, src: Arc<dyn INodeInterface>) -> VfsResult<()> {
+        let src = src
+            .downcast_arc::<OverlayNode>()
+            .map_err(|_| VfsError::NotSupported)?;
+        let upper = src.copy_up()?;
+        let (dir, _) = self.prepare_create(name)?;
+        dir.link(name, upper)
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        self.node()?.readat(offset, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        self.copy_up()?.writeat(offset, buffer)
+    }
+
+    fn truncate(&self, size: usize) -> VfsResult<()> {
+        self.copy_up()?.truncate(size)
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        let node = self.node()?;
+        let metadata = node.metadata()?;
+        let childrens = match metadata.file_type {
+            FileType::Directory => self.read_dir()?.len(),
+            _ => metadata.childrens,
+        };
+        Ok(Metadata {
+            filename: &self.name,
+            inode: metadata.inode,
+            file_type: metadata.file_type,
+            size: metadata.size,
+            childrens,
+        })
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        self.node()?.stat(stat)?;
+        // the copied up file keeps the inode number of the lower file.
+        if let Some(lower) = &self.lower
+            && self.upper().is_some()
+        {
+            let mut lower_stat = Stat::default();
+            if lower.stat(&mut lower_stat).is_ok() {
+                stat.ino = lower_stat.ino;
+            }
+        }
+        Ok(())
+    }
+
+    fn statfs(&self, statfs: &mut StatFS) -> VfsResult<()> {
+        self.node()?.statfs(statfs)
+    }
+
+    fn utimes(&self, times: &mut [TimeSpec]) -> VfsResult<()> {
+        self.copy_up()?.utimes(times)
+    }
+
+    fn resolve_link(&self) -> VfsResult<String> {
+        self.node()?.resolve_link()
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        self.node()?.poll(events)
+    }
+}
@@ -0,0 +1,516 @@
+//! The messages of 9P2000.L and the client which sends them through the transport.
+//! Only one request is sent at a time, so all the messages use the same tag.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use devices::device::P9Driver;
+use vfscore::{TimeSpec, VfsError, VfsResult};
+
+const P9_RLERROR: u8 = 7;
+const P9_TSTATFS: u8 = 8;
+const P9_TLOPEN: u8 = 12;
+const P9_TLCREATE: u8 = 14;
+const P9_TSYMLINK: u8 = 16;
+const P9_TREADLINK: u8 = 22;
+const P9_TGETATTR: u8 = 24;
+const P9_TSETATTR: u8 = 26;
+const P9_TREADDIR: u8 = 40;
+const P9_TFSYNC: u8 = 50;
+const P9_TLINK: u8 = 70;
+const P9_TMKDIR: u8 = 72;
+const P9_TRENAMEAT: u8 = 74;
+const P9_TUNLINKAT: u8 = 76;
+const P9_TVERSION: u8 = 100;
+const P9_TATTACH: u8 = 104;
+const P9_TWALK: u8 = 110;
+const P9_TREAD: u8 = 116;
+const P9_TWRITE: u8 = 118;
+const P9_TCLUNK: u8 = 120;
+
+const P9_VERSION: &str = "9P2000.L";
+const P9_NOTAG: u16 = !0;
+const P9_NOFID: u32 = !0;
+const P9_TAG: u16 = 1;
+/// The size of the header, size[4] type[1] tag[2].
+const P9_HEADER_SIZE: usize = 7;
+/// The max size of the message, the reads and the writes are split by it.
+const P9_MSIZE: u32 = 0x10000;
+/// The size of the response without the data, like Rgetattr.
+const P9_SMALL_SIZE: usize = 0x1000;
+/// The max number of the names in a Twalk.
+const P9_MAXWELEM: usize = 16;
+
+pub const P9_QTDIR: u8 = 0x80;
+pub const P9_QTSYMLINK: u8 = 0x02;
+
+const P9_GETATTR_BASIC: u64 = 0x7ff;
+
+pub const P9_SETATTR_MODE: u32 = 0x1;
+pub const P9_SETATTR_UID: u32 = 0x2;
+pub const P9_SETATTR_GID: u32 = 0x4;
+pub const P9_SETATTR_SIZE: u32 = 0x8;
+pub const P9_SETATTR_ATIME: u32 = 0x10;
+pub const P9_SETATTR_MTIME: u32 = 0x20;
+pub const P9_SETATTR_ATIME_SET: u32 = 0x80;
+pub const P9_SETATTR_MTIME_SET: u32 = 0x100;
+
+pub const P9_O_RDONLY: u32 = 0;
+pub const P9_O_RDWR: u32 = 2;
+pub const P9_O_CREAT: u32 = 0o100;
+pub const P9_O_EXCL: u32 = 0o200;
+
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Qid {
+    pub ty: u8,
+    pub path: u64,
+}
+
+#[derive(Default)]
+pub struct Attr {
+    pub qid: Qid,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub rdev: u64,
+    pub size: u64,
+    pub blksize: u64,
+    pub blocks: u64,
+    pub atime: TimeSpec,
+    pub mtime: TimeSpec,
+    pub ctime: TimeSpec,
+}
+
+#[derive(Default)]
+pub struct SetAttr {
+    pub valid: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: TimeSpec,
+    pub mtime: TimeSpec,
+}
+
+#[derive(Default)]
+pub struct P9StatFS {
+    pub ftype: u32,
+    pub bsize: u32,
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub fsid: u64,
+    pub namelen: u32,
+}
+
+pub struct P9DirEntry {
+    pub qid: Qid,
+    pub offset: u64,
+    pub ty: u8,
+    pub name: String,
+}
+
+/// The encoder of the request.
+struct Request(Vec<u8>);
+
+impl Request {
+    fn new(ty: u8, tag: u16) -> Self {
+        let mut req = Self(Vec::new());
+        req.u32(0).u8(ty).u16(tag);
+        req
+    }
+
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(&mut self, value: u64) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn str(&mut self, value: &str) -> &mut Self {
+        self.u16(value.len() as _);
+        self.0.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn finish(&mut self) -> &[u8] {
+        let size = self.0.len() as u32;
+        self.0[..4].copy_from_slice(&size.to_le_bytes());
+        &self.0
+    }
+}
+
+/// The decoder of the response.
+struct Response<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Response<'a> {
+    fn bytes(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        let data = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(VfsError::InvalidData)?;
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> VfsResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> VfsResult<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> VfsResult<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> VfsResult<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> VfsResult<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn qid(&mut self) -> VfsResult<Qid> {
+        let ty = self.u8()?;
+        // the version of the file isn't used.
+        self.u32()?;
+        Ok(Qid {
+            ty,
+            path: self.u64()?,
+        })
+    }
+
+    fn time(&mut self) -> VfsResult<TimeSpec> {
+        Ok(TimeSpec {
+            sec: self.u64()? as _,
+            nsec: self.u64()? as _,
+        })
+    }
+}
+
+/// The errno of Linux in Rlerror -> the error of the vfs.
+fn from_errno(errno: u32) -> VfsError {
+    match errno {
+        1 | 13 | 95 => VfsError::NotSupported,
+        2 => VfsError::FileNotFound,
+        17 => VfsError::AlreadyExists,
+        20 => VfsError::NotDir,
+        21 => VfsError::NotFile,
+        22 => VfsError::InvalidInput,
+        28 => VfsError::StorageFull,
+        30 => VfsError::NotWriteable,
+        39 => VfsError::DirectoryNotEmpty,
+        40 => VfsError::NotLinkFile,
+        _ => VfsError::Io,
+    }
+}
+
+pub struct P9Client {
+    device: Arc<dyn P9Driver>,
+    msize: u32,
+    next_fid: AtomicU32,
+}
+
+impl P9Client {
+    /// Negotiate the version and the message size with the server.
+    pub fn new(device: Arc<dyn P9Driver>) -> VfsResult<Arc<Self>> {
+        let mut client = Self {
+            device,
+            msize: P9_MSIZE,
+            next_fid: AtomicU32::new(0),
+        };
+        let mut req = Request::new(P9_TVERSION, P9_NOTAG);
+        req.u32(P9_MSIZE).str(P9_VERSION);
+        let msize = client.rpc(&mut req, P9_SMALL_SIZE, |resp| {
+            let msize = resp.u32()?;
+            match resp.str()? == P9_VERSION {
+                true => Ok(msize),
+                false => Err(VfsError::NotSupported),
+            }
+        })?;
+        client.msize = msize.min(P9_MSIZE);
+        Ok(Arc::new(client))
+    }
+
+    pub fn alloc_fid(&self) -> u32 {
+        self.next_fid.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Send the request and decode the response, the Rlerror is returned as the error.
+    fn rpc<T>(
+        &self,
+        req: &mut Request,
+        resp_size: usize,
+        decode: impl FnOnce(&mut Response) -> VfsResult<T>,
+    ) -> VfsResult<T> {
+        let ty = req.0[4];
+        let mut buffer = vec![0u8; resp_size.min(self.msize as usize)];
+        let len = self.device.request(req.finish(), &mut buffer);
+        let mut resp = Response {
+            buf: &buffer[..len.min(buffer.len())],
+            pos: 0,
+        };
+        let size = resp.u32()? as usize;
+        let rtype = resp.u8()?;
+        resp.u16()?;
+        resp.buf = resp.buf.get(..size).ok_or(VfsError::InvalidData)?;
+        match rtype {
+            P9_RLERROR => Err(from_errno(resp.u32()?)),
+            _ if rtype == ty + 1 => decode(&mut resp),
+            _ => Err(VfsError::InvalidData),
+        }
+    }
+
+    pub fn attach(&self, fid: u32, aname: &str) -> VfsResult<Qid> {
+        let mut req = Request::new(P9_TATTACH, P9_TAG);
+        req.u32(fid).u32(P9_NOFID).str("root").str(aname).u32(0);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.qid())
+    }
+
+    /// Walk to the file from the fid, the newfid refers to it.
+    /// The newfid is a copy of the fid if there isn't any name.
+    pub fn walk(&self, fid: u32, newfid: u32, names: &[&str]) -> VfsResult<Qid> {
+        let mut qid = Qid::default();
+        let mut from = fid;
+        let mut chunks = names.chunks(P9_MAXWELEM).peekable();
+        // walk nothing to clone the fid.
+        if chunks.peek().is_none() {
+            let mut req = Request::new(P9_TWALK, P9_TAG);
+            req.u32(fid).u32(newfid).u16(0);
+            return self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(Qid::default()));
+        }
+        for chunk in chunks {
+            let mut req = Request::new(P9_TWALK, P9_TAG);
+            req.u32(from).u32(newfid).u16(chunk.len() as _);
+            chunk.iter().for_each(|name| {
+                req.str(name);
+            });
+            let walked = self.rpc(&mut req, P9_SMALL_SIZE, |resp| {
+                let count = resp.u16()? as usize;
+                for _ in 0..count {
+                    qid = resp.qid()?;
+                }
+                Ok(count)
+            });
+            // the newfid isn't created if the walk isn't finished.
+            if !walked.as_ref().is_ok_and(|x| *x == chunk.len()) {
+                if from == newfid {
+                    let _ = self.clunk(newfid);
+                }
+                return Err(walked.err().unwrap_or(VfsError::FileNotFound));
+            }
+            from = newfid;
+        }
+        Ok(qid)
+    }
+
+    pub fn lopen(&self, fid: u32, flags: u32) -> VfsResult<Qid> {
+        let mut req = Request::new(P9_TLOPEN, P9_TAG);
+        req.u32(fid).u32(flags);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.qid())
+    }
+
+    /// Create the file in the directory of the fid, the fid refers to the new file after it.
+    pub fn lcreate(&self, fid: u32, name: &str, flags: u32, mode: u32) -> VfsResult<Qid> {
+        let mut req = Request::new(P9_TLCREATE, P9_TAG);
+        req.u32(fid).str(name).u32(flags).u32(mode).u32(0);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.qid())
+    }
+
+    pub fn mkdir(&self, dfid: u32, name: &str, mode: u32) -> VfsResult<Qid> {
+        let mut req = Request::new(P9_TMKDIR, P9_TAG);
+        req.u32(dfid).str(name).u32(mode).u32(0);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.qid())
+    }
+
+    pub fn symlink(&self, dfid: u32, name: &str, target: &str) -> VfsResult<Qid> {
+        let mut req = Request::new(P9_TSYMLINK, P9_TAG);
+        req.u32(dfid).str(name).str(target).u32(0);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.qid())
+    }
+
+    pub fn link(&self, dfid: u32, fid: u32, name: &str) -> VfsResult<()> {
+        let mut req = Request::new(P9_TLINK, P9_TAG);
+        req.u32(dfid).u32(fid).str(name);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+
+    pub fn renameat(
+        &self,
+        olddfid: u32,
+        oldname: &str,
+        newdfid: u32,
+        newname: &str,
+    ) -> VfsResult<()> {
+        let mut req = Request::new(P9_TRENAMEAT, P9_TAG);
+        req.u32(olddfid).str(oldname).u32(newdfid).str(newname);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+
+    pub fn unlinkat(&self, dfid: u32, name: &str, flags: u32) -> VfsResult<()> {
+        let mut req = Request::new(P9_TUNLINKAT, P9_TAG);
+        req.u32(dfid).str(name).u32(flags);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+
+    pub fn readlink(&self, fid: u32) -> VfsResult<String> {
+        let mut req = Request::new(P9_TREADLINK, P9_TAG);
+        req.u32(fid);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| resp.str())
+    }
+
+    pub fn read(&self, fid: u32, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        // Rread has count[4] before the data.
+        let max = self.msize as usize - P9_HEADER_SIZE - 4;
+        let mut readed = 0;
+        while readed < buffer.len() {
+            let count = (buffer.len() - readed).min(max);
+            let mut req = Request::new(P9_TREAD, P9_TAG);
+            req.u32(fid).u64((offset + readed) as _).u32(count as _);
+            let rlen = self.rpc(&mut req, P9_HEADER_SIZE + 4 + count, |resp| {
+                let rlen = (resp.u32()? as usize).min(count);
+                buffer[readed..readed + rlen].copy_from_slice(resp.bytes(rlen)?);
+                Ok(rlen)
+            })?;
+            readed += rlen;
+            if rlen < count {
+                break;
+            }
+        }
+        Ok(readed)
+    }
+
+    pub fn write(&self, fid: u32, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        // Twrite has fid[4] offset[8] count[4] before the data.
+        let max = self.msize as usize - P9_HEADER_SIZE - 16;
+        let mut written = 0;
+        while written < buffer.len() {
+            let count = (buffer.len() - written).min(max);
+            let mut req = Request::new(P9_TWRITE, P9_TAG);
+            req.u32(fid).u64((offset + written) as _).u32(count as _);
+            req.bytes(&buffer[written..written + count]);
+            let wlen = self.rpc(&mut req, P9_SMALL_SIZE, |resp| Ok(resp.u32()? as usize))?;
+            written += wlen;
+            if wlen < count {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    pub fn readdir(&self, fid: u32, offset: u64) -> VfsResult<Vec<P9DirEntry>> {
+        let count = self.msize as usize - P9_HEADER_SIZE - 4;
+        let mut req = Request::new(P9_TREADDIR, P9_TAG);
+        req.u32(fid).u64(offset).u32(count as _);
+        self.rpc(&mut req, self.msize as usize, |resp| {
+            let count = resp.u32()? as usize;
+            let end = resp.pos + count;
+            let mut entries = Vec::new();
+            while resp.pos < end {
+                entries.push(P9DirEntry {
+                    qid: resp.qid()?,
+                    offset: resp.u64()?,
+                    ty: resp.u8()?,
+                    name: resp.str()?,
+                });
+            }
+            Ok(entries)
+        })
+    }
+
+    pub fn getattr(&self, fid: u32) -> VfsResult<Attr> {
+        let mut req = Request::new(P9_TGETATTR, P9_TAG);
+        req.u32(fid).u64(P9_GETATTR_BASIC);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| {
+            resp.u64()?; // valid
+            let qid = resp.qid()?;
+            let (mode, uid, gid) = (resp.u32()?, resp.u32()?, resp.u32()?);
+            let (nlink, rdev, size) = (resp.u64()?, resp.u64()?, resp.u64()?);
+            let (blksize, blocks) = (resp.u64()?, resp.u64()?);
+            let (atime, mtime, ctime) = (resp.time()?, resp.time()?, resp.time()?);
+            Ok(Attr {
+                qid,
+                mode,
+                uid,
+                gid,
+                nlink,
+                rdev,
+                size,
+                blksize,
+                blocks,
+                atime,
+                mtime,
+                ctime,
+            })
+        })
+    }
+
+    pub fn setattr(&self, fid: u32, attr: &SetAttr) -> VfsResult<()> {
+        let mut req = Request::new(P9_TSETATTR, P9_TAG);
+        req.u32(fid).u32(attr.valid).u32(attr.mode);
+        req.u32(attr.uid).u32(attr.gid).u64(attr.size);
+        req.u64(attr.atime.sec as _).u64(attr.atime.nsec as _);
+        req.u64(attr.mtime.sec as _).u64(attr.mtime.nsec as _);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+
+    pub fn statfs(&self, fid: u32) -> VfsResult<P9StatFS> {
+        let mut req = Request::new(P9_TSTATFS, P9_TAG);
+        req.u32(fid);
+        self.rpc(&mut req, P9_SMALL_SIZE, |resp| {
+            Ok(P9StatFS {
+                ftype: resp.u32()?,
+                bsize: resp.u32()?,
+                blocks: resp.u64()?,
+                bfree: resp.u64()?,
+                bavail: resp.u64()?,
+                files: resp.u64()?,
+                ffree: resp.u64()?,
+                fsid: resp.u64()?,
+                namelen: resp.u32()?,
+            })
+        })
+    }
+
+    pub fn fsync(&self, fid: u32) -> VfsResult<()> {
+        let mut req = Request::new(P9_TFSYNC, P9_TAG);
+        req.u32(fid).u32(0);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+
+    pub fn clunk(&self, fid: u32) -> VfsResult<()> {
+        let mut req = Request::new(P9_TCLUNK, P9_TAG);
+        req.u32(fid);
+        self.rpc(&mut req, P9_SMALL_SIZE, |_| Ok(()))
+    }
+}
@@ -0,0 +1,335 @@
+//! A 9P2000.L filesystem, the directory of the host is shared by virtio-9p.
+//! Each node holds a fid walked to it, another fid is opened for reading and writing.
+
+mod client;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use devices::get_p9_devices;
+use sync::Mutex;
+use vfscore::{
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, OpenFlags, Stat, StatFS, StatMode,
+    TimeSpec, VfsError, VfsResult, UTIME_OMIT,
+};
+
+use self::client::*;
+
+pub struct P9FileSystem {
+    root: Arc<P9Node>,
+}
+
+impl P9FileSystem {
+    /// Attach to the directory exported by the device with the mount tag.
+    pub fn new(tag: &str, aname: &str) -> VfsResult<Arc<Self>> {
+        let device = get_p9_devices()
+            .into_iter()
+            .find(|x| x.mount_tag() == tag)
+            .ok_or(VfsError::FileNotFound)?;
+        let client = P9Client::new(device)?;
+        let fid = client.alloc_fid();
+        let qid = client.attach(fid, aname)?;
+        Ok(Arc::new(Self {
+            root: P9Node::new(client, fid, qid, ""),
+        }))
+    }
+}
+
+impl FileSystem for P9FileSystem {
+    fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
+        self.root.clone()
+    }
+
+    fn name(&self) -> &str {
+        "9p"
+    }
+}
+
+pub struct P9Node {
+    client: Arc<P9Client>,
+    fid: u32,
+    qid: Qid,
+    filename: String,
+    /// The fid opened for the io, it is opened when it is used first.
+    io_fid: Mutex<Option<u32>>,
+}
+
+impl Drop for P9Node {
+    fn drop(&mut self) {
+        if let Some(fid) = self.io_fid.lock().take() {
+            let _ = self.client.clunk(fid);
+        }
+        let _ = self.client.clunk(self.fid);
+    }
+}
+
+const fn mode_to_type(mode: u32) -> FileType {
+    match mode & 0o170000 {
+        0o040000 => FileType::Directory,
+        0o120000 => FileType::Link,
+        0o020000 | 0o060000 => FileType::Device,
+        0o140000 => FileType::Socket,
+        _ => FileType::File,
+    }
+}
+
+/// The type in the directory entry, DT_* of Linux.
+const fn dt_to_type(dt: u8) -> FileType {
+    match dt {
+        4 => FileType::Directory,
+        10 => FileType::Link,
+        2 | 6 => FileType::Device,
+        12 => FileType::Socket,
+        _ => FileType::File,
+    }
+}
+
+impl P9Node {
+    fn new(client: Arc<P9Client>, fid: u32, qid: Qid, filename: &str) -> Arc<Self> {
+        Arc::new(Self {
+            client,
+            fid,
+            qid,
+            filename: String::from(filename),
+            io_fid: Mutex::new(None),
+        })
+    }
+
+    fn is_dir(&self) -> bool {
+        self.qid.ty & P9_QTDIR != 0
+    }
+
+    /// Get the opened fid, the file is opened readonly if it can't be written.
+    fn io_fid(&self) -> VfsResult<u32> {
+        let mut io_fid = self.io_fid.lock();
+        if let Some(fid) = *io_fid {
+            return Ok(fid);
+        }
+        let fid = self.client.alloc_fid();
+        self.client.walk(self.fid, fid, &[])?;
+        let opened = match self.is_dir() {
+            true => self.client.lopen(fid, P9_O_RDONLY),
+            false => self
+                .client
+                .lopen(fid, P9_O_RDWR)
+                .or_else(|_| self.client.lopen(fid, P9_O_RDONLY)),
+        };
+        if let Err(err) = opened {
+            let _ = self.client.clunk(fid);
+            return Err(err);
+        }
+        *io_fid = Some(fid);
+        Ok(fid)
+    }
+
+    fn setattr(&self, attr: SetAttr) -> VfsResult<()> {
+        self.client.setattr(self.fid, &attr)
+    }
+
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        self.setattr(SetAttr {
+            valid: P9_SETATTR_MODE,
+            mode: mode & 0o7777,
+            ..Default::default()
+        })
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        let mut attr = SetAttr::default();
+        if let Some(uid) = uid {
+            attr.valid |= P9_SETATTR_UID;
+            attr.uid = uid;
+        }
+        if let Some(gid) = gid {
+            attr.valid |= P9_SETATTR_GID;
+            attr.gid = gid;
+        }
+        self.setattr(attr)
+    }
+
+    pub fn symlink(&self, name: &str, target: &str) -> VfsResult<()> {
+        self.client.symlink(self.fid, name, target).map(|_| ())
+    }
+
+    /// Move the entry to the new directory, the server replaces the entry which has the new name.
+    pub fn rename(&self, name: &str, new_dir: &P9Node, new_name: &str) -> VfsResult<()> {
+        if !Arc::ptr_eq(&self.client, &new_dir.client) {
+            return Err(VfsError::NotSupported);
+        }
+        self.client.renameat(self.fid, name, new_dir.fid, new_name)
+    }
+}
+
+impl INodeInterface for P9Node {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        if self.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        self.client.read(self.io_fid()?, offset, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        if self.is_dir() {
+            return Err(VfsError::NotFile);
+        }
+        self.client.write(self.io_fid()?, offset, buffer)
+    }
+
+    fn truncate(&self, size: usize) -> VfsResult<()> {
+        self.setattr(SetAttr {
+            valid: P9_SETATTR_SIZE,
+            size: size as _,
+            ..Default::default()
+        })
+    }
+
+    fn flush(&self) -> VfsResult<()> {
+        match *self.io_fid.lock() {
+            Some(fid) => self.client.fsync(fid),
+            None => Ok(()),
+        }
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        let attr = self.client.getattr(self.fid)?;
+        Ok(Metadata {
+            filename: &self.filename,
+            inode: attr.qid.path as _,
+            file_type: mode_to_type(attr.mode),
+            size: attr.size as _,
+            childrens: 0,
+        })
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let attr = self.client.getattr(self.fid)?;
+        stat.ino = attr.qid.path;
+        stat.mode = StatMode::from_bits_retain(attr.mode as _);
+        stat.nlink = attr.nlink as _;
+        stat.uid = attr.uid as _;
+        stat.gid = attr.gid as _;
+        stat.rdev = attr.rdev as _;
+        stat.size = attr.size as _;
+        stat.blksize = attr.blksize as _;
+        stat.blocks = attr.blocks as _;
+        stat.atime = attr.atime;
+        stat.mtime = attr.mtime;
+        stat.ctime = attr.ctime;
+        Ok(())
+    }
+
+    fn statfs(&self, statfs: &mut StatFS) -> VfsResult<()> {
+        let fs = self.client.statfs(self.fid)?;
+        statfs.ftype = fs.ftype as _;
+        statfs.bsize = fs.bsize as _;
+        statfs.blocks = fs.blocks as _;
+        statfs.bfree = fs.bfree as _;
+        statfs.bavail = fs.bavail as _;
+        statfs.files = fs.files as _;
+        statfs.ffree = fs.ffree as _;
+        statfs.fsid = fs.fsid as _;
+        statfs.namelen = fs.namelen as _;
+        Ok(())
+    }
+
+    fn utimes(&self, times: &mut [TimeSpec]) -> VfsResult<()> {
+        let mut attr = SetAttr::default();
+        if times[0].nsec != UTIME_OMIT {
+            attr.valid |= P9_SETATTR_ATIME | P9_SETATTR_ATIME_SET;
+            attr.atime = times[0];
+        }
+        if times[1].nsec != UTIME_OMIT {
+            attr.valid |= P9_SETATTR_MTIME | P9_SETATTR_MTIME_SET;
+            attr.mtime = times[1];
+        }
+        self.setattr(attr)
+    }
+
+    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        if !self.is_dir() {
+            return Err(VfsError::NotDir);
+        }
+        let fid = self.client.alloc_fid();
+        let qid = self.client.walk(self.fid, fid, &[name])?;
+        Ok(P9Node::new(self.client.clone(), fid, qid, name))
+    }
+
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.lookup(name)
+    }
+
+    fn touch(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        // the fid is the opened new file after Tlcreate, it isn't the node of the file.
+        let fid = self.client.alloc_fid();
+        self.client.walk(self.fid, fid, &[])?;
+        let created = self
+            .client
+            .lcreate(fid, name, P9_O_RDWR | P9_O_CREAT | P9_O_EXCL, 0o644);
+        let _ = self.client.clunk(fid);
+        created?;
+        self.lookup(name)
+    }
+
+    fn mkdir(&self, name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.client.mkdir(self.fid, name, 0o755)?;
+        self.lookup(name)
+    }
+
+    fn rmdir(&self, name: &str) -> VfsResult<()> {
+        self.client.unlinkat(self.fid, name, AT_REMOVEDIR)
+    }
+
+    fn remove(&self, name: &str) -> VfsResult<()> {
+        self.client.unlinkat(self.fid, name, 0)
+    }
+
+    fn unlink(&self, name: &str) -> VfsResult<()> {
+        self.client.unlinkat(self.fid, name, 0)
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        if !self.is_dir() {
+            return Err(VfsError::NotDir);
+        }
+        let fid = self.io_fid()?;
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        loop {
+            let chunk = self.client.readdir(fid, offset)?;
+            let Some(last) = chunk.last() else {
+                break;
+            };
+            offset = last.offset;
+            entries.extend(
+                chunk
+                    .into_iter()
+                    .filter(|x| x.name != "." && x.name != "..")
+                    .map(|x| DirEntry {
+                        filename: x.name,
+                        // the size isn't in the directory entry.
+                        len: 0,
+                        file_type: match x.qid.ty & P9_QTSYMLINK != 0 {
+                            true => FileType::Link,
+                            false => dt_to_type(x.ty),
+                        },
+                    }),
+            );
+        }
+        Ok(entries)
+    }
+
+    fn link(&self, name: &str, src: Arc<dyn INodeInterface>) -> VfsResult<()> {
+        let src = src
+            .downcast_arc::<P9Node>()
+            .map_err(|_| VfsError::NotSupported)?;
+        if !Arc::ptr_eq(&src.client, &self.client) {
+            return Err(VfsError::NotSupported);
+        }
+        self.client.link(self.fid, src.fid, name)
+    }
+
+    fn resolve_link(&self) -> VfsResult<String> {
+        match self.qid.ty & P9_QTSYMLINK != 0 {
+            true => self.client.readlink(self.fid),
+            false => Err(VfsError::InvalidInput),
+        }
+    }
+}
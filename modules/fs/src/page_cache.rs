@@ -0,0 +1,238 @@
+//! The page cache of the regular files on block devices.
+//! The pages are shared by read/write, mmap and sendfile.
+//! The files of ramfs are made of pages already, they are used directly.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use arch::PAGE_SIZE;
+use frame_allocator::{frame_alloc, FrameTracker};
+use ramfs::RamFile;
+use sync::Mutex;
+use vfscore::{INodeInterface, VfsError, VfsResult};
+
+use crate::{ext4::Ext4Node, fatfs_shim::FatFile};
+
+/// Try to drop the unused pages when the cache is larger than it.
+const PAGE_CACHE_LIMIT: usize = 0x1000;
+
+/// The identity of the file -> the page cache of it.
+static PAGE_CACHES: Mutex<BTreeMap<NodeKey, Arc<PageCache>>> = Mutex::new(BTreeMap::new());
+static CACHED_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// The identity of the file, the nodes opened from the same file share it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NodeKey {
+    /// The device number and the inode number of the file on the block device.
+    Block(u64, u64),
+    /// The address of the file in ramfs.
+    Memory(usize),
+}
+
+struct CachedPage {
+    frame: Arc<FrameTracker>,
+    /// The page may be different from the file, it is mapped shared or written by mmap.
+    dirty: bool,
+}
+
+pub struct PageCache {
+    /// One of the nodes of the file, it is replaced by the next one after it is dropped.
+    node: Mutex<Weak<dyn INodeInterface>>,
+    pages: Mutex<BTreeMap<usize, CachedPage>>,
+}
+
+/// The contents of the files on the block devices are stable, cache them.
+/// The pages of ramfs are mapped directly.
+/// The others are generated when reading, None for them.
+fn node_key(node: &Arc<dyn INodeInterface>) -> Option<NodeKey> {
+    if let Some(file) = node.downcast_ref::<FatFile>() {
+        let (dev, ino) = file.dev_ino();
+        return Some(NodeKey::Block(dev, ino));
+    }
+    if let Some(file) = node.downcast_ref::<Ext4Node>() {
+        let (dev, ino) = file.dev_ino();
+        return Some(NodeKey::Block(dev, ino));
+    }
+    node.downcast_ref::<RamFile>()
+        .map(|file| NodeKey::Memory(file.id()))
+}
+
+/// Get the page cache of the inode, None if it can't be cached.
+pub fn get_page_cache(node: &Arc<dyn INodeInterface>) -> Option<Arc<PageCache>> {
+    let key = node_key(node)?;
+    let mut caches = PAGE_CACHES.lock();
+    if let Some(cache) = caches.get(&key) {
+        // the pages which are not used may be stale, the inode number can be reused.
+        let mut cached = cache.node.lock();
+        if cached.strong_count() == 0 {
+            *cached = Arc::downgrade(node);
+            cache.shrink();
+        }
+        return Some(cache.clone());
+    }
+    caches.retain(|_, cache| cache.node.lock().strong_count() > 0 || cache.shrink());
+    let cache = Arc::new(PageCache {
+        node: Mutex::new(Arc::downgrade(node)),
+        pages: Mutex::new(BTreeMap::new()),
+    });
+    caches.insert(key, cache.clone());
+    Some(cache)
+}
+
+impl PageCache {
+    /// Drop the clean pages which are not mapped by anyone, return whether any page is left.
+    fn shrink(&self) -> bool {
+        let mut pages = self.pages.lock();
+        let count = pages.len();
+        pages.retain(|_, page| page.dirty || Arc::strong_count(&page.frame) > 1);
+        CACHED_PAGES.fetch_sub(count - pages.len(), Ordering::Relaxed);
+        !pages.is_empty()
+    }
+
+    fn node(&self) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.node.lock().upgrade().ok_or(VfsError::FileNotFound)
+    }
+
+    /// The size of the cached file.
+    pub fn size(&self) -> VfsResult<usize> {
+        Ok(self.node()?.metadata()?.size)
+    }
+
+    /// Get the page of the file, read it from the file if it isn't cached.
+    pub fn get_page(&self, index: usize) -> VfsResult<Arc<FrameTracker>> {
+        if let Some(page) = self.pages.lock().get(&index) {
+            return Ok(page.frame.clone());
+        }
+        let node = self.node()?;
+        if let Some(file) = node.downcast_ref::<RamFile>() {
+            return file.get_page(index);
+        }
+        if CACHED_PAGES.load(Ordering::Relaxed) >= PAGE_CACHE_LIMIT {
+            shrink();
+        }
+        let mut pages = self.pages.lock();
+        // the page may be read by others when the lock is released.
+        if let Some(page) = pages.get(&index) {
+            return Ok(page.frame.clone());
+        }
+        let frame = Arc::new(frame_alloc().ok_or(VfsError::StorageFull)?);
+        // the part after the end of the file is zero, the frame is cleared at dealloc.
+        node.readat(index * PAGE_SIZE, frame.0.get_buffer())?;
+        pages.insert(
+            index,
+            CachedPage {
+                frame: frame.clone(),
+                dirty: false,
+            },
+        );
+        CACHED_PAGES.fetch_add(1, Ordering::Relaxed);
+        Ok(frame)
+    }
+
+    pub fn read(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let node = self.node()?;
+        // the holes of ramfs are read without allocating pages.
+        if node.is::<RamFile>() {
+            return node.readat(offset, buffer);
+        }
+        let size = node.metadata()?.size;
+        if offset >= size {
+            return Ok(0);
+        }
+        let rlen = buffer.len().min(size - offset);
+        let mut readed = 0;
+        while readed < rlen {
+            let pos = offset + readed;
+            let page_off = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_off).min(rlen - readed);
+            let frame = self.get_page(pos / PAGE_SIZE)?;
+            buffer[readed..readed + len]
+                .copy_from_slice(&frame.0.get_buffer()[page_off..page_off + len]);
+            readed += len;
+        }
+        Ok(rlen)
+    }
+
+    /// Write through to the file and update the cached pages.
+    pub fn write(&self, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        let written = self.node()?.writeat(offset, buffer)?;
+        let pages = self.pages.lock();
+        let mut copied = 0;
+        while copied < written {
+            let pos = offset + copied;
+            let page_off = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_off).min(written - copied);
+            if let Some(page) = pages.get(&(pos / PAGE_SIZE)) {
+                page.frame.0.get_buffer()[page_off..page_off + len]
+                    .copy_from_slice(&buffer[copied..copied + len]);
+            }
+            copied += len;
+        }
+        Ok(written)
+    }
+
+    /// Drop the pages after the end of the file, the tail of the last page is cleared.
+    pub fn truncate(&self, size: usize) {
+        let mut pages = self.pages.lock();
+        let removed = pages.split_off(&size.div_ceil(PAGE_SIZE)).len();
+        CACHED_PAGES.fetch_sub(removed, Ordering::Relaxed);
+        if size % PAGE_SIZE != 0 {
+            if let Some(page) = pages.get(&(size / PAGE_SIZE)) {
+                page.frame.0.get_buffer()[size % PAGE_SIZE..].fill(0);
+            }
+        }
+    }
+
+    /// Mark the page is modified through the memory mapping.
+    pub fn mark_dirty(&self, index: usize) {
+        if let Some(page) = self.pages.lock().get_mut(&index) {
+            page.dirty = true;
+        }
+    }
+
+    /// Write the dirty pages back to the file, the file will not be extended.
+    pub fn sync(&self) -> VfsResult<()> {
+        let node = self.node()?;
+        let size = node.metadata()?.size;
+        let mut pages = self.pages.lock();
+        for (index, page) in pages.iter_mut().filter(|(_, page)| page.dirty) {
+            let offset = index * PAGE_SIZE;
+            if offset < size {
+                let wlen = (size - offset).min(PAGE_SIZE);
+                node.writeat(offset, &page.frame.0.get_buffer()[..wlen])?;
+            }
+            // the shared mapping may modify it again.
+            page.dirty = Arc::strong_count(&page.frame) > 1;
+        }
+        Ok(())
+    }
+}
+
+/// Drop the clean pages which are not mapped by anyone.
+fn shrink() {
+    let caches: Vec<_> = PAGE_CACHES.lock().values().cloned().collect();
+    for cache in caches {
+        cache.shrink();
+    }
+}
+
+/// Check whether any file on the block device is still used, like the mapped ones.
+pub(crate) fn device_in_use(dev: u64) -> bool {
+    PAGE_CACHES.lock().iter().any(|(key, cache)| {
+        matches!(key, NodeKey::Block(x, _) if *x == dev) && cache.node.lock().strong_count() > 0
+    })
+}
+
+/// Write back the dirty pages of all the files.
+pub fn sync_all() {
+    let caches: Vec<_> = PAGE_CACHES.lock().values().cloned().collect();
+    for cache in caches.iter().filter(|x| x.node.lock().strong_count() > 0) {
+        if let Err(err) = cache.sync() {
+            log::warn!("can't write back the page cache: {:?}", err);
+        }
+    }
+}
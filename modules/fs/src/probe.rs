@@ -0,0 +1,109 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use devices::get_blk_device;
+use sync::Mutex;
+use vfscore::{FileSystem, VfsError, VfsResult};
+
+/// A filesystem backend which can be found on a block device.
+#[derive(Clone, Copy)]
+pub struct FsProbe {
+    /// The names used by mount(2), the first one is the canonical name.
+    pub names: &'static [&'static str],
+    /// Read the superblock, return true if the backend can handle the device.
+    pub probe: fn(usize) -> bool,
+    /// Open the filesystem on the device.
+    pub open: fn(usize) -> VfsResult<Arc<dyn FileSystem>>,
+}
+
+pub static FS_PROBES: Mutex<Vec<FsProbe>> = Mutex::new(Vec::new());
+
+/// The filesystem opened on each block device and its canonical name.
+/// A device can only be opened once, otherwise the backends will corrupt it.
+static DEVICE_FS: Mutex<BTreeMap<usize, (&'static str, Arc<dyn FileSystem>)>> =
+    Mutex::new(BTreeMap::new());
+
+/// Register a filesystem backend.
+pub fn register_fs(probe: FsProbe) {
+    FS_PROBES.lock().push(probe);
+}
+
+/// Find the backend by the name passed to mount(2).
+pub fn find_fs(name: &str) -> Option<FsProbe> {
+    FS_PROBES
+        .lock()
+        .iter()
+        .find(|x| x.names.contains(&name))
+        .cloned()
+}
+
+/// Identify the filesystem on the block device.
+pub fn probe_fs(device_id: usize) -> Option<FsProbe> {
+    // copy probes out, the probe function will read the device.
+    let probes = FS_PROBES.lock().clone();
+    probes.into_iter().find(|x| (x.probe)(device_id))
+}
+
+/// Open the filesystem on the block device.
+/// fstype: the type passed to mount(2), detect it if it is None or "auto".
+/// The device is read without the lock, the first opened filesystem is kept if it is raced.
+pub fn open_device_fs(device_id: usize, fstype: Option<&str>) -> VfsResult<Arc<dyn FileSystem>> {
+    if let Some((_, fs)) = DEVICE_FS.lock().get(&device_id) {
+        return Ok(fs.clone());
+    }
+    let probe = match fstype {
+        Some(name) if name != "auto" => {
+            let probe = find_fs(name).ok_or(VfsError::NotSupported)?;
+            if !(probe.probe)(device_id) {
+                return Err(VfsError::InvalidData);
+            }
+            probe
+        }
+        _ => probe_fs(device_id).ok_or(VfsError::InvalidData)?,
+    };
+    let fs = (probe.open)(device_id)?;
+    let mut device_fs = DEVICE_FS.lock();
+    // the new one is dropped before any node is opened from it.
+    if let Some((_, fs)) = device_fs.get(&device_id) {
+        return Ok(fs.clone());
+    }
+    log::info!("found {} on block device {}", probe.names[0], device_id);
+    device_fs.insert(device_id, (probe.names[0], fs.clone()));
+    Ok(fs)
+}
+
+/// Forget the filesystem on the block device after it is unmounted,
+/// the next mount opens the device again.
+pub fn close_device_fs(device_id: usize) {
+    DEVICE_FS.lock().remove(&device_id);
+}
+
+/// The canonical name of the filesystem opened on the block device.
+pub fn device_fs_type(device_id: usize) -> Option<&'static str> {
+    DEVICE_FS.lock().get(&device_id).map(|(name, _)| *name)
+}
+
+/// Flush the filesystem opened on the block device, then the cached blocks of it.
+pub fn flush_device(device_id: usize) -> VfsResult<()> {
+    let fs = DEVICE_FS.lock().get(&device_id).map(|(_, fs)| fs.clone());
+    if let Some(fs) = fs {
+        fs.flush()?;
+    }
+    if let Some(device) = get_blk_device(device_id) {
+        device.flush();
+    }
+    Ok(())
+}
+
+/// Flush all the filesystems opened on block devices.
+pub fn flush_device_fs() {
+    // flush outside the lock, the flush will write the device.
+    let filesystems: Vec<_> = DEVICE_FS
+        .lock()
+        .values()
+        .map(|(_, fs)| fs.clone())
+        .collect();
+    for fs in filesystems {
+        if let Err(err) = fs.flush() {
+            log::warn!("can't flush {}: {:?}", fs.name(), err);
+        }
+    }
+}
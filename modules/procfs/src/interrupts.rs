@@ -4,11 +4,15 @@ use alloc::{format, string::String};
 use arch::get_int_records;
 use vfscore::{INodeInterface, StatMode, VfsResult};
 
-pub struct Interrupts {}
+use crate::{alloc_ino, proc_dev};
+
+pub struct Interrupts {
+    ino: u64,
+}
 
 impl Interrupts {
-    pub const fn new() -> Self {
-        Self {}
+    pub fn new() -> Self {
+        Self { ino: alloc_ino() }
     }
 }
 
@@ -28,16 +32,16 @@ impl INodeInterface for Interrupts {
     }
 
     fn stat(&self, stat: &mut vfscore::Stat) -> vfscore::VfsResult<()> {
-        stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::CHAR; // TODO: add access mode
+        stat.dev = proc_dev();
+        stat.ino = self.ino;
+        stat.mode = StatMode::FILE | StatMode::from_bits_truncate(0o444);
         stat.nlink = 1;
-        stat.uid = 1000;
-        stat.gid = 1000;
+        stat.uid = 0;
+        stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = 0;
         Ok(())
     }
 }
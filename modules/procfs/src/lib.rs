@@ -4,30 +4,80 @@ extern crate alloc;
 
 mod interrupts;
 mod meminfo;
-mod mounts;
+mod process;
+mod render;
+mod system;
 
-use alloc::{collections::BTreeMap, string::ToString, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use interrupts::Interrupts;
 use meminfo::MemInfo;
-use mounts::Mounts;
+use render::RenderFile;
 use vfscore::{DirEntry, FileSystem, FileType, INodeInterface, StatMode, VfsError, VfsResult};
 
+pub use process::{ProcessInfo, ProcessMap, ProcessOps};
+
 pub struct ProcFS {
     root: Arc<ProcDir>,
 }
 
 impl ProcFS {
-    pub fn new() -> Arc<Self> {
+    /// dev: the device number of the mount point.
+    /// render_mounts: generate the content of /proc/mounts.
+    /// render_dentry_state: generate the content of /proc/sys/fs/dentry-state.
+    /// render_filesystems: generate the content of /proc/filesystems.
+    pub fn new(
+        dev: u64,
+        render_mounts: fn() -> String,
+        render_dentry_state: fn() -> String,
+        render_filesystems: fn() -> String,
+    ) -> Arc<Self> {
+        PROC_DEV.store(dev, Ordering::Relaxed);
         Arc::new(Self {
-            root: ProcDir::new(),
+            root: ProcDir::new(render_mounts, render_dentry_state, render_filesystems),
         })
     }
 }
 
+/// The inode number of the root directory.
+const ROOT_INO: u64 = 1;
+
+/// The device number of the proc files, it is shared by the mounts of procfs like the inode numbers.
+static PROC_DEV: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn proc_dev() -> u64 {
+    PROC_DEV.load(Ordering::Relaxed)
+}
+
+/// Allocate the inode number of the proc file.
+pub(crate) fn alloc_ino() -> u64 {
+    static NEXT_INO: AtomicU64 = AtomicU64::new(ROOT_INO + 1);
+    NEXT_INO.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Copy the generated text from the offset, return the length copied.
+pub(crate) fn read_text(text: &str, offset: usize, buffer: &mut [u8]) -> usize {
+    let bytes = text.as_bytes();
+    if offset >= bytes.len() {
+        return 0;
+    }
+    let rlen = buffer.len().min(bytes.len() - offset);
+    buffer[..rlen].copy_from_slice(&bytes[offset..offset + rlen]);
+    rlen
+}
+
 impl FileSystem for ProcFS {
     fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
-        Arc::new(DevDirContainer {
-            inner: self.root.clone(),
+        Arc::new(ProcRoot {
+            inner: DevDirContainer {
+                inner: self.root.clone(),
+            },
         })
     }
 
@@ -41,13 +91,43 @@ pub struct ProcDir {
 }
 
 impl ProcDir {
-    pub fn new() -> Arc<ProcDir> {
+    pub fn new(
+        render_mounts: fn() -> String,
+        render_dentry_state: fn() -> String,
+        render_filesystems: fn() -> String,
+    ) -> Arc<ProcDir> {
         let mut map: BTreeMap<&str, Arc<dyn INodeInterface>> = BTreeMap::new();
-        map.insert("mounts", Arc::new(Mounts::new()));
+        map.insert("mounts", Arc::new(RenderFile::new(render_mounts)));
+        map.insert("filesystems", Arc::new(RenderFile::new(render_filesystems)));
+        map.insert("cpuinfo", Arc::new(RenderFile::new(system::render_cpuinfo)));
+        map.insert("uptime", Arc::new(RenderFile::new(system::render_uptime)));
+        map.insert("loadavg", Arc::new(RenderFile::new(system::render_loadavg)));
+        map.insert("stat", Arc::new(RenderFile::new(system::render_stat)));
+        map.insert("version", Arc::new(RenderFile::new(system::render_version)));
+        map.insert("cmdline", Arc::new(RenderFile::new(system::render_cmdline)));
         map.insert("meminfo", Arc::new(MemInfo::new()));
         map.insert("interrupts", Arc::new(Interrupts::new()));
+        map.insert("sys", ProcDir::new_sys(render_dentry_state));
         Arc::new(ProcDir { map })
     }
+
+    /// The directory /proc/sys, only fs/dentry-state is in it now.
+    fn new_sys(render_dentry_state: fn() -> String) -> Arc<dyn INodeInterface> {
+        let mut fs: BTreeMap<&str, Arc<dyn INodeInterface>> = BTreeMap::new();
+        fs.insert(
+            "dentry-state",
+            Arc::new(RenderFile::new(render_dentry_state)),
+        );
+        let mut sys: BTreeMap<&str, Arc<dyn INodeInterface>> = BTreeMap::new();
+        sys.insert("fs", ProcDir::container(fs));
+        ProcDir::container(sys)
+    }
+
+    fn container(map: BTreeMap<&'static str, Arc<dyn INodeInterface>>) -> Arc<dyn INodeInterface> {
+        Arc::new(DevDirContainer {
+            inner: Arc::new(ProcDir { map }),
+        })
+    }
 }
 
 pub struct DevDirContainer {
@@ -77,16 +157,16 @@ impl INodeInterface for DevDirContainer {
     }
 
     fn stat(&self, stat: &mut vfscore::Stat) -> VfsResult<()> {
-        stat.dev = 0;
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::DIR; // TODO: add access mode
-        stat.nlink = 1;
+        stat.dev = proc_dev();
+        stat.ino = ROOT_INO;
+        stat.mode = StatMode::DIR | StatMode::from_bits_truncate(0o555);
+        stat.nlink = 2;
         stat.uid = 0;
         stat.gid = 0;
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
-        stat.rdev = 0; // TODO: add device id
+        stat.rdev = 0;
         Ok(())
     }
 
@@ -100,3 +180,30 @@ impl INodeInterface for DevDirContainer {
         })
     }
 }
+
+/// The root directory, the processes are listed after the static files.
+pub struct ProcRoot {
+    inner: DevDirContainer,
+}
+
+impl INodeInterface for ProcRoot {
+    fn open(&self, name: &str, flags: vfscore::OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.inner
+            .open(name, flags)
+            .or_else(|_| process::open_root_entry(name))
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        let mut entries = self.inner.read_dir()?;
+        entries.extend(process::root_entries());
+        Ok(entries)
+    }
+
+    fn stat(&self, stat: &mut vfscore::Stat) -> VfsResult<()> {
+        self.inner.stat(stat)
+    }
+
+    fn metadata(&self) -> VfsResult<vfscore::Metadata> {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,506 @@
+//! The directories of the processes, like /proc/[pid] and /proc/self.
+//! The nodes are generated when they are opened, the content is rendered when it is read.
+
+use core::fmt::Write;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use arch::PAGE_SIZE;
+use vfscore::{
+    DirEntry, FileType, INodeInterface, Metadata, OpenFlags, Stat, StatMode, VfsError, VfsResult,
+};
+
+use crate::{proc_dev, read_text};
+
+/// A memory area of the process, it is a line of /proc/[pid]/maps.
+pub struct ProcessMap {
+    pub start: usize,
+    pub end: usize,
+    /// The permissions like rwxp, the last one is s if the area is shared.
+    pub perms: &'static str,
+    pub offset: usize,
+    pub ino: u64,
+    /// The file or the name like [stack], it is empty for the anonymous area.
+    pub name: String,
+}
+
+/// The information of the thread shown in stat and status.
+pub struct ProcessInfo {
+    pub pid: usize,
+    pub tid: usize,
+    pub ppid: usize,
+    /// R if it is running, S if it is sleeping and Z if it has exited.
+    pub state: char,
+    pub uid: u32,
+    pub gid: u32,
+    pub umask: u32,
+    pub threads: usize,
+    /// The time spent in the user mode and the kernel mode, in clock ticks.
+    pub utime: u64,
+    pub stime: u64,
+    /// The size of the memory areas and the mapped pages, in bytes.
+    pub vsize: usize,
+    pub rss: usize,
+    pub fd_size: usize,
+    pub cmdline: Vec<String>,
+    pub environ: Vec<String>,
+    pub exe: String,
+    pub cwd: String,
+}
+
+impl ProcessInfo {
+    /// The name of the command, it is the file name of the executable cut to 15 bytes.
+    fn comm(&self) -> &str {
+        let name = self.exe.rsplit('/').next().unwrap_or("");
+        let mut len = name.len().min(15);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        &name[..len]
+    }
+}
+
+/// The tasks seen by procfs, it is implemented by the executor.
+#[crate_interface::def_interface]
+pub trait ProcessOps {
+    /// The ids of the processes.
+    fn processes() -> Vec<usize>;
+    /// The ids of the threads in the process, it is empty if the process doesn't exist.
+    fn threads(pid: usize) -> Vec<usize>;
+    /// The process id and the thread id of the current task.
+    fn current() -> Option<(usize, usize)>;
+    fn info(tid: usize) -> Option<ProcessInfo>;
+    fn maps(tid: usize) -> Vec<ProcessMap>;
+    /// The opened files and the paths they are linked to.
+    fn fds(tid: usize) -> Vec<(usize, String)>;
+}
+
+/// The inode numbers of the process files are fixed by the thread id and the entry.
+/// The thread id 0 is used by /proc/self and /proc/thread-self.
+const PROCESS_INO: u64 = 1 << 40;
+
+fn process_ino(tid: usize, index: u64) -> u64 {
+    PROCESS_INO | (tid as u64) << 8 | index
+}
+
+fn fill_stat(stat: &mut Stat, ino: u64, mode: StatMode) {
+    stat.dev = proc_dev();
+    stat.ino = ino;
+    stat.mode = mode;
+    stat.nlink = 1;
+    stat.uid = 0;
+    stat.gid = 0;
+    stat.size = 0;
+    stat.blksize = 512;
+    stat.blocks = 0;
+    stat.rdev = 0;
+}
+
+fn is_alive(pid: usize, tid: usize) -> bool {
+    ProcessOps::threads(pid).contains(&tid)
+}
+
+/// The entries of /proc added after the static files.
+pub(crate) fn root_entries() -> Vec<DirEntry> {
+    let links = ["self", "thread-self"].map(|name| DirEntry {
+        filename: name.to_string(),
+        len: 0,
+        file_type: FileType::Link,
+    });
+    let processes = ProcessOps::processes().into_iter().map(|pid| DirEntry {
+        filename: pid.to_string(),
+        len: 0,
+        file_type: FileType::Directory,
+    });
+    links.into_iter().chain(processes).collect()
+}
+
+/// Open the entry of /proc which isn't a static file.
+pub(crate) fn open_root_entry(name: &str) -> VfsResult<Arc<dyn INodeInterface>> {
+    match name {
+        "self" => Ok(Arc::new(ProcLink::new(
+            process_ino(0, 0),
+            LinkTarget::CurrentProcess,
+        ))),
+        "thread-self" => Ok(Arc::new(ProcLink::new(
+            process_ino(0, 1),
+            LinkTarget::CurrentThread,
+        ))),
+        _ => {
+            let pid = name.parse().map_err(|_| VfsError::FileNotFound)?;
+            match is_alive(pid, pid) {
+                true => Ok(Arc::new(ProcessDir::new(pid, pid))),
+                false => Err(VfsError::FileNotFound),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ProcessFileKind {
+    Stat,
+    Status,
+    Cmdline,
+    Environ,
+    Maps,
+}
+
+/// The entries of /proc/[pid], task is only in the directory of the process.
+const PROCESS_ENTRIES: [(&str, FileType); 9] = [
+    ("cmdline", FileType::File),
+    ("cwd", FileType::Link),
+    ("environ", FileType::File),
+    ("exe", FileType::Link),
+    ("fd", FileType::Directory),
+    ("maps", FileType::File),
+    ("stat", FileType::File),
+    ("status", FileType::File),
+    ("task", FileType::Directory),
+];
+
+/// The directory /proc/[pid] or /proc/[pid]/task/[tid].
+pub struct ProcessDir {
+    pid: usize,
+    tid: usize,
+    name: String,
+}
+
+impl ProcessDir {
+    fn new(pid: usize, tid: usize) -> Self {
+        Self {
+            pid,
+            tid,
+            name: tid.to_string(),
+        }
+    }
+
+    fn entries(&self) -> &[(&'static str, FileType)] {
+        match self.pid == self.tid {
+            true => &PROCESS_ENTRIES,
+            false => &PROCESS_ENTRIES[..PROCESS_ENTRIES.len() - 1],
+        }
+    }
+}
+
+impl INodeInterface for ProcessDir {
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        if !is_alive(self.pid, self.tid) || !self.entries().iter().any(|x| x.0 == name) {
+            return Err(VfsError::FileNotFound);
+        }
+        let tid = self.tid;
+        let file = |kind| -> Arc<dyn INodeInterface> { Arc::new(ProcessFile { tid, kind }) };
+        Ok(match name {
+            "cmdline" => file(ProcessFileKind::Cmdline),
+            "environ" => file(ProcessFileKind::Environ),
+            "maps" => file(ProcessFileKind::Maps),
+            "stat" => file(ProcessFileKind::Stat),
+            "status" => file(ProcessFileKind::Status),
+            "cwd" => Arc::new(ProcLink::new(process_ino(tid, 1), LinkTarget::Cwd(tid))),
+            "exe" => Arc::new(ProcLink::new(process_ino(tid, 2), LinkTarget::Exe(tid))),
+            "fd" => Arc::new(FdDir { tid }),
+            _ => Arc::new(TaskDir { pid: self.pid }),
+        })
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(self
+            .entries()
+            .iter()
+            .map(|(name, file_type)| DirEntry {
+                filename: name.to_string(),
+                len: 0,
+                file_type: *file_type,
+            })
+            .collect())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let info = ProcessOps::info(self.tid).ok_or(VfsError::FileNotFound)?;
+        fill_stat(
+            stat,
+            process_ino(self.tid, 0),
+            StatMode::DIR | StatMode::from_bits_truncate(0o555),
+        );
+        stat.nlink = 2;
+        stat.uid = info.uid as _;
+        stat.gid = info.gid as _;
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: &self.name,
+            inode: process_ino(self.tid, 0) as _,
+            file_type: FileType::Directory,
+            size: 0,
+            childrens: self.entries().len(),
+        })
+    }
+}
+
+/// The directory /proc/[pid]/task, it has the threads of the process.
+pub struct TaskDir {
+    pid: usize,
+}
+
+impl INodeInterface for TaskDir {
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        let tid = name.parse().map_err(|_| VfsError::FileNotFound)?;
+        match is_alive(self.pid, tid) {
+            true => Ok(Arc::new(ProcessDir::new(self.pid, tid))),
+            false => Err(VfsError::FileNotFound),
+        }
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(ProcessOps::threads(self.pid)
+            .into_iter()
+            .map(|tid| DirEntry {
+                filename: tid.to_string(),
+                len: 0,
+                file_type: FileType::Directory,
+            })
+            .collect())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let mode = StatMode::DIR | StatMode::from_bits_truncate(0o555);
+        fill_stat(stat, process_ino(self.pid, 3), mode);
+        stat.nlink = 2;
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "task",
+            inode: process_ino(self.pid, 3) as _,
+            file_type: FileType::Directory,
+            size: 0,
+            childrens: ProcessOps::threads(self.pid).len(),
+        })
+    }
+}
+
+/// The directory /proc/[pid]/fd, each opened file is a link to its path.
+pub struct FdDir {
+    tid: usize,
+}
+
+impl INodeInterface for FdDir {
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        let fd = name.parse().map_err(|_| VfsError::FileNotFound)?;
+        match ProcessOps::fds(self.tid).iter().any(|x| x.0 == fd) {
+            true => Ok(Arc::new(ProcLink::new(
+                process_ino(self.tid, 0x80 + fd as u64 % 0x80),
+                LinkTarget::Fd(self.tid, fd),
+            ))),
+            false => Err(VfsError::FileNotFound),
+        }
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(ProcessOps::fds(self.tid)
+            .into_iter()
+            .map(|(fd, _)| DirEntry {
+                filename: fd.to_string(),
+                len: 0,
+                file_type: FileType::Link,
+            })
+            .collect())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let mode = StatMode::DIR | StatMode::from_bits_truncate(0o500);
+        fill_stat(stat, process_ino(self.tid, 4), mode);
+        stat.nlink = 2;
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "fd",
+            inode: process_ino(self.tid, 4) as _,
+            file_type: FileType::Directory,
+            size: 0,
+            childrens: ProcessOps::fds(self.tid).len(),
+        })
+    }
+}
+
+/// The target is resolved when the link is followed, the link may be kept in the dentry cache.
+#[derive(Clone, Copy)]
+enum LinkTarget {
+    CurrentProcess,
+    CurrentThread,
+    Cwd(usize),
+    Exe(usize),
+    Fd(usize, usize),
+}
+
+pub struct ProcLink {
+    ino: u64,
+    target: LinkTarget,
+}
+
+impl ProcLink {
+    fn new(ino: u64, target: LinkTarget) -> Self {
+        Self { ino, target }
+    }
+}
+
+impl INodeInterface for ProcLink {
+    fn resolve_link(&self) -> VfsResult<String> {
+        let current = ProcessOps::current();
+        let info = |tid| ProcessOps::info(tid).ok_or(VfsError::FileNotFound);
+        match self.target {
+            LinkTarget::CurrentProcess => current.map(|(pid, _)| pid.to_string()),
+            LinkTarget::CurrentThread => current.map(|(pid, tid)| format!("{}/task/{}", pid, tid)),
+            LinkTarget::Cwd(tid) => Some(info(tid)?.cwd),
+            LinkTarget::Exe(tid) => Some(info(tid)?.exe),
+            LinkTarget::Fd(tid, fd) => ProcessOps::fds(tid)
+                .into_iter()
+                .find(|x| x.0 == fd)
+                .map(|x| x.1),
+        }
+        .ok_or(VfsError::FileNotFound)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        fill_stat(
+            stat,
+            self.ino,
+            StatMode::LINK | StatMode::from_bits_truncate(0o777),
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "",
+            inode: self.ino as _,
+            file_type: FileType::Link,
+            size: 0,
+            childrens: 0,
+        })
+    }
+}
+
+/// The file like /proc/[pid]/stat, it is rendered from the thread when it is read.
+pub struct ProcessFile {
+    tid: usize,
+    kind: ProcessFileKind,
+}
+
+impl ProcessFile {
+    fn render(&self) -> VfsResult<String> {
+        if let ProcessFileKind::Maps = self.kind {
+            return Ok(render_maps(&ProcessOps::maps(self.tid)));
+        }
+        let info = ProcessOps::info(self.tid).ok_or(VfsError::FileNotFound)?;
+        Ok(match self.kind {
+            ProcessFileKind::Stat => render_stat(&info),
+            ProcessFileKind::Status => render_status(&info),
+            ProcessFileKind::Cmdline => join_nul(&info.cmdline),
+            ProcessFileKind::Environ => join_nul(&info.environ),
+            ProcessFileKind::Maps => unreachable!(),
+        })
+    }
+}
+
+impl INodeInterface for ProcessFile {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        Ok(read_text(&self.render()?, offset, buffer))
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        let mode = match self.kind {
+            ProcessFileKind::Environ => StatMode::FILE | StatMode::from_bits_truncate(0o400),
+            _ => StatMode::FILE | StatMode::from_bits_truncate(0o444),
+        };
+        fill_stat(stat, process_ino(self.tid, 5 + self.kind as u64), mode);
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "",
+            inode: process_ino(self.tid, 5 + self.kind as u64) as _,
+            file_type: FileType::File,
+            size: 0,
+            childrens: 0,
+        })
+    }
+}
+
+/// Each string ends with a zero, like the arguments in the memory.
+fn join_nul(strings: &[String]) -> String {
+    strings.iter().fold(String::new(), |acc, x| acc + x + "\0")
+}
+
+fn render_stat(info: &ProcessInfo) -> String {
+    // pgrp and session are the process, there isn't a terminal.
+    let mut text = format!(
+        "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 {} {} 0 0 20 0 {} 0 0 {} {} {}",
+        info.tid,
+        info.comm(),
+        info.state,
+        info.ppid,
+        info.pid,
+        info.pid,
+        info.utime,
+        info.stime,
+        info.threads,
+        info.vsize,
+        info.rss / PAGE_SIZE,
+        u64::MAX,
+    );
+    // from startcode to cnswap, then exit_signal is SIGCHLD, the others are 0.
+    text.push_str(&" 0".repeat(12));
+    text.push_str(" 17");
+    text.push_str(&" 0".repeat(14));
+    text.push('\n');
+    text
+}
+
+fn render_status(info: &ProcessInfo) -> String {
+    let state = match info.state {
+        'R' => "R (running)",
+        'Z' => "Z (zombie)",
+        _ => "S (sleeping)",
+    };
+    let mut text = String::new();
+    writeln!(text, "Name:\t{}", info.comm()).unwrap();
+    writeln!(text, "Umask:\t{:04o}", info.umask).unwrap();
+    writeln!(text, "State:\t{}", state).unwrap();
+    writeln!(text, "Tgid:\t{}", info.pid).unwrap();
+    writeln!(text, "Pid:\t{}", info.tid).unwrap();
+    writeln!(text, "PPid:\t{}", info.ppid).unwrap();
+    let (uid, gid) = (info.uid, info.gid);
+    writeln!(text, "Uid:\t{}\t{}\t{}\t{}", uid, uid, uid, uid).unwrap();
+    writeln!(text, "Gid:\t{}\t{}\t{}\t{}", gid, gid, gid, gid).unwrap();
+    writeln!(text, "FDSize:\t{}", info.fd_size).unwrap();
+    writeln!(text, "VmSize:\t{:>8} kB", info.vsize / 1024).unwrap();
+    writeln!(text, "VmRSS:\t{:>8} kB", info.rss / 1024).unwrap();
+    writeln!(text, "Threads:\t{}", info.threads).unwrap();
+    text
+}
+
+fn render_maps(maps: &[ProcessMap]) -> String {
+    let mut text = String::new();
+    for map in maps {
+        let line = format!(
+            "{:08x}-{:08x} {} {:08x} 00:00 {}",
+            map.start, map.end, map.perms, map.offset, map.ino
+        );
+        // the name is aligned like Linux.
+        match map.name.is_empty() {
+            true => writeln!(text, "{}", line).unwrap(),
+            false => writeln!(text, "{:<72} {}", line, map.name).unwrap(),
+        }
+    }
+    text
+}
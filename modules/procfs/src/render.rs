@@ -0,0 +1,40 @@
+use alloc::string::String;
+use vfscore::{INodeInterface, StatMode, VfsResult};
+
+use crate::{alloc_ino, proc_dev, read_text};
+
+/// The file generated when it is read, like /proc/mounts.
+/// The content is kept by other modules, they render it.
+pub struct RenderFile {
+    ino: u64,
+    render: fn() -> String,
+}
+
+impl RenderFile {
+    pub fn new(render: fn() -> String) -> Self {
+        Self {
+            ino: alloc_ino(),
+            render,
+        }
+    }
+}
+
+impl INodeInterface for RenderFile {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        Ok(read_text(&(self.render)(), offset, buffer))
+    }
+
+    fn stat(&self, stat: &mut vfscore::Stat) -> vfscore::VfsResult<()> {
+        stat.dev = proc_dev();
+        stat.ino = self.ino;
+        stat.mode = StatMode::FILE | StatMode::from_bits_truncate(0o444);
+        stat.nlink = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! The files about the whole system, like /proc/cpuinfo and /proc/uptime.
+
+use alloc::{format, string::String};
+use arch::{get_bootargs, get_cpu_time, get_cpus, get_int_records, get_time, time_to_usec};
+use sync::Mutex;
+
+use crate::process::ProcessOps;
+
+/// The clock ticks per second of the times in /proc/stat.
+const USER_HZ: usize = 100;
+
+/// The same release and version as uname(2).
+const LINUX_RELEASE: &str = "5.10.0-7-riscv64";
+const LINUX_VERSION: &str = "#1 SMP Debian 5.10.40-1 (2021-05-28)";
+
+/// The fixed-point loads like Linux, they are updated every 5 seconds.
+const FSHIFT: usize = 11;
+const FIXED_1: usize = 1 << FSHIFT;
+const LOAD_FREQ_USEC: usize = 5_000_000;
+/// 1 / exp(5sec / 1min), 1 / exp(5sec / 5min) and 1 / exp(5sec / 15min).
+const EXP_LOADS: [usize; 3] = [1884, 2014, 2037];
+
+/// The loads and the time of the last update.
+static LOADS: Mutex<([usize; 3], usize)> = Mutex::new(([0; 3], 0));
+
+/// The uptime, the idle time and the user and system time in usec.
+fn cpu_usec() -> (usize, usize, usize, usize) {
+    let uptime = time_to_usec(get_time());
+    let (user, system) = get_cpu_time();
+    let (user, system) = (time_to_usec(user), time_to_usec(system));
+    (uptime, uptime.saturating_sub(user + system), user, system)
+}
+
+/// The number of the running threads and all the threads.
+fn thread_count() -> (usize, usize) {
+    let threads = ProcessOps::processes()
+        .into_iter()
+        .flat_map(|pid| ProcessOps::threads(pid))
+        .filter_map(|tid| ProcessOps::info(tid));
+    threads.fold((0, 0), |(running, total), info| match info.state {
+        'R' => (running + 1, total + 1),
+        'Z' => (running, total),
+        _ => (running, total + 1),
+    })
+}
+
+/// Format the fixed-point load with 2 decimals.
+fn fixed_load(load: usize) -> String {
+    let load = load + FIXED_1 / 200;
+    format!(
+        "{}.{:02}",
+        load >> FSHIFT,
+        ((load & (FIXED_1 - 1)) * 100) >> FSHIFT
+    )
+}
+
+pub fn render_cpuinfo() -> String {
+    let mut text = String::new();
+    for (processor, cpu) in get_cpus().iter().enumerate() {
+        text += &format!("processor\t: {}\nhart\t\t: {}\n", processor, cpu.id);
+        if !cpu.isa.is_empty() {
+            text += &format!("isa\t\t: {}\n", cpu.isa);
+        }
+        if !cpu.mmu.is_empty() {
+            text += &format!("mmu\t\t: {}\n", cpu.mmu.trim_start_matches("riscv,"));
+        }
+        if !cpu.model.is_empty() {
+            text += &format!("model name\t: {}\n", cpu.model);
+        }
+        if cpu.frequency != 0 {
+            text += &format!("cpu MHz\t\t: {}\n", cpu.frequency / 1_000_000);
+        }
+        text += "\n";
+    }
+    text
+}
+
+pub fn render_uptime() -> String {
+    let (uptime, idle, ..) = cpu_usec();
+    format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime / 1_000_000,
+        uptime % 1_000_000 / 10_000,
+        idle / 1_000_000,
+        idle % 1_000_000 / 10_000
+    )
+}
+
+/// The loads are calculated when the file is read, the missed periods are applied here.
+pub fn render_loadavg() -> String {
+    let (running, total) = thread_count();
+    let now = time_to_usec(get_time());
+    let mut loads = LOADS.lock();
+    let periods = (now - loads.1) / LOAD_FREQ_USEC;
+    // the loads are the same as the active threads after a long time.
+    for _ in 0..periods.min(1000) {
+        for (load, exp) in loads.0.iter_mut().zip(EXP_LOADS) {
+            *load = (*load * exp + running * FIXED_1 * (FIXED_1 - exp)) >> FSHIFT;
+        }
+    }
+    loads.1 += periods * LOAD_FREQ_USEC;
+    let last_pid = ProcessOps::processes().last().cloned().unwrap_or(0);
+    format!(
+        "{} {} {} {}/{} {}\n",
+        fixed_load(loads.0[0]),
+        fixed_load(loads.0[1]),
+        fixed_load(loads.0[2]),
+        running,
+        total,
+        last_pid
+    )
+}
+
+pub fn render_stat() -> String {
+    let (uptime, idle, user, system) = cpu_usec();
+    let ticks = |usec: usize| usec / (1_000_000 / USER_HZ);
+    let cpus = get_cpus().len();
+    let times = |idle: usize| format!("{} 0 {} {} 0 0 0 0 0 0", ticks(user), ticks(system), idle);
+    let mut text = format!("cpu  {}\n", times(ticks(idle + (cpus - 1) * uptime)));
+    // the tasks only run on the boot cpu, the others are always idle.
+    for processor in 0..cpus {
+        match processor {
+            0 => text += &format!("cpu0 {}\n", times(ticks(idle))),
+            _ => text += &format!("cpu{} 0 0 0 {} 0 0 0 0 0 0\n", processor, ticks(uptime)),
+        }
+    }
+    let interrupts = get_int_records();
+    text += &format!("intr {}", interrupts.iter().sum::<usize>());
+    for times in interrupts {
+        text += &format!(" {}", times);
+    }
+    let (running, _) = thread_count();
+    text += &format!(
+        "\nctxt 0\nbtime 0\nprocesses {}\n",
+        ProcessOps::processes().len()
+    );
+    text += &format!("procs_running {}\nprocs_blocked 0\n", running);
+    text
+}
+
+pub fn render_version() -> String {
+    format!(
+        "Linux version {} (ByteOS) {}\n",
+        LINUX_RELEASE, LINUX_VERSION
+    )
+}
+
+pub fn render_cmdline() -> String {
+    format!("{}\n", get_bootargs())
+}
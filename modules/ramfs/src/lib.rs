@@ -1,33 +1,109 @@
 #![no_std]
 #![feature(extract_if)]
-#[macro_use]
 extern crate alloc;
 
-use core::cmp::{self, min};
+use core::{
+    cmp::{max, min},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use arch::PAGE_SIZE;
-use frame_allocator::{ceil_div, frame_alloc, FrameTracker};
+use frame_allocator::{ceil_div, frame_alloc, get_free_pages, FrameTracker};
 use sync::Mutex;
 use vfscore::{
-    DirEntry, FileSystem, FileType, INodeInterface, Metadata, Stat, StatMode, TimeSpec, VfsError,
-    VfsResult, UTIME_OMIT,
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, Stat, StatFS, StatMode, TimeSpec,
+    VfsError, VfsResult, UTIME_OMIT,
 };
 
+/// The magic number of tmpfs reported by statfs.
+const TMPFS_MAGIC: usize = 0x0102_1994;
+
 pub struct RamFs {
     root: Arc<RamDirInner>,
 }
 
 impl RamFs {
     pub fn new() -> Arc<Self> {
+        Self::new_with_size(0)
+    }
+
+    /// Create the filesystem which can hold size bytes at most, 0 means no limit.
+    pub fn new_with_size(size: usize) -> Arc<Self> {
         let inner = Arc::new(RamDirInner {
             name: String::from(""),
+            ino: 1,
+            inos: Arc::new(AtomicU64::new(2)),
+            usage: Arc::new(RamUsage {
+                pages: AtomicUsize::new(0),
+                limit: AtomicUsize::new(RamUsage::size_to_pages(size)),
+            }),
+            perm: Mutex::new(RamPerm::new(0o755)),
+            xattrs: RamXattrs::default(),
             children: Mutex::new(Vec::new()),
         });
         Arc::new(Self { root: inner })
     }
 }
 
+/// The pages used by the files, it is shared by the whole filesystem.
+pub struct RamUsage {
+    pages: AtomicUsize,
+    /// The max number of the pages, usize::MAX if it isn't limited.
+    limit: AtomicUsize,
+}
+
+impl RamUsage {
+    #[inline]
+    fn size_to_pages(size: usize) -> usize {
+        match size {
+            0 => usize::MAX,
+            _ => ceil_div(size, PAGE_SIZE),
+        }
+    }
+
+    /// Allocate a page for the file, StorageFull is returned if the limit is reached.
+    fn alloc_page(&self) -> VfsResult<Arc<FrameTracker>> {
+        let limit = self.limit.load(Ordering::Relaxed);
+        self.pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                (x < limit).then_some(x + 1)
+            })
+            .map_err(|_| VfsError::StorageFull)?;
+        match frame_alloc() {
+            Some(frame) => Ok(Arc::new(frame)),
+            None => {
+                self.free_pages(1);
+                Err(VfsError::StorageFull)
+            }
+        }
+    }
+
+    #[inline]
+    fn free_pages(&self, count: usize) {
+        self.pages.fetch_sub(count, Ordering::Relaxed);
+    }
+
+    fn statfs(&self, statfs: &mut StatFS) {
+        let used = self.pages.load(Ordering::Relaxed);
+        // the filesystem without the limit can use all the free memory.
+        let blocks = match self.limit.load(Ordering::Relaxed) {
+            usize::MAX => used + get_free_pages(),
+            limit => limit,
+        };
+        statfs.ftype = TMPFS_MAGIC as _;
+        statfs.bsize = PAGE_SIZE as _;
+        statfs.blocks = blocks as _;
+        statfs.bfree = blocks.saturating_sub(used) as _;
+        statfs.bavail = blocks.saturating_sub(used) as _;
+        // the number of the inodes isn't limited, it is reported as 0 like tmpfs.
+        statfs.files = 0;
+        statfs.ffree = 0;
+        statfs.fsid = 0;
+        statfs.namelen = 255;
+    }
+}
+
 impl FileSystem for RamFs {
     fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
         Arc::new(RamDir {
@@ -42,16 +118,40 @@ impl FileSystem for RamFs {
 
 pub struct RamDirInner {
     name: String,
+    ino: u64,
+    /// The next inode number, it is shared by the whole filesystem.
+    inos: Arc<AtomicU64>,
+    usage: Arc<RamUsage>,
+    perm: Mutex<RamPerm>,
+    xattrs: RamXattrs,
     children: Mutex<Vec<FileContainer>>,
 }
 
-// TODO: use frame insteads of Vec.
+impl RamDirInner {
+    #[inline]
+    fn alloc_ino(&self) -> u64 {
+        self.inos.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
 pub struct RamFileInner {
     name: String,
-    // content: Mutex<Vec<u8>>,
+    ino: u64,
+    /// The count of the names which refer to this file.
+    nlink: AtomicUsize,
     len: Mutex<usize>,
-    pages: Mutex<Vec<FrameTracker>>,
+    /// The index of the page -> the page, the missing pages are the holes read as zero.
+    pages: Mutex<BTreeMap<usize, Arc<FrameTracker>>>,
+    usage: Arc<RamUsage>,
     times: Mutex<[TimeSpec; 3]>, // ctime, atime, mtime.
+    perm: Mutex<RamPerm>,
+    xattrs: RamXattrs,
+}
+
+impl Drop for RamFileInner {
+    fn drop(&mut self) {
+        self.usage.free_pages(self.pages.lock().len());
+    }
 }
 
 #[allow(dead_code)]
@@ -60,10 +160,110 @@ pub struct RamLinkInner {
     link_file: Arc<dyn INodeInterface>,
 }
 
+pub struct RamSymlinkInner {
+    name: String,
+    ino: u64,
+    target: String,
+    perm: Mutex<RamPerm>,
+    xattrs: RamXattrs,
+}
+
+/// The special file made by mknod(2), like the device and the fifo.
+pub struct RamNodeInner {
+    name: String,
+    ino: u64,
+    /// The type of the file, like StatMode::CHAR.
+    file_type: StatMode,
+    rdev: u64,
+    perm: Mutex<RamPerm>,
+    xattrs: RamXattrs,
+}
+
+/// The permission bits and the owner of the node.
+#[derive(Clone, Copy)]
+pub struct RamPerm {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl RamPerm {
+    /// The new node belongs to root, the caller changes the owner.
+    const fn new(mode: u32) -> Self {
+        Self {
+            mode,
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn chown(&mut self, uid: Option<u32>, gid: Option<u32>) {
+        self.uid = uid.unwrap_or(self.uid);
+        self.gid = gid.unwrap_or(self.gid);
+    }
+
+    fn fill(&self, stat: &mut Stat, file_type: StatMode) {
+        stat.mode = file_type | StatMode::from_bits_retain(self.mode as _);
+        stat.uid = self.uid as _;
+        stat.gid = self.gid as _;
+    }
+}
+
+/// How setxattr(2) treats the attribute which exists.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum XattrMode {
+    Any,
+    /// Fail if the attribute exists.
+    Create,
+    /// Fail if the attribute doesn't exist.
+    Replace,
+}
+
+/// The extended attributes of the node, the name -> the value.
+#[derive(Default)]
+pub struct RamXattrs(Mutex<BTreeMap<String, Vec<u8>>>);
+
+impl RamXattrs {
+    /// Get the value, FileNotFound if there isn't the attribute.
+    pub fn get(&self, name: &str) -> VfsResult<Vec<u8>> {
+        self.0
+            .lock()
+            .get(name)
+            .cloned()
+            .ok_or(VfsError::FileNotFound)
+    }
+
+    pub fn set(&self, name: &str, value: &[u8], mode: XattrMode) -> VfsResult<()> {
+        let mut xattrs = self.0.lock();
+        match (mode, xattrs.contains_key(name)) {
+            (XattrMode::Create, true) => Err(VfsError::AlreadyExists),
+            (XattrMode::Replace, false) => Err(VfsError::FileNotFound),
+            _ => {
+                xattrs.insert(String::from(name), value.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.0.lock().keys().cloned().collect()
+    }
+
+    pub fn remove(&self, name: &str) -> VfsResult<()> {
+        self.0
+            .lock()
+            .remove(name)
+            .map(|_| ())
+            .ok_or(VfsError::FileNotFound)
+    }
+}
+
 pub enum FileContainer {
     File(Arc<RamFileInner>),
     Dir(Arc<RamDirInner>),
     Link(Arc<RamLinkInner>),
+    Symlink(Arc<RamSymlinkInner>),
+    Node(Arc<RamNodeInner>),
 }
 
 impl FileContainer {
@@ -74,10 +274,20 @@ impl FileContainer {
                 inner: file.clone(),
             })),
             FileContainer::Dir(dir) => Ok(Arc::new(RamDir { inner: dir.clone() })),
+            // the moved directory is used directly, the link only forwards the file operations.
+            FileContainer::Link(link) if link.link_file.is::<RamDir>() => {
+                Ok(link.link_file.clone())
+            }
             FileContainer::Link(link) => Ok(Arc::new(RamLink {
                 inner: link.clone(),
                 link_file: link.link_file.clone(),
             })),
+            FileContainer::Symlink(symlink) => Ok(Arc::new(RamSymlink {
+                inner: symlink.clone(),
+            })),
+            FileContainer::Node(node) => Ok(Arc::new(RamNode {
+                inner: node.clone(),
+            })),
         }
     }
 
@@ -87,6 +297,29 @@ impl FileContainer {
             FileContainer::File(file) => &file.name,
             FileContainer::Dir(dir) => &dir.name,
             FileContainer::Link(link) => &link.name,
+            FileContainer::Symlink(symlink) => &symlink.name,
+            FileContainer::Node(node) => &node.name,
+        }
+    }
+
+    /// The directory of the entry, the moved directory is a link to it.
+    fn dir(&self) -> Option<&Arc<RamDirInner>> {
+        match self {
+            FileContainer::Dir(dir) => Some(dir),
+            FileContainer::Link(link) => link.link_file.downcast_ref::<RamDir>().map(|x| &x.inner),
+            _ => None,
+        }
+    }
+
+    /// The entry is removed by remove(2) or replaced by rename(2), the file loses a name.
+    fn drop_name(&self) {
+        let file = match self {
+            FileContainer::File(file) => Some(file),
+            FileContainer::Link(link) => link.link_file.downcast_ref::<RamFile>().map(|x| &x.inner),
+            _ => None,
+        };
+        if let Some(file) = file {
+            file.nlink.fetch_sub(1, Ordering::Relaxed);
         }
     }
 }
@@ -97,10 +330,184 @@ pub struct RamLink {
     link_file: Arc<dyn INodeInterface>,
 }
 
+pub struct RamSymlink {
+    inner: Arc<RamSymlinkInner>,
+}
+
+impl RamSymlink {
+    pub fn xattrs(&self) -> &RamXattrs {
+        &self.inner.xattrs
+    }
+
+    /// The mode of the symbolic link is always 0777, only the owner can be changed.
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        self.inner.perm.lock().chown(uid, gid);
+        Ok(())
+    }
+}
+
+pub struct RamNode {
+    inner: Arc<RamNodeInner>,
+}
+
+impl RamNode {
+    pub fn xattrs(&self) -> &RamXattrs {
+        &self.inner.xattrs
+    }
+
+    /// The type of the file and the device number.
+    pub fn device(&self) -> (StatMode, u64) {
+        (self.inner.file_type, self.inner.rdev)
+    }
+
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        self.inner.perm.lock().mode = mode & 0o7777;
+        Ok(())
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        self.inner.perm.lock().chown(uid, gid);
+        Ok(())
+    }
+}
+
+impl RamLink {
+    /// The file which this name refers to.
+    pub fn link_file(&self) -> &Arc<dyn INodeInterface> {
+        &self.link_file
+    }
+}
+
 pub struct RamDir {
     inner: Arc<RamDirInner>,
 }
 
+impl RamDir {
+    /// The address of the directory, the nodes of the same directory have the same one.
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
+    pub fn xattrs(&self) -> &RamXattrs {
+        &self.inner.xattrs
+    }
+
+    /// Change the max size of the filesystem, it can't be less than the used size.
+    pub fn set_size(&self, size: usize) -> VfsResult<()> {
+        let usage = &self.inner.usage;
+        let limit = RamUsage::size_to_pages(size);
+        if usage.pages.load(Ordering::Relaxed) > limit {
+            return Err(VfsError::InvalidInput);
+        }
+        usage.limit.store(limit, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        self.inner.perm.lock().mode = mode & 0o7777;
+        Ok(())
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        self.inner.perm.lock().chown(uid, gid);
+        Ok(())
+    }
+
+    /// Create a symbolic link, the target is only a path.
+    pub fn symlink(&self, name: &str, target: &str) -> VfsResult<()> {
+        let mut children = self.inner.children.lock();
+        if children.iter().any(|x| x.filename() == name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        children.push(FileContainer::Symlink(Arc::new(RamSymlinkInner {
+            name: String::from(name),
+            ino: self.inner.alloc_ino(),
+            target: String::from(target),
+            perm: Mutex::new(RamPerm::new(0o777)),
+            xattrs: RamXattrs::default(),
+        })));
+        Ok(())
+    }
+
+    /// Make the special file, the device is opened by the device number.
+    /// The fifos aren't supported, they need the pipes.
+    pub fn mknod(&self, name: &str, file_type: StatMode, rdev: u64) -> VfsResult<()> {
+        if ![StatMode::CHAR, StatMode::BLOCK, StatMode::SOCKET].contains(&file_type) {
+            return Err(VfsError::NotSupported);
+        }
+        let mut children = self.inner.children.lock();
+        if children.iter().any(|x| x.filename() == name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        children.push(FileContainer::Node(Arc::new(RamNodeInner {
+            name: String::from(name),
+            ino: self.inner.alloc_ino(),
+            file_type,
+            rdev,
+            perm: Mutex::new(RamPerm::new(0o644)),
+            xattrs: RamXattrs::default(),
+        })));
+        Ok(())
+    }
+
+    /// Move the entry to the new name in the new directory, the entry which has the name is replaced.
+    /// The moved entry is a link to the same node, so the opened files still refer to it.
+    pub fn rename(&self, name: &str, new_dir: &RamDir, new_name: &str) -> VfsResult<()> {
+        // the inode numbers are shared by the same filesystem.
+        if !Arc::ptr_eq(&self.inner.inos, &new_dir.inner.inos) {
+            return Err(VfsError::NotSupported);
+        }
+        let same = Arc::ptr_eq(&self.inner, &new_dir.inner);
+        if same && name == new_name {
+            return Ok(());
+        }
+        // the directories are locked in the order of their addresses.
+        let (mut children, mut new_children) = match same {
+            true => (self.inner.children.lock(), None),
+            false if Arc::as_ptr(&self.inner) < Arc::as_ptr(&new_dir.inner) => {
+                let children = self.inner.children.lock();
+                (children, Some(new_dir.inner.children.lock()))
+            }
+            false => {
+                let new_children = new_dir.inner.children.lock();
+                (self.inner.children.lock(), Some(new_children))
+            }
+        };
+        let is_dir = children
+            .iter()
+            .find(|x| x.filename() == name)
+            .ok_or(VfsError::FileNotFound)?
+            .dir()
+            .is_some();
+        let targets = new_children.as_deref_mut().unwrap_or(&mut *children);
+        if let Some(index) = targets.iter().position(|x| x.filename() == new_name) {
+            match (is_dir, targets[index].dir()) {
+                (true, None) => return Err(VfsError::NotDir),
+                (false, Some(_)) => return Err(VfsError::NotFile),
+                (true, Some(dir)) if !dir.children.lock().is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty)
+                }
+                _ => {}
+            }
+            targets.remove(index).drop_name();
+        }
+        let index = children.iter().position(|x| x.filename() == name).unwrap();
+        let entry = children.remove(index);
+        let link_file = match &entry {
+            FileContainer::Link(link) => link.link_file.clone(),
+            entry => entry.to_inode()?,
+        };
+        new_children
+            .as_deref_mut()
+            .unwrap_or(&mut *children)
+            .push(FileContainer::Link(Arc::new(RamLinkInner {
+                name: String::from(new_name),
+                link_file,
+            })));
+        Ok(())
+    }
+}
+
 impl INodeInterface for RamDir {
     fn open(&self, name: &str, _flags: vfscore::OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
         self.inner
@@ -123,10 +530,14 @@ impl INodeInterface for RamDir {
         log::info!("touch file: {}", name);
         let new_inner = Arc::new(RamFileInner {
             name: String::from(name),
-            // content: Mutex::new(Vec::new()),
+            ino: self.inner.alloc_ino(),
+            nlink: AtomicUsize::new(1),
             times: Mutex::new([Default::default(); 3]),
+            perm: Mutex::new(RamPerm::new(0o644)),
+            xattrs: RamXattrs::default(),
             len: Mutex::new(0),
-            pages: Mutex::new(vec![]),
+            pages: Mutex::new(BTreeMap::new()),
+            usage: self.inner.usage.clone(),
         });
 
         let new_file = Arc::new(RamFile {
@@ -152,6 +563,11 @@ impl INodeInterface for RamDir {
 
         let new_inner = Arc::new(RamDirInner {
             name: String::from(name),
+            ino: self.inner.alloc_ino(),
+            inos: self.inner.inos.clone(),
+            usage: self.inner.usage.clone(),
+            perm: Mutex::new(RamPerm::new(0o755)),
+            xattrs: RamXattrs::default(),
             children: Mutex::new(Vec::new()),
         });
 
@@ -168,21 +584,16 @@ impl INodeInterface for RamDir {
     }
 
     fn rmdir(&self, name: &str) -> VfsResult<()> {
-        // TODO: identify whether the dir is empty(through metadata.childrens)
-        // return DirectoryNotEmpty if not empty.
-        let len = self
-            .inner
-            .children
-            .lock()
-            .extract_if(|x| match x {
-                FileContainer::Dir(x) => x.name == name,
-                _ => false,
-            })
-            .count();
-        match len > 0 {
-            true => Ok(()),
-            false => Err(VfsError::FileNotFound),
+        let mut children = self.inner.children.lock();
+        let index = children
+            .iter()
+            .position(|x| x.filename() == name && x.dir().is_some())
+            .ok_or(VfsError::FileNotFound)?;
+        if !children[index].dir().unwrap().children.lock().is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
         }
+        children.remove(index);
+        Ok(())
     }
 
     fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
@@ -194,7 +605,6 @@ impl INodeInterface for RamDir {
             .map(|x| match x {
                 FileContainer::File(file) => DirEntry {
                     filename: file.name.clone(),
-                    // len: file.content.lock().len(),
                     len: *file.len.lock(),
                     file_type: FileType::File,
                 },
@@ -203,27 +613,38 @@ impl INodeInterface for RamDir {
                     len: 0,
                     file_type: FileType::Directory,
                 },
-                FileContainer::Link(link) => DirEntry {
-                    filename: link.name.clone(),
-                    len: 0,
+                FileContainer::Link(link) => {
+                    let metadata = link.link_file.metadata();
+                    DirEntry {
+                        filename: link.name.clone(),
+                        len: metadata.as_ref().map_or(0, |x| x.size),
+                        file_type: metadata.map_or(FileType::Link, |x| x.file_type),
+                    }
+                }
+                FileContainer::Symlink(symlink) => DirEntry {
+                    filename: symlink.name.clone(),
+                    len: symlink.target.len(),
                     file_type: FileType::Link,
                 },
+                FileContainer::Node(node) => DirEntry {
+                    filename: node.name.clone(),
+                    len: 0,
+                    file_type: node_file_type(node.file_type),
+                },
             })
             .collect())
     }
 
     fn remove(&self, name: &str) -> VfsResult<()> {
-        let len = self
+        let removed: Vec<FileContainer> = self
             .inner
             .children
             .lock()
-            .extract_if(|x| match x {
-                FileContainer::File(x) => x.name == name,
-                FileContainer::Dir(_) => false,
-                FileContainer::Link(x) => x.name == name,
-            })
-            .count();
-        match len > 0 {
+            .extract_if(|x| x.filename() == name && x.dir().is_none())
+            .collect();
+        // the file is alive until the last name is removed.
+        removed.iter().for_each(FileContainer::drop_name);
+        match removed.len() > 0 {
             true => Ok(()),
             false => Err(VfsError::FileNotFound),
         }
@@ -244,11 +665,16 @@ impl INodeInterface for RamDir {
     }
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
-        stat.ino = 1; // TODO: convert path to number(ino)
-        stat.mode = StatMode::DIR; // TODO: add access mode
-        stat.nlink = 1;
-        stat.uid = 0;
-        stat.gid = 0;
+        let subdirs = self
+            .inner
+            .children
+            .lock()
+            .iter()
+            .filter(|x| x.dir().is_some())
+            .count();
+        stat.ino = self.inner.ino;
+        stat.nlink = (2 + subdirs) as _;
+        self.inner.perm.lock().fill(stat, StatMode::DIR);
         stat.size = 0;
         stat.blksize = 512;
         stat.blocks = 0;
@@ -259,6 +685,11 @@ impl INodeInterface for RamDir {
         Ok(())
     }
 
+    fn statfs(&self, statfs: &mut StatFS) -> VfsResult<()> {
+        self.inner.usage.statfs(statfs);
+        Ok(())
+    }
+
     fn link(&self, name: &str, src: Arc<dyn INodeInterface>) -> VfsResult<()> {
         // Find file, return VfsError::AlreadyExists if file exists
         self.inner
@@ -268,6 +699,15 @@ impl INodeInterface for RamDir {
             .find(|x| x.filename() == name)
             .map_or(Ok(()), |_| Err(VfsError::AlreadyExists))?;
 
+        // link to the file itself rather than the other name of it.
+        let src = match src.downcast_ref::<RamLink>() {
+            Some(link) => link.link_file.clone(),
+            None => src,
+        };
+        if let Some(file) = src.downcast_ref::<RamFile>() {
+            file.inner.nlink.fetch_add(1, Ordering::Relaxed);
+        }
+
         let new_inner = Arc::new(RamLinkInner {
             name: String::from(name),
             link_file: src,
@@ -286,107 +726,177 @@ pub struct RamFile {
     inner: Arc<RamFileInner>,
 }
 
-impl INodeInterface for RamFile {
-    fn readat(&self, mut offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
-        let mut buffer_off = 0;
-        // let file_size = self.inner.content.lock().len();
-        log::debug!("read ramfs: offset: {} len: {}", offset, buffer.len());
-        let file_size = *self.inner.len.lock();
-        let inner = self.inner.pages.lock();
-        match offset >= file_size {
-            true => Ok(0),
-            false => {
-                // let origin_read_len = min(buffer.len(), file_size - offset);
-                // let read_len = if offset >= real_size {
-                //     min(origin_read_len, real_size - offset)
-                // } else {
-                //     0
-                // };
-                let read_len = min(buffer.len(), file_size - offset);
-                let mut last_len = read_len;
-                // let content = self.inner.content.lock();
-                // buffer[..read_len].copy_from_slice(&content[offset..(offset + read_len)]);
-                loop {
-                    let curr_size = cmp::min(PAGE_SIZE - offset % PAGE_SIZE, last_len);
-                    if curr_size == 0 {
-                        break;
-                    }
-                    let index = offset / PAGE_SIZE;
-                    let page_data = inner[index].0.get_buffer();
-                    buffer[buffer_off..buffer_off + curr_size].copy_from_slice(
-                        &page_data[offset % PAGE_SIZE..offset % PAGE_SIZE + curr_size],
-                    );
-                    offset += curr_size;
-                    last_len -= curr_size;
-                    buffer_off += curr_size;
-                }
-                // Ok(origin_read_len)
-                Ok(read_len)
+impl RamFile {
+    /// The address of the file, the nodes of the same file have the same one.
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
+    pub fn xattrs(&self) -> &RamXattrs {
+        &self.inner.xattrs
+    }
+
+    pub fn chmod(&self, mode: u32) -> VfsResult<()> {
+        self.inner.perm.lock().mode = mode & 0o7777;
+        Ok(())
+    }
+
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        self.inner.perm.lock().chown(uid, gid);
+        Ok(())
+    }
+
+    /// Get the page of the file to map it directly, the page of the hole is allocated.
+    pub fn get_page(&self, index: usize) -> VfsResult<Arc<FrameTracker>> {
+        let mut pages = self.inner.pages.lock();
+        if let Some(page) = pages.get(&index) {
+            return Ok(page.clone());
+        }
+        let page = self.inner.usage.alloc_page()?;
+        pages.insert(index, page.clone());
+        Ok(page)
+    }
+
+    /// Allocate the pages of the holes in the range, the file is extended unless keep_size is set.
+    pub fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> VfsResult<()> {
+        let mut pages = self.inner.pages.lock();
+        for index in offset / PAGE_SIZE..ceil_div(offset + len, PAGE_SIZE) {
+            if !pages.contains_key(&index) {
+                pages.insert(index, self.inner.usage.alloc_page()?);
             }
         }
+        if !keep_size {
+            let mut file_size = self.inner.len.lock();
+            *file_size = max(*file_size, offset + len);
+        }
+        Ok(())
     }
 
-    fn writeat(&self, mut offset: usize, buffer: &[u8]) -> VfsResult<usize> {
-        log::info!("write to ramfs");
-        let mut buffer_off = 0;
-        let pages = ceil_div(offset + buffer.len(), PAGE_SIZE);
+    /// Make the range a hole, the whole pages are freed and the others are cleared.
+    pub fn punch_hole(&self, offset: usize, len: usize) -> VfsResult<()> {
+        let mut pages = self.inner.pages.lock();
+        let end = match pages.last_key_value() {
+            Some((index, _)) => min(offset.saturating_add(len), (index + 1) * PAGE_SIZE),
+            None => return Ok(()),
+        };
+        if offset >= end {
+            return Ok(());
+        }
+        let (first, last) = (ceil_div(offset, PAGE_SIZE), end / PAGE_SIZE);
+        if first < last {
+            let mut tail = pages.split_off(&last);
+            let removed = pages.split_off(&first);
+            self.inner.usage.free_pages(removed.len());
+            pages.append(&mut tail);
+        }
+        if offset % PAGE_SIZE != 0 {
+            if let Some(page) = pages.get(&(offset / PAGE_SIZE)) {
+                let stop = min(PAGE_SIZE, end - offset / PAGE_SIZE * PAGE_SIZE);
+                page.0.get_buffer()[offset % PAGE_SIZE..stop].fill(0);
+            }
+        }
+        if end % PAGE_SIZE != 0 && end / PAGE_SIZE != offset / PAGE_SIZE {
+            if let Some(page) = pages.get(&(end / PAGE_SIZE)) {
+                page.0.get_buffer()[..end % PAGE_SIZE].fill(0);
+            }
+        }
+        Ok(())
+    }
 
-        let mut inner = self.inner.pages.lock();
+    /// The offset of the first data at or after the offset, None if there are only holes.
+    pub fn seek_data(&self, offset: usize) -> Option<usize> {
+        let file_size = *self.inner.len.lock();
+        self.inner
+            .pages
+            .lock()
+            .range(offset / PAGE_SIZE..)
+            .next()
+            .map(|(index, _)| max(offset, index * PAGE_SIZE))
+            .filter(|x| *x < file_size)
+    }
 
-        for _ in inner.len()..pages {
-            inner.push(frame_alloc().expect("can't alloc frame in ram fs"));
+    /// The offset of the first hole at or after the offset, the end of the file is a hole.
+    pub fn seek_hole(&self, offset: usize) -> Option<usize> {
+        let file_size = *self.inner.len.lock();
+        if offset >= file_size {
+            return None;
         }
-
-        let mut wsize = buffer.len();
-        loop {
-            let curr_size = cmp::min(PAGE_SIZE - offset % PAGE_SIZE, wsize);
-            if curr_size == 0 {
+        let mut index = offset / PAGE_SIZE;
+        for page_index in self.inner.pages.lock().range(index..).map(|(x, _)| *x) {
+            if page_index != index {
                 break;
             }
-            let index = offset / PAGE_SIZE;
-            let page_data = inner[index].0.get_buffer();
-            page_data[offset % PAGE_SIZE..offset % PAGE_SIZE + curr_size]
-                .copy_from_slice(&buffer[buffer_off..buffer_off + curr_size]);
-            offset += curr_size;
-            buffer_off += curr_size;
-            wsize -= curr_size;
+            index += 1;
         }
+        Some(min(max(offset, index * PAGE_SIZE), file_size))
+    }
+}
 
+impl INodeInterface for RamFile {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        log::debug!("read ramfs: offset: {} len: {}", offset, buffer.len());
         let file_size = *self.inner.len.lock();
-        if offset > file_size {
-            *self.inner.len.lock() = offset;
+        if offset >= file_size {
+            return Ok(0);
         }
-        Ok(buffer.len())
+        let rlen = min(buffer.len(), file_size - offset);
+        let pages = self.inner.pages.lock();
+        let mut readed = 0;
+        while readed < rlen {
+            let pos = offset + readed;
+            let page_off = pos % PAGE_SIZE;
+            let len = min(PAGE_SIZE - page_off, rlen - readed);
+            let data = &mut buffer[readed..readed + len];
+            match pages.get(&(pos / PAGE_SIZE)) {
+                Some(page) => data.copy_from_slice(&page.0.get_buffer()[page_off..page_off + len]),
+                None => data.fill(0),
+            }
+            readed += len;
+        }
+        Ok(rlen)
     }
 
-    fn truncate(&self, size: usize) -> VfsResult<()> {
-        // self.inner.content.lock().drain(size..);
-        *self.inner.len.lock() = size;
-
-        log::info!("truncate ramfs:{} insize: {}", size, self.inner.len.lock());
-
-        let mut page_cont = self.inner.pages.lock();
-        let pages = page_cont.len();
-        let target_pages = ceil_div(size, PAGE_SIZE);
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> VfsResult<usize> {
+        log::info!("write to ramfs");
+        let mut pages = self.inner.pages.lock();
+        let mut written = 0;
+        while written < buffer.len() {
+            let pos = offset + written;
+            let page_off = pos % PAGE_SIZE;
+            let len = min(PAGE_SIZE - page_off, buffer.len() - written);
+            let index = pos / PAGE_SIZE;
+            if !pages.contains_key(&index) {
+                match self.inner.usage.alloc_page() {
+                    Ok(page) => {
+                        pages.insert(index, page);
+                    }
+                    // the written part is kept if the filesystem is full.
+                    Err(err) if written == 0 => return Err(err),
+                    Err(_) => break,
+                }
+            }
+            pages[&index].0.get_buffer()[page_off..page_off + len]
+                .copy_from_slice(&buffer[written..written + len]);
+            written += len;
+        }
 
-        let curr_page = ceil_div(size, PAGE_SIZE);
-        page_cont
-            .iter()
-            .skip(curr_page)
-            .for_each(|x| x.0.drop_clear());
+        let mut file_size = self.inner.len.lock();
+        *file_size = max(*file_size, offset + written);
+        Ok(written)
+    }
 
+    fn truncate(&self, size: usize) -> VfsResult<()> {
+        log::info!("truncate ramfs: {}", size);
+        let mut pages = self.inner.pages.lock();
+        // the file is extended with a hole, the pages after the end are dropped.
+        let removed = pages.split_off(&ceil_div(size, PAGE_SIZE));
+        self.inner.usage.free_pages(removed.len());
         if size % PAGE_SIZE != 0 {
-            let page = size / PAGE_SIZE;
-            let offset = size % PAGE_SIZE;
-            if let Some(page) = page_cont.get(page) {
-                page.0.get_buffer()[offset..].fill(0);
+            if let Some(page) = pages.get(&(size / PAGE_SIZE)) {
+                page.0.get_buffer()[size % PAGE_SIZE..].fill(0);
             }
         }
-
-        for _ in pages..target_pages {
-            page_cont.push(frame_alloc().expect("can't alloc frame in ram fs"));
-        }
-
+        *self.inner.len.lock() = size;
         Ok(())
     }
 
@@ -395,7 +905,6 @@ impl INodeInterface for RamFile {
             filename: &self.inner.name,
             inode: 0,
             file_type: FileType::File,
-            // size: self.inner.content.lock().len(),
             size: *self.inner.len.lock(),
             childrens: 0,
         })
@@ -403,20 +912,13 @@ impl INodeInterface for RamFile {
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
         log::debug!("stat ramfs");
-        // stat.ino = 1; // TODO: convert path to number(ino)
-        if self.inner.name.ends_with(".s") {
-            stat.ino = 2; // TODO: convert path to number(ino)
-        } else {
-            stat.ino = 1; // TODO: convert path to number(ino)
-        }
-        stat.mode = StatMode::FILE; // TODO: add access mode
-        stat.nlink = 1;
-        stat.uid = 0;
-        stat.gid = 0;
-        // stat.size = self.inner.content.lock().len() as u64;
+        stat.ino = self.inner.ino;
+        stat.nlink = self.inner.nlink.load(Ordering::Relaxed) as _;
+        self.inner.perm.lock().fill(stat, StatMode::FILE);
         stat.size = *self.inner.len.lock() as u64;
         stat.blksize = 512;
-        stat.blocks = 0;
+        // the holes don't use any block.
+        stat.blocks = (self.inner.pages.lock().len() * PAGE_SIZE / 512) as _;
         stat.rdev = 0; // TODO: add device id
 
         stat.atime = self.inner.times.lock()[1];
@@ -424,6 +926,11 @@ impl INodeInterface for RamFile {
         Ok(())
     }
 
+    fn statfs(&self, statfs: &mut StatFS) -> VfsResult<()> {
+        self.inner.usage.statfs(statfs);
+        Ok(())
+    }
+
     fn utimes(&self, times: &mut [vfscore::TimeSpec]) -> VfsResult<()> {
         if times[0].nsec != UTIME_OMIT {
             self.inner.times.lock()[1] = times[0];
@@ -441,15 +948,7 @@ impl INodeInterface for RamLink {
     }
 
     fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
-        // self.link_file.stat(stat)
-        stat.ino = self as *const RamLink as u64;
-        stat.blksize = 4096;
-        stat.blocks = 8;
-        stat.size = 3;
-        stat.uid = 0;
-        stat.gid = 0;
-        stat.mode = StatMode::LINK;
-        Ok(())
+        self.link_file.stat(stat)
     }
 
     fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
@@ -463,4 +962,66 @@ impl INodeInterface for RamLink {
     fn truncate(&self, size: usize) -> VfsResult<()> {
         self.link_file.truncate(size)
     }
+
+    fn resolve_link(&self) -> VfsResult<String> {
+        self.link_file.resolve_link()
+    }
+}
+
+impl INodeInterface for RamSymlink {
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: &self.inner.name,
+            inode: 0,
+            file_type: FileType::Link,
+            size: self.inner.target.len(),
+            childrens: 0,
+        })
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.ino = self.inner.ino;
+        stat.nlink = 1;
+        self.inner.perm.lock().fill(stat, StatMode::LINK);
+        stat.size = self.inner.target.len() as u64;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = 0;
+        Ok(())
+    }
+
+    fn resolve_link(&self) -> VfsResult<String> {
+        Ok(self.inner.target.clone())
+    }
+}
+
+/// The sockets have their own type, the others are shown as the devices.
+fn node_file_type(file_type: StatMode) -> FileType {
+    match file_type == StatMode::SOCKET {
+        true => FileType::Socket,
+        false => FileType::Device,
+    }
+}
+
+impl INodeInterface for RamNode {
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: &self.inner.name,
+            inode: 0,
+            file_type: node_file_type(self.inner.file_type),
+            size: 0,
+            childrens: 0,
+        })
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        stat.ino = self.inner.ino;
+        stat.nlink = 1;
+        self.inner.perm.lock().fill(stat, self.inner.file_type);
+        stat.size = 0;
+        stat.blksize = 512;
+        stat.blocks = 0;
+        stat.rdev = self.inner.rdev;
+        Ok(())
+    }
 }
@@ -0,0 +1,166 @@
+//! The device hierarchy, each device is under its bus in /sys/devices.
+//! /sys/class, /sys/block, /sys/bus and /sys/dev link to the devices.
+
+use alloc::{collections::BTreeMap, format, string::String};
+use devices::{
+    device::{BlkDriver, Driver},
+    partition::{blk_device_minor, blk_device_name, blk_device_partition, BLK_DEVICE_MAJOR},
+    ALL_DEVICES, RTC_DEVICE_MAJOR, TTY_DEVICE_MAJOR, TTY_SERIAL_MINOR,
+};
+
+use crate::Entry;
+
+/// A device of the class, like the block device sda.
+struct ClassDevice {
+    class: &'static str,
+    name: String,
+    /// The major and the minor number, the network devices don't have them.
+    dev: Option<(u32, u32)>,
+    /// The other attributes, like size of the block devices.
+    attrs: BTreeMap<&'static str, String>,
+    /// The other lines of uevent, like DEVTYPE=disk.
+    uevent: String,
+}
+
+impl ClassDevice {
+    fn new(class: &'static str, name: String, dev: Option<(u32, u32)>) -> Self {
+        Self {
+            class,
+            name,
+            dev,
+            attrs: BTreeMap::new(),
+            uevent: String::new(),
+        }
+    }
+
+    /// Add the attributes to the directory of the device.
+    fn add_to(self, root: &mut Entry, path: &str) {
+        let mut uevent = String::new();
+        if let Some((major, minor)) = self.dev {
+            uevent += &format!("MAJOR={}\nMINOR={}\nDEVNAME={}\n", major, minor, self.name);
+            root.insert(
+                &format!("{}/dev", path),
+                Entry::File(format!("{}:{}\n", major, minor)),
+            );
+            // the devices are found by the numbers in /sys/dev.
+            let kind = match self.class {
+                "block" => "block",
+                _ => "char",
+            };
+            let link = format!("dev/{}/{}:{}", kind, major, minor);
+            root.insert(&link, Entry::Link(format!("../../{}", path)));
+        }
+        uevent += &self.uevent;
+        root.insert(&format!("{}/uevent", path), Entry::File(uevent));
+        for (name, value) in self.attrs {
+            root.insert(&format!("{}/{}", path, name), Entry::File(value));
+        }
+        root.insert(
+            &format!("{}/subsystem", path),
+            Entry::Link(up(path, "class/") + self.class),
+        );
+        root.insert(
+            &format!("class/{}/{}", self.class, self.name),
+            Entry::Link(format!("../../{}", path)),
+        );
+    }
+}
+
+/// The relative path from the directory to the root of sysfs.
+fn up(dir: &str, rest: &str) -> String {
+    "../".repeat(dir.split('/').count()) + rest
+}
+
+/// The bus of the device, the virtio devices have their own bus.
+fn bus_name(driver_id: &str) -> &'static str {
+    match driver_id.starts_with("virtio") {
+        true => "virtio",
+        false => "platform",
+    }
+}
+
+/// Build the directories of the devices in the registry.
+pub(crate) fn build(root: &mut Entry) {
+    let all_devices = ALL_DEVICES.lock();
+    let mut bus_devices: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut disk_paths: BTreeMap<usize, String> = BTreeMap::new();
+    // create the directory of the device on the bus, return the path of it.
+    let mut add_bus_device = |root: &mut Entry, driver_id: &str| {
+        let bus = bus_name(driver_id);
+        let index = bus_devices.entry(bus).or_insert(0);
+        let name = match bus {
+            "virtio" => format!("virtio{}", index),
+            _ => format!("{}.{}", driver_id, index),
+        };
+        *index += 1;
+        let path = format!("devices/{}/{}", bus, name);
+        let uevent = format!("DRIVER={}\n", driver_id);
+        root.insert(&format!("{}/uevent", path), Entry::File(uevent));
+        root.insert(
+            &format!("bus/{}/devices/{}", bus, name),
+            Entry::Link(format!("../../../{}", path)),
+        );
+        root.insert(
+            &format!("{}/subsystem", path),
+            Entry::Link(format!("../../../bus/{}", bus)),
+        );
+        path
+    };
+
+    for (device_id, blk) in all_devices.blk.iter().enumerate() {
+        let name = blk_device_name(device_id);
+        let mut device = ClassDevice::new(
+            "block",
+            name.clone(),
+            Some((BLK_DEVICE_MAJOR, blk_device_minor(device_id))),
+        );
+        // the size is unknown if the driver doesn't report it.
+        if blk.capacity() != 0 {
+            device.attrs.insert("size", format!("{}\n", blk.capacity()));
+        }
+        device.attrs.insert("ro", String::from("0\n"));
+        // the partitions are under the directory of the disk.
+        let path = match blk_device_partition(device_id) {
+            Some((disk_id, index)) => {
+                device.attrs.insert("partition", format!("{}\n", index));
+                device.uevent = format!("DEVTYPE=partition\nPARTN={}\n", index);
+                format!("{}/{}", disk_paths[&disk_id], name)
+            }
+            None => {
+                device.uevent = String::from("DEVTYPE=disk\n");
+                let path = format!("{}/block/{}", add_bus_device(root, blk.get_id()), name);
+                root.insert(
+                    &format!("block/{}", name),
+                    Entry::Link(format!("../{}", path)),
+                );
+                disk_paths.insert(device_id, path.clone());
+                path
+            }
+        };
+        device.add_to(root, &path);
+    }
+
+    for (index, net) in all_devices.net.iter().enumerate() {
+        let name = format!("eth{}", index);
+        let mut device = ClassDevice::new("net", name.clone(), None);
+        // the index 1 is the loopback device.
+        device.uevent = format!("INTERFACE={}\nIFINDEX={}\n", name, index + 2);
+        let path = format!("{}/net/{}", add_bus_device(root, net.get_id()), name);
+        device.add_to(root, &path);
+    }
+
+    for (index, uart) in all_devices.uart.iter().enumerate() {
+        let name = format!("ttyS{}", index);
+        let minor = TTY_SERIAL_MINOR + index as u32;
+        let device = ClassDevice::new("tty", name.clone(), Some((TTY_DEVICE_MAJOR, minor)));
+        let path = format!("{}/tty/{}", add_bus_device(root, uart.get_id()), name);
+        device.add_to(root, &path);
+    }
+
+    for (index, rtc) in all_devices.rtc.iter().enumerate() {
+        let name = format!("rtc{}", index);
+        let device = ClassDevice::new("rtc", name.clone(), Some((RTC_DEVICE_MAJOR, index as u32)));
+        let path = format!("{}/rtc/{}", add_bus_device(root, rtc.get_id()), name);
+        device.add_to(root, &path);
+    }
+}
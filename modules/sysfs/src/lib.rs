@@ -0,0 +1,243 @@
+#![no_std]
+
+extern crate alloc;
+
+mod device;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use vfscore::{
+    DirEntry, FileSystem, FileType, INodeInterface, Metadata, OpenFlags, Stat, StatMode, VfsError,
+    VfsResult,
+};
+
+/// The filesystem mounted at /sys, it shows the devices found at the boot.
+pub struct SysFS {
+    root: Arc<SysDir>,
+}
+
+impl SysFS {
+    pub fn new() -> Arc<Self> {
+        let mut root = Entry::Dir(BTreeMap::new());
+        device::build(&mut root);
+        Arc::new(Self {
+            root: SysDir::new(root),
+        })
+    }
+}
+
+impl FileSystem for SysFS {
+    fn root_dir(&'static self) -> Arc<dyn INodeInterface> {
+        self.root.clone()
+    }
+
+    fn name(&self) -> &str {
+        "sysfs"
+    }
+}
+
+/// Allocate the inode number of the sysfs node.
+fn alloc_ino() -> u64 {
+    static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+    NEXT_INO.fetch_add(1, Ordering::Relaxed)
+}
+
+fn fill_stat(stat: &mut Stat, ino: u64, mode: StatMode, size: u64) {
+    stat.dev = 0;
+    stat.ino = ino;
+    stat.mode = mode;
+    stat.nlink = 1;
+    stat.uid = 0;
+    stat.gid = 0;
+    stat.size = size;
+    stat.blksize = 4096;
+    stat.blocks = 0;
+    stat.rdev = 0;
+}
+
+/// The tree described by the devices, it is turned into the nodes at last.
+pub(crate) enum Entry {
+    Dir(BTreeMap<String, Entry>),
+    /// The attribute, like dev and uevent.
+    File(String),
+    /// The symbolic link, the target is relative to the directory of the link.
+    Link(String),
+}
+
+impl Entry {
+    /// Add the entry to the path, the directories are created if they don't exist.
+    pub(crate) fn insert(&mut self, path: &str, entry: Entry) {
+        let Entry::Dir(children) = self else {
+            panic!("can't add {} to the file in sysfs", path);
+        };
+        match path.split_once('/') {
+            Some((name, rest)) => children
+                .entry(name.to_string())
+                .or_insert_with(|| Entry::Dir(BTreeMap::new()))
+                .insert(rest, entry),
+            None => {
+                children.insert(path.to_string(), entry);
+            }
+        }
+    }
+
+    fn into_node(self) -> Arc<dyn INodeInterface> {
+        match self {
+            Entry::Dir(_) => SysDir::new(self),
+            Entry::File(text) => Arc::new(SysFile {
+                ino: alloc_ino(),
+                text,
+            }),
+            Entry::Link(target) => Arc::new(SysLink {
+                ino: alloc_ino(),
+                target,
+            }),
+        }
+    }
+}
+
+pub struct SysDir {
+    ino: u64,
+    children: BTreeMap<String, (FileType, Arc<dyn INodeInterface>)>,
+}
+
+impl SysDir {
+    fn new(entry: Entry) -> Arc<Self> {
+        let Entry::Dir(children) = entry else {
+            unreachable!()
+        };
+        let children = children
+            .into_iter()
+            .map(|(name, entry)| {
+                let file_type = match entry {
+                    Entry::Dir(_) => FileType::Directory,
+                    Entry::File(_) => FileType::File,
+                    Entry::Link(_) => FileType::Link,
+                };
+                (name, (file_type, entry.into_node()))
+            })
+            .collect();
+        Arc::new(Self {
+            ino: alloc_ino(),
+            children,
+        })
+    }
+}
+
+impl INodeInterface for SysDir {
+    fn open(&self, name: &str, _flags: OpenFlags) -> VfsResult<Arc<dyn INodeInterface>> {
+        self.children
+            .get(name)
+            .map(|x| x.1.clone())
+            .ok_or(VfsError::FileNotFound)
+    }
+
+    fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
+        Ok(self
+            .children
+            .iter()
+            .map(|(name, (file_type, _))| DirEntry {
+                filename: name.clone(),
+                len: 0,
+                file_type: *file_type,
+            })
+            .collect())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        fill_stat(
+            stat,
+            self.ino,
+            StatMode::DIR | StatMode::from_bits_truncate(0o755),
+            0,
+        );
+        stat.nlink = 2;
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "",
+            inode: self.ino as _,
+            file_type: FileType::Directory,
+            size: 0,
+            childrens: self.children.len(),
+        })
+    }
+}
+
+/// The read-only attribute, the text is generated when the tree is built.
+pub struct SysFile {
+    ino: u64,
+    text: String,
+}
+
+impl INodeInterface for SysFile {
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> VfsResult<usize> {
+        let bytes = self.text.as_bytes();
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let rlen = buffer.len().min(bytes.len() - offset);
+        buffer[..rlen].copy_from_slice(&bytes[offset..offset + rlen]);
+        Ok(rlen)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        // the attributes always have the size of a page like linux.
+        fill_stat(
+            stat,
+            self.ino,
+            StatMode::FILE | StatMode::from_bits_truncate(0o444),
+            4096,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "",
+            inode: self.ino as _,
+            file_type: FileType::File,
+            size: 4096,
+            childrens: 0,
+        })
+    }
+}
+
+pub struct SysLink {
+    ino: u64,
+    target: String,
+}
+
+impl INodeInterface for SysLink {
+    fn resolve_link(&self) -> VfsResult<String> {
+        Ok(self.target.clone())
+    }
+
+    fn stat(&self, stat: &mut Stat) -> VfsResult<()> {
+        fill_stat(
+            stat,
+            self.ino,
+            StatMode::LINK | StatMode::from_bits_truncate(0o777),
+            self.target.len() as _,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            filename: "",
+            inode: self.ino as _,
+            file_type: FileType::Link,
+            size: self.target.len(),
+            childrens: 0,
+        })
+    }
+}